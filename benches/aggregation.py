@@ -0,0 +1,86 @@
+"""Compare single- and multi-threaded hex aggregation.
+
+Builds a million short synthetic pipe segments over Greater Manchester and times
+`hex_summary_from_arrow` on them, with no network access, once per thread count.
+The thread pool can only be configured once per process, so each run is a fresh
+interpreter:
+
+    python benches/aggregation.py [--segments N] [--resolution R] [--threads 1 4 ...]
+
+Each run's summary is hashed as well, to confirm the thread count does not change
+the result.
+"""
+
+import argparse
+import json
+import os
+import subprocess
+import sys
+
+RUN = """
+import hashlib, json, sys, time
+
+import numpy as np
+import pyarrow as pa
+import shapely
+
+import infra_hex_py
+
+segments, resolution, threads = map(int, sys.argv[1:])
+infra_hex_py.configure_aggregation(threads)
+
+rng = np.random.default_rng(0)
+start = np.column_stack(
+    [rng.uniform(-2.40, -2.10, segments), rng.uniform(53.40, 53.55, segments)]
+)
+end = start + rng.normal(0, 0.0005, (segments, 2))
+coords = np.stack([start, end], axis=1)
+lines = shapely.linestrings(coords)
+batch = pa.record_batch({"geometry": shapely.to_wkb(lines)})
+
+started = time.perf_counter()
+summary = infra_hex_py.hex_summary_from_arrow(batch, resolution, output="pyarrow")
+seconds = time.perf_counter() - started
+
+digest = hashlib.sha256()
+for column in summary.columns[:2]:
+    digest.update(str(column.to_pylist()).encode())
+print(json.dumps({"seconds": seconds, "hexes": summary.num_rows,
+                  "digest": digest.hexdigest()[:12]}))
+"""
+
+
+def run(segments, resolution, threads):
+    output = subprocess.run(
+        [sys.executable, "-c", RUN, str(segments), str(resolution), str(threads)],
+        check=True,
+        capture_output=True,
+        text=True,
+    ).stdout
+    return json.loads(output.splitlines()[-1])
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--segments", type=int, default=1_000_000)
+    parser.add_argument("--resolution", type=int, default=11)
+    parser.add_argument("--threads", type=int, nargs="+", default=[1, os.cpu_count()])
+    args = parser.parse_args()
+
+    baseline = None
+    for threads in args.threads:
+        result = run(args.segments, args.resolution, threads)
+        baseline = baseline or result["seconds"]
+        print(
+            "{:>3} threads: {:7.2f}s ({:4.1f}x)  {} hexes  sha256 {}".format(
+                threads,
+                result["seconds"],
+                baseline / result["seconds"],
+                result["hexes"],
+                result["digest"],
+            )
+        )
+
+
+if __name__ == "__main__":
+    main()
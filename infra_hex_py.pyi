@@ -1,13 +1,1307 @@
+import contextlib
+import datetime
+import os
+from typing import Literal, Mapping, Protocol, Sequence, TypedDict, Unpack, overload
+
+import geopandas as gpd
+import polars as pl
 import pyarrow as pa
 
+class SupportsGeoInterface(Protocol):
+    @property
+    def __geo_interface__(self) -> Mapping[str, object]: ...
+
+AggregateFunction = Literal["min", "max", "mean", "sum", "count_distinct"]
+
+class HttpOptions(TypedDict, total=False):
+    proxy_url: str
+    http_proxy: str
+    https_proxy: str
+    proxy_username: str
+    proxy_password: str
+    headers: Mapping[str, str]
+    user_agent: str
+    max_retries: int
+    backoff_base_seconds: float
+    max_retry_after_seconds: float
+    compression: bool
+    connect_timeout_secs: float
+    read_timeout_secs: float
+    api_key: str
+    api_key_header: str
+    cadent_url: str
+    ons_url: str
+    verify_counts: bool
+    tile_size_deg: float
+    max_concurrent_tiles: int
+    arcgis_output_format: Literal["auto", "geojson", "json"]
+    extra_query_params: Mapping[str, str]
+    ca_cert_path: str | os.PathLike[str]
+    danger_accept_invalid_certs: bool
+    http2_prior_knowledge: bool
+    pool_idle_timeout_secs: float
+    redirect_policy: Literal["follow", "error"] | int
+
+class EndpointStatus(TypedDict):
+    up: bool
+    status: int | None
+    url: str
+    error: str | None
+
+class FetchEstimate(TypedDict):
+    feature_count: int
+    estimated_requests: int
+    estimated_hexes: int
+    resolution: int
+
+class BuiltUpArea(TypedDict):
+    object_id: int
+    code: str
+    name: str
+    geojson: str
+    wkb: bytes
+    bbox: BBox
+
+class AreaNotFoundError(LookupError, ValueError):
+    object_id: int | None
+
+class FetchErrorDetail(TypedDict):
+    kind: Literal[
+        "timeout", "connection", "http_status", "invalid_response", "pagination", "other"
+    ]
+    message: str
+    url: str | None
+    status: int | None
+    network_failure: Literal["dns", "tls", "connection_refused", "timeout"] | None
+
+class FetchError(RuntimeError):
+    errors: list[FetchErrorDetail]
+    elapsed_secs: float
+
+class FetchTimeoutError(FetchError, TimeoutError):
+    url: str | None
+
+class PaginationError(FetchError): ...
+
+class InfraNetworkError(FetchError):
+    host: str | None
+
+class InfraGeometryError(ValueError): ...
+
+class PoolStats(TypedDict):
+    clients: int
+    in_flight: int
+
+class BuildInfo(TypedDict):
+    version: str
+    infra_hex_rs_version: str
+    cadent_datasets_url: str
+    cadent_layers: dict[str, str]
+    networks: list[str]
+    ons_built_up_areas_url: str
+    ons_local_authority_urls: dict[str, str]
+
+__version__: str
+HEX_SUMMARY_SCHEMA_VERSION: int
+
+def build_info() -> BuildInfo: ...
+
+class SummaryStats(TypedDict):
+    total_records: int
+    hex_count: int
+    max_pipe_count: int
+    mean_pipe_count: float
+    total_pipe_length_m: float | None
+    fetch_seconds: float
+    query_id: str
+
+class ArrowBatch:
+    @property
+    def num_rows(self) -> int: ...
+    @property
+    def column_names(self) -> list[str]: ...
+    def __len__(self) -> int: ...
+    def __arrow_c_schema__(self) -> object: ...
+    def __arrow_c_array__(
+        self, requested_schema: object | None = None
+    ) -> tuple[object, object]: ...
+    def __arrow_c_stream__(self, requested_schema: object | None = None) -> object: ...
+
+class NormalizedSummary:
+    @property
+    def cells(self) -> pa.RecordBatch | ArrowBatch | pl.DataFrame: ...
+    @property
+    def summary(self) -> pa.RecordBatch | ArrowBatch | pl.DataFrame: ...
+
+class BBox:
+    min_lat: float
+    min_lon: float
+    max_lat: float
+    max_lon: float
+    def __init__(
+        self, *, min_lat: float, min_lon: float, max_lat: float, max_lon: float
+    ) -> None: ...
+    @staticmethod
+    def from_corners(
+        *, min_lat: float, min_lon: float, max_lat: float, max_lon: float
+    ) -> BBox: ...
+    @staticmethod
+    def from_bounds(minx: float, miny: float, maxx: float, maxy: float) -> BBox: ...
+    @staticmethod
+    def from_geojson(geojson: str) -> BBox: ...
+    @property
+    def area_km2(self) -> float: ...
+    def split(self, n: int) -> list[BBox]: ...
+
+class FailedPage(TypedDict):
+    bbox: tuple[float, float, float, float]
+    offset: int | None
+    limit: int | None
+
+class RegionCache:
+    @overload
+    @staticmethod
+    def from_bbox(
+        bbox: BBox,
+        *,
+        layer: str = "gas_pipes",
+        network: str | None = None,
+        deduplicate: bool = True,
+        http_options: HttpOptions | None = None,
+        fields: Sequence[str] | None = None,
+        allow_partial: bool = False,
+    ) -> RegionCache: ...
+    @overload
+    @staticmethod
+    def from_bbox(
+        min_lat: float,
+        min_lon: float,
+        max_lat: float,
+        max_lon: float,
+        *,
+        layer: str = "gas_pipes",
+        network: str | None = None,
+        deduplicate: bool = True,
+        http_options: HttpOptions | None = None,
+        fields: Sequence[str] | None = None,
+        allow_partial: bool = False,
+    ) -> RegionCache: ...
+    def hex_summary(
+        self,
+        resolution: int | None = None,
+        material: str | None = None,
+        *,
+        zoom: int | None = None,
+        parent_zoom: int | None = None,
+        coordinate_precision: int | None = None,
+        geometry_encoding: Literal[
+            "geoarrow_separated", "geoarrow", "wkb"
+        ] = "geoarrow_separated",
+        weighting: Literal["count", "length"] = "count",
+        aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+        count_mode: Literal["records", "unique_assets"] = "records",
+        assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+        boundary_pipe_count: bool = False,
+        include_metadata_columns: bool = True,
+        output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+        output_layout: Literal["wide", "normalized"] = "wide",
+        max_batch_rows: int | None = None,
+    ) -> (
+        pa.RecordBatch
+        | pa.Table
+        | ArrowBatch
+        | list[ArrowBatch]
+        | pl.DataFrame
+        | NormalizedSummary
+    ): ...
+    @property
+    def failed_pages(self) -> list[FailedPage]: ...
+    def fetch_errors(self) -> pa.RecordBatch | ArrowBatch: ...
+    def refetch(
+        self,
+        failed: Sequence[FailedPage] | None = None,
+        *,
+        http_options: HttpOptions | None = None,
+    ) -> RegionCache: ...
+    def to_pipe_records(self) -> pa.RecordBatch | ArrowBatch: ...
+    def __len__(self) -> int: ...
+
+def check_endpoints(
+    *,
+    http_options: HttpOptions | None = None,
+) -> dict[str, EndpointStatus]: ...
+@overload
+def estimate_fetch(
+    bbox: BBox,
+    resolution: int | Literal["auto"] | None = None,
+    *,
+    zoom: int | Literal["auto"] | None = None,
+    target_hex_count: int = 5000,
+    layer: str | Sequence[str] | None = None,
+    date_from: str | datetime.date | None = None,
+    date_to: str | datetime.date | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+) -> FetchEstimate: ...
+@overload
+def estimate_fetch(
+    min_lat: float,
+    min_lon: float,
+    max_lat: float,
+    max_lon: float,
+    resolution: int | Literal["auto"] | None = None,
+    *,
+    zoom: int | Literal["auto"] | None = None,
+    target_hex_count: int = 5000,
+    layer: str | Sequence[str] | None = None,
+    date_from: str | datetime.date | None = None,
+    date_to: str | datetime.date | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+) -> FetchEstimate: ...
+def get_built_up_area(
+    object_id: int,
+    *,
+    http_options: HttpOptions | None = None,
+) -> BuiltUpArea: ...
+def get_built_up_areas(
+    object_ids: Sequence[int],
+    *,
+    http_options: HttpOptions | None = None,
+) -> list[BuiltUpArea]: ...
+@overload
+def get_hex_summary(
+    bbox: BBox,
+    resolution: int | Literal["auto"] | None = None,
+    *,
+    zoom: int | Literal["auto"] | None = None,
+    target_hex_count: int = 5000,
+    grid: Literal["hex", "osgb", "square"] = "hex",
+    grid_size_m: int | None = None,
+    grid_origin: tuple[int, int] | None = None,
+    parent_zoom: int | None = None,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    hex_area: bool = False,
+    include_density: bool = False,
+    normalize: Literal["none", "per_km2", "per_km_edge"] = "none",
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    k_ring: int = 0,
+    layer: str | Sequence[str] | None = None,
+    date_from: str | datetime.date | None = None,
+    date_to: str | datetime.date | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    include_query_id: bool = False,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+    with_stats: bool = False,
+    length_method: Literal["geodesic", "haversine", "projected_27700"] = "geodesic",
+    snap_to_hex: bool = False,
+    max_total_features: int | None = None,
+    error_mode: Literal["collect", "fail_fast"] = "collect",
+    deadline_seconds: float | None = None,
+    partial_on_deadline: bool = False,
+    containment: Literal[
+        "intersects", "centroid", "within", "majority"
+    ] = "intersects",
+    telemetry: bool = False,
+    strict_location: bool = False,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+    | tuple[
+        pa.RecordBatch
+        | pa.Table
+        | ArrowBatch
+        | list[ArrowBatch]
+        | pl.DataFrame
+        | NormalizedSummary,
+        SummaryStats,
+    ]
+): ...
+@overload
 def get_hex_summary(
     min_lat: float,
     min_lon: float,
     max_lat: float,
     max_lon: float,
-    zoom: int,
-) -> pa.RecordBatch: ...
+    resolution: int | Literal["auto"] | None = None,
+    *,
+    zoom: int | Literal["auto"] | None = None,
+    target_hex_count: int = 5000,
+    grid: Literal["hex", "osgb", "square"] = "hex",
+    grid_size_m: int | None = None,
+    grid_origin: tuple[int, int] | None = None,
+    parent_zoom: int | None = None,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    hex_area: bool = False,
+    include_density: bool = False,
+    normalize: Literal["none", "per_km2", "per_km_edge"] = "none",
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    k_ring: int = 0,
+    layer: str | Sequence[str] | None = None,
+    date_from: str | datetime.date | None = None,
+    date_to: str | datetime.date | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    include_query_id: bool = False,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+    with_stats: bool = False,
+    length_method: Literal["geodesic", "haversine", "projected_27700"] = "geodesic",
+    snap_to_hex: bool = False,
+    max_total_features: int | None = None,
+    error_mode: Literal["collect", "fail_fast"] = "collect",
+    deadline_seconds: float | None = None,
+    partial_on_deadline: bool = False,
+    containment: Literal[
+        "intersects", "centroid", "within", "majority"
+    ] = "intersects",
+    telemetry: bool = False,
+    lonlat: bool = False,
+    strict_location: bool = False,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+    | tuple[
+        pa.RecordBatch
+        | pa.Table
+        | ArrowBatch
+        | list[ArrowBatch]
+        | pl.DataFrame
+        | NormalizedSummary,
+        SummaryStats,
+    ]
+): ...
+@overload
+def get_hex_summary_geodataframe(
+    bbox: BBox,
+    resolution: int | None = None,
+    *,
+    zoom: int | None = None,
+    parent_zoom: int | None = None,
+    deduplicate: bool = True,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    k_ring: int = 0,
+    layer: str | Sequence[str] | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    include_query_id: bool = False,
+    with_stats: bool = False,
+    length_method: Literal["geodesic", "haversine", "projected_27700"] = "geodesic",
+) -> gpd.GeoDataFrame | tuple[gpd.GeoDataFrame, SummaryStats]: ...
+@overload
+def get_hex_summary_geodataframe(
+    min_lat: float,
+    min_lon: float,
+    max_lat: float,
+    max_lon: float,
+    resolution: int | None = None,
+    *,
+    zoom: int | None = None,
+    parent_zoom: int | None = None,
+    deduplicate: bool = True,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    k_ring: int = 0,
+    layer: str | Sequence[str] | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    include_query_id: bool = False,
+    with_stats: bool = False,
+    length_method: Literal["geodesic", "haversine", "projected_27700"] = "geodesic",
+) -> gpd.GeoDataFrame | tuple[gpd.GeoDataFrame, SummaryStats]: ...
 def get_hex_summary_polygon_area(
     object_id: int,
-    zoom: int,
-) -> pa.RecordBatch: ...
+    resolution: int | None = None,
+    *,
+    zoom: int | None = None,
+    parent_zoom: int | None = None,
+    layer: str = "gas_pipes",
+    network: str | None = None,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    buffer_m: float | None = None,
+    clipped_area: bool = False,
+    include_density: bool = False,
+    normalize: Literal["none", "per_km2", "per_km_edge"] = "none",
+    containment: Literal[
+        "intersects", "centroid", "within", "majority"
+    ] = "intersects",
+    strict_coverage: bool = False,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    simplify_tolerance_m: float | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    include_query_id: bool = False,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+): ...
+def get_hex_summary_for_area_names(
+    names: Sequence[str],
+    resolution: int | None = None,
+    *,
+    zoom: int | None = None,
+    ignore_errors: bool = False,
+    parent_zoom: int | None = None,
+    layer: str = "gas_pipes",
+    network: str | None = None,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    buffer_m: float | None = None,
+    clipped_area: bool = False,
+    include_density: bool = False,
+    normalize: Literal["none", "per_km2", "per_km_edge"] = "none",
+    containment: Literal[
+        "intersects", "centroid", "within", "majority"
+    ] = "intersects",
+    strict_coverage: bool = False,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    simplify_tolerance_m: float | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    include_query_id: bool = False,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+): ...
+def get_hex_summary_for_arcgis_feature(
+    layer_url: str,
+    where: str,
+    resolution: int | None = None,
+    *,
+    zoom: int | None = None,
+    parent_zoom: int | None = None,
+    layer: str = "gas_pipes",
+    network: str | None = None,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    buffer_m: float | None = None,
+    clipped_area: bool = False,
+    include_density: bool = False,
+    normalize: Literal["none", "per_km2", "per_km_edge"] = "none",
+    containment: Literal[
+        "intersects", "centroid", "within", "majority"
+    ] = "intersects",
+    strict_coverage: bool = False,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    simplify_tolerance_m: float | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    include_query_id: bool = False,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+): ...
+def get_hex_summary_for_polygon(
+    polygon_geojson: str | SupportsGeoInterface,
+    resolution: int | None = None,
+    *,
+    zoom: int | None = None,
+    parent_zoom: int | None = None,
+    layer: str = "gas_pipes",
+    network: str | None = None,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    buffer_m: float | None = None,
+    clipped_area: bool = False,
+    include_density: bool = False,
+    normalize: Literal["none", "per_km2", "per_km_edge"] = "none",
+    containment: Literal[
+        "intersects", "centroid", "within", "majority"
+    ] = "intersects",
+    strict_coverage: bool = False,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    simplify_tolerance_m: float | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    include_query_id: bool = False,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+): ...
+def get_hex_summary_around_point(
+    lat: float,
+    lon: float,
+    radius_m: float,
+    resolution: int | None = None,
+    *,
+    zoom: int | None = None,
+    parent_zoom: int | None = None,
+    layer: str = "gas_pipes",
+    network: str | None = None,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    clipped_area: bool = False,
+    include_density: bool = False,
+    normalize: Literal["none", "per_km2", "per_km_edge"] = "none",
+    containment: Literal[
+        "intersects", "centroid", "within", "majority"
+    ] = "intersects",
+    strict_coverage: bool = False,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    simplify_tolerance_m: float | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    include_query_id: bool = False,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+): ...
+def get_hex_summary_local_authority(
+    code_or_objectid: str | int,
+    resolution: int | None = None,
+    *,
+    zoom: int | None = None,
+    boundary_resolution: Literal["generalised", "full"] = "generalised",
+    parent_zoom: int | None = None,
+    layer: str = "gas_pipes",
+    network: str | None = None,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    buffer_m: float | None = None,
+    clipped_area: bool = False,
+    include_density: bool = False,
+    normalize: Literal["none", "per_km2", "per_km_edge"] = "none",
+    containment: Literal[
+        "intersects", "centroid", "within", "majority"
+    ] = "intersects",
+    strict_coverage: bool = False,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    simplify_tolerance_m: float | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    include_query_id: bool = False,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+): ...
+@overload
+def get_hex_summary_all_networks(
+    bbox: BBox,
+    resolution: int | Literal["auto"] | None = None,
+    *,
+    zoom: int | Literal["auto"] | None = None,
+    target_hex_count: int = 5000,
+    parent_zoom: int | None = None,
+    deduplicate: bool = True,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+    telemetry: bool = False,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+): ...
+@overload
+def get_hex_summary_all_networks(
+    min_lat: float,
+    min_lon: float,
+    max_lat: float,
+    max_lon: float,
+    resolution: int | Literal["auto"] | None = None,
+    *,
+    zoom: int | Literal["auto"] | None = None,
+    target_hex_count: int = 5000,
+    parent_zoom: int | None = None,
+    deduplicate: bool = True,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+    telemetry: bool = False,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+): ...
+def hex_summary_from_geojson(
+    path_or_str: str,
+    resolution: int | None = None,
+    clip_geojson: str | SupportsGeoInterface | None = None,
+    *,
+    zoom: int | None = None,
+    parent_zoom: int | None = None,
+    coordinate_precision: int | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    clipped_area: bool = False,
+    containment: Literal[
+        "intersects", "centroid", "within", "majority"
+    ] = "intersects",
+    hex_area: bool = False,
+    include_density: bool = False,
+    normalize: Literal["none", "per_km2", "per_km_edge"] = "none",
+    include_points: bool = False,
+    include_metadata_columns: bool = True,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+): ...
+@overload
+def get_pipe_cell_traces(
+    bbox: BBox,
+    resolution: int | Literal["auto"] | None = None,
+    *,
+    zoom: int | Literal["auto"] | None = None,
+    target_hex_count: int = 5000,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    layer: str | Sequence[str] | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+) -> pa.RecordBatch | ArrowBatch | pl.DataFrame: ...
+@overload
+def get_pipe_cell_traces(
+    min_lat: float,
+    min_lon: float,
+    max_lat: float,
+    max_lon: float,
+    resolution: int | Literal["auto"] | None = None,
+    *,
+    zoom: int | Literal["auto"] | None = None,
+    target_hex_count: int = 5000,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    layer: str | Sequence[str] | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+) -> pa.RecordBatch | ArrowBatch | pl.DataFrame: ...
+@overload
+def get_pipe_hex_assignments(
+    bbox: BBox,
+    resolution: int | Literal["auto"] | None = None,
+    *,
+    zoom: int | Literal["auto"] | None = None,
+    target_hex_count: int = 5000,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    layer: str | Sequence[str] | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+) -> pa.RecordBatch | ArrowBatch | pl.DataFrame: ...
+@overload
+def get_pipe_hex_assignments(
+    min_lat: float,
+    min_lon: float,
+    max_lat: float,
+    max_lon: float,
+    resolution: int | Literal["auto"] | None = None,
+    *,
+    zoom: int | Literal["auto"] | None = None,
+    target_hex_count: int = 5000,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    layer: str | Sequence[str] | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+) -> pa.RecordBatch | ArrowBatch | pl.DataFrame: ...
+@overload
+def get_record_hex_assignments(
+    bbox: BBox,
+    resolution: int | Literal["auto"] | None = None,
+    *,
+    zoom: int | Literal["auto"] | None = None,
+    target_hex_count: int = 5000,
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    deduplicate: bool = True,
+    layer: str | Sequence[str] | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+) -> pa.RecordBatch | ArrowBatch | pl.DataFrame: ...
+@overload
+def get_record_hex_assignments(
+    min_lat: float,
+    min_lon: float,
+    max_lat: float,
+    max_lon: float,
+    resolution: int | Literal["auto"] | None = None,
+    *,
+    zoom: int | Literal["auto"] | None = None,
+    target_hex_count: int = 5000,
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    deduplicate: bool = True,
+    layer: str | Sequence[str] | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+) -> pa.RecordBatch | ArrowBatch | pl.DataFrame: ...
+def get_pipes_in_cell(
+    hex_id: str,
+    resolution: int | None = None,
+    *,
+    zoom: int | None = None,
+    deduplicate: bool = True,
+    layer: str | Sequence[str] | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+) -> pa.RecordBatch | ArrowBatch | pl.DataFrame: ...
+def hex_summary_for_cells(
+    cells: Sequence[str],
+    resolution: int | None = None,
+    *,
+    zoom: int | None = None,
+    deduplicate: bool = True,
+    nearest_distance: bool = False,
+    layer: str | Sequence[str] | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+    telemetry: bool = False,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+): ...
+def hex_summary_from_arrow(
+    batch: pa.RecordBatch | pa.Table,
+    resolution: int | None = None,
+    geometry_column: str = "geometry",
+    encoding: str = "wkb",
+    skip_invalid: bool = False,
+    *,
+    zoom: int | None = None,
+    parent_zoom: int | None = None,
+    coordinate_precision: int | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    include_metadata_columns: bool = True,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+): ...
+def hex_summary_from_records(
+    records: pa.RecordBatch | pa.Table,
+    resolution: int | None = None,
+    *,
+    zoom: int | None = None,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    skip_invalid: bool = False,
+    parent_zoom: int | None = None,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    coordinate_precision: int | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+): ...
+@overload
+def save_raw_fetch(
+    path: str,
+    bbox: BBox,
+    *,
+    deduplicate: bool = True,
+    layer: str | Sequence[str] | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    fields: Sequence[str] | None = None,
+) -> int: ...
+@overload
+def save_raw_fetch(
+    path: str,
+    min_lat: float,
+    min_lon: float,
+    max_lat: float,
+    max_lon: float,
+    *,
+    deduplicate: bool = True,
+    layer: str | Sequence[str] | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    fields: Sequence[str] | None = None,
+) -> int: ...
+@overload
+def fetch_raw_response(
+    bbox: BBox,
+    *,
+    layer: str = "gas_pipes",
+    limit: int = 100,
+    offset: int = 0,
+    fields: Sequence[str] | None = None,
+    http_options: HttpOptions | None = None,
+) -> bytes: ...
+@overload
+def fetch_raw_response(
+    min_lat: float,
+    min_lon: float,
+    max_lat: float,
+    max_lon: float,
+    *,
+    layer: str = "gas_pipes",
+    limit: int = 100,
+    offset: int = 0,
+    fields: Sequence[str] | None = None,
+    http_options: HttpOptions | None = None,
+) -> bytes: ...
+def hex_summary_from_saved(
+    path: str,
+    resolution: int | None = None,
+    clip_geojson: str | SupportsGeoInterface | None = None,
+    *,
+    zoom: int | None = None,
+    deduplicate: bool = True,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    boundary_pipe_count: bool = False,
+    parent_zoom: int | None = None,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    coordinate_precision: int | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+): ...
+def hex_summary_schema(
+    *,
+    grid: Literal["hex", "osgb", "square"] = "hex",
+    layer: str | Sequence[str] | None = None,
+    parent_zoom: int | None = None,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    boundary_pipe_count: bool = False,
+    clipped_area: bool = False,
+    k_ring: int = 0,
+    hex_area: bool = False,
+    include_density: bool = False,
+    normalize: Literal["none", "per_km2", "per_km_edge"] = "none",
+    nearest_distance: bool = False,
+    include_metadata_columns: bool = True,
+    include_query_id: bool = False,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+) -> pa.Schema: ...
+def diff_hex_summaries(
+    batch_old: pa.RecordBatch | pa.Table,
+    batch_new: pa.RecordBatch | pa.Table,
+) -> pa.RecordBatch | ArrowBatch: ...
+def merge_hex_summaries(
+    batches: Sequence[pa.RecordBatch | pa.Table],
+) -> pa.RecordBatch | ArrowBatch: ...
+def reset_clients() -> None: ...
+def enable_fixture_mode(
+    fixtures_dir: str | os.PathLike[str] | None = None, *, record: bool = False
+) -> None: ...
+def disable_fixture_mode() -> bool: ...
+def shutdown_runtime() -> None: ...
+def session() -> contextlib.AbstractContextManager[None]: ...
+def warmup(*, http_options: HttpOptions | None = None) -> None: ...
+def pool_stats() -> PoolStats: ...
+def configure_aggregation(num_threads: int) -> None: ...
+def configure_runtime(worker_threads: int) -> None: ...
+def set_default_network(name: str) -> str: ...
+
+class Config:
+    @property
+    def options(self) -> HttpOptions: ...
+    def __getitem__(self, key: str) -> object: ...
+    def __contains__(self, key: str) -> bool: ...
+    def __len__(self) -> int: ...
+
+def configure(**options: Unpack[HttpOptions]) -> Config: ...
+def get_config() -> Config: ...
+def write_hex_summary_flatgeobuf(
+    batch: pa.RecordBatch | pa.Table | pa.RecordBatchReader,
+    path: str,
+) -> int: ...
+def write_hex_summary_gpkg(
+    object_id: int,
+    resolution: int | None,
+    path: str | os.PathLike[str],
+    *,
+    zoom: int | None = None,
+    parent_zoom: int | None = None,
+    layer: str = "gas_pipes",
+    network: str | None = None,
+    deduplicate: bool = True,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    buffer_m: float | None = None,
+    clipped_area: bool = False,
+    include_density: bool = False,
+    normalize: Literal["none", "per_km2", "per_km_edge"] = "none",
+    containment: Literal[
+        "intersects", "centroid", "within", "majority"
+    ] = "intersects",
+    strict_coverage: bool = False,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    simplify_tolerance_m: float | None = None,
+    include_metadata_columns: bool = True,
+    include_query_id: bool = False,
+) -> int: ...
+class PolygonAreasWritten(TypedDict):
+    fetched: list[int]
+    skipped: list[int]
+
+@overload
+def get_hex_summary_polygon_areas(
+    object_ids: Sequence[int],
+    resolution: int | None = None,
+    *,
+    output_dir: None = None,
+    skip_existing: Literal[False] = False,
+    **kwargs: object,
+) -> dict[int, object]: ...
+@overload
+def get_hex_summary_polygon_areas(
+    object_ids: Sequence[int],
+    resolution: int | None = None,
+    *,
+    output_dir: str | os.PathLike[str],
+    skip_existing: bool = False,
+    **kwargs: object,
+) -> PolygonAreasWritten: ...
+def write_hex_summary_ndjson(
+    batch: pa.RecordBatch | pa.Table | pa.RecordBatchReader,
+    path: str,
+) -> int: ...
+def get_hex_summary_bng(
+    min_e: float,
+    min_n: float,
+    max_e: float,
+    max_n: float,
+    resolution: int | Literal["auto"] | None = None,
+    *,
+    zoom: int | Literal["auto"] | None = None,
+    target_hex_count: int = 5000,
+    grid: Literal["hex", "osgb", "square"] = "hex",
+    grid_size_m: int | None = None,
+    grid_origin: tuple[int, int] | None = None,
+    parent_zoom: int | None = None,
+    deduplicate: bool = True,
+    id_field: str | None = None,
+    weighting: Literal["count", "length"] = "count",
+    aggregations: Mapping[str, Sequence[AggregateFunction]] | None = None,
+    count_mode: Literal["records", "unique_assets"] = "records",
+    assignment: Literal["coverage", "all_intersecting", "midpoint"] = "coverage",
+    boundary_pipe_count: bool = False,
+    hex_area: bool = False,
+    include_density: bool = False,
+    normalize: Literal["none", "per_km2", "per_km_edge"] = "none",
+    max_hexes: int = 2_000_000,
+    min_pipe_count: int | None = None,
+    top_n: int | None = None,
+    layer: str | Sequence[str] | None = None,
+    date_from: str | datetime.date | None = None,
+    date_to: str | datetime.date | None = None,
+    network: str | None = None,
+    http_options: HttpOptions | None = None,
+    coordinate_precision: int | None = None,
+    geometry_encoding: Literal[
+        "geoarrow_separated", "geoarrow", "wkb"
+    ] = "geoarrow_separated",
+    include_metadata_columns: bool = True,
+    include_query_id: bool = False,
+    output: Literal["arrow", "pyarrow", "capsule", "polars"] = "arrow",
+    output_layout: Literal["wide", "normalized"] = "wide",
+    max_batch_rows: int | None = None,
+    with_stats: bool = False,
+    length_method: Literal["geodesic", "haversine", "projected_27700"] = "geodesic",
+    snap_to_hex: bool = False,
+    max_total_features: int | None = None,
+    error_mode: Literal["collect", "fail_fast"] = "collect",
+    deadline_seconds: float | None = None,
+    partial_on_deadline: bool = False,
+    containment: Literal[
+        "intersects", "centroid", "within", "majority"
+    ] = "intersects",
+    telemetry: bool = False,
+) -> (
+    pa.RecordBatch
+    | pa.Table
+    | ArrowBatch
+    | list[ArrowBatch]
+    | pl.DataFrame
+    | NormalizedSummary
+    | tuple[
+        pa.RecordBatch
+        | pa.Table
+        | ArrowBatch
+        | list[ArrowBatch]
+        | pl.DataFrame
+        | NormalizedSummary,
+        SummaryStats,
+    ]
+): ...
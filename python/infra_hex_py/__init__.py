@@ -1,7 +1,306 @@
+import contextlib
+import os
+
 # Re-export from compiled Rust extension
-from infra_hex_py.infra_hex_py import get_hex_summary, get_hex_summary_polygon_area
+from infra_hex_py.infra_hex_py import (
+    AreaNotFoundError,
+    ArrowBatch,
+    BBox,
+    Config,
+    FetchError,
+    FetchTimeoutError,
+    HEX_SUMMARY_SCHEMA_VERSION,
+    InfraGeometryError,
+    InfraNetworkError,
+    NormalizedSummary,
+    PaginationError,
+    RegionCache,
+    __version__,
+    build_info,
+    check_endpoints,
+    configure,
+    configure_aggregation,
+    configure_runtime,
+    diff_hex_summaries,
+    disable_fixture_mode,
+    enable_fixture_mode,
+    estimate_fetch,
+    fetch_raw_response,
+    get_built_up_area,
+    get_built_up_areas,
+    get_config,
+    get_hex_summary,
+    get_hex_summary_all_networks,
+    get_hex_summary_around_point,
+    get_hex_summary_bng,
+    get_hex_summary_for_arcgis_feature,
+    get_hex_summary_for_area_names,
+    get_hex_summary_for_polygon,
+    get_hex_summary_local_authority,
+    get_hex_summary_polygon_area,
+    get_pipe_cell_traces,
+    get_pipe_hex_assignments,
+    get_pipes_in_cell,
+    get_record_hex_assignments,
+    hex_summary_for_cells,
+    hex_summary_from_arrow,
+    hex_summary_from_geojson,
+    hex_summary_from_records,
+    hex_summary_from_saved,
+    hex_summary_schema,
+    merge_hex_summaries,
+    pool_stats,
+    reset_clients,
+    save_raw_fetch,
+    set_default_network,
+    shutdown_runtime,
+    warmup,
+    write_hex_summary_flatgeobuf,
+    write_hex_summary_ndjson,
+)
+
+__all__ = [
+    "AreaNotFoundError",
+    "ArrowBatch",
+    "BBox",
+    "Config",
+    "FetchError",
+    "FetchTimeoutError",
+    "HEX_SUMMARY_SCHEMA_VERSION",
+    "InfraGeometryError",
+    "InfraNetworkError",
+    "NormalizedSummary",
+    "PaginationError",
+    "RegionCache",
+    "__version__",
+    "build_info",
+    "check_endpoints",
+    "configure",
+    "configure_aggregation",
+    "configure_runtime",
+    "diff_hex_summaries",
+    "disable_fixture_mode",
+    "enable_fixture_mode",
+    "estimate_fetch",
+    "fetch_raw_response",
+    "get_built_up_area",
+    "get_built_up_areas",
+    "get_config",
+    "get_hex_summary",
+    "get_hex_summary_all_networks",
+    "get_hex_summary_around_point",
+    "get_hex_summary_bng",
+    "get_hex_summary_for_arcgis_feature",
+    "get_hex_summary_for_area_names",
+    "get_hex_summary_for_polygon",
+    "get_hex_summary_geodataframe",
+    "get_hex_summary_local_authority",
+    "get_hex_summary_polygon_area",
+    "get_hex_summary_polygon_areas",
+    "get_pipe_cell_traces",
+    "get_pipe_hex_assignments",
+    "get_pipes_in_cell",
+    "get_record_hex_assignments",
+    "hex_summary_for_cells",
+    "hex_summary_from_arrow",
+    "hex_summary_from_geojson",
+    "hex_summary_from_records",
+    "hex_summary_from_saved",
+    "hex_summary_schema",
+    "merge_hex_summaries",
+    "pool_stats",
+    "reset_clients",
+    "save_raw_fetch",
+    "session",
+    "set_default_network",
+    "shutdown_runtime",
+    "warmup",
+    "write_hex_summary_flatgeobuf",
+    "write_hex_summary_gpkg",
+    "write_hex_summary_ndjson",
+]
+
+
+def get_hex_summary_geodataframe(*args, **kwargs):
+    """Like `get_hex_summary`, but returns a `geopandas.GeoDataFrame`.
+
+    Takes the same arguments apart from `output` and `output_layout`. The hex
+    polygons become the active geometry column, in EPSG:27700 (British National
+    Grid). geopandas is imported on the first call, so only code using this
+    function needs it; it also needs pyarrow. With `with_stats=True` it returns a
+    `(GeoDataFrame, stats)` tuple.
+    """
+    try:
+        import geopandas
+    except ImportError as e:
+        raise ImportError(
+            "get_hex_summary_geodataframe requires geopandas; install it with "
+            "`pip install geopandas`"
+        ) from e
+    try:
+        import pyarrow
+    except ImportError as e:
+        raise ImportError(
+            "get_hex_summary_geodataframe requires pyarrow; install it with "
+            "`pip install infra-hex-py[pyarrow]`"
+        ) from e
+
+    for name in ("output", "output_layout"):
+        if name in kwargs:
+            raise TypeError(
+                "get_hex_summary_geodataframe() got an unexpected keyword argument "
+                "'{}'".format(name)
+            )
+    result = get_hex_summary(*args, **kwargs)
+    if kwargs.get("with_stats"):
+        batch, stats = result
+        gdf = geopandas.GeoDataFrame.from_arrow(pyarrow.Table.from_batches([batch]))
+        return gdf, stats
+    return geopandas.GeoDataFrame.from_arrow(pyarrow.Table.from_batches([result]))
+
+
+def get_hex_summary_polygon_areas(
+    object_ids, resolution=None, *, output_dir=None, skip_existing=False, **kwargs
+):
+    """Summarises several built-up areas, one `get_hex_summary_polygon_area` call
+    each.
+
+    Takes the same keyword arguments as `get_hex_summary_polygon_area`, applied to
+    every area, and fetches repeated ids once. Returns a dict mapping each
+    OBJECTID, in the order given, to its summary.
+
+    With `output_dir`, each area's summary is instead written to
+    `{output_dir}/{object_id}.parquet` (created if missing) as soon as it is
+    fetched, with its schema metadata, and not kept in memory. Each file is
+    written under a temporary name and then renamed, so a job that dies partway
+    leaves only complete files. With `skip_existing=True`, areas whose file
+    already exists are not fetched again, so rerunning the same call resumes
+    where it stopped. This mode needs pyarrow, takes no `output`,
+    `output_layout` or `max_batch_rows`, and returns a dict with the `fetched`
+    and the `skipped` OBJECTIDs, each in the order given.
+    """
+    object_ids = list(dict.fromkeys(object_ids))
+    if output_dir is None:
+        if skip_existing:
+            raise ValueError("skip_existing needs an output_dir")
+        return {
+            object_id: get_hex_summary_polygon_area(object_id, resolution, **kwargs)
+            for object_id in object_ids
+        }
+
+    try:
+        import pyarrow.parquet
+    except ImportError as e:
+        raise ImportError(
+            "get_hex_summary_polygon_areas with output_dir requires pyarrow; "
+            "install it with `pip install infra-hex-py[pyarrow]`"
+        ) from e
+    for name in ("output", "output_layout", "max_batch_rows"):
+        if name in kwargs:
+            raise TypeError(
+                "get_hex_summary_polygon_areas() got an unexpected keyword argument "
+                "'{}' with output_dir".format(name)
+            )
+
+    os.makedirs(output_dir, exist_ok=True)
+    fetched, skipped = [], []
+    for object_id in object_ids:
+        path = os.path.join(output_dir, "{}.parquet".format(object_id))
+        if skip_existing and os.path.exists(path):
+            skipped.append(object_id)
+            continue
+        batch = get_hex_summary_polygon_area(
+            object_id, resolution, output="pyarrow", **kwargs
+        )
+        partial = path + ".partial"
+        pyarrow.parquet.write_table(pyarrow.Table.from_batches([batch]), partial)
+        os.replace(partial, path)
+        fetched.append(object_id)
+    return {"fetched": fetched, "skipped": skipped}
+
+
+def write_hex_summary_gpkg(object_id, resolution, path, **kwargs):
+    """Writes the summary of a built-up area and its boundary to a GeoPackage.
+
+    The `hex_summary` layer holds what `get_hex_summary_polygon_area(object_id,
+    resolution, **kwargs)` returns, and the `area_boundary` layer the boundary it
+    was clipped to: the ONS polygon, grown by `buffer_m` if that is passed, with
+    the area's `object_id`, `code` and `name`. Both are in EPSG:27700 (British
+    National Grid), so QGIS overlays them directly. `path` is overwritten.
+    Takes the same keyword arguments as `get_hex_summary_polygon_area` apart from
+    `output`, `output_layout`, `max_batch_rows` and `geometry_encoding`, and needs
+    geopandas and pyarrow.
+
+    Returns the number of hexes written.
+    """
+    try:
+        import geopandas
+        import shapely
+    except ImportError as e:
+        raise ImportError(
+            "write_hex_summary_gpkg requires geopandas; install it with "
+            "`pip install geopandas`"
+        ) from e
+    try:
+        import pyarrow
+    except ImportError as e:
+        raise ImportError(
+            "write_hex_summary_gpkg requires pyarrow; install it with "
+            "`pip install infra-hex-py[pyarrow]`"
+        ) from e
+
+    for name in ("output", "output_layout", "max_batch_rows", "geometry_encoding"):
+        if name in kwargs:
+            raise TypeError(
+                "write_hex_summary_gpkg() got an unexpected keyword argument "
+                "'{}'".format(name)
+            )
+    area = get_built_up_area(object_id, http_options=kwargs.get("http_options"))
+    batch = get_hex_summary_polygon_area(
+        object_id, resolution, output="pyarrow", **kwargs
+    )
+
+    # GeoPackage has no dictionary type; store provider and parent ids as strings
+    table = pyarrow.Table.from_batches([batch])
+    for i, field in enumerate(table.schema):
+        if pyarrow.types.is_dictionary(field.type):
+            column = table.column(i).cast(field.type.value_type)
+            table = table.set_column(i, field.with_type(field.type.value_type), column)
+    hexes = geopandas.GeoDataFrame.from_arrow(table)
+
+    boundary = geopandas.GeoDataFrame(
+        {
+            "object_id": [area["object_id"]],
+            "code": [area["code"]],
+            "name": [area["name"]],
+        },
+        geometry=[shapely.from_wkb(area["wkb"])],
+        crs=4326,
+    ).to_crs(27700)
+    if kwargs.get("buffer_m"):
+        boundary.geometry = boundary.buffer(kwargs["buffer_m"])
+
+    # Writing a layer keeps the file's other layers, so start from a fresh file
+    if os.path.exists(path):
+        os.remove(path)
+    hexes.to_file(path, layer="hex_summary", driver="GPKG")
+    boundary.to_file(path, layer="area_boundary", driver="GPKG")
+    return len(hexes)
+
+
+@contextlib.contextmanager
+def session():
+    """Calls `shutdown_runtime` on leaving the block, even if it raised.
+
+    Fetches inside the block share the runtime and pooled connections as usual;
+    on exit their worker threads stop and the connections close, so tests and
+    short-lived workers do not leave them behind.
+    """
+    try:
+        yield
+    finally:
+        shutdown_runtime()
 
-__all__ = ["get_hex_summary", "get_hex_summary_polygon_area"]
 
 # Optional viz exports (require infra-hex-py[viz])
 try:
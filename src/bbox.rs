@@ -0,0 +1,390 @@
+use geo::{BoundingRect, GeodesicArea, Geometry, Rect, coord};
+use geojson::GeoJson;
+use infra_hex_rs::BBox;
+use pyo3::prelude::*;
+
+use crate::crs::check_bng_coverage;
+use crate::summary::{Resolution, auto_resolution, bbox_polygon, validate_resolution};
+
+/// A WGS84 bounding box with explicit, named corner constructors.
+///
+/// Replaces four loose floats, whose (lat, lon) vs (lon, lat) ordering is easy to
+/// get wrong. Every constructor validates ranges and raises `ValueError` naming the
+/// offending parameter.
+#[pyclass(name = "BBox", module = "infra_hex_py", frozen)]
+#[derive(Debug, Clone, Copy)]
+pub struct PyBBox {
+    #[pyo3(get)]
+    pub min_lat: f64,
+    #[pyo3(get)]
+    pub min_lon: f64,
+    #[pyo3(get)]
+    pub max_lat: f64,
+    #[pyo3(get)]
+    pub max_lon: f64,
+}
+
+impl PyBBox {
+    fn try_new(min_lat: f64, min_lon: f64, max_lat: f64, max_lon: f64) -> PyResult<Self> {
+        for (name, value, limit) in [
+            ("min_lat", min_lat, 90.0),
+            ("max_lat", max_lat, 90.0),
+            ("min_lon", min_lon, 180.0),
+            ("max_lon", max_lon, 180.0),
+        ] {
+            if !(-limit..=limit).contains(&value) {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "{} must be between {} and {}, got {}",
+                    name, -limit, limit, value
+                )));
+            }
+        }
+        if min_lat >= max_lat {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "min_lat ({}) must be less than max_lat ({})",
+                min_lat, max_lat
+            )));
+        }
+        if min_lon > max_lon {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "min_lon ({}) is greater than max_lon ({}); bounding boxes crossing the \
+                 antimeridian are not supported",
+                min_lon, max_lon
+            )));
+        }
+        if min_lon == max_lon {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "min_lon ({}) must be less than max_lon ({})",
+                min_lon, max_lon
+            )));
+        }
+
+        Ok(Self {
+            min_lat,
+            min_lon,
+            max_lat,
+            max_lon,
+        })
+    }
+
+    pub fn to_bbox(self) -> BBox {
+        BBox::new(self.min_lat, self.min_lon, self.max_lat, self.max_lon)
+    }
+
+    fn rect(&self) -> Rect<f64> {
+        Rect::new(
+            coord! { x: self.min_lon, y: self.min_lat },
+            coord! { x: self.max_lon, y: self.max_lat },
+        )
+    }
+}
+
+#[pymethods]
+impl PyBBox {
+    #[new]
+    #[pyo3(signature = (*, min_lat, min_lon, max_lat, max_lon))]
+    fn new(min_lat: f64, min_lon: f64, max_lat: f64, max_lon: f64) -> PyResult<Self> {
+        Self::try_new(min_lat, min_lon, max_lat, max_lon)
+    }
+
+    /// Build from named corners (keyword-only).
+    #[staticmethod]
+    #[pyo3(signature = (*, min_lat, min_lon, max_lat, max_lon))]
+    fn from_corners(min_lat: f64, min_lon: f64, max_lat: f64, max_lon: f64) -> PyResult<Self> {
+        Self::try_new(min_lat, min_lon, max_lat, max_lon)
+    }
+
+    /// Build from `(minx, miny, maxx, maxy)`, i.e. shapely / GeoPandas `bounds` order.
+    #[staticmethod]
+    fn from_bounds(minx: f64, miny: f64, maxx: f64, maxy: f64) -> PyResult<Self> {
+        Self::try_new(miny, minx, maxy, maxx)
+    }
+
+    /// Build from the envelope of a GeoJSON geometry, Feature or FeatureCollection.
+    #[staticmethod]
+    fn from_geojson(geojson: &str) -> PyResult<Self> {
+        let geojson: GeoJson = geojson.parse().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid GeoJSON: {}", e))
+        })?;
+        let geometry = Geometry::<f64>::try_from(geojson).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid GeoJSON geometry: {}",
+                e
+            ))
+        })?;
+        let rect = geometry.bounding_rect().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>("GeoJSON geometry is empty")
+        })?;
+
+        Self::try_new(rect.min().y, rect.min().x, rect.max().y, rect.max().x)
+    }
+
+    /// Geodesic area of the box on the WGS84 ellipsoid, in square kilometres.
+    #[getter]
+    fn area_km2(&self) -> f64 {
+        self.rect().to_polygon().geodesic_area_unsigned() / 1_000_000.0
+    }
+
+    /// Split into an `n` x `n` grid of tiles, ordered south to north then west to
+    /// east. Adjacent tiles share their edges exactly.
+    fn split(&self, n: usize) -> PyResult<Vec<Self>> {
+        if n == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "n must be at least 1",
+            ));
+        }
+
+        let edges = |min: f64, max: f64| -> Vec<f64> {
+            (0..=n)
+                .map(|i| match i {
+                    0 => min,
+                    i if i == n => max,
+                    i => min + (max - min) * i as f64 / n as f64,
+                })
+                .collect()
+        };
+        let lats = edges(self.min_lat, self.max_lat);
+        let lons = edges(self.min_lon, self.max_lon);
+
+        let mut tiles = Vec::with_capacity(n * n);
+        for lat in lats.windows(2) {
+            for lon in lons.windows(2) {
+                tiles.push(Self {
+                    min_lat: lat[0],
+                    min_lon: lon[0],
+                    max_lat: lat[1],
+                    max_lon: lon[1],
+                });
+            }
+        }
+        Ok(tiles)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "BBox(min_lat={}, min_lon={}, max_lat={}, max_lon={})",
+            self.min_lat, self.min_lon, self.max_lat, self.max_lon
+        )
+    }
+}
+
+/// Resolves the `(min_lat, min_lon, max_lat, max_lon)` arguments of functions that
+/// also accept a single [`PyBBox`] in place of the four floats.
+///
+/// Four floats are validated as [`PyBBox`] corners are, and either way the box
+/// must overlap the area the BNG hex grid covers (see [`check_bng_coverage`]).
+pub fn resolve_bbox(
+    min_lat: &Bound<'_, PyAny>,
+    min_lon: Option<&Bound<'_, PyAny>>,
+    max_lat: Option<&Bound<'_, PyAny>>,
+    max_lon: Option<&Bound<'_, PyAny>>,
+) -> PyResult<BBox> {
+    if let Ok(bbox) = min_lat.cast::<PyBBox>() {
+        if min_lon.is_some() || max_lat.is_some() || max_lon.is_some() {
+            return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "Expected either a BBox or four corner floats",
+            ));
+        }
+        return covered(bbox.get().to_bbox());
+    }
+    covered(bbox_from_floats(min_lat, min_lon, max_lat, max_lon)?)
+}
+
+/// A `resolution` argument of the bbox-based functions: a hex resolution, or
+/// `"auto"` to choose one from the query area (see [`auto_resolution`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolutionArg {
+    Fixed(u8),
+    Auto,
+}
+
+impl ResolutionArg {
+    /// Reads an integer resolution or the string `"auto"`.
+    ///
+    /// # Errors
+    /// Raises `ValueError` for any other string, and `TypeError` or
+    /// `OverflowError` for values that are not an integer from 0 to 255.
+    pub fn extract(value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        match value.extract::<String>() {
+            Ok(name) if name == "auto" => Ok(Self::Auto),
+            Ok(name) => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "resolution must be an integer or \"auto\", got {:?}",
+                name
+            ))),
+            Err(_) => value.extract().map(Self::Fixed),
+        }
+    }
+
+    /// Validates a fixed resolution, or chooses one aiming for
+    /// `target_hex_count` hexes over `bbox`.
+    pub fn resolve(self, bbox: &BBox, target_hex_count: u64) -> PyResult<Resolution> {
+        match self {
+            Self::Fixed(resolution) => validate_resolution(resolution).map(Resolution::Fixed),
+            Self::Auto => {
+                auto_resolution(&bbox_polygon(bbox), target_hex_count).map(Resolution::Auto)
+            }
+        }
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+}
+
+/// Resolves the `(min_lat, min_lon, max_lat, max_lon, resolution)` arguments of
+/// the bbox-based summary functions, which also accept a [`PyBBox`] in place of
+/// the four floats (`f(bbox, resolution)`). The box is checked as in
+/// [`resolve_bbox`] and the resolution with [`ResolutionArg::resolve`].
+pub fn resolve_bbox_args(
+    min_lat: &Bound<'_, PyAny>,
+    min_lon: Option<&Bound<'_, PyAny>>,
+    max_lat: Option<&Bound<'_, PyAny>>,
+    max_lon: Option<&Bound<'_, PyAny>>,
+    resolution: Option<ResolutionArg>,
+    target_hex_count: u64,
+) -> PyResult<(BBox, Resolution)> {
+    let (bbox, resolution) =
+        bbox_and_resolution_args(min_lat, min_lon, max_lat, max_lon, resolution)?;
+    let resolution = resolution.ok_or_else(|| {
+        PyErr::new::<pyo3::exceptions::PyTypeError, _>("Missing required argument: resolution")
+    })?;
+    let resolution = resolution.resolve(&bbox, target_hex_count)?;
+    Ok((bbox, resolution))
+}
+
+/// [`resolve_bbox_args`] without resolving the resolution, which is `None` if
+/// not given, as for a summary of grid squares.
+pub fn bbox_and_resolution_args(
+    min_lat: &Bound<'_, PyAny>,
+    min_lon: Option<&Bound<'_, PyAny>>,
+    max_lat: Option<&Bound<'_, PyAny>>,
+    max_lon: Option<&Bound<'_, PyAny>>,
+    resolution: Option<ResolutionArg>,
+) -> PyResult<(BBox, Option<ResolutionArg>)> {
+    if let Ok(bbox) = min_lat.cast::<PyBBox>() {
+        if max_lat.is_some() || max_lon.is_some() {
+            return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "Expected (bbox, resolution) when a BBox is given",
+            ));
+        }
+        let resolution = match (min_lon, resolution) {
+            (Some(_), Some(_)) => {
+                return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                    "resolution given both positionally and by keyword",
+                ));
+            }
+            (Some(value), None) => Some(ResolutionArg::extract(value)?),
+            (None, resolution) => resolution,
+        };
+        Ok((covered(bbox.get().to_bbox())?, resolution))
+    } else {
+        Ok((
+            covered(bbox_from_floats(min_lat, min_lon, max_lat, max_lon)?)?,
+            resolution,
+        ))
+    }
+}
+
+fn bbox_from_floats(
+    min_lat: &Bound<'_, PyAny>,
+    min_lon: Option<&Bound<'_, PyAny>>,
+    max_lat: Option<&Bound<'_, PyAny>>,
+    max_lon: Option<&Bound<'_, PyAny>>,
+) -> PyResult<BBox> {
+    let required = |name: &str, value: Option<&Bound<'_, PyAny>>| -> PyResult<f64> {
+        value
+            .ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+                    "Missing required argument: {}",
+                    name
+                ))
+            })?
+            .extract()
+    };
+    PyBBox::try_new(
+        min_lat.extract()?,
+        required("min_lon", min_lon)?,
+        required("max_lat", max_lat)?,
+        required("max_lon", max_lon)?,
+    )
+    .map(PyBBox::to_bbox)
+}
+
+fn covered(bbox: BBox) -> PyResult<BBox> {
+    check_bng_coverage(&bbox).map_err(|e| {
+        let swapped = swap_lat_lon(&bbox);
+        let hint = if in_great_britain(&swapped) {
+            format!(
+                ". Read the other way round, as {}, it would be in Great Britain: the \
+                 latitudes and longitudes may be swapped",
+                describe_bbox(&swapped)
+            )
+        } else {
+            String::new()
+        };
+        PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{}{}", e, hint))
+    })?;
+    Ok(bbox)
+}
+
+/// Rough extent of Great Britain, `[min_lon, min_lat, max_lon, max_lat]`: a box
+/// centred outside it is more likely a coordinate-order mistake than a real query.
+pub const GREAT_BRITAIN_EXTENT: [f64; 4] = [-8.7, 49.8, 1.8, 60.9];
+
+/// Whether the centre of `bbox` lies within [`GREAT_BRITAIN_EXTENT`].
+pub fn in_great_britain(bbox: &BBox) -> bool {
+    let [min_lon, min_lat, max_lon, max_lat] = GREAT_BRITAIN_EXTENT;
+    let lat = (bbox.min_lat + bbox.max_lat) / 2.0;
+    let lon = (bbox.min_lon + bbox.max_lon) / 2.0;
+    (min_lat..=max_lat).contains(&lat) && (min_lon..=max_lon).contains(&lon)
+}
+
+/// `bbox` with its latitudes read as longitudes and vice versa.
+pub fn swap_lat_lon(bbox: &BBox) -> BBox {
+    BBox::new(bbox.min_lon, bbox.min_lat, bbox.max_lon, bbox.max_lat)
+}
+
+/// `lat <min> to <max>, lon <min> to <max>`, for messages.
+pub fn describe_bbox(bbox: &BBox) -> String {
+    format!(
+        "lat {} to {}, lon {} to {}",
+        bbox.min_lat, bbox.max_lat, bbox.min_lon, bbox.max_lon
+    )
+}
+
+/// Reorders bbox arguments given as `(min_lon, min_lat, max_lon, max_lat)`, for
+/// functions taking `lonlat=True`, into the `(min_lat, min_lon, max_lat, max_lon)`
+/// order [`resolve_bbox_args`] expects. Without `lonlat` they are returned as given.
+///
+/// # Errors
+/// Raises `TypeError` if `lonlat` is set with a [`PyBBox`], whose corners are
+/// named, or without all four floats.
+#[allow(clippy::type_complexity)]
+pub fn lonlat_bbox_args<'a, 'py>(
+    min_lat: &'a Bound<'py, PyAny>,
+    min_lon: Option<&'a Bound<'py, PyAny>>,
+    max_lat: Option<&'a Bound<'py, PyAny>>,
+    max_lon: Option<&'a Bound<'py, PyAny>>,
+    lonlat: bool,
+) -> PyResult<(
+    &'a Bound<'py, PyAny>,
+    Option<&'a Bound<'py, PyAny>>,
+    Option<&'a Bound<'py, PyAny>>,
+    Option<&'a Bound<'py, PyAny>>,
+)> {
+    if !lonlat {
+        return Ok((min_lat, min_lon, max_lat, max_lon));
+    }
+    if min_lat.cast::<PyBBox>().is_ok() {
+        return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+            "lonlat=True applies to four corner floats, not a BBox, whose corners are named",
+        ));
+    }
+    // Positionally, `min_lat` holds the minimum longitude, `min_lon` the minimum
+    // latitude, and so on
+    match (min_lon, max_lat, max_lon) {
+        (Some(lat_min), Some(lon_max), Some(lat_max)) => {
+            Ok((lat_min, Some(min_lat), Some(lat_max), Some(lon_max)))
+        }
+        _ => Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+            "lonlat=True expects four corner floats: (min_lon, min_lat, max_lon, max_lat)",
+        )),
+    }
+}
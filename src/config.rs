@@ -0,0 +1,109 @@
+//! Session-wide defaults for `http_options`, set by `configure` and read back
+//! with `get_config`.
+//!
+//! The defaults are kept as the Python values they were given as, so every fetch
+//! parses them together with its own `http_options` and a per-call key always
+//! takes precedence over the configured one.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// The `http_options` keys set by `configure`, with their values.
+static DEFAULTS: Mutex<BTreeMap<String, Py<PyAny>>> = Mutex::new(BTreeMap::new());
+
+/// `http_options` keys whose values are never shown in a `Config` repr.
+const SECRET_KEYS: [&str; 3] = ["api_key", "proxy_password", "proxy_url"];
+
+/// The configured defaults, as a dict a call's `http_options` can be merged into.
+pub fn defaults(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    PyConfig::current(py).options(py)
+}
+
+/// Sets each of `options`, removing those given as `None`, and returns the
+/// defaults that result without storing them yet.
+pub fn merged_with<'py>(
+    py: Python<'py>,
+    options: &Bound<'py, PyDict>,
+) -> PyResult<BTreeMap<String, Py<PyAny>>> {
+    let mut merged = PyConfig::current(py).options;
+    for (key, value) in options.iter() {
+        let key: String = key.extract()?;
+        if value.is_none() {
+            merged.remove(&key);
+        } else {
+            merged.insert(key, value.unbind());
+        }
+    }
+    Ok(merged)
+}
+
+/// Replaces every default with `options`.
+pub fn store(options: BTreeMap<String, Py<PyAny>>) {
+    *DEFAULTS.lock().unwrap_or_else(PoisonError::into_inner) = options;
+}
+
+/// A snapshot of the defaults set with `configure`, as `get_config` returns it.
+///
+/// Keys are `http_options` keys; a call's own `http_options` take precedence
+/// over them. Later `configure` calls do not change a snapshot already taken.
+#[pyclass(name = "Config", module = "infra_hex_py", frozen)]
+pub struct PyConfig {
+    options: BTreeMap<String, Py<PyAny>>,
+}
+
+impl PyConfig {
+    /// The defaults in effect now.
+    pub fn current(py: Python<'_>) -> Self {
+        let options = DEFAULTS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone_ref(py)))
+            .collect();
+        PyConfig { options }
+    }
+}
+
+#[pymethods]
+impl PyConfig {
+    /// The configured `http_options`, as a new dict.
+    #[getter]
+    fn options<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        for (key, value) in &self.options {
+            dict.set_item(key, value.bind(py))?;
+        }
+        Ok(dict)
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &str) -> PyResult<Py<PyAny>> {
+        self.options
+            .get(key)
+            .map(|value| value.clone_ref(py))
+            .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyKeyError, _>(key.to_string()))
+    }
+
+    fn __contains__(&self, key: &str) -> bool {
+        self.options.contains_key(key)
+    }
+
+    fn __len__(&self) -> usize {
+        self.options.len()
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        let mut options = Vec::with_capacity(self.options.len());
+        for (key, value) in &self.options {
+            let value = if SECRET_KEYS.contains(&key.as_str()) {
+                "<redacted>".to_string()
+            } else {
+                value.bind(py).repr()?.to_string()
+            };
+            options.push(format!("{}={}", key, value));
+        }
+        Ok(format!("Config({})", options.join(", ")))
+    }
+}
@@ -0,0 +1,266 @@
+use geo::{Buffer, Coord, MapCoords, MultiPolygon, coord};
+use infra_hex_rs::{BBox, InfraHexError};
+use proj::Proj;
+
+/// Valid British National Grid extent in metres: `[min_e, min_n, max_e, max_n]`.
+pub const BNG_EXTENT: [f64; 4] = [0.0, 0.0, 700_000.0, 1_300_000.0];
+
+/// EPSG's WGS84 area of use for British National Grid:
+/// `[min_lon, min_lat, max_lon, max_lat]`.
+pub const BNG_AREA_OF_USE: [f64; 4] = [-9.01, 49.75, 2.01, 61.01];
+
+/// Rejects a WGS84 bounding box that lies wholly outside [`BNG_AREA_OF_USE`].
+///
+/// The hex grid is defined in BNG, whose projection distorts rapidly away from
+/// Great Britain, so boxes elsewhere (near the poles, say) would silently yield
+/// meaningless hexes. Boxes that only partly overlap are fine: hexes are clipped
+/// to the box.
+///
+/// # Errors
+/// Returns [`InfraHexError::Config`] if the box does not overlap the area of use.
+pub fn check_bng_coverage(bbox: &BBox) -> Result<(), InfraHexError> {
+    let [min_lon, min_lat, max_lon, max_lat] = BNG_AREA_OF_USE;
+    if bbox.max_lon < min_lon
+        || bbox.min_lon > max_lon
+        || bbox.max_lat < min_lat
+        || bbox.min_lat > max_lat
+    {
+        return Err(InfraHexError::Config(format!(
+            "Bounding box ({}, {}) to ({}, {}) lies outside the British National Grid area \
+             of use (lat {} to {}, lon {} to {}); the hex grid only covers Great Britain",
+            bbox.min_lat,
+            bbox.min_lon,
+            bbox.max_lat,
+            bbox.max_lon,
+            min_lat,
+            max_lat,
+            min_lon,
+            max_lon
+        )));
+    }
+    Ok(())
+}
+
+/// Converts a British National Grid (EPSG:27700) extent to a WGS84 [`BBox`].
+///
+/// All four corners are transformed with PROJ's OSGB36 -> WGS84 pipeline and the
+/// result is their lat/lon envelope, since an axis-aligned BNG rectangle is not
+/// axis-aligned in WGS84.
+///
+/// # Errors
+/// Returns [`InfraHexError::Config`] if the extent is inverted or falls outside
+/// [`BNG_EXTENT`].
+pub fn bng_extent_to_wgs84(
+    min_e: f64,
+    min_n: f64,
+    max_e: f64,
+    max_n: f64,
+) -> Result<BBox, InfraHexError> {
+    let [bng_min_e, bng_min_n, bng_max_e, bng_max_n] = BNG_EXTENT;
+    for (name, value, lo, hi) in [
+        ("min_e", min_e, bng_min_e, bng_max_e),
+        ("max_e", max_e, bng_min_e, bng_max_e),
+        ("min_n", min_n, bng_min_n, bng_max_n),
+        ("max_n", max_n, bng_min_n, bng_max_n),
+    ] {
+        if !(lo..=hi).contains(&value) {
+            return Err(InfraHexError::Config(format!(
+                "{} = {} is outside the British National Grid domain ({} to {})",
+                name, value, lo, hi
+            )));
+        }
+    }
+    if min_e >= max_e || min_n >= max_n {
+        return Err(InfraHexError::Config(format!(
+            "Empty BNG extent: ({}, {}) to ({}, {})",
+            min_e, min_n, max_e, max_n
+        )));
+    }
+
+    let proj = Proj::new_known_crs("EPSG:27700", "EPSG:4326", None)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+
+    let mut bbox = BBox::new(f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for corner in [
+        (min_e, min_n),
+        (min_e, max_n),
+        (max_e, min_n),
+        (max_e, max_n),
+    ] {
+        let (lon, lat) = proj
+            .convert(corner)
+            .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+        bbox.min_lat = bbox.min_lat.min(lat);
+        bbox.min_lon = bbox.min_lon.min(lon);
+        bbox.max_lat = bbox.max_lat.max(lat);
+        bbox.max_lon = bbox.max_lon.max(lon);
+    }
+
+    Ok(bbox)
+}
+
+/// A PROJ transform between two CRSs, created once and reused across geometries
+/// (setting up a PROJ context is far slower than transforming a few vertices).
+pub struct Transform(Proj);
+
+impl Transform {
+    /// WGS84 (lon/lat) to British National Grid, as used by the hex grid.
+    pub fn wgs84_to_bng() -> Result<Self, InfraHexError> {
+        Self::new("EPSG:4326", "EPSG:27700")
+    }
+
+    /// British National Grid to WGS84 (lon/lat).
+    pub fn bng_to_wgs84() -> Result<Self, InfraHexError> {
+        Self::new("EPSG:27700", "EPSG:4326")
+    }
+
+    fn new(from: &str, to: &str) -> Result<Self, InfraHexError> {
+        Proj::new_known_crs(from, to, None)
+            .map(Self)
+            .map_err(|e| InfraHexError::Geometry(e.to_string()))
+    }
+
+    /// Transforms every vertex of `geometry`; edges stay straight in the target CRS.
+    pub fn apply<G>(&self, geometry: &G) -> Result<G, InfraHexError>
+    where
+        G: MapCoords<f64, f64, Output = G>,
+    {
+        geometry.try_map_coords(|c| {
+            let (x, y) = self
+                .0
+                .convert((c.x, c.y))
+                .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+            Ok(coord! { x: x, y: y })
+        })
+    }
+}
+
+/// Projects a WGS84 (lon/lat) multipolygon to British National Grid (EPSG:27700).
+pub fn wgs84_multipolygon_to_bng(
+    geometry: &MultiPolygon<f64>,
+) -> Result<MultiPolygon<f64>, InfraHexError> {
+    Transform::wgs84_to_bng()?.apply(geometry)
+}
+
+/// Buffers a WGS84 multipolygon outward by `distance_m` metres.
+///
+/// The buffer is computed in BNG, so the distance is accurate anywhere in Great
+/// Britain rather than varying with latitude as a buffer in degrees would.
+pub fn buffer_wgs84_multipolygon(
+    geometry: &MultiPolygon<f64>,
+    distance_m: f64,
+) -> Result<MultiPolygon<f64>, InfraHexError> {
+    let buffered = wgs84_multipolygon_to_bng(geometry)?.buffer(distance_m);
+    Transform::bng_to_wgs84()?.apply(&buffered)
+}
+
+/// A circle of `radius_m` metres around the WGS84 point (`lat`, `lon`).
+///
+/// Like [`buffer_wgs84_multipolygon`], the circle is drawn in BNG, so it stays
+/// round on the ground instead of stretching east-west as one in degrees would.
+pub fn wgs84_circle(lat: f64, lon: f64, radius_m: f64) -> Result<MultiPolygon<f64>, InfraHexError> {
+    let centre = Transform::wgs84_to_bng()?.apply(&geo::Point::new(lon, lat))?;
+    Transform::bng_to_wgs84()?.apply(&centre.buffer(radius_m))
+}
+
+/// The CRS of incoming geometry, as named by a response's `srsName` or GeoJSON
+/// `crs` member, or inferred from its coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SourceCrs {
+    /// WGS84 in GeoJSON (lon, lat) axis order.
+    Wgs84,
+    /// WGS84 in EPSG authority (lat, lon) axis order, as WFS 2.0 servers return
+    /// for `urn:ogc:def:crs:EPSG::4326`.
+    Wgs84LatLon,
+    /// British National Grid eastings and northings.
+    Bng,
+}
+
+impl SourceCrs {
+    /// Parses an `srsName` such as `EPSG:27700`, `urn:ogc:def:crs:EPSG::4326`,
+    /// `http://www.opengis.net/def/crs/EPSG/0/27700` or `urn:ogc:def:crs:OGC:1.3:CRS84`.
+    ///
+    /// URN and `/def/crs/` URI forms of EPSG:4326 use the authority's (lat, lon)
+    /// axis order; the short `EPSG:4326` form and CRS84 are (lon, lat).
+    ///
+    /// # Errors
+    /// Returns [`InfraHexError::Config`] for any other CRS.
+    pub fn from_srs_name(name: &str) -> Result<Self, InfraHexError> {
+        let lower = name.trim().to_ascii_lowercase();
+        let code = lower.rsplit([':', '/', '#']).next().unwrap_or_default();
+        match code {
+            "crs84" => Ok(Self::Wgs84),
+            "4326" if lower.contains("def:crs") || lower.contains("/def/crs/") => {
+                Ok(Self::Wgs84LatLon)
+            }
+            "4326" => Ok(Self::Wgs84),
+            "27700" => Ok(Self::Bng),
+            _ => Err(InfraHexError::Config(format!(
+                "Unsupported CRS {:?}; expected WGS84 (EPSG:4326) or British National Grid \
+                 (EPSG:27700)",
+                name
+            ))),
+        }
+    }
+
+    /// Infers the CRS of a geometry whose location is also known as a named
+    /// (lon, lat) `reference` point, such as a Cadent record's `geo_point_2d`.
+    ///
+    /// Any coordinate outside the WGS84 range means BNG metres. Otherwise the
+    /// first vertex is compared with the reference in both axis orders.
+    pub fn detect(coords: impl IntoIterator<Item = Coord<f64>>, reference: Coord<f64>) -> Self {
+        let mut coords = coords.into_iter().peekable();
+        let Some(&first) = coords.peek() else {
+            return Self::Wgs84;
+        };
+        if std::iter::once(first)
+            .chain(coords)
+            .any(|c| c.x.abs() > 180.0 || c.y.abs() > 90.0)
+        {
+            return Self::Bng;
+        }
+
+        let distance = |a: Coord<f64>, b: Coord<f64>| (a.x - b.x).hypot(a.y - b.y);
+        let swapped = coord! { x: reference.y, y: reference.x };
+        if distance(first, swapped) < distance(first, reference) {
+            Self::Wgs84LatLon
+        } else {
+            Self::Wgs84
+        }
+    }
+
+    /// Name used in logs and the `source_crs` schema metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Wgs84 => "EPSG:4326",
+            Self::Wgs84LatLon => "EPSG:4326 (lat, lon)",
+            Self::Bng => "EPSG:27700",
+        }
+    }
+}
+
+/// Converts geometries from any [`SourceCrs`] to WGS84 (lon, lat), creating the
+/// PROJ transform only once a BNG geometry is seen.
+#[derive(Default)]
+pub struct Wgs84Reprojector {
+    bng: Option<Transform>,
+}
+
+impl Wgs84Reprojector {
+    pub fn apply<G>(&mut self, geometry: &G, crs: SourceCrs) -> Result<G, InfraHexError>
+    where
+        G: MapCoords<f64, f64, Output = G>,
+    {
+        match crs {
+            SourceCrs::Wgs84 => Ok(geometry.map_coords(|c| c)),
+            SourceCrs::Wgs84LatLon => Ok(geometry.map_coords(|c| coord! { x: c.y, y: c.x })),
+            SourceCrs::Bng => {
+                let transform = match self.bng.take() {
+                    Some(transform) => transform,
+                    None => Transform::bng_to_wgs84()?,
+                };
+                self.bng.insert(transform).apply(geometry)
+            }
+        }
+    }
+}
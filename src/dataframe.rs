@@ -0,0 +1,396 @@
+use std::sync::Arc;
+
+use arrow_array::ffi::{FFI_ArrowArray, FFI_ArrowSchema, to_ffi};
+use arrow_array::{Array, ArrayRef, RecordBatch, StructArray};
+use arrow_cast::cast;
+use arrow_schema::{DataType, Field};
+use geoarrow_array::GeoArrowArray;
+use geoarrow_array::array::from_arrow_array;
+use geoarrow_array::cast::to_wkb;
+use infra_hex_rs::InfraHexError;
+use polars_arrow::ffi::{ArrowArray, ArrowSchema, import_array_from_c, import_field_from_c};
+use polars_core::prelude::{Column, DataFrame, Series};
+use pyo3::prelude::*;
+use pyo3::types::{PyCapsule, PyList, PyTuple};
+use pyo3_arrow::ffi::{
+    ArrayIterator, to_array_pycapsules, to_schema_pycapsule, to_stream_pycapsule,
+};
+use pyo3_arrow::{PyRecordBatch, PyTable};
+use pyo3_polars::PyDataFrame;
+
+use crate::summary::normalise_hex_summary;
+
+/// The Python container a summary is returned in, chosen with `output=`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A `pyarrow.RecordBatch` if pyarrow is installed, otherwise a
+    /// [`PyArrowBatch`], so pyarrow is never required.
+    #[default]
+    Arrow,
+    /// Always a `pyarrow.RecordBatch` with a GeoArrow polygon `geometry` column.
+    PyArrow,
+    /// Always a [`PyArrowBatch`], for libraries that read the Arrow PyCapsule
+    /// interface (polars, DuckDB, nanoarrow, ...).
+    Capsule,
+    /// A `polars.DataFrame` with `geometry` as WKB binary, for pipelines that
+    /// do not otherwise need pyarrow.
+    Polars,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Result<Self, InfraHexError> {
+        match name {
+            "arrow" => Ok(Self::Arrow),
+            "pyarrow" => Ok(Self::PyArrow),
+            "capsule" => Ok(Self::Capsule),
+            "polars" => Ok(Self::Polars),
+            other => Err(InfraHexError::Config(format!(
+                "Unknown output {:?}; expected \"arrow\", \"pyarrow\", \"capsule\" or \"polars\"",
+                other
+            ))),
+        }
+    }
+
+    /// Hands `batch` to Python in this format.
+    ///
+    /// # Errors
+    /// Raises `ImportError` for [`OutputFormat::PyArrow`] when pyarrow is not
+    /// installed, and for [`OutputFormat::Polars`] when polars is not.
+    pub fn to_python(self, py: Python<'_>, batch: RecordBatch) -> PyResult<Py<PyAny>> {
+        match self {
+            Self::Arrow if py.import("pyarrow").is_err() => Self::Capsule.to_python(py, batch),
+            Self::Arrow | Self::PyArrow => {
+                require_pyarrow(py)?;
+                PyRecordBatch::new(batch)
+                    .into_pyarrow(py)
+                    .map(|bound| bound.unbind())
+                    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+            }
+            Self::Capsule => Ok(Py::new(py, PyArrowBatch(batch))?.into_any()),
+            Self::Polars => {
+                require_polars(py)?;
+                let df = to_polars(&batch).map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string())
+                })?;
+                PyDataFrame(df)
+                    .into_pyobject(py)
+                    .map(|bound| bound.unbind())
+            }
+        }
+    }
+
+    /// Hands `batch` to Python in this format as consecutive slices of at most
+    /// `max_rows` rows, sharing its buffers: a `pyarrow.Table` with one record
+    /// batch per slice, a list of [`PyArrowBatch`] for [`OutputFormat::Capsule`],
+    /// or a `polars.DataFrame` with one chunk per slice. Rows keep their order, so
+    /// the slices put back together are `batch`; a batch with no rows is one
+    /// empty slice.
+    ///
+    /// # Errors
+    /// As for [`Self::to_python`].
+    pub fn to_python_batches(
+        self,
+        py: Python<'_>,
+        batch: RecordBatch,
+        max_rows: usize,
+    ) -> PyResult<Py<PyAny>> {
+        let slices = split_batch(&batch, max_rows);
+        match self {
+            Self::Arrow if py.import("pyarrow").is_err() => {
+                Self::Capsule.to_python_batches(py, batch, max_rows)
+            }
+            Self::Arrow | Self::PyArrow => {
+                require_pyarrow(py)?;
+                PyTable::try_new(slices, batch.schema())?
+                    .into_pyarrow(py)
+                    .map(|bound| bound.unbind())
+            }
+            Self::Capsule => {
+                let slices = slices
+                    .into_iter()
+                    .map(|slice| Py::new(py, PyArrowBatch(slice)))
+                    .collect::<PyResult<Vec<_>>>()?;
+                Ok(PyList::new(py, slices)?.into_any().unbind())
+            }
+            Self::Polars => {
+                require_polars(py)?;
+                let mut df: Option<DataFrame> = None;
+                for slice in &slices {
+                    let chunk = to_polars(slice).map_err(|e| {
+                        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string())
+                    })?;
+                    match &mut df {
+                        None => df = Some(chunk),
+                        Some(df) => {
+                            df.vstack_mut_owned(chunk).map_err(|e| {
+                                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string())
+                            })?;
+                        }
+                    }
+                }
+                PyDataFrame(df.unwrap_or_default())
+                    .into_pyobject(py)
+                    .map(|bound| bound.unbind())
+            }
+        }
+    }
+}
+
+fn require_pyarrow(py: Python<'_>) -> PyResult<()> {
+    py.import("pyarrow").map(drop).map_err(|_| {
+        PyErr::new::<pyo3::exceptions::PyImportError, _>(
+            "output=\"pyarrow\" requires the pyarrow package; install it with \
+             `pip install pyarrow`, or use output=\"capsule\"",
+        )
+    })
+}
+
+fn require_polars(py: Python<'_>) -> PyResult<()> {
+    // pyo3-polars imports polars lazily and panics if it is missing
+    py.import("polars").map(drop).map_err(|_| {
+        PyErr::new::<pyo3::exceptions::PyImportError, _>(
+            "output=\"polars\" requires the polars package; install it with \
+             `pip install polars`, or use the default output=\"arrow\"",
+        )
+    })
+}
+
+/// `batch` as zero-copy slices of at most `max_rows` rows each, in order; one
+/// empty slice when it has no rows.
+fn split_batch(batch: &RecordBatch, max_rows: usize) -> Vec<RecordBatch> {
+    let rows = batch.num_rows();
+    if rows == 0 {
+        return vec![batch.clone()];
+    }
+    (0..rows)
+        .step_by(max_rows.max(1))
+        .map(|offset| batch.slice(offset, max_rows.min(rows - offset)))
+        .collect()
+}
+
+/// How a summary is laid out, chosen with `output_layout=`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputLayout {
+    /// One batch, each row carrying its hex's geometry.
+    #[default]
+    Wide,
+    /// A [`PyNormalizedSummary`]: the measures, and a lookup of each hex's
+    /// geometry stored once (see [`normalise_hex_summary`]).
+    Normalized,
+}
+
+impl OutputLayout {
+    pub fn from_name(name: &str) -> Result<Self, InfraHexError> {
+        match name {
+            "wide" => Ok(Self::Wide),
+            "normalized" => Ok(Self::Normalized),
+            other => Err(InfraHexError::Config(format!(
+                "Unknown output_layout {:?}; expected \"wide\" or \"normalized\"",
+                other
+            ))),
+        }
+    }
+}
+
+/// Where a summary goes: its [`OutputFormat`] and [`OutputLayout`], and with
+/// `max_batch_rows` the most rows a wide summary's batches may hold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SummaryOutput {
+    pub format: OutputFormat,
+    pub layout: OutputLayout,
+    pub max_batch_rows: Option<usize>,
+}
+
+impl SummaryOutput {
+    /// Hands `batch` to Python in this layout, each batch in this format.
+    pub fn to_python(self, py: Python<'_>, batch: RecordBatch) -> PyResult<Py<PyAny>> {
+        match self.layout {
+            OutputLayout::Wide => match self.max_batch_rows {
+                Some(max_rows) => self.format.to_python_batches(py, batch, max_rows),
+                None => self.format.to_python(py, batch),
+            },
+            OutputLayout::Normalized => {
+                let (cells, summary) = normalise_hex_summary(&batch).map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string())
+                })?;
+                let normalized = PyNormalizedSummary {
+                    cells: self.format.to_python(py, cells)?,
+                    summary: self.format.to_python(py, summary)?,
+                };
+                Ok(Py::new(py, normalized)?.into_any())
+            }
+        }
+    }
+}
+
+/// A summary returned with `output_layout="normalized"`, as two tables.
+///
+/// `cells` has one row per distinct hex, with its `hex_id`, `resolution` and
+/// `geometry`; `summary` has `hex_id` and every measure column, and carries the
+/// schema metadata. Looking each `summary` row up in `cells` by `hex_id` gives
+/// the `output_layout="wide"` batch.
+#[pyclass(name = "NormalizedSummary", module = "infra_hex_py", frozen)]
+pub struct PyNormalizedSummary {
+    #[pyo3(get)]
+    cells: Py<PyAny>,
+    #[pyo3(get)]
+    summary: Py<PyAny>,
+}
+
+#[pymethods]
+impl PyNormalizedSummary {
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!(
+            "NormalizedSummary(cells={}, summary={})",
+            self.cells.bind(py).len()?,
+            self.summary.bind(py).len()?
+        ))
+    }
+}
+
+/// A record batch exported through the Arrow PyCapsule interface, returned with
+/// `output="capsule"` (or by default when pyarrow is not installed).
+///
+/// Holds one record batch and hands it out zero-copy to any library that reads
+/// `__arrow_c_stream__` or `__arrow_c_array__`, e.g. `pyarrow.table(summary)`,
+/// `polars.DataFrame(summary)` or `duckdb.arrow(summary)`. Schema metadata
+/// (`skipped_features`, ...) is exported along with the columns.
+#[pyclass(name = "ArrowBatch", module = "infra_hex_py", frozen)]
+pub struct PyArrowBatch(RecordBatch);
+
+impl PyArrowBatch {
+    /// The batch as a single struct column, carrying the schema metadata.
+    fn struct_field(&self) -> Field {
+        let schema = self.0.schema();
+        Field::new_struct("", schema.fields().clone(), false)
+            .with_metadata(schema.metadata().clone())
+    }
+}
+
+#[pymethods]
+impl PyArrowBatch {
+    /// Number of rows (hexes) in the summary.
+    #[getter]
+    fn num_rows(&self) -> usize {
+        self.0.num_rows()
+    }
+
+    /// Names of the summary's columns, in order.
+    #[getter]
+    fn column_names(&self) -> Vec<String> {
+        self.0
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.num_rows()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ArrowBatch(num_rows={}, columns={:?})",
+            self.0.num_rows(),
+            self.column_names()
+        )
+    }
+
+    fn __arrow_c_schema__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyCapsule>> {
+        Ok(to_schema_pycapsule(py, self.0.schema_ref().as_ref())?)
+    }
+
+    #[pyo3(signature = (requested_schema=None))]
+    fn __arrow_c_array__<'py>(
+        &self,
+        py: Python<'py>,
+        requested_schema: Option<Bound<'py, PyCapsule>>,
+    ) -> PyResult<Bound<'py, PyTuple>> {
+        let array: ArrayRef = Arc::new(StructArray::from(self.0.clone()));
+        Ok(to_array_pycapsules(
+            py,
+            Arc::new(self.struct_field()),
+            &array,
+            requested_schema,
+        )?)
+    }
+
+    #[pyo3(signature = (requested_schema=None))]
+    fn __arrow_c_stream__<'py>(
+        &self,
+        py: Python<'py>,
+        requested_schema: Option<Bound<'py, PyCapsule>>,
+    ) -> PyResult<Bound<'py, PyCapsule>> {
+        let array: ArrayRef = Arc::new(StructArray::from(self.0.clone()));
+        let reader = ArrayIterator::new(std::iter::once(Ok(array)), Arc::new(self.struct_field()));
+        Ok(to_stream_pycapsule(py, Box::new(reader), requested_schema)?)
+    }
+}
+
+/// Converts a summary batch to a polars [`DataFrame`].
+///
+/// Columns cross over through the Arrow C data interface, so numeric buffers are
+/// shared rather than copied; strings and binary are converted to polars' view
+/// layout. GeoArrow geometry is encoded as
+/// WKB and dictionary columns such as `provider` are decoded to strings. Schema
+/// metadata has no polars equivalent and is dropped.
+fn to_polars(batch: &RecordBatch) -> Result<DataFrame, InfraHexError> {
+    let schema = batch.schema();
+    let columns = schema
+        .fields()
+        .iter()
+        .zip(batch.columns())
+        .map(|(field, column)| {
+            let (field, column) = polars_compatible(field, column)?;
+            import_series(&field, &column).map(Column::from)
+        })
+        .collect::<Result<Vec<_>, InfraHexError>>()?;
+
+    DataFrame::new(batch.num_rows(), columns).map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+fn polars_compatible(field: &Field, column: &ArrayRef) -> Result<(Field, ArrayRef), InfraHexError> {
+    if field
+        .extension_type_name()
+        .is_some_and(|name| name.starts_with("geoarrow."))
+    {
+        let geometry = from_arrow_array(column.as_ref(), field)
+            .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+        let wkb = to_wkb::<i32>(geometry.as_ref())
+            .map_err(|e| InfraHexError::Geometry(e.to_string()))?
+            .to_array_ref();
+        let field = Field::new(field.name(), DataType::Binary, field.is_nullable());
+        return Ok((field, wkb));
+    }
+    if let DataType::Dictionary(_, values) = field.data_type() {
+        let decoded = cast(column, values).map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+        let field = Field::new(field.name(), values.as_ref().clone(), field.is_nullable());
+        return Ok((field, decoded));
+    }
+    Ok((field.clone(), Arc::clone(column)))
+}
+
+fn import_series(field: &Field, column: &ArrayRef) -> Result<Series, InfraHexError> {
+    let (array, _) =
+        to_ffi(&column.to_data()).map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+    let schema =
+        FFI_ArrowSchema::try_from(field).map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+
+    // SAFETY: arrow-rs and polars-arrow both implement the Arrow C data interface,
+    // whose structs have one fixed `#[repr(C)]` layout. Ownership of `array`'s
+    // buffers passes to polars, which calls its release callback when done;
+    // `schema` is only borrowed and released by arrow-rs when it drops.
+    let array = unsafe {
+        let array = std::mem::transmute::<FFI_ArrowArray, ArrowArray>(array);
+        let schema = &*(&schema as *const FFI_ArrowSchema).cast::<ArrowSchema>();
+        let field =
+            import_field_from_c(schema).map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+        import_array_from_c(array, field.dtype)
+            .map_err(|e| InfraHexError::Geometry(e.to_string()))?
+    };
+
+    Series::from_arrow(field.name().as_str().into(), array)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
@@ -0,0 +1,391 @@
+use std::ffi::CString;
+use std::time::Duration;
+
+use infra_hex_rs::InfraHexError;
+use pyo3::create_exception;
+use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3::types::{PyDict, PyList, PyTuple, PyType};
+
+use crate::client::{TransferStats, is_pagination_error};
+
+create_exception!(
+    infra_hex_py,
+    FetchError,
+    pyo3::exceptions::PyRuntimeError,
+    "Raised when fetching from Cadent or ONS fails. `errors` lists each failure as a \
+     dict with `kind`, `message`, `url` and `status`, and `elapsed_secs` is how long \
+     the fetch ran."
+);
+
+create_exception!(
+    infra_hex_py,
+    PaginationError,
+    FetchError,
+    "Raised when the pages of a Cadent query do not fit together, such as a page \
+     whose records sort before the previous page's, or pages overlapping so that \
+     records were skipped. Retrying the fetch usually succeeds."
+);
+
+create_exception!(
+    infra_hex_py,
+    InfraNetworkError,
+    FetchError,
+    "Raised when no request of a fetch reached its server: the host name did not \
+     resolve, the TLS handshake failed, or the connection was refused or timed out. \
+     The message says which and what to check, and `host` names the host."
+);
+
+static FETCH_TIMEOUT_ERROR: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+
+/// `FetchTimeoutError(FetchError, TimeoutError)`, raised when every failure of a
+/// fetch was a timeout.
+///
+/// Built at runtime because `create_exception!` only takes a single base class.
+pub fn fetch_timeout_error(py: Python<'_>) -> PyResult<&Bound<'_, PyType>> {
+    FETCH_TIMEOUT_ERROR
+        .get_or_try_init(py, || {
+            let bases = PyTuple::new(
+                py,
+                [
+                    py.get_type::<FetchError>(),
+                    py.get_type::<pyo3::exceptions::PyTimeoutError>(),
+                ],
+            )?;
+            let namespace = PyDict::new(py);
+            namespace.set_item("__module__", "infra_hex_py")?;
+            namespace.set_item(
+                "__doc__",
+                "Raised when every failure of a fetch was a timeout; `url` is the first \
+                 URL that timed out.",
+            )?;
+            let class = py
+                .get_type::<PyType>()
+                .call1(("FetchTimeoutError", bases, namespace))?;
+            Ok(class.cast_into::<PyType>()?.unbind())
+        })
+        .map(|class| class.bind(py))
+}
+
+static AREA_NOT_FOUND_ERROR: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+
+/// `AreaNotFoundError(LookupError, ValueError)`, raised when no ONS built-up area
+/// or local authority district matches the requested id. A `ValueError` too, so
+/// callers catching the error raised before it existed still catch it.
+///
+/// Built at runtime because `create_exception!` only takes a single base class.
+pub fn area_not_found_error(py: Python<'_>) -> PyResult<&Bound<'_, PyType>> {
+    AREA_NOT_FOUND_ERROR
+        .get_or_try_init(py, || {
+            let bases = PyTuple::new(
+                py,
+                [
+                    py.get_type::<pyo3::exceptions::PyLookupError>(),
+                    py.get_type::<pyo3::exceptions::PyValueError>(),
+                ],
+            )?;
+            let namespace = PyDict::new(py);
+            namespace.set_item("__module__", "infra_hex_py")?;
+            namespace.set_item(
+                "__doc__",
+                "Raised when no ONS built-up area or local authority district matches the \
+                 requested id. `object_id` holds the OBJECTID asked for, or None if the \
+                 area was looked up another way.",
+            )?;
+            let class = py
+                .get_type::<PyType>()
+                .call1(("AreaNotFoundError", bases, namespace))?;
+            Ok(class.cast_into::<PyType>()?.unbind())
+        })
+        .map(|class| class.bind(py))
+}
+
+/// Broad category of a fetch failure, exposed as the `kind` of each error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorKind {
+    Timeout,
+    Connection,
+    HttpStatus,
+    InvalidResponse,
+    Pagination,
+    Other,
+}
+
+impl ErrorKind {
+    fn of(error: &InfraHexError) -> Self {
+        match error {
+            InfraHexError::Http(e) if e.is_timeout() => Self::Timeout,
+            InfraHexError::Http(e) if e.is_connect() => Self::Connection,
+            InfraHexError::Http(e) if e.status().is_some() => Self::HttpStatus,
+            InfraHexError::Http(e) if e.is_decode() || e.is_body() => Self::InvalidResponse,
+            InfraHexError::Json(_) | InfraHexError::Geometry(_) => Self::InvalidResponse,
+            error if is_pagination_error(error) => Self::Pagination,
+            _ => Self::Other,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Timeout => "timeout",
+            Self::Connection => "connection",
+            Self::HttpStatus => "http_status",
+            Self::InvalidResponse => "invalid_response",
+            Self::Pagination => "pagination",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// Why a request never reached its server, told apart from the reqwest error's
+/// chain of causes so that [`InfraNetworkError`] can say what to check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NetworkFailure {
+    Dns,
+    Tls,
+    Refused,
+    Timeout,
+}
+
+impl NetworkFailure {
+    /// The failure behind a connection error or timeout, or `None` for other
+    /// errors and connection errors of no recognised cause.
+    fn of(error: &InfraHexError) -> Option<Self> {
+        let InfraHexError::Http(e) = error else {
+            return None;
+        };
+        if !e.is_connect() && !e.is_timeout() {
+            return None;
+        }
+        let mut causes = Vec::new();
+        let mut source = std::error::Error::source(e);
+        while let Some(cause) = source {
+            causes.push(cause);
+            source = cause.source();
+        }
+        let io_kind = |kind| {
+            causes.iter().any(|cause| {
+                cause
+                    .downcast_ref::<std::io::Error>()
+                    .is_some_and(|e| e.kind() == kind)
+            })
+        };
+        let text = causes
+            .iter()
+            .map(|cause| cause.to_string().to_lowercase())
+            .collect::<Vec<_>>()
+            .join(": ");
+
+        if text.contains("dns error") || text.contains("failed to lookup address") {
+            Some(Self::Dns)
+        } else if io_kind(std::io::ErrorKind::ConnectionRefused) {
+            Some(Self::Refused)
+        } else if e.is_timeout() || io_kind(std::io::ErrorKind::TimedOut) {
+            Some(Self::Timeout)
+        } else if ["ssl", "tls", "certificate", "handshake"]
+            .iter()
+            .any(|word| text.contains(word))
+        {
+            Some(Self::Tls)
+        } else {
+            None
+        }
+    }
+
+    /// The `network_failure` of each error's details.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Dns => "dns",
+            Self::Tls => "tls",
+            Self::Refused => "connection_refused",
+            Self::Timeout => "timeout",
+        }
+    }
+
+    /// What went wrong reaching `host`, and what to check.
+    fn message(&self, host: &str) -> String {
+        match self {
+            Self::Dns => format!(
+                "Could not resolve {}: the DNS lookup failed. Check the host name and \
+                 the network's DNS, or set http_options[\"proxy_url\"] if the internet \
+                 is only reachable through a proxy",
+                host
+            ),
+            Self::Tls => format!(
+                "The TLS handshake with {} failed. Behind a proxy that inspects HTTPS, \
+                 pass its root certificate as http_options[\"ca_cert_path\"]",
+                host
+            ),
+            Self::Refused => format!(
+                "{} refused the connection: nothing is listening on that port, or a \
+                 firewall rejected it. Check the URL and any proxy settings",
+                host
+            ),
+            Self::Timeout => format!(
+                "Connecting to {} timed out: a firewall may be dropping the traffic, or \
+                 the server is down. Check any proxy settings, or raise \
+                 http_options[\"connect_timeout_secs\"]",
+                host
+            ),
+        }
+    }
+}
+
+/// What [`FetchError`]'s `errors` say about one failure, kept apart from the
+/// error itself so that it can outlive it, as for a [`crate::client::FailedPage`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorDetail {
+    pub kind: &'static str,
+    pub message: String,
+    pub url: Option<String>,
+    pub status: Option<u16>,
+    pub network_failure: Option<&'static str>,
+}
+
+impl ErrorDetail {
+    pub fn of(error: &InfraHexError) -> Self {
+        Self {
+            kind: ErrorKind::of(error).name(),
+            message: error.to_string(),
+            url: error_url(error),
+            status: match error {
+                InfraHexError::Http(e) => e.status().map(|status| status.as_u16()),
+                _ => None,
+            },
+            network_failure: NetworkFailure::of(error).map(|failure| failure.name()),
+        }
+    }
+}
+
+fn error_host(error: &InfraHexError) -> Option<String> {
+    match error {
+        InfraHexError::Http(e) => e.url().and_then(|url| url.host_str()).map(str::to_string),
+        _ => None,
+    }
+}
+
+fn error_url(error: &InfraHexError) -> Option<String> {
+    match error {
+        InfraHexError::Http(e) => e.url().map(|url| url.to_string()),
+        _ => None,
+    }
+}
+
+/// Emits a `UserWarning` if the fetch recorded in `stats` is missing records that
+/// Cadent counted but did not return, even after retrying (see
+/// `HttpOptions::verify_counts`), so a summary with holes in it is not mistaken
+/// for a complete one.
+pub fn warn_missing_records(py: Python<'_>, stats: &TransferStats) -> PyResult<()> {
+    let missing = stats.missing_records();
+    if missing == 0 {
+        return Ok(());
+    }
+    let message = format!(
+        "Cadent counted {} more records than it returned, even after retrying; the \
+         summary is missing them. Retry later, or raise http_options[\"max_retries\"]",
+        missing
+    );
+    PyErr::warn(
+        py,
+        py.get_type::<pyo3::exceptions::PyUserWarning>().as_any(),
+        &CString::new(message)?,
+        1,
+    )
+}
+
+/// Converts the errors of a failed fetch that ran for `elapsed` into a Python
+/// exception: `FetchTimeoutError` if all of them were timeouts, since retrying
+/// may well succeed, `PaginationError` if all of them were inconsistent pages,
+/// `InfraNetworkError` if none of them reached the server (see
+/// [`NetworkFailure`]), and `FetchError` otherwise.
+pub fn fetch_error(py: Python<'_>, errors: &[InfraHexError], elapsed: Duration) -> PyErr {
+    build_fetch_error(py, errors, elapsed).unwrap_or_else(|e| e)
+}
+
+/// A `FetchTimeoutError` for a fetch stopped by its `deadline` after running for
+/// `elapsed`, with no `errors` of its own and no `url`.
+pub fn deadline_error(py: Python<'_>, deadline: Duration, elapsed: Duration) -> PyErr {
+    build_deadline_error(py, deadline, elapsed).unwrap_or_else(|e| e)
+}
+
+fn build_deadline_error(py: Python<'_>, deadline: Duration, elapsed: Duration) -> PyResult<PyErr> {
+    let err = PyErr::from_type(
+        fetch_timeout_error(py)?.clone(),
+        format!(
+            "Deadline of {}s passed after {:.1}s, before the fetch finished; pass \
+             partial_on_deadline=True to summarise the records fetched by then",
+            deadline.as_secs_f64(),
+            elapsed.as_secs_f64()
+        ),
+    );
+    let value = err.value(py);
+    value.setattr("url", py.None())?;
+    value.setattr("errors", PyList::empty(py))?;
+    value.setattr("elapsed_secs", elapsed.as_secs_f64())?;
+    Ok(err)
+}
+
+fn build_fetch_error(
+    py: Python<'_>,
+    errors: &[InfraHexError],
+    elapsed: Duration,
+) -> PyResult<PyErr> {
+    let all_of = |kind| !errors.is_empty() && errors.iter().all(|e| ErrorKind::of(e) == kind);
+    let timed_out = all_of(ErrorKind::Timeout);
+
+    let err = if timed_out {
+        let url = errors.iter().find_map(error_url);
+        let err = PyErr::from_type(
+            fetch_timeout_error(py)?.clone(),
+            format!(
+                "Timed out after {:.1}s fetching {} ({} request(s) timed out): {}",
+                elapsed.as_secs_f64(),
+                url.as_deref().unwrap_or("upstream data"),
+                errors.len(),
+                errors[0]
+            ),
+        );
+        err.value(py).setattr("url", url)?;
+        err
+    } else if all_of(ErrorKind::Pagination) {
+        PaginationError::new_err(format!("Fetch had {} errors: {:?}", errors.len(), errors))
+    } else if let Some(failures) = errors
+        .iter()
+        .map(|e| Some((NetworkFailure::of(e)?, error_host(e)?)))
+        .collect::<Option<Vec<_>>>()
+        && !failures.is_empty()
+    {
+        let mut messages: Vec<String> = Vec::new();
+        for (failure, host) in &failures {
+            let message = failure.message(host);
+            if !messages.contains(&message) {
+                messages.push(message);
+            }
+        }
+        let err = InfraNetworkError::new_err(format!(
+            "No request reached its server ({} failed): {}",
+            errors.len(),
+            messages.join("; ")
+        ));
+        err.value(py).setattr("host", &failures[0].1)?;
+        err
+    } else {
+        FetchError::new_err(format!("Fetch had {} errors: {:?}", errors.len(), errors))
+    };
+
+    let details = PyList::empty(py);
+    for error in errors {
+        let error = ErrorDetail::of(error);
+        let detail = PyDict::new(py);
+        detail.set_item("kind", error.kind)?;
+        detail.set_item("message", error.message)?;
+        detail.set_item("url", error.url)?;
+        detail.set_item("network_failure", error.network_failure)?;
+        detail.set_item("status", error.status)?;
+        details.append(detail)?;
+    }
+
+    let value = err.value(py);
+    value.setattr("errors", details)?;
+    value.setattr("elapsed_secs", elapsed.as_secs_f64())?;
+    Ok(err)
+}
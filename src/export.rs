@@ -0,0 +1,247 @@
+use std::io::{self, Write};
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, Int64Type, UInt8Type, UInt32Type, UInt64Type};
+use arrow_array::{Array, RecordBatch, RecordBatchReader};
+use arrow_cast::display::{ArrayFormatter, FormatOptions};
+use arrow_schema::{ArrowError, DataType, Field};
+use flatgeobuf::{ColumnType, FgbCrs, FgbWriter, FgbWriterOptions, GeometryType};
+use geojson::{Feature, Geometry, Value as GeoJsonValue};
+use geozero::{ColumnValue, PropertyProcessor};
+use infra_hex_rs::HexCell;
+use serde_json::{Map, Number, Value};
+
+use crate::crs::Transform;
+
+/// Writes summary batches as newline-delimited GeoJSON, one Feature per hex row.
+///
+/// Each geometry is the hex polygon rebuilt from `hex_id` and reprojected to WGS84,
+/// as RFC 7946 (and BigQuery) expect; every other non-geometry column becomes a
+/// property. Rows are written as each batch arrives, so memory stays bounded by
+/// one batch. Returns the number of features written.
+///
+/// # Errors
+/// Malformed input (no `hex_id` column, an invalid hex id) is reported as
+/// [`io::ErrorKind::InvalidData`]; anything else comes from `writer`.
+pub fn write_ndjson(
+    batches: impl IntoIterator<Item = Result<RecordBatch, ArrowError>>,
+    mut writer: impl Write,
+) -> io::Result<usize> {
+    let to_wgs84 = Transform::bng_to_wgs84().map_err(invalid_data)?;
+    let mut written = 0;
+
+    for batch in batches {
+        let batch = batch.map_err(invalid_data)?;
+        let hex_ids = batch
+            .column_by_name("hex_id")
+            .ok_or_else(|| invalid_data("Summary has no hex_id column"))?
+            .as_string_opt::<i32>()
+            .ok_or_else(|| invalid_data("hex_id must be a string column"))?;
+
+        let schema = batch.schema();
+        let options = FormatOptions::default();
+        let properties = schema
+            .fields()
+            .iter()
+            .zip(batch.columns())
+            .filter(|(field, _)| !is_geometry(field))
+            .map(|(field, column)| {
+                let formatter = ArrayFormatter::try_new(column.as_ref(), &options)?;
+                Ok((field.name().as_str(), column.as_ref(), formatter))
+            })
+            .collect::<Result<Vec<_>, ArrowError>>()
+            .map_err(invalid_data)?;
+
+        for (row, hex_id) in hex_ids.iter().enumerate() {
+            let hex_id = hex_id.ok_or_else(|| invalid_data("hex_id must not be null"))?;
+            let cell = HexCell::from_hex_id(hex_id).map_err(invalid_data)?;
+            let polygon = to_wgs84.apply(&cell.to_polygon()).map_err(invalid_data)?;
+
+            let properties: Map<String, Value> = properties
+                .iter()
+                .map(|(name, column, formatter)| {
+                    (name.to_string(), property_value(*column, formatter, row))
+                })
+                .collect();
+            let feature = Feature {
+                geometry: Some(Geometry::new(GeoJsonValue::from(&polygon))),
+                properties: Some(properties),
+                ..Default::default()
+            };
+
+            serde_json::to_writer(&mut writer, &feature)?;
+            writer.write_all(b"\n")?;
+            written += 1;
+        }
+    }
+
+    writer.flush()?;
+    Ok(written)
+}
+
+/// Writes summary batches as an indexed FlatGeobuf file, one feature per hex row.
+///
+/// As in [`write_ndjson`], each geometry is the hex polygon rebuilt from `hex_id`
+/// in WGS84, and every other column becomes an attribute: `UInt32` counts as
+/// `uint`, strings (and dictionary columns such as `provider`) as `string`, and
+/// so on. Features are buffered in a temporary file until the packed Hilbert
+/// R-tree index can be written ahead of them, so a viewer can fetch just the
+/// hexes in view with HTTP range requests. A summary with no rows gives a valid
+/// file with no features. Returns the number of features written.
+///
+/// # Errors
+/// As for [`write_ndjson`].
+pub fn write_flatgeobuf(
+    reader: impl RecordBatchReader,
+    mut writer: impl Write,
+) -> io::Result<usize> {
+    let to_wgs84 = Transform::bng_to_wgs84().map_err(invalid_data)?;
+    let schema = reader.schema();
+    let columns: Vec<(usize, &Field, ColumnType)> = schema
+        .fields()
+        .iter()
+        .filter(|field| !is_geometry(field))
+        .enumerate()
+        .map(|(i, field)| (i, field.as_ref(), column_type(field.data_type())))
+        .collect();
+
+    let options = FgbWriterOptions {
+        write_index: true,
+        crs: FgbCrs {
+            code: 4326,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut fgb = FgbWriter::create_with_options("hex_summary", GeometryType::Polygon, options)
+        .map_err(invalid_data)?;
+    for (_, field, column_type) in &columns {
+        fgb.add_column(field.name(), *column_type, |_, column| {
+            column.nullable = field.is_nullable();
+        });
+    }
+
+    let mut written = 0;
+    for batch in reader {
+        let batch = batch.map_err(invalid_data)?;
+        let hex_ids = batch
+            .column_by_name("hex_id")
+            .ok_or_else(|| invalid_data("Summary has no hex_id column"))?
+            .as_string_opt::<i32>()
+            .ok_or_else(|| invalid_data("hex_id must be a string column"))?;
+
+        let format_options = FormatOptions::default();
+        let values = columns
+            .iter()
+            .map(|(i, field, column_type)| {
+                let column = batch.column_by_name(field.name()).ok_or_else(|| {
+                    ArrowError::SchemaError(format!("No column {}", field.name()))
+                })?;
+                let formatter = ArrayFormatter::try_new(column.as_ref(), &format_options)?;
+                Ok((
+                    *i,
+                    field.name().as_str(),
+                    *column_type,
+                    column.as_ref(),
+                    formatter,
+                ))
+            })
+            .collect::<Result<Vec<_>, ArrowError>>()
+            .map_err(invalid_data)?;
+
+        for (row, hex_id) in hex_ids.iter().enumerate() {
+            let hex_id = hex_id.ok_or_else(|| invalid_data("hex_id must not be null"))?;
+            let cell = HexCell::from_hex_id(hex_id).map_err(invalid_data)?;
+            let polygon = to_wgs84.apply(&cell.to_polygon()).map_err(invalid_data)?;
+
+            let mut result = Ok(());
+            fgb.add_feature_geom(geo::Geometry::Polygon(polygon), |feature| {
+                for (i, name, column_type, column, formatter) in &values {
+                    if column.is_null(row) {
+                        continue;
+                    }
+                    let text = formatter.value(row).to_string();
+                    let value = column_value(*column_type, *column, row, &text);
+                    if let Err(e) = feature.property(*i, name, &value) {
+                        result = Err(invalid_data(e));
+                        return;
+                    }
+                }
+            })
+            .map_err(invalid_data)?;
+            result?;
+            written += 1;
+        }
+    }
+
+    fgb.write(&mut writer).map_err(invalid_data)?;
+    writer.flush()?;
+    Ok(written)
+}
+
+/// The FlatGeobuf attribute type for an Arrow column; anything without a direct
+/// equivalent is written as a string in Arrow's display form.
+fn column_type(data_type: &DataType) -> ColumnType {
+    match data_type {
+        DataType::Boolean => ColumnType::Bool,
+        DataType::UInt8 => ColumnType::UByte,
+        DataType::UInt32 => ColumnType::UInt,
+        DataType::UInt64 => ColumnType::ULong,
+        DataType::Int64 => ColumnType::Long,
+        DataType::Float64 => ColumnType::Double,
+        _ => ColumnType::String,
+    }
+}
+
+/// The value of one cell as `column_type`, falling back to its display form
+/// `text`.
+fn column_value<'a>(
+    column_type: ColumnType,
+    column: &'a dyn Array,
+    row: usize,
+    text: &'a str,
+) -> ColumnValue<'a> {
+    match column_type {
+        ColumnType::Bool => ColumnValue::Bool(column.as_boolean().value(row)),
+        ColumnType::UByte => ColumnValue::UByte(column.as_primitive::<UInt8Type>().value(row)),
+        ColumnType::UInt => ColumnValue::UInt(column.as_primitive::<UInt32Type>().value(row)),
+        ColumnType::ULong => ColumnValue::ULong(column.as_primitive::<UInt64Type>().value(row)),
+        ColumnType::Long => ColumnValue::Long(column.as_primitive::<Int64Type>().value(row)),
+        ColumnType::Double => ColumnValue::Double(column.as_primitive::<Float64Type>().value(row)),
+        _ => match column.data_type() {
+            DataType::Utf8 => ColumnValue::String(column.as_string::<i32>().value(row)),
+            _ => ColumnValue::String(text),
+        },
+    }
+}
+
+fn is_geometry(field: &Field) -> bool {
+    field.name() == "geometry"
+        || field
+            .extension_type_name()
+            .is_some_and(|name| name.starts_with("geoarrow."))
+}
+
+/// A JSON property for one cell: numbers and strings as themselves, anything else
+/// in Arrow's display form.
+fn property_value(column: &dyn Array, formatter: &ArrayFormatter, row: usize) -> Value {
+    if column.is_null(row) {
+        return Value::Null;
+    }
+    match column.data_type() {
+        DataType::Utf8 => Value::from(column.as_string::<i32>().value(row)),
+        DataType::UInt8 => Value::from(column.as_primitive::<UInt8Type>().value(row)),
+        DataType::UInt32 => Value::from(column.as_primitive::<UInt32Type>().value(row)),
+        DataType::UInt64 => Value::from(column.as_primitive::<UInt64Type>().value(row)),
+        DataType::Int64 => Value::from(column.as_primitive::<Int64Type>().value(row)),
+        DataType::Float64 => {
+            let value = column.as_primitive::<Float64Type>().value(row);
+            Number::from_f64(value).map_or(Value::Null, Value::Number)
+        }
+        _ => Value::from(formatter.value(row).to_string()),
+    }
+}
+
+fn invalid_data(error: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
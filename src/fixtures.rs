@@ -0,0 +1,170 @@
+//! Fixture mode: answering every HTTP request from canned JSON responses on disk,
+//! for tests, demos and CI that must not touch the network.
+//!
+//! Each response is a file `<key>.json` in the fixtures directory, holding
+//! `{"url": ..., "status": ..., "body": ...}` (`"text"` instead of `"body"` for a
+//! response that was not JSON). The key is [`request_key`], a hash of the request
+//! URL with its query parameters sorted, so the order they are written in does not
+//! matter. In record mode requests go to the network as usual and each response is
+//! saved as the fixture for its request.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use infra_hex_rs::InfraHexError;
+use reqwest::header::{CONTENT_ENCODING, HeaderValue};
+use reqwest::{ResponseBuilderExt, StatusCode, Url};
+
+/// Where requests are answered from, when fixture mode is on.
+#[derive(Debug, Clone)]
+pub struct FixtureMode {
+    pub dir: PathBuf,
+    /// Fetch from the network and save each response, rather than replaying.
+    pub record: bool,
+}
+
+static FIXTURE_MODE: Mutex<Option<FixtureMode>> = Mutex::new(None);
+
+/// Answers every later request from `mode`, until [`disable`] is called.
+pub fn enable(mode: FixtureMode) {
+    *FIXTURE_MODE.lock().unwrap_or_else(PoisonError::into_inner) = Some(mode);
+}
+
+/// Sends requests to the network again, returning the mode that was on, if any.
+pub fn disable() -> Option<FixtureMode> {
+    FIXTURE_MODE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+}
+
+/// The fixture mode in effect, if any.
+pub fn current() -> Option<FixtureMode> {
+    FIXTURE_MODE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Whether requests are being replayed from disk, so no API key is needed.
+pub fn replaying() -> bool {
+    current().is_some_and(|mode| !mode.record)
+}
+
+/// `GET <url without its query>` followed by a `name=value` line for each decoded
+/// query parameter, sorted.
+pub fn canonical_request(url: &Url) -> String {
+    let mut pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    pairs.sort();
+    let mut base = url.clone();
+    base.set_query(None);
+    base.set_fragment(None);
+
+    let mut canonical = format!("GET {}", base);
+    for (name, value) in pairs {
+        canonical.push_str(&format!("\n{}={}", name, value));
+    }
+    canonical
+}
+
+/// The hash of [`canonical_request`] (see [`stable_hash`]).
+pub fn request_key(url: &Url) -> String {
+    stable_hash(&canonical_request(url))
+}
+
+/// The 64-bit FNV-1a hash of `text`, as 16 hex digits. Stable across builds and
+/// platforms, unlike the standard library's hasher.
+pub fn stable_hash(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+impl FixtureMode {
+    /// The file holding the response to a GET of `url`.
+    pub fn path(&self, url: &Url) -> PathBuf {
+        self.dir.join(format!("{}.json", request_key(url)))
+    }
+
+    /// The saved response to a GET of `url`, as if it had come from the network.
+    ///
+    /// # Errors
+    /// Returns [`InfraHexError::Config`] naming the file expected if there is no
+    /// fixture for the request, or it cannot be read.
+    pub fn load(&self, url: &Url) -> Result<reqwest::Response, InfraHexError> {
+        let path = self.path(url);
+        let contents = std::fs::read(&path).map_err(|e| {
+            InfraHexError::Config(format!(
+                "No fixture for GET {} (expected {}): {}",
+                url,
+                path.display(),
+                e
+            ))
+        })?;
+        let invalid = |reason: String| {
+            InfraHexError::Config(format!("Invalid fixture {}: {}", path.display(), reason))
+        };
+        let fixture: serde_json::Value =
+            serde_json::from_slice(&contents).map_err(|e| invalid(e.to_string()))?;
+
+        let status = match fixture.get("status") {
+            None => StatusCode::OK,
+            Some(status) => status
+                .as_u64()
+                .and_then(|status| u16::try_from(status).ok())
+                .and_then(|status| StatusCode::from_u16(status).ok())
+                .ok_or_else(|| invalid(format!("status {} is not an HTTP status", status)))?,
+        };
+        let body = match (fixture.get("body"), fixture.get("text")) {
+            (Some(body), _) => serde_json::to_vec(body).map_err(|e| invalid(e.to_string()))?,
+            (None, Some(text)) => text
+                .as_str()
+                .ok_or_else(|| invalid("text is not a string".into()))?
+                .as_bytes()
+                .to_vec(),
+            (None, None) => return Err(invalid("it has neither body nor text".into())),
+        };
+        response(url, status, None, body)
+    }
+
+    /// Saves `body`, the decoded body of a `status` response to a GET of `url`, as
+    /// its fixture.
+    pub fn save(&self, url: &Url, status: StatusCode, body: &[u8]) -> Result<(), InfraHexError> {
+        let mut fixture = serde_json::json!({
+            "url": url.as_str(),
+            "status": status.as_u16(),
+        });
+        match serde_json::from_slice::<serde_json::Value>(body) {
+            Ok(body) => fixture["body"] = body,
+            Err(_) => fixture["text"] = String::from_utf8_lossy(body).into(),
+        }
+        let contents = serde_json::to_vec_pretty(&fixture)?;
+        write_fixture(&self.dir, &self.path(url), &contents)
+    }
+}
+
+fn write_fixture(dir: &Path, path: &Path, contents: &[u8]) -> Result<(), InfraHexError> {
+    std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(path, contents))
+        .map_err(|e| {
+            InfraHexError::Config(format!("Failed to write fixture {}: {}", path.display(), e))
+        })
+}
+
+/// A response to `url` with `status` and `body`, encoded as `encoding` says.
+pub fn response(
+    url: &Url,
+    status: StatusCode,
+    encoding: Option<&HeaderValue>,
+    body: Vec<u8>,
+) -> Result<reqwest::Response, InfraHexError> {
+    let mut builder = http::Response::builder().status(status).url(url.clone());
+    if let Some(encoding) = encoding {
+        builder = builder.header(CONTENT_ENCODING, encoding);
+    }
+    builder
+        .body(body)
+        .map(reqwest::Response::from)
+        .map_err(|e| InfraHexError::Api(format!("Invalid response to {}: {}", url, e)))
+}
@@ -1,98 +1,5447 @@
-use geo::BoundingRect;
-use infra_hex_rs::{
-    BBox, BuiltUpAreaClient, CadentClient, InfraClient, to_hex_summary,
-    to_hex_summary_for_multipolygon,
-};
+mod bbox;
+mod client;
+mod config;
+mod crs;
+mod dataframe;
+mod errors;
+mod export;
+mod fixtures;
+#[cfg(feature = "mock")]
+mod mock;
+mod network;
+mod osgb;
+mod query_id;
+mod records;
+mod region;
+mod saved;
+mod summary;
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+
+use arrow_array::RecordBatch;
+use geo::{BoundingRect, MultiPolygon, Validation};
+use infra_hex_rs::{BBox, BuiltUpArea, InfraHexError, InfraResult, PipelineRecord};
+use pyo3::create_exception;
 use pyo3::prelude::*;
-use pyo3_arrow::PyRecordBatch;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyMapping, PySequence};
+use pyo3_arrow::PySchema;
+use pyo3_arrow::input::AnyRecordBatch;
+
+use bbox::{
+    PyBBox, ResolutionArg, bbox_and_resolution_args, describe_bbox, in_great_britain,
+    lonlat_bbox_args, resolve_bbox, resolve_bbox_args, swap_lat_lon,
+};
+use client::{
+    ApiKey, ArcGisPolygonClient, BoundaryResolution, BuiltUpAreaClient, CadentClient, CadentLayer,
+    DateRange, EndpointStatus, ErrorMode, FeatureBudget, FeatureFormat, HttpOptions,
+    LocalAuthorityClient, LocalAuthorityId, ProxyAuth, RecordQuery, RedirectPolicy, TransferStats,
+};
+use config::PyConfig;
+use crs::{bng_extent_to_wgs84, buffer_wgs84_multipolygon, check_bng_coverage, wgs84_circle};
+use dataframe::{OutputFormat, OutputLayout, PyArrowBatch, PyNormalizedSummary, SummaryOutput};
+use errors::{
+    FetchError, InfraNetworkError, PaginationError, area_not_found_error, deadline_error,
+    fetch_error, fetch_timeout_error, warn_missing_records,
+};
+use network::Network;
+use osgb::{Grid, Squares, check_square_budget, grid_squares, to_grid_summary};
+use query_id::{QueryDescription, multipolygon_value};
+use records::{
+    IdField, ParsedRecords, RECORD_FIELDS, deduplicate_records, multipolygon_from_geojson,
+    multipolygon_from_geojson_value, record_column_name, records_from_batch, records_from_geojson,
+    records_from_wkb, records_to_batch, repair_clip_polygon, select_record_fields,
+};
+use region::PyRegionCache;
+use saved::{RawFetchMetadata, read_raw_fetch, records_outside_bbox, write_raw_fetch};
+use summary::{
+    Aggregation, Assignment, CellKey, Containment, CountMode, GeometryEncoding, GeometryOptions,
+    HEX_SUMMARY_SCHEMA_VERSION, HexFilter, LengthMethod, Normalize, SummaryColumns, SummaryStats,
+    Weighting, bbox_polygon, check_clip_resolution, check_hex_budget, clamp_to_coverage,
+    clip_hex_summary_to_bbox, conform_hex_summary, data_bbox_metadata,
+    diff_hex_summaries as diff_summaries, estimate_hex_count, filter_hex_summary, hex_cells_bbox,
+    hex_cover_query_area, merge_hex_summaries as merge_summaries, parse_hex_cells, query_metadata,
+    records_in_cell, select_hex_cells, snap_bbox_to_hexes, stack_area_summaries, to_hex_summary,
+    to_hex_summary_by_source, to_hex_summary_for_multipolygon, to_hex_summary_min_count,
+    to_pipe_cell_traces, to_pipe_hex_assignments, to_record_hex_assignments, total_pipe_length_m,
+    validate_k_ring, validate_parent_zoom, validate_resolution, with_aggregations,
+    with_area_columns, with_boundary_pipe_counts, with_count_mode, with_count_mode_by_source,
+    with_density, with_geometry_options, with_hex_areas, with_k_ring, with_metadata,
+    with_metadata_columns, with_nearest_pipe_distances, with_normalized_density,
+    with_parent_hex_ids, with_query_id_column, with_weighting,
+};
+
+create_exception!(
+    infra_hex_py,
+    InfraGeometryError,
+    pyo3::exceptions::PyValueError,
+    "Raised when a clip or boundary polygon is invalid and cannot be repaired."
+);
+
+/// Repairs an invalid clip or boundary polygon (see [`repair_clip_polygon`]),
+/// emitting a `UserWarning` when it had to. `label` names the polygon in the
+/// warning and errors.
+///
+/// # Errors
+/// Raises `InfraGeometryError` if nothing valid is left after repair.
+fn repaired_clip(
+    py: Python<'_>,
+    polygon: MultiPolygon<f64>,
+    label: &str,
+) -> PyResult<MultiPolygon<f64>> {
+    if polygon.is_valid() {
+        return Ok(polygon);
+    }
+    let repaired = repair_clip_polygon(polygon, label)
+        .map_err(|e| InfraGeometryError::new_err(e.to_string()))?;
+    let message = format!(
+        "{} is not a valid polygon (self-intersecting or overlapping rings); \
+         repaired it before clipping",
+        label
+    );
+    PyErr::warn(
+        py,
+        py.get_type::<pyo3::exceptions::PyUserWarning>().as_any(),
+        &CString::new(message)?,
+        1,
+    )?;
+    Ok(repaired)
+}
+
+/// Warns when `clip` covers fewer than [`summary::MIN_CLIP_HEXES`] hexes at
+/// `zoom`, which is usually a resolution chosen too coarse for a small area.
+/// `label` names the clip in the message.
+fn warn_coarse_clip(
+    py: Python<'_>,
+    clip: &MultiPolygon<f64>,
+    label: &str,
+    zoom: u8,
+) -> PyResult<()> {
+    let Some(message) = check_clip_resolution(clip, label, zoom)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    else {
+        return Ok(());
+    };
+    PyErr::warn(
+        py,
+        py.get_type::<pyo3::exceptions::PyUserWarning>().as_any(),
+        &CString::new(message)?,
+        1,
+    )
+}
+
+/// Clamps a WGS84 boundary to the part inside `network`'s coverage, returning it
+/// with the fraction of the boundary's area that is covered (`None` for a network
+/// without a known coverage). `label` names the boundary in messages.
+///
+/// A partly covered boundary warns, or with `strict` raises `ValueError`, since the
+/// uncovered part would otherwise just show up as hexes with few or no pipes. One
+/// wholly outside the coverage always raises.
+fn covered_clip(
+    py: Python<'_>,
+    clip: MultiPolygon<f64>,
+    label: &str,
+    network: Network,
+    strict: bool,
+) -> PyResult<(MultiPolygon<f64>, Option<f64>)> {
+    let Some(coverage) = network
+        .coverage()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    else {
+        return Ok((clip, None));
+    };
+    let (covered, fraction) = clamp_to_coverage(&clip, &coverage)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    if fraction == 1.0 {
+        return Ok((covered, Some(fraction)));
+    }
+
+    let outside = format!(
+        "{:.1}% of {} lies outside the {} network's coverage",
+        100.0 * (1.0 - fraction),
+        label,
+        network.name()
+    );
+    if fraction == 0.0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "{} lies entirely outside the {} network's coverage, so it has no pipes to count",
+            label,
+            network.name()
+        )));
+    }
+    if strict {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "{}; pass strict_coverage=False to summarise only the covered part",
+            outside
+        )));
+    }
+    let message = format!(
+        "{}, where it has no pipes; the summary covers only the rest",
+        outside
+    );
+    PyErr::warn(
+        py,
+        py.get_type::<pyo3::exceptions::PyUserWarning>().as_any(),
+        &CString::new(message)?,
+        1,
+    )?;
+    Ok((covered, Some(fraction)))
+}
+
+static RUNTIME: Mutex<Option<Arc<tokio::runtime::Runtime>>> = Mutex::new(None);
+
+/// Worker threads for [`RUNTIME`], set by `configure_runtime`; 0 keeps tokio's
+/// default of one per CPU core.
+static WORKER_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// The network fetched from when a function's `network` is omitted, set by
+/// `set_default_network`.
+static DEFAULT_NETWORK: Mutex<Network> = Mutex::new(Network::Cadent);
+
+/// The tokio runtime every fetch runs on, created on first use and again after
+/// `shutdown_runtime`.
+///
+/// Shared rather than created per call because the cached HTTP clients' pooled
+/// connections are driven by tasks on the runtime that opened them.
+///
+/// # Errors
+/// Raises `RuntimeError` when called on a thread already inside a tokio runtime,
+/// such as a callback run by another Rust extension's async task, where
+/// `block_on` would panic. A running asyncio loop is fine: it is not a tokio
+/// runtime, and the fetch simply blocks it.
+fn runtime() -> PyResult<Arc<tokio::runtime::Runtime>> {
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+            "infra_hex_py cannot fetch from a thread that is already running a tokio \
+             runtime; call it from a plain Python thread instead, e.g. with \
+             `await asyncio.to_thread(...)`",
+        ));
+    }
+    let mut shared = RUNTIME.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(runtime) = shared.as_ref() {
+        return Ok(Arc::clone(runtime));
+    }
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    match WORKER_THREADS.load(Ordering::SeqCst) {
+        0 => {}
+        threads => {
+            builder.worker_threads(threads);
+        }
+    }
+    let runtime = builder
+        .enable_all()
+        .build()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    Ok(Arc::clone(shared.insert(Arc::new(runtime))))
+}
+
+/// Stop the fetch runtime's worker threads and drop the shared HTTP clients.
+///
+/// The free functions share one runtime and one set of pooled connections for
+/// the life of the process; this releases them, e.g. at the end of a test or a
+/// short-lived worker. The next fetch starts a fresh runtime, so
+/// `configure_runtime` may be called again in between. A fetch still running on
+/// another thread keeps the old runtime until it finishes.
+#[pyfunction]
+fn shutdown_runtime() {
+    let runtime = RUNTIME
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    client::reset_clients();
+    if let Some(runtime) = runtime.and_then(|runtime| Arc::try_unwrap(runtime).ok()) {
+        runtime.shutdown_timeout(RUNTIME_SHUTDOWN_TIMEOUT);
+    }
+}
+
+/// How long `shutdown_runtime` waits for the runtime's tasks, such as idle
+/// connections closing, before leaving its threads to exit on their own.
+const RUNTIME_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Set how many worker threads the fetch runtime uses.
+///
+/// By default there is one per CPU core, which in a container or cloud function
+/// with a tight CPU quota can be far more than the quota allows. The runtime is
+/// created by the first fetch, so call this before any fetch; calling it again
+/// before then replaces the earlier setting.
+///
+/// # Errors
+/// Raises `ValueError` if `worker_threads` is less than 1, and `RuntimeError` if
+/// the runtime is running (see `shutdown_runtime`).
+#[pyfunction]
+fn configure_runtime(worker_threads: usize) -> PyResult<()> {
+    if worker_threads == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "worker_threads must be at least 1",
+        ));
+    }
+    if RUNTIME
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+    {
+        return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+            "configure_runtime must be called before the first fetch or after \
+             shutdown_runtime; the runtime is running",
+        ));
+    }
+    WORKER_THREADS.store(worker_threads, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Set how many threads trace pipes into hexes when building a summary.
+///
+/// By default rayon uses one thread per CPU core (or `RAYON_NUM_THREADS`). The
+/// thread pool is created by the first summary, so call this before building
+/// any; `num_threads=1` aggregates on a single thread. Summaries are identical
+/// whatever the thread count.
+///
+/// # Errors
+/// Raises `ValueError` if `num_threads` is less than 1, and `RuntimeError` if
+/// the thread pool has already started.
+#[pyfunction]
+fn configure_aggregation(num_threads: usize) -> PyResult<()> {
+    if num_threads == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "num_threads must be at least 1",
+        ));
+    }
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build_global()
+        .map_err(|_| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                "configure_aggregation must be called before the first summary; the \
+                 thread pool has already started",
+            )
+        })
+}
+
+/// Set the network every function fetches from when `network` is not passed.
+///
+/// Until this is called the default is `"cadent"`. The setting lasts for the
+/// rest of the current process only, and is shared by all its threads; worker
+/// processes (e.g. from `multiprocessing`) must each set it themselves. An
+/// explicit `network=` argument always wins.
+///
+/// # Returns
+/// The name of the previous default, so it can be restored
+///
+/// # Errors
+/// Raises `ValueError` for an unknown network, listing the selectable ones.
+#[pyfunction]
+fn set_default_network(name: &str) -> PyResult<&'static str> {
+    let network = extract_network(Some(name))?;
+    let mut default = DEFAULT_NETWORK
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    Ok(std::mem::replace(&mut *default, network).name())
+}
+
+/// Set defaults for the `http_options` of every later fetch.
+///
+/// Takes any `http_options` key as a keyword argument, e.g.
+/// `configure(read_timeout_secs=10, user_agent="my-team/1.0")`, and merges it
+/// into the current defaults; passing `None` removes a default again. A call's
+/// own `http_options` keys take precedence over the defaults. As with
+/// `set_default_network`, the settings last for the current process and are
+/// shared by all its threads.
+///
+/// The shared HTTP clients are dropped (as by `reset_clients`), so clients built
+/// afterwards use the new timeouts, proxies and endpoints.
+///
+/// # Returns
+/// The resulting `Config`
+///
+/// # Errors
+/// Raises `ValueError` for an unknown key or an invalid value, leaving the
+/// defaults unchanged.
+#[pyfunction]
+#[pyo3(signature = (**options))]
+fn configure(py: Python<'_>, options: Option<&Bound<'_, PyDict>>) -> PyResult<PyConfig> {
+    let Some(options) = options else {
+        return Ok(PyConfig::current(py));
+    };
+    let merged = config::merged_with(py, options)?;
+    let dict = PyDict::new(py);
+    for (key, value) in &merged {
+        dict.set_item(key, value.bind(py))?;
+    }
+    parse_http_options(&dict)?;
+    config::store(merged);
+    client::reset_clients();
+    Ok(PyConfig::current(py))
+}
+
+/// The defaults set with `configure`, as a `Config`.
+#[pyfunction]
+fn get_config(py: Python<'_>) -> PyConfig {
+    PyConfig::current(py)
+}
+
+/// Parses the optional `http_options` dict accepted by every fetch function.
+///
+/// Supported keys are `proxy_url`, `http_proxy`, `https_proxy`, `proxy_username`
+/// and `proxy_password` (given together), `headers` (a dict of str to str), `user_agent`,
+/// `max_retries`, `backoff_base_seconds`, `max_retry_after_seconds`, `compression`,
+/// `verify_counts`, `tile_size_deg`, `max_concurrent_tiles`, `arcgis_output_format`,
+/// `extra_query_params` (a dict of str to str appended to every query string),
+/// `ca_cert_path`, `danger_accept_invalid_certs`, `http2_prior_knowledge`,
+/// `pool_idle_timeout_secs` and `redirect_policy` (`"follow"`, `"error"` or a
+/// maximum number of redirects). The options are validated here so
+/// that a bad proxy URL or header raises `ValueError` before any request is made.
+/// Keys set with `configure` apply unless the dict gives them too.
+fn extract_http_options(http_options: Option<&Bound<'_, PyDict>>) -> PyResult<HttpOptions> {
+    Python::attach(|py| {
+        let merged = config::defaults(py)?;
+        if let Some(dict) = http_options {
+            merged.update(dict.as_mapping())?;
+        }
+        parse_http_options(&merged)
+    })
+}
+
+/// Checks that `http_options["tile_size_deg"]` does not split `bbox` into too many
+/// tiles (see [`HttpOptions::check_tiling`]), raising `ValueError` before any
+/// request if it does.
+fn check_tiling(http_options: &HttpOptions, bbox: &BBox) -> PyResult<()> {
+    http_options
+        .check_tiling(bbox)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Parses `http_options` alone, without the defaults set by `configure`.
+fn parse_http_options(dict: &Bound<'_, PyDict>) -> PyResult<HttpOptions> {
+    let mut options = HttpOptions::default();
+    let mut proxy_username: Option<String> = None;
+    let mut proxy_password: Option<String> = None;
+
+    for (key, value) in dict.iter() {
+        let key: String = key.extract()?;
+        match key.as_str() {
+            "proxy_url" => options.proxy_url = value.extract()?,
+            "http_proxy" => options.http_proxy = value.extract()?,
+            "https_proxy" => options.https_proxy = value.extract()?,
+            "proxy_username" => proxy_username = value.extract()?,
+            "proxy_password" => proxy_password = value.extract()?,
+            "headers" => options.headers = value.extract()?,
+            "user_agent" => options.user_agent = value.extract()?,
+            "max_retries" => options.retry.max_retries = value.extract()?,
+            "backoff_base_seconds" => options.retry.backoff_base_seconds = value.extract()?,
+            "max_retry_after_seconds" => options.retry.max_retry_after_seconds = value.extract()?,
+            "compression" => options.compression = value.extract()?,
+            "connect_timeout_secs" => options.connect_timeout_secs = value.extract()?,
+            "read_timeout_secs" => options.read_timeout_secs = value.extract()?,
+            "api_key" => options.api_key = value.extract::<Option<String>>()?.map(ApiKey::new),
+            "api_key_header" => options.api_key_header = value.extract()?,
+            "cadent_url" => options.cadent_url = value.extract()?,
+            "ons_url" => options.ons_url = value.extract()?,
+            "verify_counts" => options.verify_counts = value.extract()?,
+            "tile_size_deg" => options.tile_size_deg = value.extract()?,
+            "max_concurrent_tiles" => options.max_concurrent_tiles = value.extract()?,
+            "extra_query_params" => options.extra_query_params = value.extract()?,
+            "ca_cert_path" => {
+                options.ca_cert_path = value
+                    .extract::<Option<std::path::PathBuf>>()?
+                    .map(|path| path.to_string_lossy().into_owned())
+            }
+            "danger_accept_invalid_certs" => {
+                options.danger_accept_invalid_certs = value.extract()?
+            }
+            "http2_prior_knowledge" => options.http2_prior_knowledge = value.extract()?,
+            "pool_idle_timeout_secs" => options.pool_idle_timeout_secs = value.extract()?,
+            "redirect_policy" => {
+                options.redirect_policy = match value.extract::<String>() {
+                    Ok(name) => RedirectPolicy::from_name(&name).map_err(|e| {
+                        PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+                    })?,
+                    Err(_) => RedirectPolicy::Follow(value.extract()?),
+                }
+            }
+            "arcgis_output_format" => {
+                options.arcgis_format = FeatureFormat::from_name(&value.extract::<String>()?)
+                    .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?
+            }
+            other => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Unknown http_options key: {:?}",
+                    other
+                )));
+            }
+        }
+    }
+
+    options.proxy_auth = match (proxy_username, proxy_password) {
+        (Some(username), Some(password)) => Some(ProxyAuth::new(username, password)),
+        (None, None) => None,
+        _ => {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "proxy_username and proxy_password must be given together",
+            ));
+        }
+    };
+    options
+        .validate()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+
+    Ok(options)
+}
+
+/// Parses the `count_mode` argument (`"records"` or `"unique_assets"`).
+fn extract_count_mode(name: &str) -> PyResult<CountMode> {
+    CountMode::from_name(name)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Parses the `weighting` argument (`"count"` or `"length"`).
+fn extract_weighting(name: &str) -> PyResult<Weighting> {
+    Weighting::from_name(name)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Parses the `aggregations` argument, a dict of Cadent field names to lists of
+/// `"min"`, `"max"`, `"mean"`, `"sum"` and `"count_distinct"`, into the columns
+/// it adds, each once and in the order given.
+fn extract_aggregations(aggregations: Option<&Bound<'_, PyDict>>) -> PyResult<Vec<Aggregation>> {
+    let mut parsed: Vec<Aggregation> = Vec::new();
+    for (field, functions) in aggregations.into_iter().flat_map(|dict| dict.iter()) {
+        let field: String = field.extract()?;
+        for function in functions.extract::<Vec<String>>()? {
+            let aggregation = Aggregation::new(&field, &function)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+            if !parsed.contains(&aggregation) {
+                parsed.push(aggregation);
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+/// Parses the `normalize` argument (`"none"`, `"per_km2"` or `"per_km_edge"`).
+fn extract_normalize(name: &str) -> PyResult<Normalize> {
+    Normalize::from_name(name)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Parses the `length_method` argument (`"geodesic"`, `"haversine"` or
+/// `"projected_27700"`).
+fn extract_length_method(name: &str) -> PyResult<LengthMethod> {
+    LengthMethod::from_name(name)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Parses the `output` argument, raising `ValueError` for an unknown format.
+fn extract_output(name: &str) -> PyResult<OutputFormat> {
+    OutputFormat::from_name(name)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+fn extract_summary_output(
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+) -> PyResult<SummaryOutput> {
+    let layout = OutputLayout::from_name(output_layout)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    match max_batch_rows {
+        Some(0) => {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "max_batch_rows must be at least 1",
+            ));
+        }
+        Some(_) if layout == OutputLayout::Normalized => {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "max_batch_rows cannot be combined with output_layout=\"normalized\"",
+            ));
+        }
+        _ => {}
+    }
+    Ok(SummaryOutput {
+        format: extract_output(output)?,
+        layout,
+        max_batch_rows,
+    })
+}
+
+/// Picks the hex resolution from the `resolution` argument or its deprecated
+/// keyword alias `zoom`, emitting a `DeprecationWarning` when `zoom` is used.
+/// Returns `None` when neither is given.
+///
+/// # Errors
+/// Raises `TypeError` if both are given.
+fn extract_resolution_alias<T>(
+    py: Python<'_>,
+    resolution: Option<T>,
+    zoom: Option<T>,
+) -> PyResult<Option<T>> {
+    match (resolution, zoom) {
+        (Some(_), Some(_)) => Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+            "resolution given both as resolution and by its deprecated alias zoom",
+        )),
+        (None, Some(zoom)) => {
+            PyErr::warn(
+                py,
+                py.get_type::<pyo3::exceptions::PyDeprecationWarning>()
+                    .as_any(),
+                c"zoom is deprecated; pass resolution instead (the same 0-15 hex resolution)",
+                1,
+            )?;
+            Ok(Some(zoom))
+        }
+        (resolution, None) => Ok(resolution),
+    }
+}
+
+/// Validates the required hex resolution, given as `resolution` or the deprecated
+/// `zoom` (see [`extract_resolution_alias`]).
+///
+/// # Errors
+/// Raises `TypeError` if neither or both are given, and `ValueError` if the
+/// resolution is above [`summary::MAX_ZOOM`].
+fn extract_resolution(py: Python<'_>, resolution: Option<u8>, zoom: Option<u8>) -> PyResult<u8> {
+    let resolution = extract_resolution_alias(py, resolution, zoom)?.ok_or_else(|| {
+        PyErr::new::<pyo3::exceptions::PyTypeError, _>("Missing required argument: resolution")
+    })?;
+    validate_resolution(resolution)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Reads the `resolution` argument of the bbox-based functions, or its deprecated
+/// alias `zoom`, either of which may be `"auto"` (see [`ResolutionArg`]).
+fn extract_resolution_arg(
+    py: Python<'_>,
+    resolution: Option<&Bound<'_, PyAny>>,
+    zoom: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Option<ResolutionArg>> {
+    extract_resolution_alias(
+        py,
+        resolution.map(ResolutionArg::extract).transpose()?,
+        zoom.map(ResolutionArg::extract).transpose()?,
+    )
+}
+
+/// Validates the optional `parent_zoom` argument, which must be coarser than the
+/// resolution.
+fn extract_parent_zoom(zoom: u8, parent_zoom: Option<i64>) -> PyResult<Option<u8>> {
+    parent_zoom
+        .map(|parent_zoom| validate_parent_zoom(zoom, parent_zoom))
+        .transpose()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Validates the `grid` / `grid_size_m` / `grid_origin` arguments, returning the
+/// squares of a `grid="osgb"` or `grid="square"` summary or `None` for hexes.
+fn extract_grid_squares(
+    grid: &str,
+    grid_size_m: Option<i64>,
+    grid_origin: Option<(i64, i64)>,
+) -> PyResult<Option<Squares>> {
+    grid_squares(grid, grid_size_m, grid_origin)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Raises `ValueError` naming the first of `options`, each paired with whether
+/// it was given, since a summary of squares (`grid`) has no hexes for them to act
+/// on.
+fn reject_hex_only_options(grid: &str, options: &[(&str, bool)]) -> PyResult<()> {
+    match options.iter().find(|(_, given)| *given) {
+        Some((name, _)) => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "{} only applies to hex summaries, not grid={:?}",
+            name, grid
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Validates the `min_pipe_count` / `top_n` arguments.
+fn extract_hex_filter(min_pipe_count: Option<i64>, top_n: Option<usize>) -> PyResult<HexFilter> {
+    HexFilter::new(min_pipe_count, top_n)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Parses the `containment` argument (`"intersects"`, `"centroid"`, `"within"` or
+/// `"majority"`).
+fn extract_containment(name: &str) -> PyResult<Containment> {
+    Containment::from_name(name)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Parses the `assignment` argument (`"coverage"`, its alias `"all_intersecting"`,
+/// or `"midpoint"`).
+fn extract_assignment(name: &str) -> PyResult<Assignment> {
+    Assignment::from_name(name)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Parses the `error_mode` argument (`"collect"` or `"fail_fast"`).
+fn extract_error_mode(name: &str) -> PyResult<ErrorMode> {
+    ErrorMode::from_name(name)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Validates the `max_total_features` argument, which must allow at least one
+/// record.
+fn extract_max_total_features(max_total_features: Option<usize>) -> PyResult<Option<usize>> {
+    if max_total_features == Some(0) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "max_total_features must be at least 1",
+        ));
+    }
+    Ok(max_total_features)
+}
+
+/// Validates the `deadline_seconds` argument, which must be a positive, finite
+/// number of seconds.
+fn extract_deadline_seconds(deadline_seconds: Option<f64>) -> PyResult<Option<Duration>> {
+    deadline_seconds
+        .map(|seconds| {
+            Duration::try_from_secs_f64(seconds)
+                .ok()
+                .filter(|deadline| !deadline.is_zero())
+                .ok_or_else(|| {
+                    PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                        "deadline_seconds must be a positive number of seconds, got {}",
+                        seconds
+                    ))
+                })
+        })
+        .transpose()
+}
+
+/// Validates the `coordinate_precision` / `simplify_tolerance_m` /
+/// `geometry_encoding` arguments.
+fn extract_geometry_options(
+    coordinate_precision: Option<i64>,
+    simplify_tolerance_m: Option<f64>,
+    geometry_encoding: &str,
+) -> PyResult<GeometryOptions> {
+    GeometryEncoding::from_name(geometry_encoding)
+        .and_then(|encoding| {
+            GeometryOptions::new(coordinate_precision, simplify_tolerance_m, encoding)
+        })
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Reads a polygon argument (`name` in errors) from a GeoJSON string or any object
+/// with a `__geo_interface__`: shapely geometries, `geojson` objects, Fiona
+/// features and so on. The interface mapping is converted directly, with no JSON
+/// round trip.
+///
+/// # Errors
+/// Raises `TypeError` for any other object, and `ValueError` if the GeoJSON is
+/// not a Polygon or MultiPolygon.
+fn extract_polygon(value: &Bound<'_, PyAny>, name: &str) -> PyResult<MultiPolygon<f64>> {
+    let polygon = if let Ok(source) = value.extract::<&str>() {
+        multipolygon_from_geojson(source)
+    } else if let Ok(interface) = value.getattr("__geo_interface__") {
+        multipolygon_from_geojson_value(geo_interface_to_json(&interface)?)
+    } else {
+        return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+            "{} must be a GeoJSON string or an object with __geo_interface__, got {}",
+            name,
+            value.get_type().name()?
+        )));
+    };
+    polygon.map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Converts a `__geo_interface__` value (mappings, sequences such as shapely's
+/// coordinate tuples, strings, numbers and None) to JSON.
+fn geo_interface_to_json(value: &Bound<'_, PyAny>) -> PyResult<serde_json::Value> {
+    use serde_json::Value;
+
+    if value.is_none() {
+        return Ok(Value::Null);
+    }
+    if let Ok(mapping) = value.cast::<PyMapping>() {
+        return mapping
+            .items()?
+            .iter()
+            .map(|item| {
+                let (key, value): (String, Bound<'_, PyAny>) = item.extract()?;
+                Ok((key, geo_interface_to_json(&value)?))
+            })
+            .collect::<PyResult<serde_json::Map<_, _>>>()
+            .map(Value::Object);
+    }
+    if let Ok(text) = value.extract::<String>() {
+        return Ok(Value::String(text));
+    }
+    if let Ok(flag) = value.cast::<PyBool>() {
+        return Ok(Value::Bool(flag.is_true()));
+    }
+    if let Ok(number) = value.extract::<i64>() {
+        return Ok(Value::from(number));
+    }
+    if let Ok(number) = value.extract::<f64>() {
+        return Ok(serde_json::Number::from_f64(number).map_or(Value::Null, Value::Number));
+    }
+    if let Ok(sequence) = value.cast::<PySequence>() {
+        return sequence
+            .try_iter()?
+            .map(|item| geo_interface_to_json(&item?))
+            .collect::<PyResult<Vec<_>>>()
+            .map(Value::Array);
+    }
+    Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+        "Unsupported value in __geo_interface__: {}",
+        value.get_type().name()?
+    )))
+}
+
+/// Which Cadent layers to fetch, parsed from the Python `layer` argument.
+#[derive(Debug)]
+enum LayerSelection {
+    /// A single layer (`layer="..."` or the default); plain summary schema.
+    Single(CadentLayer),
+    /// A list of layers, tagged with one `count_<layer>` column each.
+    Tagged(Vec<CadentLayer>),
+}
+
+impl LayerSelection {
+    fn layers(&self) -> &[CadentLayer] {
+        match self {
+            LayerSelection::Single(layer) => std::slice::from_ref(layer),
+            LayerSelection::Tagged(layers) => layers,
+        }
+    }
+}
+
+/// Parses a Cadent layer name, raising `ValueError` listing the supported names.
+fn extract_layer(name: &str) -> PyResult<CadentLayer> {
+    CadentLayer::from_name(name)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Parses an `id_field` name, raising `ValueError` for one that names no
+/// attribute. `None` is `default`, usually the network's (see
+/// [`Network::id_field`]).
+fn extract_id_field(id_field: Option<&str>, default: IdField) -> PyResult<IdField> {
+    id_field
+        .map(IdField::new)
+        .transpose()
+        .map(|id_field| id_field.unwrap_or(default))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Parses a `network` name, raising `ValueError` listing the selectable ones.
+/// `None` is the session default (see `set_default_network`).
+fn extract_network(name: Option<&str>) -> PyResult<Network> {
+    match name {
+        Some(name) => Network::from_name(name)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())),
+        None => Ok(*DEFAULT_NETWORK
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)),
+    }
+}
+
+/// Parses the `layer` argument: `None`, a layer name, or a list of layer names.
+fn extract_layers(layer: Option<&Bound<'_, PyAny>>) -> PyResult<LayerSelection> {
+    let Some(layer) = layer else {
+        return Ok(LayerSelection::Single(CadentLayer::default()));
+    };
+    if let Ok(name) = layer.extract::<String>() {
+        return Ok(LayerSelection::Single(extract_layer(&name)?));
+    }
+
+    let names: Vec<String> = layer.extract()?;
+    if names.is_empty() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "layer list must not be empty",
+        ));
+    }
+    let mut layers = Vec::with_capacity(names.len());
+    for name in &names {
+        let layer = extract_layer(name)?;
+        if layers.contains(&layer) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Duplicate layer: {:?}",
+                name
+            )));
+        }
+        layers.push(layer);
+    }
+    Ok(LayerSelection::Tagged(layers))
+}
+
+/// Parses the `date_from` / `date_to` arguments, each an ISO `YYYY-MM-DD` string
+/// or a `datetime.date`, raising `ValueError` for an invalid date or if any of
+/// `layers` has no date field to filter on.
+fn extract_date_range(
+    date_from: Option<&Bound<'_, PyAny>>,
+    date_to: Option<&Bound<'_, PyAny>>,
+    layers: &LayerSelection,
+) -> PyResult<DateRange> {
+    let iso = |date: Option<&Bound<'_, PyAny>>| -> PyResult<Option<String>> {
+        date.map(|date| match date.extract::<String>() {
+            Ok(date) => Ok(date),
+            Err(_) => Ok(date.str()?.to_string()),
+        })
+        .transpose()
+    };
+    let dates = DateRange::new(iso(date_from)?, iso(date_to)?)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    if dates.is_bounded()
+        && let Some(layer) = layers
+            .layers()
+            .iter()
+            .find(|layer| layer.date_field().is_none())
+    {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "Layer {:?} has no date attribute, so date_from / date_to cannot filter it",
+            layer.name()
+        )));
+    }
+    Ok(dates)
+}
+
+/// Validates the `fields` argument against the fields each of `layers` has, as
+/// listed by the dataset's metadata, raising `ValueError` naming the available
+/// ones for an unknown field, or for one pipe records do not carry.
+fn extract_record_fields(
+    py: Python<'_>,
+    fields: Option<Vec<String>>,
+    network: Network,
+    layers: &LayerSelection,
+    http_options: &HttpOptions,
+) -> PyResult<Option<Vec<String>>> {
+    let Some(fields) = fields else {
+        return Ok(None);
+    };
+    for (i, field) in fields.iter().enumerate() {
+        if fields[..i].contains(field) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Duplicate field {:?}",
+                field
+            )));
+        }
+    }
+
+    let runtime = runtime()?;
+    for layer in layers.layers() {
+        let started = Instant::now();
+        let available = runtime
+            .block_on(network.field_names(*layer, http_options))
+            .map_err(|e| fetch_error(py, &[e], started.elapsed()))?;
+        let unknown: Vec<_> = fields
+            .iter()
+            .filter(|field| !available.contains(field))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Unknown field(s) {:?} for layer {:?}; available fields: {}",
+                unknown,
+                layer.name(),
+                available.join(", ")
+            )));
+        }
+    }
+    if let Some(field) = fields
+        .iter()
+        .find(|field| record_column_name(field).is_none())
+    {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "Field {:?} is not carried by pipe records; fields can be any of: {}",
+            field,
+            RECORD_FIELDS.join(", ")
+        )));
+    }
+    Ok(Some(fields))
+}
+
+/// Fetches the records of each selected layer of `network` within `bbox`,
+/// concurrently, deduplicating repeated ids within each layer if `deduplicate`
+/// names the id attribute. Response sizes are recorded in `stats`, and `budget`
+/// is shared by all the layers.
+#[allow(clippy::too_many_arguments)]
+fn fetch_layers(
+    py: Python<'_>,
+    bbox: &BBox,
+    network: Network,
+    layers: &LayerSelection,
+    query: &RecordQuery,
+    http_options: &HttpOptions,
+    deduplicate: Option<&IdField>,
+    stats: &TransferStats,
+    budget: &FeatureBudget,
+) -> PyResult<Vec<Vec<PipelineRecord>>> {
+    check_tiling(http_options, bbox)?;
+    let runtime = runtime()?;
+
+    let started = Instant::now();
+    let fetched = runtime.block_on(budget.until_deadline(futures::future::join_all(
+        layers.layers().iter().map(|layer| {
+            network.fetch_layer_by_bbox(*layer, bbox, query, http_options, stats, budget)
+        }),
+    )));
+
+    let mut records = Vec::with_capacity(fetched.len());
+    for result in fetched {
+        let result =
+            result.map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        if !result.errors.is_empty() {
+            return Err(fetch_error(py, &result.errors, started.elapsed()));
+        }
+        let mut layer_records = result.records;
+        if let Some(id_field) = deduplicate {
+            deduplicate_records(&mut layer_records, id_field);
+        }
+        records.push(layer_records);
+    }
+    warn_missing_records(py, stats)?;
+    Ok(records)
+}
+
+/// Bins the records fetched by [`fetch_layers`] into a hex summary batch, with a
+/// `count_<layer>` column per layer for a list of layers.
+fn layers_hex_summary(
+    layers: &LayerSelection,
+    records: &[Vec<PipelineRecord>],
+    zoom: u8,
+    assignment: Assignment,
+    min_pipe_count: u32,
+) -> PyResult<RecordBatch> {
+    match layers {
+        LayerSelection::Single(_) => {
+            to_hex_summary_min_count(&records[0], zoom, assignment, min_pipe_count)
+        }
+        LayerSelection::Tagged(tagged) => {
+            let results: Vec<_> = tagged
+                .iter()
+                .zip(records)
+                .map(|(layer, records)| (layer.name(), records.as_slice()))
+                .collect();
+            to_hex_summary_by_source(&results, zoom, assignment)
+        }
+    }
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+}
+
+/// The `lonlat` and `strict_location` arguments of a summary of four corner
+/// floats, which could have been given in the wrong order.
+#[derive(Debug, Clone, Copy)]
+struct LocationCheck {
+    lonlat: bool,
+    strict: bool,
+}
+
+impl LocationCheck {
+    /// Called when no pipes were found in `bbox`: if it is centred outside Great
+    /// Britain, the likeliest cause is latitudes and longitudes given the wrong way
+    /// round, so this warns, or with `strict` raises `ValueError`, showing both
+    /// readings of the four numbers.
+    fn empty_result(self, py: Python<'_>, bbox: &BBox) -> PyResult<()> {
+        if in_great_britain(bbox) {
+            return Ok(());
+        }
+        let swapped = swap_lat_lon(bbox);
+        let (given, as_lat_lon, as_lon_lat) = if self.lonlat {
+            (
+                (bbox.min_lon, bbox.min_lat, bbox.max_lon, bbox.max_lat),
+                swapped,
+                *bbox,
+            )
+        } else {
+            (
+                (bbox.min_lat, bbox.min_lon, bbox.max_lat, bbox.max_lon),
+                *bbox,
+                swapped,
+            )
+        };
+        let message = format!(
+            "No pipes found in a bbox centred outside Great Britain; the coordinates may \
+             be swapped. Read as (min_lat, min_lon, max_lat, max_lon), ({}, {}, {}, {}) is \
+             {}; read as (min_lon, min_lat, max_lon, max_lat) (lonlat=True), it is {}",
+            given.0,
+            given.1,
+            given.2,
+            given.3,
+            describe_bbox(&as_lat_lon),
+            describe_bbox(&as_lon_lat)
+        );
+        if self.strict {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(message));
+        }
+        PyErr::warn(
+            py,
+            py.get_type::<pyo3::exceptions::PyUserWarning>().as_any(),
+            &CString::new(message)?,
+            1,
+        )
+    }
+}
+
+/// Fetches `network` records within `bbox` and bins them into a summary batch of
+/// `grid`'s cells.
+///
+/// Layers are fetched concurrently. Response sizes are recorded under
+/// `bytes_transferred` / `bytes_decoded` in the schema metadata, along with
+/// `coerced_to_2d`, the records whose Z or M values were dropped. `parent_zoom`
+/// must already have been checked with [`validate_parent_zoom`]. With
+/// `deduplicate`, repeated ids under `id_field` within each layer are counted
+/// once, and the ids are described in the schema metadata (see
+/// [`IdField::metadata`]). With
+/// `max_total_features`, the fetch stops early once that many records have been
+/// fetched across all layers and the summary is marked `truncated`. With
+/// [`ErrorMode::FailFast`], the first failed request stops the fetch. With a
+/// `deadline`, the fetch stops once that long has passed since the call began,
+/// raising `FetchTimeoutError` unless `partial_on_deadline`. Records are
+/// binned by `assignment`, and hexes kept by `containment` against the requested
+/// `bbox`. The hex-only options (`parent_zoom`, `count_mode`, `boundary_pipe_count`,
+/// `k_ring`, `hex_area`, `include_density`, `normalize`, `aggregations` and
+/// `snap_to_hex`) must be left unset for a [`Grid::Squares`] summary. With a `location_check`, fetching no
+/// records at all is checked for swapped coordinates (see [`LocationCheck`]).
+/// The query's id (see [`bbox_query`]) is recorded as `query_id` in the schema
+/// metadata and the `with_stats` dict, and with `include_query_id` as a column.
+#[allow(clippy::too_many_arguments)]
+fn hex_summary_for_bbox(
+    py: Python<'_>,
+    bbox: BBox,
+    grid: Grid,
+    parent_zoom: Option<u8>,
+    deduplicate: bool,
+    id_field: &IdField,
+    count_mode: CountMode,
+    assignment: Assignment,
+    weighting: Weighting,
+    aggregations: &[Aggregation],
+    boundary_pipe_count: bool,
+    hex_area: bool,
+    include_density: bool,
+    normalize: Normalize,
+    filter: &HexFilter,
+    k_ring: u32,
+    network: Network,
+    layers: &LayerSelection,
+    query: &RecordQuery,
+    http_options: &HttpOptions,
+    geometry_options: &GeometryOptions,
+    include_metadata_columns: bool,
+    include_query_id: bool,
+    output: SummaryOutput,
+    with_stats: bool,
+    length_method: LengthMethod,
+    snap_to_hex: bool,
+    max_total_features: Option<usize>,
+    error_mode: ErrorMode,
+    deadline: Option<Duration>,
+    partial_on_deadline: bool,
+    containment: Containment,
+    telemetry: bool,
+    location_check: Option<LocationCheck>,
+) -> PyResult<Py<PyAny>> {
+    let started = Instant::now();
+    let query_id = bbox_query(&bbox, network, layers, query, grid)
+        .with_option("parent_zoom", &parent_zoom)
+        .with("deduplicate", deduplicate)
+        .with("id_field", id_field.name())
+        .with_option("count_mode", &count_mode)
+        .with_option("assignment", &assignment)
+        .with_option("weighting", &weighting)
+        .with("aggregations", aggregation_names(aggregations))
+        .with("boundary_pipe_count", boundary_pipe_count)
+        .with("hex_area", hex_area)
+        .with("include_density", include_density)
+        .with_option("normalize", &normalize)
+        .with_option("filter", filter)
+        .with("k_ring", k_ring)
+        .with_option("geometry_options", geometry_options)
+        .with("include_metadata_columns", include_metadata_columns)
+        .with_option("length_method", &length_method)
+        .with("snap_to_hex", snap_to_hex)
+        .with("max_total_features", max_total_features)
+        .with_option("containment", &containment)
+        .id();
+    let fetch_bbox = match grid {
+        Grid::Hex(resolution) if snap_to_hex => snap_bbox_to_hexes(&bbox, resolution.zoom())
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?,
+        _ => bbox,
+    };
+    let stats = TransferStats::default().with_telemetry(telemetry);
+    let budget = FeatureBudget::new(max_total_features)
+        .with_error_mode(error_mode)
+        .with_deadline(deadline.map(|deadline| started + deadline));
+    let fetch_started = Instant::now();
+    let records = fetch_layers(
+        py,
+        &fetch_bbox,
+        network,
+        layers,
+        query,
+        http_options,
+        count_mode.deduplicate(deduplicate).then_some(id_field),
+        &stats,
+        &budget,
+    )?;
+    let fetch_seconds = fetch_started.elapsed().as_secs_f64();
+    if budget.truncated() {
+        let message = format!(
+            "Stopped fetching after max_total_features={} records; the summary only \
+             covers part of the bbox",
+            max_total_features.unwrap_or_default()
+        );
+        PyErr::warn(
+            py,
+            py.get_type::<pyo3::exceptions::PyUserWarning>().as_any(),
+            &CString::new(message)?,
+            1,
+        )?;
+    }
+    if budget.timed_out() {
+        let deadline = deadline.unwrap_or_default();
+        if !partial_on_deadline {
+            return Err(deadline_error(py, deadline, started.elapsed()));
+        }
+        let message = format!(
+            "Stopped fetching at deadline_seconds={}; the summary only covers part of \
+             the bbox",
+            deadline.as_secs_f64()
+        );
+        PyErr::warn(
+            py,
+            py.get_type::<pyo3::exceptions::PyUserWarning>().as_any(),
+            &CString::new(message)?,
+            1,
+        )?;
+    }
+    if let Some(check) = location_check
+        && records.iter().all(Vec::is_empty)
+    {
+        check.empty_result(py, &bbox)?;
+    }
+    let sources: Vec<_> = layers
+        .layers()
+        .iter()
+        .zip(&records)
+        .map(|(layer, records)| (layer.name(), records.as_slice()))
+        .collect();
+    let batch = match grid {
+        Grid::Hex(resolution) => {
+            let zoom = resolution.zoom();
+            let batch = layers_hex_summary(layers, &records, zoom, assignment, filter.min_count())?;
+            let batch = with_count_mode_by_source(batch, &sources, zoom, assignment, count_mode)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+            let batch =
+                with_weighting(batch, records.iter().flatten(), zoom, assignment, weighting)
+                    .and_then(|batch| {
+                        with_aggregations(
+                            batch,
+                            records.iter().flatten(),
+                            zoom,
+                            assignment,
+                            aggregations,
+                        )
+                    })
+                    .map_err(|e| {
+                        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string())
+                    })?;
+            if boundary_pipe_count {
+                with_boundary_pipe_counts(
+                    batch,
+                    records.iter().flatten(),
+                    zoom,
+                    assignment,
+                    count_mode,
+                )
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+            } else {
+                batch
+            }
+        }
+        Grid::Squares(squares) => to_grid_summary(
+            &sources,
+            matches!(layers, LayerSelection::Tagged(_)),
+            squares,
+            assignment,
+            weighting,
+            filter.min_count(),
+        )
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+    };
+    let batch = clip_hex_summary_to_bbox(batch, &bbox, containment)
+        .and_then(|batch| {
+            with_metadata(
+                batch,
+                containment
+                    .metadata()
+                    .into_iter()
+                    .chain(assignment.metadata()),
+            )
+        })
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = filter_hex_summary(batch, filter)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let summary_stats = if with_stats {
+        let stats = summary_stats(
+            py,
+            &batch,
+            &records,
+            weighting,
+            length_method,
+            fetch_seconds,
+        )?;
+        stats.set_item("query_id", &query_id)?;
+        Some(stats)
+    } else {
+        None
+    };
+    let batch = if k_ring > 0 {
+        with_k_ring(batch, k_ring, &bbox_polygon(&bbox))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = match parent_zoom {
+        Some(parent_zoom) => with_parent_hex_ids(batch, parent_zoom)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+        None => batch,
+    };
+    let batch = if hex_area {
+        with_hex_areas(batch)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = if include_density {
+        with_density(batch, None)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = with_normalized_density(batch, normalize, None)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = if include_metadata_columns {
+        with_metadata_columns(batch, grid.zoom(), Some(network.name()))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = if include_query_id {
+        with_query_id_column(batch, &query_id)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let data_bbox = data_bbox_metadata(&batch)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let ids = if count_mode.deduplicate(deduplicate) {
+        id_field.metadata(records.iter().flatten())
+    } else {
+        Vec::new()
+    };
+    let batch = with_metadata(
+        batch,
+        stats
+            .metadata()
+            .into_iter()
+            .chain(data_bbox)
+            .chain(query_metadata(Some(&bbox), None, grid.zoom()))
+            .chain([("query_id".to_string(), query_id)])
+            .chain(ids)
+            .chain(grid.metadata())
+            .chain(budget.metadata())
+            .chain(query.dates.metadata())
+            .chain(
+                (with_stats && weighting == Weighting::Length).then(|| length_method.metadata()),
+            ),
+    )
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_geometry_options(batch, geometry_options)
+        .and_then(conform_hex_summary)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    let summary = output.to_python(py, batch)?;
+    match summary_stats {
+        Some(stats) => Ok((summary, stats).into_pyobject(py)?.into_any().unbind()),
+        None => Ok(summary),
+    }
+}
+
+/// The [`QueryDescription`] of a summary of `network`'s `layers` within `bbox`
+/// binned into `grid`, before its options are added.
+fn bbox_query(
+    bbox: &BBox,
+    network: Network,
+    layers: &LayerSelection,
+    query: &RecordQuery,
+    grid: Grid,
+) -> QueryDescription {
+    QueryDescription::new("bbox")
+        .with(
+            "bbox",
+            serde_json::json!([bbox.min_lat, bbox.min_lon, bbox.max_lat, bbox.max_lon]),
+        )
+        .with("network", network.name())
+        .with_option("layers", layers)
+        .with_option("dates", &query.dates)
+        .with_option("grid", &grid)
+}
+
+/// The column names of `aggregations`, sorted so that the order they were given
+/// in does not change a query's id.
+fn aggregation_names(aggregations: &[Aggregation]) -> Vec<String> {
+    let mut names: Vec<String> = aggregations.iter().map(Aggregation::column_name).collect();
+    names.sort();
+    names
+}
+
+/// Builds the `with_stats=True` dict for `batch`, a clipped and filtered summary of
+/// `records`, measuring pipe lengths with `length_method`.
+fn summary_stats<'py>(
+    py: Python<'py>,
+    batch: &RecordBatch,
+    records: &[Vec<PipelineRecord>],
+    weighting: Weighting,
+    length_method: LengthMethod,
+    fetch_seconds: f64,
+) -> PyResult<Bound<'py, PyDict>> {
+    let total_pipe_length_m = match weighting {
+        Weighting::Length => Some(
+            total_pipe_length_m(records.iter().flatten(), length_method)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+        ),
+        Weighting::Count => None,
+    };
+    let stats = SummaryStats {
+        total_records: records.iter().map(Vec::len).sum(),
+        total_pipe_length_m,
+        fetch_seconds,
+        ..SummaryStats::from_summary(batch)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    };
+
+    let dict = PyDict::new(py);
+    dict.set_item("total_records", stats.total_records)?;
+    dict.set_item("hex_count", stats.hex_count)?;
+    dict.set_item("max_pipe_count", stats.max_pipe_count)?;
+    dict.set_item("mean_pipe_count", stats.mean_pipe_count)?;
+    dict.set_item("total_pipe_length_m", stats.total_pipe_length_m)?;
+    dict.set_item("fetch_seconds", stats.fetch_seconds)?;
+    Ok(dict)
+}
+
+/// Get hex summary for a WGS84 bounding box.
+///
+/// Accepts either the four corner floats or a `BBox` instance: `(bbox, resolution)`.
+/// `resolution` is the hex resolution, from 0 (coarsest) to 15 (finest), numbered
+/// like H3's; the older `zoom` keyword still works but emits a
+/// `DeprecationWarning`.
+///
+/// `resolution="auto"` chooses the resolution instead: the one whose hex area
+/// best divides the bbox into `target_hex_count` hexes (default 5000). The
+/// choice is recorded under `resolution` in the schema metadata, as well as in
+/// the `resolution` column. A bbox with zero area raises `ValueError`.
+/// `coordinate_precision` rounds the hex polygon coordinates to that many decimal
+/// places of BNG metres (0-15; 0 rounds to whole metres).
+///
+/// `geometry_encoding` sets the layout of the `geometry` column. The default,
+/// `"geoarrow_separated"`, is a `geoarrow.polygon` column with separate x and y
+/// coordinate arrays. `"geoarrow"` gives the same polygons with interleaved
+/// coordinates, which GeoArrow renderers such as lonboard take as is rather than
+/// decoding WKB, and `"wkb"` a `geoarrow.wkb` binary column. Each carries the
+/// EPSG:27700 CRS in its extension metadata. `output="polars"` always has WKB.
+///
+/// `layer` selects the Cadent dataset (default `"gas_pipes"`). Passing a list of
+/// layer names fetches each and adds a `count_<layer>` column per layer.
+///
+/// `network="mock"` (in builds with the `mock` feature) serves a fixed set of
+/// synthetic pipes around central Manchester instead, with no API key or network
+/// access, for tests and demos. Without `network`, the session default is used:
+/// `"cadent"` unless changed with `set_default_network`.
+///
+/// `parent_zoom`, if given, must be less than `resolution` and adds a `parent_hex_id`
+/// column: the `parent_zoom` hex containing each hex's centre.
+///
+/// `grid="osgb"` bins pipes into Ordnance Survey National Grid squares instead
+/// of hexes, `grid_size_m` (100, 1000 or 10000; default 1000) metres a side, and
+/// takes no `resolution`. Rows are keyed by a Utf8 `grid_ref` such as `"SJ8398"`
+/// in place of `hex_id`, `geometry` is the square in EPSG:27700, and the metadata
+/// columns are only `provider`. `grid` and `grid_size_m` are recorded in the
+/// schema metadata, and `max_hexes` caps the squares. The hex-only options
+/// (`parent_zoom`, `count_mode="unique_assets"`, `boundary_pipe_count`,
+/// `hex_area`, `include_density`, `normalize`, `aggregations`, `k_ring`,
+/// `snap_to_hex` and `output_layout="normalized"`) raise `ValueError` with `grid="osgb"`, as does
+/// `grid_size_m` with `grid="hex"`.
+///
+/// `grid="square"` bins pipes into a regular grid of squares instead, for models
+/// that need neither hexes nor National Grid references: `grid_size_m` any whole
+/// number of metres from 1 to 100000 (default 1000), laid out from
+/// `grid_origin`, a BNG `(easting, northing)` corner (default `(0, 0)`). Rows are
+/// keyed by a Utf8 `cell_id` naming the size and south-west corner of the square,
+/// such as `"250m_E383000_N398250"`, and otherwise behave as with `grid="osgb"`;
+/// `grid_origin` is also recorded in the schema metadata, as a JSON array, and
+/// raises `ValueError` with any other grid.
+///
+/// Fails fast with `ValueError` if the bbox would cover more than `max_hexes`
+/// hexes at `resolution` (estimated from its area; default 2,000,000).
+///
+/// Assets returned more than once (e.g. by overlapping pages) are counted once,
+/// keyed on their `asset_id`; pass `deduplicate=False` to count every record.
+/// `id_field` names another attribute to key them on instead, for data from a
+/// network that keeps its ids elsewhere (the default is the network's own,
+/// `asset_id` for Cadent). Records without the attribute are keyed on a hash of
+/// their geometry, so only exact repeats of them count once. The attribute is
+/// recorded under `id_field` in the schema metadata, and the number of records
+/// keyed on their geometry, if any, under `geometry_id_records`.
+///
+/// `weighting="length"` adds a Float64 `weighted_count` column in which each asset
+/// contributes the fraction of its length inside each hex, summing to 1 per asset.
+///
+/// `aggregations` adds per-hex statistics of record attributes, computed over
+/// the records counted in each hex: a dict of Cadent field names to lists of
+/// `"min"`, `"max"`, `"mean"`, `"sum"` and `"count_distinct"`, such as
+/// `{"diameter": ["min", "max", "mean"], "material": ["count_distinct"]}`. Each
+/// adds a nullable `<field>_<function>` column (`diameter_min`, ...), Float64 or,
+/// for `count_distinct`, UInt32. Null values are skipped, so a hex where every
+/// record's value is null gets null. Only `diameter`, `carr_dia` and `depth` are
+/// numeric; any other field raises `ValueError` with a function other than
+/// `count_distinct`, as do unknown fields and functions.
+///
+/// `count_mode="records"` (the default) counts every record. Cadent sometimes splits
+/// one main into several segment records sharing an `asset_id`;
+/// `count_mode="unique_assets"` counts those once per hex, keeping every segment
+/// (so `deduplicate` has no effect) and counting records without an id individually.
+///
+/// `assignment` chooses which hexes a pipe counts in: `"coverage"` (the default,
+/// also spelled `"all_intersecting"`) every hex its line passes through, so a long
+/// main counts in each of them, or `"midpoint"` only the hex containing the point
+/// halfway along it, so each record counts exactly once and `pipe_count` sums to
+/// the number of records binned (before clipping drops any edge hexes).
+/// `"midpoint"` is recorded under `assignment` in the schema metadata, and with
+/// `weighting="length"` gives each pipe its whole weight in that hex. An unknown value raises `ValueError`.
+///
+/// `boundary_pipe_count=True` adds a UInt32 `boundary_pipe_count` column: how many
+/// of each hex's counted pipes also cross other hexes, so its count depends on how
+/// such pipes are assigned.
+///
+/// `hex_area=True` adds a Float64 `hex_area_m2` column with the ground area of
+/// each hex in square metres, measured on the WGS84 ellipsoid, so `pipe_count`
+/// can be divided by it for a density map. Hexes are equal on the BNG plane, but
+/// their true areas differ by up to about 0.1% across the country.
+///
+/// `include_density=True` does that division too, adding Float64 `cell_area_km2`
+/// (the same ground area, in km²) and `pipe_density_per_km2` columns. With
+/// `weighting="length"` the density is of `weighted_count`, each pipe counted by
+/// the share of its length in the hex, rather than of `pipe_count`.
+///
+/// `normalize` adds a nullable Float64 `pipe_density` column of that same count
+/// `N` (`weighted_count` or `pipe_count`) scaled by a hex measure: with
+/// `"per_km2"` it is `N / A`, `A` the hex's ground area in km² as in
+/// `cell_area_km2`, and with `"per_km_edge"` it is `N / (P / 6)`, `P` the hex's
+/// geodesic perimeter in km on the WGS84 ellipsoid, so `P / 6` is its mean edge
+/// length. The default `"none"` adds no column.
+///
+/// `min_pipe_count` drops hexes with fewer pipes, and `top_n` keeps only the `n`
+/// densest. Both apply to the final `pipe_count`, after clipping.
+///
+/// The summary keeps every hex overlapping the bbox, but pipes are only fetched
+/// within it, so counts of hexes along its edges depend on where the edge falls.
+/// `snap_to_hex=True` fetches the bbox grown outward to whole hexes at
+/// `resolution` instead, so every returned hex is fully covered and tiles fetched
+/// side by side agree on their shared edge hexes (exactly so for pipes shorter
+/// than a hex). Counts may then include pipes slightly outside the requested bbox.
+///
+/// `containment` chooses which hexes are kept: `"intersects"` (the default)
+/// keeps every hex overlapping the bbox, `"centroid"` those whose centre lies
+/// inside it, `"within"` only hexes entirely inside it, so no edge hex is
+/// partly covered, and `"majority"` those with more than half their area inside
+/// it. Another choice is recorded under `containment` in the schema metadata. An
+/// unknown value raises `ValueError`.
+///
+/// The extent the returned hexes actually cover is recorded under `data_bbox`
+/// in the schema metadata, as a JSON `[min_lat, min_lon, max_lat, max_lon]`
+/// array. It reaches past the requested bbox to the far side of its edge hexes,
+/// and falls short of it where no pipes were returned, so adjacent tiles can be
+/// checked for gaps. A summary with no rows has no `data_bbox`.
+///
+/// For provenance, the schema metadata also records the query itself:
+/// `query_bbox`, the requested bbox in the same JSON form, and `zoom`, the hex
+/// resolution. The boundary functions record their boundary's envelope as
+/// `query_bbox`, and `query_object_id` when it was looked up by OBJECTID. Writing
+/// the batch with `pyarrow.parquet.write_table` keeps them in the file.
+///
+/// `date_from` / `date_to` (ISO `"YYYY-MM-DD"` strings or `datetime.date`s) only
+/// fetch pipes whose `inst_date` is on or after `date_from` and before `date_to`,
+/// so e.g. `date_to="1980-01-01"` summarises mains installed before 1980; pipes
+/// with no `inst_date` are left out. The filter is applied by the API, so
+/// `pipe_count` and every other column reflect only that subset, and the bounds
+/// are recorded under `date_from` / `date_to` in the schema metadata. An invalid
+/// date, or a layer with no date attribute, raises `ValueError`.
+///
+/// `max_total_features` caps the records fetched across every layer and every
+/// subdivided query of the bbox, guarding batch jobs against pulling a whole
+/// network. Once it is reached, requests still in flight are cancelled and the
+/// summary covers what was gathered, with a `UserWarning` and `truncated` set
+/// to `"true"` in the schema metadata (`"false"` if the cap was not reached).
+///
+/// With `error_mode="collect"` (the default) every part of the fetch is
+/// attempted and a `FetchError` lists all the requests that failed. With
+/// `"fail_fast"` the first failure cancels the requests still in flight and is
+/// raised on its own.
+///
+/// `deadline_seconds` bounds the wall-clock time of the whole call. When it
+/// passes, the requests still in flight are cancelled and a `FetchTimeoutError`
+/// (a `TimeoutError`) is raised; with `partial_on_deadline=True` the records
+/// fetched by then are summarised instead, with a `UserWarning` and `complete`
+/// set to `"false"` in the schema metadata (`"true"` if the fetch finished in
+/// time). A deadline that is not a positive number raises `ValueError`.
+///
+/// `telemetry=True` adds a `telemetry` entry to the schema metadata, a JSON
+/// object with the HTTP `requests` issued, the `retries` among them,
+/// `bytes_transferred` / `bytes_decoded`, and `latency_seconds`: the `min`,
+/// `p50`, `p90`, `p99`, `max` and `mean` of each request's latency. It covers
+/// every layer, page and subdivided query of the call.
+///
+/// `lonlat=True` reads the four floats as `(min_lon, min_lat, max_lon, max_lat)`,
+/// for data in that order. Latitudes and longitudes the wrong way round give a
+/// box far from Great Britain. If no part of it is in the BNG area of use it
+/// raises `ValueError`. If part of it is, the fetch finds no pipes. So finding
+/// no pipes in a box centred outside Great Britain gives a `UserWarning` showing
+/// both readings of the four numbers; with `strict_location=True` it raises
+/// `ValueError` instead.
+///
+/// `k_ring=k` (k >= 1) adds every hex within grid distance `k` of the remaining
+/// hexes, with zero counts, plus a UInt32 `grid_distance` column (steps to the
+/// nearest populated hex) and a Boolean `outside_query_area` column marking added
+/// hexes that do not overlap the bbox. A negative `k_ring` raises `ValueError`.
+///
+/// Unless `include_metadata_columns=False`, every row also carries its
+/// `resolution` (UInt8) and `provider` (the network name, dictionary
+/// encoded), so batches concatenated from several calls keep their provenance.
+/// The schema metadata records a `query_id`, a hash of every parameter deciding
+/// the rows (not `http_options`, the output options or `telemetry`) and the
+/// package version, which is the same for repeated calls on any platform;
+/// `include_query_id=True` also adds it as a constant `query_id` column
+/// (dictionary encoded) before `geometry`.
+///
+/// `output="polars"` returns a `polars.DataFrame` instead, with `geometry` as WKB
+/// binary, `provider` as strings and no schema metadata. It raises `ImportError`
+/// if polars is not installed. Without pyarrow installed, the default
+/// `output="arrow"` returns an `ArrowBatch` instead of a pyarrow RecordBatch:
+/// any library reading the Arrow PyCapsule interface can ingest it zero-copy.
+/// `output="capsule"` always returns an `ArrowBatch` and `output="pyarrow"`
+/// always a pyarrow RecordBatch.
+///
+/// `output_layout="normalized"` returns a `NormalizedSummary` instead, whose
+/// `cells` holds each hex's `hex_id`, `resolution` and `geometry` once and whose
+/// `summary` holds `hex_id` and the count columns, each in the `output` format.
+/// Joining them on `hex_id` gives the default `output_layout="wide"` batch.
+///
+/// `max_batch_rows` hands a wide summary over in batches of at most that many
+/// rows, for consumers that cannot take one huge batch: a `pyarrow.Table` of them
+/// (`output="pyarrow"`, or `"arrow"` with pyarrow installed), a list of
+/// `ArrowBatch` (`output="capsule"`) or a `polars.DataFrame` with one chunk per
+/// batch. Rows keep the summary's order, so concatenating the batches gives the
+/// unsplit summary. Raises `ValueError` for 0 or with
+/// `output_layout="normalized"`.
+///
+/// `with_stats=True` returns a `(summary, stats)` tuple, where `stats` is a dict
+/// of `total_records` (after deduplication), `hex_count` (populated hexes, after
+/// clipping and filtering), `max_pipe_count`, `mean_pipe_count`,
+/// `total_pipe_length_m` (metres of the fetched pipes, with
+/// `weighting="length"`; otherwise None) and `fetch_seconds`. An empty summary
+/// has zeros throughout.
+///
+/// `length_method` chooses how `total_pipe_length_m` is measured: `"geodesic"`
+/// (the default) along the WGS84 ellipsoid, `"haversine"` along a sphere
+/// (within about 0.5%), or `"projected_27700"` on the BNG plane, whose scale
+/// varies by up to about 0.07% across the country. The method is recorded under
+/// `length_method` in the schema metadata.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, [parent_hex_id,] pipe_count,
+/// [boundary_pipe_count,] [weighted_count,] [count_<layer>...,] [grid_distance,
+/// outside_query_area,] [hex_area_m2,] [cell_area_km2, pipe_density_per_km2,]
+/// [pipe_density,] [<field>_<function>...,] [resolution, provider,] geometry. A bbox with no pipes gives zero rows with the same columns, not an error.
+#[pyfunction]
+#[pyo3(signature = (min_lat, min_lon=None, max_lat=None, max_lon=None, resolution=None, *, zoom=None, target_hex_count=summary::DEFAULT_TARGET_HEX_COUNT, grid="hex", grid_size_m=None, grid_origin=None, parent_zoom=None, deduplicate=true, id_field=None, weighting="count", aggregations=None, count_mode="records", assignment="coverage", boundary_pipe_count=false, hex_area=false, include_density=false, normalize="none", max_hexes=summary::DEFAULT_MAX_HEXES, min_pipe_count=None, top_n=None, k_ring=0, layer=None, date_from=None, date_to=None, network=None, http_options=None, coordinate_precision=None, geometry_encoding="geoarrow_separated", include_metadata_columns=true, include_query_id=false, output="arrow", output_layout="wide", max_batch_rows=None, with_stats=false, length_method="geodesic", snap_to_hex=false, max_total_features=None, error_mode="collect", deadline_seconds=None, partial_on_deadline=false, containment="intersects", telemetry=false, lonlat=false, strict_location=false))]
+#[allow(clippy::too_many_arguments)]
+fn get_hex_summary(
+    py: Python<'_>,
+    min_lat: &Bound<'_, PyAny>,
+    min_lon: Option<&Bound<'_, PyAny>>,
+    max_lat: Option<&Bound<'_, PyAny>>,
+    max_lon: Option<&Bound<'_, PyAny>>,
+    resolution: Option<&Bound<'_, PyAny>>,
+    zoom: Option<&Bound<'_, PyAny>>,
+    target_hex_count: u64,
+    grid: &str,
+    grid_size_m: Option<i64>,
+    grid_origin: Option<(i64, i64)>,
+    parent_zoom: Option<i64>,
+    deduplicate: bool,
+    id_field: Option<&str>,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    count_mode: &str,
+    assignment: &str,
+    boundary_pipe_count: bool,
+    hex_area: bool,
+    include_density: bool,
+    normalize: &str,
+    max_hexes: u64,
+    min_pipe_count: Option<i64>,
+    top_n: Option<usize>,
+    k_ring: i64,
+    layer: Option<&Bound<'_, PyAny>>,
+    date_from: Option<&Bound<'_, PyAny>>,
+    date_to: Option<&Bound<'_, PyAny>>,
+    network: Option<&str>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    coordinate_precision: Option<i64>,
+    geometry_encoding: &str,
+    include_metadata_columns: bool,
+    include_query_id: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+    with_stats: bool,
+    length_method: &str,
+    snap_to_hex: bool,
+    max_total_features: Option<usize>,
+    error_mode: &str,
+    deadline_seconds: Option<f64>,
+    partial_on_deadline: bool,
+    containment: &str,
+    telemetry: bool,
+    lonlat: bool,
+    strict_location: bool,
+) -> PyResult<Py<PyAny>> {
+    let max_total_features = extract_max_total_features(max_total_features)?;
+    let error_mode = extract_error_mode(error_mode)?;
+    let deadline = extract_deadline_seconds(deadline_seconds)?;
+    let containment = extract_containment(containment)?;
+    let layers = extract_layers(layer)?;
+    let query = RecordQuery {
+        dates: extract_date_range(date_from, date_to, &layers)?,
+        ..RecordQuery::default()
+    };
+    let network = extract_network(network)?;
+    let id_field = extract_id_field(id_field, network.id_field())?;
+    let http_options = extract_http_options(http_options)?;
+    let weighting = extract_weighting(weighting)?;
+    let aggregations = extract_aggregations(aggregations)?;
+    let length_method = extract_length_method(length_method)?;
+    let count_mode = extract_count_mode(count_mode)?;
+    let assignment = extract_assignment(assignment)?;
+    let normalize = extract_normalize(normalize)?;
+    let filter = extract_hex_filter(min_pipe_count, top_n)?;
+    let k_ring = validate_k_ring(k_ring)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let geometry_options = extract_geometry_options(coordinate_precision, None, geometry_encoding)?;
+    let output = extract_summary_output(output, output_layout, max_batch_rows)?;
+    let squares = extract_grid_squares(grid, grid_size_m, grid_origin)?;
+    let resolution = extract_resolution_arg(py, resolution, zoom)?;
+    let (min_lat, min_lon, max_lat, max_lon) =
+        lonlat_bbox_args(min_lat, min_lon, max_lat, max_lon, lonlat)?;
+    let (bbox, grid, parent_zoom) = match squares {
+        Some(squares) => {
+            let (bbox, resolution) =
+                bbox_and_resolution_args(min_lat, min_lon, max_lat, max_lon, resolution)?;
+            reject_hex_only_options(
+                grid,
+                &[
+                    ("resolution", resolution.is_some()),
+                    ("parent_zoom", parent_zoom.is_some()),
+                    (
+                        "count_mode=\"unique_assets\"",
+                        count_mode == CountMode::UniqueAssets,
+                    ),
+                    ("boundary_pipe_count", boundary_pipe_count),
+                    ("hex_area", hex_area),
+                    ("include_density", include_density),
+                    ("normalize", normalize != Normalize::None),
+                    ("aggregations", !aggregations.is_empty()),
+                    ("k_ring", k_ring > 0),
+                    ("snap_to_hex", snap_to_hex),
+                    (
+                        "output_layout=\"normalized\"",
+                        output.layout == OutputLayout::Normalized,
+                    ),
+                ],
+            )?;
+            check_square_budget(&bbox, squares.size_m(), max_hexes)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+            (bbox, Grid::Squares(squares), None)
+        }
+        None => {
+            let (bbox, resolution) = resolve_bbox_args(
+                min_lat,
+                min_lon,
+                max_lat,
+                max_lon,
+                resolution,
+                target_hex_count,
+            )?;
+            let zoom = resolution.zoom();
+            let parent_zoom = extract_parent_zoom(zoom, parent_zoom)?;
+            check_hex_budget(&bbox_polygon(&bbox), zoom, max_hexes)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+            (bbox, Grid::Hex(resolution), parent_zoom)
+        }
+    };
+    hex_summary_for_bbox(
+        py,
+        bbox,
+        grid,
+        parent_zoom,
+        deduplicate,
+        &id_field,
+        count_mode,
+        assignment,
+        weighting,
+        &aggregations,
+        boundary_pipe_count,
+        hex_area,
+        include_density,
+        normalize,
+        &filter,
+        k_ring,
+        network,
+        &layers,
+        &query,
+        &http_options,
+        &geometry_options,
+        include_metadata_columns,
+        include_query_id,
+        output,
+        with_stats,
+        length_method,
+        snap_to_hex,
+        max_total_features,
+        error_mode,
+        deadline,
+        partial_on_deadline,
+        containment,
+        telemetry,
+        Some(LocationCheck {
+            lonlat,
+            strict: strict_location,
+        }),
+    )
+}
+
+/// Get hex summary for a bounding box given in British National Grid (EPSG:27700).
+///
+/// # Arguments
+/// * `min_e`, `min_n`, `max_e`, `max_n` - Extent in BNG eastings/northings (metres)
+/// * `resolution` - Hex grid resolution (0-15, or `"auto"` as in `get_hex_summary`;
+///   `zoom` is a deprecated alias)
+///
+/// The extent is transformed to WGS84 with PROJ's OSGB grid transform. Raises
+/// `ValueError` if it is inverted or falls outside the BNG domain. `layer`,
+/// `network`, `target_hex_count`, `grid`, `grid_size_m`, `grid_origin`,
+/// `parent_zoom`, `deduplicate`, `id_field`, `count_mode`, `assignment`,
+/// `boundary_pipe_count`, `hex_area`, `include_density`, `normalize`, `weighting`,
+/// `aggregations`, `max_hexes`, `min_pipe_count`, `top_n`, `date_from`, `date_to`, `geometry_encoding`, `include_metadata_columns`, `include_query_id`,
+/// `output`, `output_layout`, `with_stats`, `length_method`, `snap_to_hex`,
+/// `max_total_features`, `error_mode`, `deadline_seconds`, `partial_on_deadline`,
+/// `containment` and `telemetry` behave as in `get_hex_summary`.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count, [count_<layer>...,]
+/// [hex_area_m2,] [cell_area_km2, pipe_density_per_km2,] [pipe_density,]
+/// [resolution, provider,] geometry
+#[pyfunction]
+#[pyo3(signature = (min_e, min_n, max_e, max_n, resolution=None, *, zoom=None, target_hex_count=summary::DEFAULT_TARGET_HEX_COUNT, grid="hex", grid_size_m=None, grid_origin=None, parent_zoom=None, deduplicate=true, id_field=None, weighting="count", aggregations=None, count_mode="records", assignment="coverage", boundary_pipe_count=false, hex_area=false, include_density=false, normalize="none", max_hexes=summary::DEFAULT_MAX_HEXES, min_pipe_count=None, top_n=None, layer=None, date_from=None, date_to=None, network=None, http_options=None, coordinate_precision=None, geometry_encoding="geoarrow_separated", include_metadata_columns=true, include_query_id=false, output="arrow", output_layout="wide", max_batch_rows=None, with_stats=false, length_method="geodesic", snap_to_hex=false, max_total_features=None, error_mode="collect", deadline_seconds=None, partial_on_deadline=false, containment="intersects", telemetry=false))]
+#[allow(clippy::too_many_arguments)]
+fn get_hex_summary_bng(
+    py: Python<'_>,
+    min_e: f64,
+    min_n: f64,
+    max_e: f64,
+    max_n: f64,
+    resolution: Option<&Bound<'_, PyAny>>,
+    zoom: Option<&Bound<'_, PyAny>>,
+    target_hex_count: u64,
+    grid: &str,
+    grid_size_m: Option<i64>,
+    grid_origin: Option<(i64, i64)>,
+    parent_zoom: Option<i64>,
+    deduplicate: bool,
+    id_field: Option<&str>,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    count_mode: &str,
+    assignment: &str,
+    boundary_pipe_count: bool,
+    hex_area: bool,
+    include_density: bool,
+    normalize: &str,
+    max_hexes: u64,
+    min_pipe_count: Option<i64>,
+    top_n: Option<usize>,
+    layer: Option<&Bound<'_, PyAny>>,
+    date_from: Option<&Bound<'_, PyAny>>,
+    date_to: Option<&Bound<'_, PyAny>>,
+    network: Option<&str>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    coordinate_precision: Option<i64>,
+    geometry_encoding: &str,
+    include_metadata_columns: bool,
+    include_query_id: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+    with_stats: bool,
+    length_method: &str,
+    snap_to_hex: bool,
+    max_total_features: Option<usize>,
+    error_mode: &str,
+    deadline_seconds: Option<f64>,
+    partial_on_deadline: bool,
+    containment: &str,
+    telemetry: bool,
+) -> PyResult<Py<PyAny>> {
+    let max_total_features = extract_max_total_features(max_total_features)?;
+    let error_mode = extract_error_mode(error_mode)?;
+    let deadline = extract_deadline_seconds(deadline_seconds)?;
+    let containment = extract_containment(containment)?;
+    let squares = extract_grid_squares(grid, grid_size_m, grid_origin)?;
+    let resolution = extract_resolution_arg(py, resolution, zoom)?;
+    let layers = extract_layers(layer)?;
+    let query = RecordQuery {
+        dates: extract_date_range(date_from, date_to, &layers)?,
+        ..RecordQuery::default()
+    };
+    let network = extract_network(network)?;
+    let id_field = extract_id_field(id_field, network.id_field())?;
+    let http_options = extract_http_options(http_options)?;
+    let weighting = extract_weighting(weighting)?;
+    let aggregations = extract_aggregations(aggregations)?;
+    let length_method = extract_length_method(length_method)?;
+    let count_mode = extract_count_mode(count_mode)?;
+    let assignment = extract_assignment(assignment)?;
+    let normalize = extract_normalize(normalize)?;
+    let filter = extract_hex_filter(min_pipe_count, top_n)?;
+    let geometry_options = extract_geometry_options(coordinate_precision, None, geometry_encoding)?;
+    let output = extract_summary_output(output, output_layout, max_batch_rows)?;
+    let bbox = bng_extent_to_wgs84(min_e, min_n, max_e, max_n)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let (grid, parent_zoom) = match squares {
+        Some(squares) => {
+            reject_hex_only_options(
+                grid,
+                &[
+                    ("resolution", resolution.is_some()),
+                    ("parent_zoom", parent_zoom.is_some()),
+                    (
+                        "count_mode=\"unique_assets\"",
+                        count_mode == CountMode::UniqueAssets,
+                    ),
+                    ("boundary_pipe_count", boundary_pipe_count),
+                    ("hex_area", hex_area),
+                    ("include_density", include_density),
+                    ("normalize", normalize != Normalize::None),
+                    ("aggregations", !aggregations.is_empty()),
+                    ("snap_to_hex", snap_to_hex),
+                    (
+                        "output_layout=\"normalized\"",
+                        output.layout == OutputLayout::Normalized,
+                    ),
+                ],
+            )?;
+            check_square_budget(&bbox, squares.size_m(), max_hexes)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+            (Grid::Squares(squares), None)
+        }
+        None => {
+            let resolution = resolution
+                .ok_or_else(|| {
+                    PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                        "Missing required argument: resolution",
+                    )
+                })?
+                .resolve(&bbox, target_hex_count)?;
+            let zoom = resolution.zoom();
+            let parent_zoom = extract_parent_zoom(zoom, parent_zoom)?;
+            check_hex_budget(&bbox_polygon(&bbox), zoom, max_hexes)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+            (Grid::Hex(resolution), parent_zoom)
+        }
+    };
+    hex_summary_for_bbox(
+        py,
+        bbox,
+        grid,
+        parent_zoom,
+        deduplicate,
+        &id_field,
+        count_mode,
+        assignment,
+        weighting,
+        &aggregations,
+        boundary_pipe_count,
+        hex_area,
+        include_density,
+        normalize,
+        &filter,
+        0,
+        network,
+        &layers,
+        &query,
+        &http_options,
+        &geometry_options,
+        include_metadata_columns,
+        include_query_id,
+        output,
+        with_stats,
+        length_method,
+        snap_to_hex,
+        max_total_features,
+        error_mode,
+        deadline,
+        partial_on_deadline,
+        containment,
+        telemetry,
+        None,
+    )
+}
+
+/// Estimate what `get_hex_summary` would fetch for a bbox, before fetching it.
+///
+/// Takes the bbox and `resolution` as `get_hex_summary` does, and `layer`,
+/// `date_from`, `date_to`, `network` and `http_options` as there too. Each layer
+/// costs one count query, from which the paging plan of a full fetch is worked
+/// out: how many pages the records take, and how far the bbox would be split
+/// into quadrants to stay under the API's paging limit, assuming its records
+/// are spread evenly. The `mock` network makes no requests.
+///
+/// # Returns
+/// A dict of `feature_count` (records matching across every layer),
+/// `estimated_requests` (HTTP requests a fetch would make, before retries),
+/// `estimated_hexes` (hexes at the resolution covering the bbox, populated or
+/// not) and the `resolution` used.
+///
+/// # Errors
+/// Raises `FetchError` if a count query fails.
+#[pyfunction]
+#[pyo3(signature = (min_lat, min_lon=None, max_lat=None, max_lon=None, resolution=None, *, zoom=None, target_hex_count=summary::DEFAULT_TARGET_HEX_COUNT, layer=None, date_from=None, date_to=None, network=None, http_options=None))]
+#[allow(clippy::too_many_arguments)]
+fn estimate_fetch<'py>(
+    py: Python<'py>,
+    min_lat: &Bound<'py, PyAny>,
+    min_lon: Option<&Bound<'py, PyAny>>,
+    max_lat: Option<&Bound<'py, PyAny>>,
+    max_lon: Option<&Bound<'py, PyAny>>,
+    resolution: Option<&Bound<'py, PyAny>>,
+    zoom: Option<&Bound<'py, PyAny>>,
+    target_hex_count: u64,
+    layer: Option<&Bound<'py, PyAny>>,
+    date_from: Option<&Bound<'py, PyAny>>,
+    date_to: Option<&Bound<'py, PyAny>>,
+    network: Option<&str>,
+    http_options: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let layers = extract_layers(layer)?;
+    let query = RecordQuery {
+        dates: extract_date_range(date_from, date_to, &layers)?,
+        ..RecordQuery::default()
+    };
+    let network = extract_network(network)?;
+    let http_options = extract_http_options(http_options)?;
+    let resolution = extract_resolution_arg(py, resolution, zoom)?;
+    let (bbox, resolution) = resolve_bbox_args(
+        min_lat,
+        min_lon,
+        max_lat,
+        max_lon,
+        resolution,
+        target_hex_count,
+    )?;
+    let estimated_hexes = estimate_hex_count(&bbox_polygon(&bbox), resolution.zoom())
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+
+    let started = Instant::now();
+    let estimates =
+        runtime()?.block_on(futures::future::join_all(layers.layers().iter().map(
+            |layer| network.estimate_layer_by_bbox(*layer, &bbox, &query, &http_options),
+        )));
+    let (mut feature_count, mut requests) = (0, 0);
+    for estimate in estimates {
+        let estimate = estimate.map_err(|e| fetch_error(py, &[e], started.elapsed()))?;
+        feature_count += estimate.feature_count;
+        requests += estimate.requests;
+    }
+
+    let dict = PyDict::new(py);
+    dict.set_item("feature_count", feature_count)?;
+    dict.set_item("estimated_requests", requests)?;
+    dict.set_item("estimated_hexes", estimated_hexes)?;
+    dict.set_item("resolution", resolution.zoom())?;
+    Ok(dict)
+}
+
+/// Get hex summary for exactly the hexes in `cells`, a list of hex ids.
+///
+/// Pipes are fetched once, for the bounding box of all the cells, and the result
+/// has one row per cell in input order, including cells with no pipes (a
+/// `pipe_count` of 0). The cells must all be at one resolution, and at
+/// `resolution` if it is given; repeated ids are kept once, with a `UserWarning`.
+/// Since the whole bounding box is fetched, cells spread far apart are better
+/// summarised in separate calls.
+///
+/// With `nearest_distance`, a `nearest_pipe_m` column gives each empty cell the
+/// geodesic distance in metres from its centre to the nearest fetched pipe, and
+/// is null for cells with pipes. Only pipes within the cells' bounding box are
+/// fetched, so it is null if none are.
+///
+/// `deduplicate`, `layer`, `network`, `http_options`, `coordinate_precision`,
+/// `geometry_encoding`, `include_metadata_columns`, `output`, `output_layout` and
+/// `telemetry` are as for `get_hex_summary`.
+///
+/// # Errors
+/// Raises `ValueError` if `cells` is empty, contains an invalid hex id, mixes
+/// resolutions, or has a cell at a resolution other than `resolution`.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count, [count_<layer>...,]
+/// [nearest_pipe_m,] [resolution, provider,] geometry
+#[pyfunction]
+#[pyo3(signature = (cells, resolution=None, *, zoom=None, deduplicate=true, nearest_distance=false, layer=None, network=None, http_options=None, coordinate_precision=None, geometry_encoding="geoarrow_separated", include_metadata_columns=true, output="arrow", output_layout="wide", max_batch_rows=None, telemetry=false))]
+#[allow(clippy::too_many_arguments)]
+fn hex_summary_for_cells(
+    py: Python<'_>,
+    cells: Vec<String>,
+    resolution: Option<u8>,
+    zoom: Option<u8>,
+    deduplicate: bool,
+    nearest_distance: bool,
+    layer: Option<&Bound<'_, PyAny>>,
+    network: Option<&str>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    coordinate_precision: Option<i64>,
+    geometry_encoding: &str,
+    include_metadata_columns: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+    telemetry: bool,
+) -> PyResult<Py<PyAny>> {
+    let layers = extract_layers(layer)?;
+    let network = extract_network(network)?;
+    let http_options = extract_http_options(http_options)?;
+    let geometry_options = extract_geometry_options(coordinate_precision, None, geometry_encoding)?;
+    let output = extract_summary_output(output, output_layout, max_batch_rows)?;
+    let resolution = extract_resolution_alias(py, resolution, zoom)?
+        .map(validate_resolution)
+        .transpose()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let (cells, duplicates) = parse_hex_cells(&cells, resolution)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    if duplicates > 0 {
+        let message = format!(
+            "cells contains {} duplicate hex id(s); each is summarised once",
+            duplicates
+        );
+        PyErr::warn(
+            py,
+            py.get_type::<pyo3::exceptions::PyUserWarning>().as_any(),
+            &CString::new(message)?,
+            1,
+        )?;
+    }
+    let zoom = cells[0].zoom_level;
+    let bbox = hex_cells_bbox(&cells)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+
+    let stats = TransferStats::default().with_telemetry(telemetry);
+    let records = fetch_layers(
+        py,
+        &bbox,
+        network,
+        &layers,
+        &RecordQuery::default(),
+        &http_options,
+        deduplicate.then(|| network.id_field()).as_ref(),
+        &stats,
+        &FeatureBudget::default(),
+    )?;
+    let batch = layers_hex_summary(&layers, &records, zoom, Assignment::Coverage, 0)?;
+    let batch = select_hex_cells(batch, &cells)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = if nearest_distance {
+        with_nearest_pipe_distances(batch, records.iter().flatten())
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = if include_metadata_columns {
+        with_metadata_columns(batch, Some(zoom), Some(network.name()))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let data_bbox = data_bbox_metadata(&batch)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_metadata(
+        batch,
+        stats
+            .metadata()
+            .into_iter()
+            .chain(data_bbox)
+            .chain(query_metadata(Some(&bbox), None, Some(zoom))),
+    )
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_geometry_options(batch, &geometry_options)
+        .and_then(conform_hex_summary)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    output.to_python(py, batch)
+}
+
+/// Get the pipes passing through one hex, e.g. to list them when a hex is clicked
+/// on a map.
+///
+/// Only the bounding box of the hex is fetched, so this is quick, and of the
+/// records returned only those whose line passes through the hex polygon are
+/// kept: the same pipes its `pipe_count` counts, except that (as in every bbox
+/// fetch) a pipe located outside the bounding box is not returned. `resolution`,
+/// if given, must be the hex id's. `deduplicate`, `layer`, `network`,
+/// `http_options` and `output` are as for `get_hex_summary`; records from several
+/// layers are listed together.
+///
+/// # Errors
+/// Raises `ValueError` if `hex_id` is not a valid hex id, or is not at
+/// `resolution`.
+///
+/// # Returns
+/// A PyArrow RecordBatch in the `RegionCache.to_pipe_records()` layout: one row
+/// per pipe with its attributes and a WKB `geometry` column (EPSG:4326), with no
+/// rows if no pipes pass through the hex.
+#[pyfunction]
+#[pyo3(signature = (hex_id, resolution=None, *, zoom=None, deduplicate=true, layer=None, network=None, http_options=None, output="arrow"))]
+#[allow(clippy::too_many_arguments)]
+fn get_pipes_in_cell(
+    py: Python<'_>,
+    hex_id: String,
+    resolution: Option<u8>,
+    zoom: Option<u8>,
+    deduplicate: bool,
+    layer: Option<&Bound<'_, PyAny>>,
+    network: Option<&str>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    output: &str,
+) -> PyResult<Py<PyAny>> {
+    let layers = extract_layers(layer)?;
+    let network = extract_network(network)?;
+    let http_options = extract_http_options(http_options)?;
+    let output = extract_output(output)?;
+    let resolution = extract_resolution_alias(py, resolution, zoom)?
+        .map(validate_resolution)
+        .transpose()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let (cells, _) = parse_hex_cells(std::slice::from_ref(&hex_id), resolution)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let bbox = hex_cells_bbox(&cells)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+
+    let stats = TransferStats::default();
+    let records: Vec<PipelineRecord> = fetch_layers(
+        py,
+        &bbox,
+        network,
+        &layers,
+        &RecordQuery::default(),
+        &http_options,
+        deduplicate.then(|| network.id_field()).as_ref(),
+        &stats,
+        &FeatureBudget::default(),
+    )?
+    .into_iter()
+    .flatten()
+    .collect();
+    let batch = records_in_cell(records, &cells[0])
+        .and_then(|records| records_to_batch(&records))
+        .and_then(|batch| {
+            let zoom = Some(cells[0].zoom_level);
+            with_metadata(
+                batch,
+                stats
+                    .metadata()
+                    .into_iter()
+                    .chain(query_metadata(Some(&bbox), None, zoom)),
+            )
+        })
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    output.to_python(py, batch)
+}
+
+/// Get the pipe-to-hex mapping for a bounding box: one row per pipe and hex it
+/// passes through, the long form the summary functions aggregate.
+///
+/// A pipe crossing several hexes has one row per hex. As in `get_hex_summary`,
+/// only hexes overlapping the bbox are kept, so grouping the rows by `hex_id`
+/// and counting them reproduces its `pipe_count`. `resolution="auto"`,
+/// `target_hex_count`, `deduplicate`, `layer`, `network`, `http_options` and
+/// `output` behave as in `get_hex_summary`; records from several layers are
+/// listed together.
+///
+/// `id_field` names the attribute holding each pipe's id, for `pipe_id` and for
+/// deduplication, as in `get_hex_summary`.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: pipe_id (the `id_field` value, or for a
+/// record without one an id hashed from its geometry), hex_id, geometry (the pipe
+/// as WKB in EPSG:4326)
+#[pyfunction]
+#[pyo3(signature = (min_lat, min_lon=None, max_lat=None, max_lon=None, resolution=None, *, zoom=None, target_hex_count=summary::DEFAULT_TARGET_HEX_COUNT, deduplicate=true, id_field=None, layer=None, network=None, http_options=None, output="arrow"))]
+#[allow(clippy::too_many_arguments)]
+fn get_pipe_hex_assignments(
+    py: Python<'_>,
+    min_lat: &Bound<'_, PyAny>,
+    min_lon: Option<&Bound<'_, PyAny>>,
+    max_lat: Option<&Bound<'_, PyAny>>,
+    max_lon: Option<&Bound<'_, PyAny>>,
+    resolution: Option<&Bound<'_, PyAny>>,
+    zoom: Option<&Bound<'_, PyAny>>,
+    target_hex_count: u64,
+    deduplicate: bool,
+    id_field: Option<&str>,
+    layer: Option<&Bound<'_, PyAny>>,
+    network: Option<&str>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    output: &str,
+) -> PyResult<Py<PyAny>> {
+    let layers = extract_layers(layer)?;
+    let network = extract_network(network)?;
+    let id_field = extract_id_field(id_field, network.id_field())?;
+    let http_options = extract_http_options(http_options)?;
+    let output = extract_output(output)?;
+    let resolution = extract_resolution_arg(py, resolution, zoom)?;
+    let (bbox, resolution) = resolve_bbox_args(
+        min_lat,
+        min_lon,
+        max_lat,
+        max_lon,
+        resolution,
+        target_hex_count,
+    )?;
+    let zoom = resolution.zoom();
+
+    let stats = TransferStats::default();
+    let records: Vec<PipelineRecord> = fetch_layers(
+        py,
+        &bbox,
+        network,
+        &layers,
+        &RecordQuery::default(),
+        &http_options,
+        deduplicate.then_some(&id_field),
+        &stats,
+        &FeatureBudget::default(),
+    )?
+    .into_iter()
+    .flatten()
+    .collect();
+    let batch = to_pipe_hex_assignments(&records, zoom, &bbox_polygon(&bbox), &id_field)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_metadata(
+        batch,
+        stats
+            .metadata()
+            .into_iter()
+            .chain(query_metadata(Some(&bbox), None, Some(zoom)))
+            .chain(resolution.metadata())
+            .chain(id_field.metadata(&records)),
+    )
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    output.to_python(py, batch)
+}
+
+/// Get the ordered trace of each pipe through the hex grid for a bounding box: one
+/// row per pipe with the hexes its line passes through, in the order it passes
+/// through them, for animating flow along pipes or connectivity analysis.
+///
+/// A pipe that leaves a hex and later re-enters it lists the hex again, and a
+/// pipe of several parts lists each part's hexes in turn. The traces run the
+/// whole length of each pipe, including hexes outside the bbox, so the set of
+/// hexes in a trace is a superset of the pipe's `get_pipe_hex_assignments` rows.
+/// The other arguments are as for `get_pipe_hex_assignments`.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: pipe_id (the `id_field` value, or for a
+/// record without one an id hashed from its geometry), cell_sequence (a list of
+/// hex ids)
+#[pyfunction]
+#[pyo3(signature = (min_lat, min_lon=None, max_lat=None, max_lon=None, resolution=None, *, zoom=None, target_hex_count=summary::DEFAULT_TARGET_HEX_COUNT, deduplicate=true, id_field=None, layer=None, network=None, http_options=None, output="arrow"))]
+#[allow(clippy::too_many_arguments)]
+fn get_pipe_cell_traces(
+    py: Python<'_>,
+    min_lat: &Bound<'_, PyAny>,
+    min_lon: Option<&Bound<'_, PyAny>>,
+    max_lat: Option<&Bound<'_, PyAny>>,
+    max_lon: Option<&Bound<'_, PyAny>>,
+    resolution: Option<&Bound<'_, PyAny>>,
+    zoom: Option<&Bound<'_, PyAny>>,
+    target_hex_count: u64,
+    deduplicate: bool,
+    id_field: Option<&str>,
+    layer: Option<&Bound<'_, PyAny>>,
+    network: Option<&str>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    output: &str,
+) -> PyResult<Py<PyAny>> {
+    let layers = extract_layers(layer)?;
+    let network = extract_network(network)?;
+    let id_field = extract_id_field(id_field, network.id_field())?;
+    let http_options = extract_http_options(http_options)?;
+    let output = extract_output(output)?;
+    let resolution = extract_resolution_arg(py, resolution, zoom)?;
+    let (bbox, resolution) = resolve_bbox_args(
+        min_lat,
+        min_lon,
+        max_lat,
+        max_lon,
+        resolution,
+        target_hex_count,
+    )?;
+    let zoom = resolution.zoom();
+
+    let stats = TransferStats::default();
+    let records: Vec<PipelineRecord> = fetch_layers(
+        py,
+        &bbox,
+        network,
+        &layers,
+        &RecordQuery::default(),
+        &http_options,
+        deduplicate.then_some(&id_field),
+        &stats,
+        &FeatureBudget::default(),
+    )?
+    .into_iter()
+    .flatten()
+    .collect();
+    let batch = to_pipe_cell_traces(&records, zoom, &id_field)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_metadata(
+        batch,
+        stats
+            .metadata()
+            .into_iter()
+            .chain(query_metadata(Some(&bbox), None, Some(zoom)))
+            .chain(resolution.metadata())
+            .chain(id_field.metadata(&records)),
+    )
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    output.to_python(py, batch)
+}
+
+/// Get the asset-to-hex mapping for a bounding box: one row per asset and hex it
+/// falls in, for joining hex-level results back onto asset tables.
+///
+/// Assets are grouped by `asset_id`; each record without one is its own asset,
+/// with a synthetic id `unidentified-<n>` that is stable across fetches of the
+/// same data. Hexes are not clipped to the bbox, so every part of an asset is
+/// mapped. `assignment` behaves as in `get_hex_summary`; the other arguments as in
+/// `get_pipe_hex_assignments`.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: asset_id, hex_id and, with
+/// `assignment="coverage"`, fraction_of_length (the share of the asset's length in
+/// the hex, summing to 1.0 per asset)
+#[pyfunction]
+#[pyo3(signature = (min_lat, min_lon=None, max_lat=None, max_lon=None, resolution=None, *, zoom=None, target_hex_count=summary::DEFAULT_TARGET_HEX_COUNT, assignment="coverage", deduplicate=true, layer=None, network=None, http_options=None, output="arrow"))]
+#[allow(clippy::too_many_arguments)]
+fn get_record_hex_assignments(
+    py: Python<'_>,
+    min_lat: &Bound<'_, PyAny>,
+    min_lon: Option<&Bound<'_, PyAny>>,
+    max_lat: Option<&Bound<'_, PyAny>>,
+    max_lon: Option<&Bound<'_, PyAny>>,
+    resolution: Option<&Bound<'_, PyAny>>,
+    zoom: Option<&Bound<'_, PyAny>>,
+    target_hex_count: u64,
+    assignment: &str,
+    deduplicate: bool,
+    layer: Option<&Bound<'_, PyAny>>,
+    network: Option<&str>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    output: &str,
+) -> PyResult<Py<PyAny>> {
+    let assignment = extract_assignment(assignment)?;
+    let layers = extract_layers(layer)?;
+    let network = extract_network(network)?;
+    let http_options = extract_http_options(http_options)?;
+    let output = extract_output(output)?;
+    let resolution = extract_resolution_arg(py, resolution, zoom)?;
+    let (bbox, resolution) = resolve_bbox_args(
+        min_lat,
+        min_lon,
+        max_lat,
+        max_lon,
+        resolution,
+        target_hex_count,
+    )?;
+    let zoom = resolution.zoom();
+
+    let stats = TransferStats::default();
+    let records: Vec<PipelineRecord> = fetch_layers(
+        py,
+        &bbox,
+        network,
+        &layers,
+        &RecordQuery::default(),
+        &http_options,
+        deduplicate.then(|| network.id_field()).as_ref(),
+        &stats,
+        &FeatureBudget::default(),
+    )?
+    .into_iter()
+    .flatten()
+    .collect();
+    let batch = to_record_hex_assignments(&records, zoom, assignment)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_metadata(
+        batch,
+        stats
+            .metadata()
+            .into_iter()
+            .chain(query_metadata(Some(&bbox), None, Some(zoom)))
+            .chain(resolution.metadata()),
+    )
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    output.to_python(py, batch)
+}
+
+/// Get hex summary for pipelines within a built-up area polygon.
+///
+/// # Arguments
+/// * `object_id` - The OBJECTID of the built-up area from ONS Open Geography Portal
+/// * `resolution` - Hex grid resolution (0-15; `zoom` is a deprecated alias)
+/// * `parent_zoom` - Add a `parent_hex_id` column at this coarser resolution, as in
+///   `get_hex_summary`
+/// * `layer` - Cadent layer to query (default `"gas_pipes"`)
+/// * `network` - Where to fetch pipes from: `"cadent"` (the default, unless changed
+///   with `set_default_network`), or `"mock"` for the embedded synthetic fixture in
+///   builds with the `mock` feature
+/// * `deduplicate` - Count records sharing an `asset_id` once (default true)
+/// * `id_field` - The attribute to deduplicate on instead, as in `get_hex_summary`
+/// * `count_mode` - `"records"` (default) or `"unique_assets"`, as in `get_hex_summary`
+/// * `assignment` - `"coverage"` (default) or `"midpoint"`, as in `get_hex_summary`
+/// * `boundary_pipe_count` - Add a `boundary_pipe_count` column, as in
+///   `get_hex_summary`
+/// * `weighting` - `"count"` (default) or `"length"` to add a `weighted_count` column
+/// * `aggregations` - Per-hex statistics of record attributes, as in `get_hex_summary`
+/// * `buffer_m` - Optional distance in metres to grow the area by before fetching
+///   and clipping, to catch infrastructure just outside the boundary
+/// * `clipped_area` - Add a `clipped_area_m2` column with the area of each hex inside
+///   the (buffered) built-up area, for normalising boundary hexes
+/// * `include_density` - Add `cell_area_km2` and `pipe_density_per_km2` columns, as
+///   in `get_hex_summary`, with each hex's area being that of its part inside the
+///   (buffered) area; the density is null for boundary slivers keeping less than 1%
+///   of their hex, whose tiny areas would give meaningless densities
+/// * `normalize` - `"none"` (default), `"per_km2"` or `"per_km_edge"` to add a
+///   `pipe_density` column, as in `get_hex_summary`; `"per_km2"` divides by the
+///   area inside the (buffered) area, null for slivers as above, while
+///   `"per_km_edge"` always uses the whole hex's edge length
+/// * `containment` - Which hexes to keep: `"intersects"` (default) every hex
+///   overlapping the (buffered) area, `"centroid"` those whose centre lies inside
+///   it, `"within"` only those entirely inside it, or `"majority"` those with more
+///   than half their area inside it; a choice other than the default is recorded
+///   under `containment` in the schema metadata
+/// * `strict_coverage` - Raise `ValueError` if part of the (buffered) area lies
+///   outside the network's coverage, rather than warning and summarising only the
+///   covered part (default false); the covered fraction of the area is recorded
+///   under `coverage_fraction` in the schema metadata either way
+/// * `max_hexes` - Cap on the estimated number of output hexes, checked before the
+///   pipeline fetch (default 2,000,000)
+/// * `min_pipe_count` - Drop hexes with fewer pipes than this
+/// * `top_n` - Keep only the `n` densest hexes
+/// * `http_options` - Optional dict of HTTP client options (proxy, headers, retries)
+/// * `coordinate_precision` - Optional number of decimal places to round coordinates to
+/// * `simplify_tolerance_m` - Optional Douglas-Peucker tolerance in metres; rings that
+///   would become invalid are left unsimplified
+/// * `geometry_encoding` - `"geoarrow_separated"` (default), `"geoarrow"` or `"wkb"`,
+///   as in `get_hex_summary`
+/// * `include_metadata_columns` - Add `resolution` and `provider` columns, as in
+///   `get_hex_summary` (default true)
+/// * `include_query_id` - Add the `query_id` recorded in the schema metadata as a
+///   column, as in `get_hex_summary` (default false)
+/// * `output` - `"arrow"` (default), `"pyarrow"`, `"capsule"` or `"polars"`, as in
+///   `get_hex_summary`
+/// * `output_layout` - `"wide"` (default) or `"normalized"`, as in `get_hex_summary`
+/// * `max_batch_rows` - Split the summary into batches of at most this many rows,
+///   as in `get_hex_summary`
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count, [resolution, provider,]
+/// geometry
+#[pyfunction]
+#[pyo3(signature = (object_id, resolution=None, *, zoom=None, parent_zoom=None, layer="gas_pipes", network=None, deduplicate=true, id_field=None, weighting="count", aggregations=None, count_mode="records", assignment="coverage", boundary_pipe_count=false, buffer_m=None, clipped_area=false, include_density=false, normalize="none", containment="intersects", strict_coverage=false, max_hexes=summary::DEFAULT_MAX_HEXES, min_pipe_count=None, top_n=None, http_options=None, coordinate_precision=None, simplify_tolerance_m=None, geometry_encoding="geoarrow_separated", include_metadata_columns=true, include_query_id=false, output="arrow", output_layout="wide", max_batch_rows=None))]
+#[allow(clippy::too_many_arguments)]
+fn get_hex_summary_polygon_area(
+    py: Python<'_>,
+    object_id: i64,
+    resolution: Option<u8>,
+    zoom: Option<u8>,
+    parent_zoom: Option<i64>,
+    layer: &str,
+    network: Option<&str>,
+    deduplicate: bool,
+    id_field: Option<&str>,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    count_mode: &str,
+    assignment: &str,
+    boundary_pipe_count: bool,
+    buffer_m: Option<f64>,
+    clipped_area: bool,
+    include_density: bool,
+    normalize: &str,
+    containment: &str,
+    strict_coverage: bool,
+    max_hexes: u64,
+    min_pipe_count: Option<i64>,
+    top_n: Option<usize>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    coordinate_precision: Option<i64>,
+    simplify_tolerance_m: Option<f64>,
+    geometry_encoding: &str,
+    include_metadata_columns: bool,
+    include_query_id: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+) -> PyResult<Py<PyAny>> {
+    let zoom = extract_resolution(py, resolution, zoom)?;
+    let options = AreaSummaryOptions::new(
+        zoom,
+        parent_zoom,
+        layer,
+        network,
+        deduplicate,
+        id_field,
+        weighting,
+        aggregations,
+        count_mode,
+        assignment,
+        boundary_pipe_count,
+        buffer_m,
+        clipped_area,
+        include_density,
+        normalize,
+        containment,
+        strict_coverage,
+        max_hexes,
+        min_pipe_count,
+        top_n,
+        http_options,
+        coordinate_precision,
+        simplify_tolerance_m,
+        geometry_encoding,
+        include_metadata_columns,
+        include_query_id,
+        output,
+        output_layout,
+        max_batch_rows,
+    )?;
+
+    let runtime = runtime()?;
+
+    let stats = TransferStats::default();
+    let area_client = BuiltUpAreaClient::new(&options.http_options)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+        .with_transfer_stats(&stats);
+
+    let started = Instant::now();
+    let built_up_area = runtime
+        .block_on(async { area_client.fetch_by_object_id(object_id).await })
+        .map_err(|e| fetch_error(py, &[e], started.elapsed()))?
+        .ok_or_else(|| built_up_area_not_found(py, object_id))?;
+
+    hex_summary_for_area(
+        py,
+        &runtime,
+        &stats,
+        built_up_area.geometry,
+        &format!("Built-up area OBJECTID {}", object_id),
+        Some(object_id),
+        QueryDescription::new("built_up_area").with("object_id", object_id),
+        zoom,
+        &options,
+    )
+}
+
+/// Get one hex summary for the pipelines within each of several built-up areas,
+/// chosen by name.
+///
+/// Each name is looked up in the ONS built-up areas, by its English or Welsh name
+/// and ignoring case and surrounding whitespace, once however often it is given.
+/// The areas it picks out are then fetched and summarised concurrently, each as
+/// by `get_hex_summary_polygon_area`, and their rows stacked in the order of
+/// `names`, an area named twice appearing once.
+///
+/// # Arguments
+/// * `names` - The names of the built-up areas, e.g. `["Manchester", "Salford"]`
+/// * `resolution` - Hex grid resolution (0-15; `zoom` is a deprecated alias)
+/// * `ignore_errors` - Warn about names that match no area, or more than one, and
+///   summarise the rest, rather than raising (default false); the problem names
+///   are listed under `unresolved_area_names` (a JSON array) in the schema metadata
+///
+/// Every other argument behaves as in `get_hex_summary_polygon_area`, applying to
+/// each area in turn, so `min_pipe_count` and `top_n` filter each area's hexes
+/// separately. A hex on the edge of two areas has a row for each.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count, [resolution, provider,]
+/// area_name, object_id, geometry; `area_name` is the name ONS gives the area
+///
+/// # Errors
+/// Raises `AreaNotFoundError` listing every name that matches no area, or more than
+/// one with their OBJECTIDs, unless `ignore_errors` is set and some name matched.
+#[pyfunction]
+#[pyo3(signature = (names, resolution=None, *, zoom=None, ignore_errors=false, parent_zoom=None, layer="gas_pipes", network=None, deduplicate=true, id_field=None, weighting="count", aggregations=None, count_mode="records", assignment="coverage", boundary_pipe_count=false, buffer_m=None, clipped_area=false, include_density=false, normalize="none", containment="intersects", strict_coverage=false, max_hexes=summary::DEFAULT_MAX_HEXES, min_pipe_count=None, top_n=None, http_options=None, coordinate_precision=None, simplify_tolerance_m=None, geometry_encoding="geoarrow_separated", include_metadata_columns=true, include_query_id=false, output="arrow", output_layout="wide", max_batch_rows=None))]
+#[allow(clippy::too_many_arguments)]
+fn get_hex_summary_for_area_names(
+    py: Python<'_>,
+    names: Vec<String>,
+    resolution: Option<u8>,
+    zoom: Option<u8>,
+    ignore_errors: bool,
+    parent_zoom: Option<i64>,
+    layer: &str,
+    network: Option<&str>,
+    deduplicate: bool,
+    id_field: Option<&str>,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    count_mode: &str,
+    assignment: &str,
+    boundary_pipe_count: bool,
+    buffer_m: Option<f64>,
+    clipped_area: bool,
+    include_density: bool,
+    normalize: &str,
+    containment: &str,
+    strict_coverage: bool,
+    max_hexes: u64,
+    min_pipe_count: Option<i64>,
+    top_n: Option<usize>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    coordinate_precision: Option<i64>,
+    simplify_tolerance_m: Option<f64>,
+    geometry_encoding: &str,
+    include_metadata_columns: bool,
+    include_query_id: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+) -> PyResult<Py<PyAny>> {
+    let zoom = extract_resolution(py, resolution, zoom)?;
+    let options = AreaSummaryOptions::new(
+        zoom,
+        parent_zoom,
+        layer,
+        network,
+        deduplicate,
+        id_field,
+        weighting,
+        aggregations,
+        count_mode,
+        assignment,
+        boundary_pipe_count,
+        buffer_m,
+        clipped_area,
+        include_density,
+        normalize,
+        containment,
+        strict_coverage,
+        max_hexes,
+        min_pipe_count,
+        top_n,
+        http_options,
+        coordinate_precision,
+        simplify_tolerance_m,
+        geometry_encoding,
+        include_metadata_columns,
+        include_query_id,
+        output,
+        output_layout,
+        max_batch_rows,
+    )?;
+    if names.is_empty() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "names must not be empty",
+        ));
+    }
+
+    let runtime = runtime()?;
+
+    let stats = TransferStats::default();
+    let area_client = BuiltUpAreaClient::new(&options.http_options)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+        .with_transfer_stats(&stats);
+
+    let mut unique_names: Vec<String> = Vec::new();
+    for name in &names {
+        let key = name.trim().to_uppercase();
+        if !unique_names.contains(&key) {
+            unique_names.push(key);
+        }
+    }
+
+    let started = Instant::now();
+    let lookups = runtime.block_on(futures::future::join_all(
+        unique_names
+            .iter()
+            .map(|name| area_client.search_by_name(name)),
+    ));
+    let mut matches: HashMap<String, Vec<BuiltUpArea>> = HashMap::new();
+    for (name, lookup) in unique_names.into_iter().zip(lookups) {
+        let areas = lookup.map_err(|e| fetch_error(py, &[e], started.elapsed()))?;
+        matches.insert(name, areas);
+    }
+
+    let mut areas: Vec<BuiltUpArea> = Vec::new();
+    let mut problems: Vec<String> = Vec::new();
+    let mut unresolved: Vec<String> = Vec::new();
+    for name in &names {
+        let key = name.trim().to_uppercase();
+        let Some(found) = matches.remove(&key) else {
+            // An earlier repeat of the name has already been handled
+            continue;
+        };
+        match found.len() {
+            0 => problems.push(format!("'{}' matches no built-up area", name.trim())),
+            1 => {
+                for area in found {
+                    if areas.iter().all(|seen| seen.object_id != area.object_id) {
+                        areas.push(area);
+                    }
+                }
+                continue;
+            }
+            _ => problems.push(format!(
+                "'{}' matches {} built-up areas: {}",
+                name.trim(),
+                found.len(),
+                found
+                    .iter()
+                    .map(|area| format!("{} (OBJECTID {})", area.name, area.object_id))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+        unresolved.push(name.trim().to_string());
+    }
+    if !problems.is_empty() {
+        let message = format!("Could not resolve every area name: {}", problems.join("; "));
+        if !ignore_errors || areas.is_empty() {
+            return Err(area_not_found(py, message, None));
+        }
+        PyErr::warn(
+            py,
+            py.get_type::<pyo3::exceptions::PyUserWarning>().as_any(),
+            &CString::new(message)?,
+            1,
+        )?;
+    }
+
+    let query_id = area_query_id(
+        QueryDescription::new("built_up_area_names")
+            .with(
+                "names",
+                names.iter().map(|name| name.trim()).collect::<Vec<_>>(),
+            )
+            .with("ignore_errors", ignore_errors),
+        zoom,
+        &options,
+    );
+    let clips = areas
+        .iter()
+        .map(|area| {
+            AreaClip::new(
+                py,
+                area.geometry.clone(),
+                &format!("Built-up area {} (OBJECTID {})", area.name, area.object_id),
+                zoom,
+                &options,
+            )
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let started = Instant::now();
+    let fetched = runtime.block_on(futures::future::join_all(
+        clips.iter().map(|clip| clip.fetch(&options, &stats)),
+    ));
+    let mut errors = Vec::new();
+    let mut records = Vec::with_capacity(fetched.len());
+    for result in fetched {
+        match result {
+            Ok(result) => {
+                errors.extend(result.errors);
+                records.push(result.records);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(fetch_error(py, &errors, started.elapsed()));
+    }
+    warn_missing_records(py, &stats)?;
+
+    for records in &mut records {
+        options.deduplicate(records);
+    }
+    let ids = options.id_metadata(records.iter().flatten());
+    let batches = areas
+        .iter()
+        .zip(&clips)
+        .zip(&records)
+        .map(|((area, clip), records)| {
+            let batch = clip.summary(records, zoom, &options, &query_id)?;
+            with_area_columns(batch, &area.name, area.object_id)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    let batch = stack_area_summaries(&batches)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    let data_bbox = data_bbox_metadata(&batch)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let unresolved = (!unresolved.is_empty()).then(|| {
+        (
+            "unresolved_area_names".to_string(),
+            serde_json::Value::from(unresolved).to_string(),
+        )
+    });
+    let batch = with_metadata(
+        batch,
+        stats
+            .metadata()
+            .into_iter()
+            .chain(data_bbox)
+            .chain(query_metadata(None, None, Some(zoom)))
+            .chain([("query_id".to_string(), query_id)])
+            .chain(ids)
+            .chain(unresolved),
+    )
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_geometry_options(batch, &options.geometry_options)
+        .and_then(conform_hex_summary)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    options.output.to_python(py, batch)
+}
+
+/// Get hex summary for pipelines within an ONS Local Authority District.
+///
+/// # Arguments
+/// * `code_or_objectid` - The district's ONS code (e.g. `"E08000003"`) or its OBJECTID
+/// * `resolution` - Hex grid resolution (0-15; `zoom` is a deprecated alias)
+/// * `boundary_resolution` - `"generalised"` (default, the 20m BGC boundaries) or
+///   `"full"` (BFC); full-resolution boundaries are much larger to download
+///
+/// Every other argument behaves as in `get_hex_summary_polygon_area`, and an unknown
+/// district raises `AreaNotFoundError`.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count, [resolution, provider,]
+/// geometry
+#[pyfunction]
+#[pyo3(signature = (code_or_objectid, resolution=None, *, zoom=None, boundary_resolution="generalised", parent_zoom=None, layer="gas_pipes", network=None, deduplicate=true, id_field=None, weighting="count", aggregations=None, count_mode="records", assignment="coverage", boundary_pipe_count=false, buffer_m=None, clipped_area=false, include_density=false, normalize="none", containment="intersects", strict_coverage=false, max_hexes=summary::DEFAULT_MAX_HEXES, min_pipe_count=None, top_n=None, http_options=None, coordinate_precision=None, simplify_tolerance_m=None, geometry_encoding="geoarrow_separated", include_metadata_columns=true, include_query_id=false, output="arrow", output_layout="wide", max_batch_rows=None))]
+#[allow(clippy::too_many_arguments)]
+fn get_hex_summary_local_authority(
+    py: Python<'_>,
+    code_or_objectid: &Bound<'_, PyAny>,
+    resolution: Option<u8>,
+    zoom: Option<u8>,
+    boundary_resolution: &str,
+    parent_zoom: Option<i64>,
+    layer: &str,
+    network: Option<&str>,
+    deduplicate: bool,
+    id_field: Option<&str>,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    count_mode: &str,
+    assignment: &str,
+    boundary_pipe_count: bool,
+    buffer_m: Option<f64>,
+    clipped_area: bool,
+    include_density: bool,
+    normalize: &str,
+    containment: &str,
+    strict_coverage: bool,
+    max_hexes: u64,
+    min_pipe_count: Option<i64>,
+    top_n: Option<usize>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    coordinate_precision: Option<i64>,
+    simplify_tolerance_m: Option<f64>,
+    geometry_encoding: &str,
+    include_metadata_columns: bool,
+    include_query_id: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+) -> PyResult<Py<PyAny>> {
+    let zoom = extract_resolution(py, resolution, zoom)?;
+    let id = if let Ok(code) = code_or_objectid.extract::<&str>() {
+        LocalAuthorityId::code(code)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?
+    } else if let Ok(object_id) = code_or_objectid.extract::<i64>() {
+        LocalAuthorityId::ObjectId(object_id)
+    } else {
+        return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+            "code_or_objectid must be an ONS code string or an integer OBJECTID",
+        ));
+    };
+    let boundary_resolution = BoundaryResolution::from_name(boundary_resolution)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let options = AreaSummaryOptions::new(
+        zoom,
+        parent_zoom,
+        layer,
+        network,
+        deduplicate,
+        id_field,
+        weighting,
+        aggregations,
+        count_mode,
+        assignment,
+        boundary_pipe_count,
+        buffer_m,
+        clipped_area,
+        include_density,
+        normalize,
+        containment,
+        strict_coverage,
+        max_hexes,
+        min_pipe_count,
+        top_n,
+        http_options,
+        coordinate_precision,
+        simplify_tolerance_m,
+        geometry_encoding,
+        include_metadata_columns,
+        include_query_id,
+        output,
+        output_layout,
+        max_batch_rows,
+    )?;
+
+    let runtime = runtime()?;
+
+    let stats = TransferStats::default();
+    let district_client = LocalAuthorityClient::new(&options.http_options, boundary_resolution)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+        .with_transfer_stats(&stats);
+
+    let started = Instant::now();
+    let district = runtime
+        .block_on(async { district_client.fetch(&id).await })
+        .map_err(|e| fetch_error(py, &[e], started.elapsed()))?
+        .ok_or_else(|| {
+            let object_id = match id {
+                LocalAuthorityId::ObjectId(object_id) => Some(object_id),
+                LocalAuthorityId::Code(_) => None,
+            };
+            area_not_found(
+                py,
+                format!("No local authority district found with {}", id),
+                object_id,
+            )
+        })?;
+
+    hex_summary_for_area(
+        py,
+        &runtime,
+        &stats,
+        district.geometry,
+        &format!(
+            "Local authority {} {} (OBJECTID {})",
+            district.code, district.name, district.object_id
+        ),
+        Some(district.object_id),
+        QueryDescription::new("local_authority")
+            .with("id", id.to_string())
+            .with_option("boundary_resolution", &boundary_resolution),
+        zoom,
+        &options,
+    )
+}
+
+/// Get hex summary for pipelines within a polygon from any ArcGIS feature service
+/// layer, e.g. an ONS Open Geography ward, LSOA or national park.
+///
+/// # Arguments
+/// * `layer_url` - The layer endpoint, `https://.../FeatureServer/<layer>`
+/// * `where` - SQL where clause selecting exactly one polygon feature, e.g.
+///   `"WD24CD = 'E05011362'"`
+/// * `resolution` - Hex grid resolution (0-15; `zoom` is a deprecated alias)
+///
+/// Every other argument behaves as in `get_hex_summary_polygon_area`.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count, [resolution, provider,]
+/// geometry
+///
+/// # Errors
+/// Raises `ValueError` if `layer_url` is not a feature service layer or `where`
+/// matches more than one feature, and `AreaNotFoundError` if it matches none.
+#[pyfunction]
+#[pyo3(signature = (layer_url, r#where, resolution=None, *, zoom=None, parent_zoom=None, layer="gas_pipes", network=None, deduplicate=true, id_field=None, weighting="count", aggregations=None, count_mode="records", assignment="coverage", boundary_pipe_count=false, buffer_m=None, clipped_area=false, include_density=false, normalize="none", containment="intersects", strict_coverage=false, max_hexes=summary::DEFAULT_MAX_HEXES, min_pipe_count=None, top_n=None, http_options=None, coordinate_precision=None, simplify_tolerance_m=None, geometry_encoding="geoarrow_separated", include_metadata_columns=true, include_query_id=false, output="arrow", output_layout="wide", max_batch_rows=None))]
+#[allow(clippy::too_many_arguments)]
+fn get_hex_summary_for_arcgis_feature(
+    py: Python<'_>,
+    layer_url: &str,
+    r#where: &str,
+    resolution: Option<u8>,
+    zoom: Option<u8>,
+    parent_zoom: Option<i64>,
+    layer: &str,
+    network: Option<&str>,
+    deduplicate: bool,
+    id_field: Option<&str>,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    count_mode: &str,
+    assignment: &str,
+    boundary_pipe_count: bool,
+    buffer_m: Option<f64>,
+    clipped_area: bool,
+    include_density: bool,
+    normalize: &str,
+    containment: &str,
+    strict_coverage: bool,
+    max_hexes: u64,
+    min_pipe_count: Option<i64>,
+    top_n: Option<usize>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    coordinate_precision: Option<i64>,
+    simplify_tolerance_m: Option<f64>,
+    geometry_encoding: &str,
+    include_metadata_columns: bool,
+    include_query_id: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+) -> PyResult<Py<PyAny>> {
+    let zoom = extract_resolution(py, resolution, zoom)?;
+    if r#where.trim().is_empty() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "where must be a non-empty SQL where clause",
+        ));
+    }
+    let options = AreaSummaryOptions::new(
+        zoom,
+        parent_zoom,
+        layer,
+        network,
+        deduplicate,
+        id_field,
+        weighting,
+        aggregations,
+        count_mode,
+        assignment,
+        boundary_pipe_count,
+        buffer_m,
+        clipped_area,
+        include_density,
+        normalize,
+        containment,
+        strict_coverage,
+        max_hexes,
+        min_pipe_count,
+        top_n,
+        http_options,
+        coordinate_precision,
+        simplify_tolerance_m,
+        geometry_encoding,
+        include_metadata_columns,
+        include_query_id,
+        output,
+        output_layout,
+        max_batch_rows,
+    )?;
+
+    let stats = TransferStats::default();
+    let feature_client = ArcGisPolygonClient::new(&options.http_options, layer_url)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?
+        .with_transfer_stats(&stats);
+    let label = format!("Feature {:?} of {}", r#where, feature_client.query_url());
+
+    let runtime = runtime()?;
+
+    // The count guards against downloading many polygons, so the query sent
+    // alongside it is capped at two features: enough to tell one match from several
+    let started = Instant::now();
+    let (matches, features) = runtime.block_on(futures::future::join(
+        feature_client.count(r#where),
+        feature_client.query_up_to(r#where, "*", 2),
+    ));
+    let matches = matches.map_err(|e| fetch_error(py, &[e], started.elapsed()))?;
+    match matches {
+        0 => {
+            return Err(area_not_found(
+                py,
+                format!(
+                    "No feature of {} matches {:?}",
+                    feature_client.query_url(),
+                    r#where
+                ),
+                None,
+            ));
+        }
+        1 => {}
+        n => {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Expected exactly one feature of {} to match {:?}, found {}",
+                feature_client.query_url(),
+                r#where,
+                n
+            )));
+        }
+    }
+
+    let feature = features
+        .map_err(|e| fetch_error(py, &[e], started.elapsed()))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            area_not_found(
+                py,
+                format!("{} was not returned by the service", label),
+                None,
+            )
+        })?;
+
+    hex_summary_for_area(
+        py,
+        &runtime,
+        &stats,
+        feature.geometry,
+        &label,
+        None,
+        QueryDescription::new("arcgis_feature")
+            .with("layer_url", feature_client.query_url().to_string())
+            .with("where", r#where),
+        zoom,
+        &options,
+    )
+}
+
+/// Get hex summary for pipelines within a polygon you already have, e.g. a
+/// catchment drawn in QGIS or a boundary loaded with geopandas.
+///
+/// # Arguments
+/// * `polygon_geojson` - The WGS84 Polygon or MultiPolygon as GeoJSON (a geometry,
+///   Feature or single-feature FeatureCollection), or any object with a
+///   `__geo_interface__`, such as a shapely geometry
+/// * `resolution` - Hex grid resolution (0-15; `zoom` is a deprecated alias)
+///
+/// No boundary lookup is needed, so the pipe fetch starts straight away. Every
+/// other argument behaves as in `get_hex_summary_polygon_area`.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count, [resolution, provider,]
+/// geometry
+///
+/// # Errors
+/// Raises `ValueError` if `polygon_geojson` is not a polygon, `TypeError` if it is
+/// neither a string nor has a `__geo_interface__`, and
+/// `InfraGeometryError` if it is invalid beyond repair.
+#[pyfunction]
+#[pyo3(signature = (polygon_geojson, resolution=None, *, zoom=None, parent_zoom=None, layer="gas_pipes", network=None, deduplicate=true, id_field=None, weighting="count", aggregations=None, count_mode="records", assignment="coverage", boundary_pipe_count=false, buffer_m=None, clipped_area=false, include_density=false, normalize="none", containment="intersects", strict_coverage=false, max_hexes=summary::DEFAULT_MAX_HEXES, min_pipe_count=None, top_n=None, http_options=None, coordinate_precision=None, simplify_tolerance_m=None, geometry_encoding="geoarrow_separated", include_metadata_columns=true, include_query_id=false, output="arrow", output_layout="wide", max_batch_rows=None))]
+#[allow(clippy::too_many_arguments)]
+fn get_hex_summary_for_polygon(
+    py: Python<'_>,
+    polygon_geojson: &Bound<'_, PyAny>,
+    resolution: Option<u8>,
+    zoom: Option<u8>,
+    parent_zoom: Option<i64>,
+    layer: &str,
+    network: Option<&str>,
+    deduplicate: bool,
+    id_field: Option<&str>,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    count_mode: &str,
+    assignment: &str,
+    boundary_pipe_count: bool,
+    buffer_m: Option<f64>,
+    clipped_area: bool,
+    include_density: bool,
+    normalize: &str,
+    containment: &str,
+    strict_coverage: bool,
+    max_hexes: u64,
+    min_pipe_count: Option<i64>,
+    top_n: Option<usize>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    coordinate_precision: Option<i64>,
+    simplify_tolerance_m: Option<f64>,
+    geometry_encoding: &str,
+    include_metadata_columns: bool,
+    include_query_id: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+) -> PyResult<Py<PyAny>> {
+    let zoom = extract_resolution(py, resolution, zoom)?;
+    let options = AreaSummaryOptions::new(
+        zoom,
+        parent_zoom,
+        layer,
+        network,
+        deduplicate,
+        id_field,
+        weighting,
+        aggregations,
+        count_mode,
+        assignment,
+        boundary_pipe_count,
+        buffer_m,
+        clipped_area,
+        include_density,
+        normalize,
+        containment,
+        strict_coverage,
+        max_hexes,
+        min_pipe_count,
+        top_n,
+        http_options,
+        coordinate_precision,
+        simplify_tolerance_m,
+        geometry_encoding,
+        include_metadata_columns,
+        include_query_id,
+        output,
+        output_layout,
+        max_batch_rows,
+    )?;
+    let polygon = extract_polygon(polygon_geojson, "polygon_geojson")?;
+    let query = QueryDescription::new("polygon").with("polygon", multipolygon_value(&polygon));
+
+    hex_summary_for_area(
+        py,
+        runtime()?.as_ref(),
+        &TransferStats::default(),
+        polygon,
+        "Polygon",
+        None,
+        query,
+        zoom,
+        &options,
+    )
+}
+
+/// Get hex summary for pipelines within `radius_m` metres of a point, such as
+/// "pipes within 2km of this site".
+///
+/// # Arguments
+/// * `lat`, `lon` - The WGS84 centre of the circle
+/// * `radius_m` - The circle's radius in metres
+/// * `resolution` - Hex grid resolution (0-15; `zoom` is a deprecated alias)
+///
+/// The circle is drawn in British National Grid, so it is round on the ground,
+/// and pipes are fetched in its bounding box and clipped to it. Every other
+/// argument behaves as in `get_hex_summary_polygon_area`; the buffer is the
+/// circle itself, so there is no `buffer_m`.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count, [resolution, provider,]
+/// geometry
+///
+/// # Errors
+/// Raises `ValueError` if `radius_m` is not a positive number of metres, or the
+/// point is not a latitude and longitude within the British National Grid's area
+/// of use.
+#[pyfunction]
+#[pyo3(signature = (lat, lon, radius_m, resolution=None, *, zoom=None, parent_zoom=None, layer="gas_pipes", network=None, deduplicate=true, id_field=None, weighting="count", aggregations=None, count_mode="records", assignment="coverage", boundary_pipe_count=false, clipped_area=false, include_density=false, normalize="none", containment="intersects", strict_coverage=false, max_hexes=summary::DEFAULT_MAX_HEXES, min_pipe_count=None, top_n=None, http_options=None, coordinate_precision=None, simplify_tolerance_m=None, geometry_encoding="geoarrow_separated", include_metadata_columns=true, include_query_id=false, output="arrow", output_layout="wide", max_batch_rows=None))]
+#[allow(clippy::too_many_arguments)]
+fn get_hex_summary_around_point(
+    py: Python<'_>,
+    lat: f64,
+    lon: f64,
+    radius_m: f64,
+    resolution: Option<u8>,
+    zoom: Option<u8>,
+    parent_zoom: Option<i64>,
+    layer: &str,
+    network: Option<&str>,
+    deduplicate: bool,
+    id_field: Option<&str>,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    count_mode: &str,
+    assignment: &str,
+    boundary_pipe_count: bool,
+    clipped_area: bool,
+    include_density: bool,
+    normalize: &str,
+    containment: &str,
+    strict_coverage: bool,
+    max_hexes: u64,
+    min_pipe_count: Option<i64>,
+    top_n: Option<usize>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    coordinate_precision: Option<i64>,
+    simplify_tolerance_m: Option<f64>,
+    geometry_encoding: &str,
+    include_metadata_columns: bool,
+    include_query_id: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+) -> PyResult<Py<PyAny>> {
+    let zoom = extract_resolution(py, resolution, zoom)?;
+    if !(radius_m.is_finite() && radius_m > 0.0) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "radius_m must be a positive number of metres, got {}",
+            radius_m
+        )));
+    }
+    if !((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "({}, {}) is not a WGS84 latitude and longitude",
+            lat, lon
+        )));
+    }
+    check_bng_coverage(&BBox::new(lat, lon, lat, lon))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let options = AreaSummaryOptions::new(
+        zoom,
+        parent_zoom,
+        layer,
+        network,
+        deduplicate,
+        id_field,
+        weighting,
+        aggregations,
+        count_mode,
+        assignment,
+        boundary_pipe_count,
+        None,
+        clipped_area,
+        include_density,
+        normalize,
+        containment,
+        strict_coverage,
+        max_hexes,
+        min_pipe_count,
+        top_n,
+        http_options,
+        coordinate_precision,
+        simplify_tolerance_m,
+        geometry_encoding,
+        include_metadata_columns,
+        include_query_id,
+        output,
+        output_layout,
+        max_batch_rows,
+    )?;
+    let circle = wgs84_circle(lat, lon, radius_m)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let query = QueryDescription::new("point_radius")
+        .with("lat", lat)
+        .with("lon", lon)
+        .with("radius_m", radius_m);
+
+    hex_summary_for_area(
+        py,
+        runtime()?.as_ref(),
+        &TransferStats::default(),
+        circle,
+        "Circle",
+        None,
+        query,
+        zoom,
+        &options,
+    )
+}
+
+/// The validated arguments shared by the boundary-clipped summary functions.
+struct AreaSummaryOptions {
+    parent_zoom: Option<u8>,
+    layer: CadentLayer,
+    network: Network,
+    deduplicate: bool,
+    id_field: IdField,
+    weighting: Weighting,
+    aggregations: Vec<Aggregation>,
+    count_mode: CountMode,
+    assignment: Assignment,
+    boundary_pipe_count: bool,
+    buffer_m: Option<f64>,
+    clipped_area: bool,
+    include_density: bool,
+    normalize: Normalize,
+    containment: Containment,
+    strict_coverage: bool,
+    max_hexes: u64,
+    filter: HexFilter,
+    http_options: HttpOptions,
+    geometry_options: GeometryOptions,
+    include_metadata_columns: bool,
+    include_query_id: bool,
+    output: SummaryOutput,
+}
+
+impl AreaSummaryOptions {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        zoom: u8,
+        parent_zoom: Option<i64>,
+        layer: &str,
+        network: Option<&str>,
+        deduplicate: bool,
+        id_field: Option<&str>,
+        weighting: &str,
+        aggregations: Option<&Bound<'_, PyDict>>,
+        count_mode: &str,
+        assignment: &str,
+        boundary_pipe_count: bool,
+        buffer_m: Option<f64>,
+        clipped_area: bool,
+        include_density: bool,
+        normalize: &str,
+        containment: &str,
+        strict_coverage: bool,
+        max_hexes: u64,
+        min_pipe_count: Option<i64>,
+        top_n: Option<usize>,
+        http_options: Option<&Bound<'_, PyDict>>,
+        coordinate_precision: Option<i64>,
+        simplify_tolerance_m: Option<f64>,
+        geometry_encoding: &str,
+        include_metadata_columns: bool,
+        include_query_id: bool,
+        output: &str,
+        output_layout: &str,
+        max_batch_rows: Option<usize>,
+    ) -> PyResult<Self> {
+        if let Some(buffer_m) = buffer_m
+            && !(buffer_m.is_finite() && buffer_m >= 0.0)
+        {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "buffer_m must be a non-negative number of metres, got {}",
+                buffer_m
+            )));
+        }
+        let network = extract_network(network)?;
+
+        Ok(Self {
+            parent_zoom: extract_parent_zoom(zoom, parent_zoom)?,
+            layer: extract_layer(layer)?,
+            network,
+            deduplicate,
+            id_field: extract_id_field(id_field, network.id_field())?,
+            weighting: extract_weighting(weighting)?,
+            aggregations: extract_aggregations(aggregations)?,
+            count_mode: extract_count_mode(count_mode)?,
+            assignment: extract_assignment(assignment)?,
+            boundary_pipe_count,
+            buffer_m,
+            clipped_area,
+            include_density,
+            normalize: extract_normalize(normalize)?,
+            containment: extract_containment(containment)?,
+            strict_coverage,
+            max_hexes,
+            filter: extract_hex_filter(min_pipe_count, top_n)?,
+            http_options: extract_http_options(http_options)?,
+            geometry_options: extract_geometry_options(
+                coordinate_precision,
+                simplify_tolerance_m,
+                geometry_encoding,
+            )?,
+            include_metadata_columns,
+            include_query_id,
+            output: extract_summary_output(output, output_layout, max_batch_rows)?,
+        })
+    }
+
+    /// Removes repeated pipes from `records`, if the options deduplicate.
+    fn deduplicate(&self, records: &mut Vec<PipelineRecord>) {
+        if self.count_mode.deduplicate(self.deduplicate) {
+            deduplicate_records(records, &self.id_field);
+        }
+    }
+
+    /// The [`IdField::metadata`] of the deduplicated `records`, if the options
+    /// deduplicate.
+    fn id_metadata<'a>(
+        &self,
+        records: impl IntoIterator<Item = &'a PipelineRecord>,
+    ) -> Vec<(String, String)> {
+        if self.count_mode.deduplicate(self.deduplicate) {
+            self.id_field.metadata(records)
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Repairs and optionally buffers a WGS84 boundary, then fetches the network's records
+/// in its envelope and bins them into a summary clipped to it. `label` names the
+/// boundary in errors, and `object_id`, if it was looked up by one, is recorded
+/// in the schema metadata. `query` describes how the boundary was chosen; with
+/// `zoom` and the options added, its id is recorded as `query_id` in the schema
+/// metadata, and with `include_query_id` as a column.
+///
+/// Unless the boundary fills most of its envelope, the fetch is filtered
+/// server-side to the pipes passing through the hexes it overlaps (see
+/// [`hex_cover_query_area`]), which leaves the summary unchanged.
+#[allow(clippy::too_many_arguments)]
+fn hex_summary_for_area(
+    py: Python<'_>,
+    runtime: &tokio::runtime::Runtime,
+    stats: &TransferStats,
+    boundary: MultiPolygon<f64>,
+    label: &str,
+    object_id: Option<i64>,
+    query: QueryDescription,
+    zoom: u8,
+    options: &AreaSummaryOptions,
+) -> PyResult<Py<PyAny>> {
+    let query_id = area_query_id(query, zoom, options);
+    let area = AreaClip::new(py, boundary, label, zoom, options)?;
+
+    let started = Instant::now();
+    let result = runtime
+        .block_on(area.fetch(options, stats))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    if !result.errors.is_empty() {
+        return Err(fetch_error(py, &result.errors, started.elapsed()));
+    }
+    warn_missing_records(py, stats)?;
+
+    let mut records = result.records;
+    options.deduplicate(&mut records);
+    let ids = options.id_metadata(&records);
+    let batch = area.summary(&records, zoom, options, &query_id)?;
+    let data_bbox = data_bbox_metadata(&batch)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let coverage_fraction = area
+        .coverage_fraction
+        .map(|fraction| ("coverage_fraction".to_string(), fraction.to_string()));
+    let batch = with_metadata(
+        batch,
+        stats
+            .metadata()
+            .into_iter()
+            .chain(data_bbox)
+            .chain(query_metadata(Some(&area.bbox), object_id, Some(zoom)))
+            .chain([("query_id".to_string(), query_id)])
+            .chain(ids)
+            .chain(coverage_fraction),
+    )
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_geometry_options(batch, &options.geometry_options)
+        .and_then(conform_hex_summary)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    options.output.to_python(py, batch)
+}
+
+/// The id of an area summary at `zoom` with `options`, whose boundary `query`
+/// describes.
+fn area_query_id(query: QueryDescription, zoom: u8, options: &AreaSummaryOptions) -> String {
+    query
+        .with("zoom", zoom)
+        .with_option("parent_zoom", &options.parent_zoom)
+        .with_option("layer", &options.layer)
+        .with("network", options.network.name())
+        .with("deduplicate", options.deduplicate)
+        .with("id_field", options.id_field.name())
+        .with_option("weighting", &options.weighting)
+        .with("aggregations", aggregation_names(&options.aggregations))
+        .with_option("count_mode", &options.count_mode)
+        .with_option("assignment", &options.assignment)
+        .with("boundary_pipe_count", options.boundary_pipe_count)
+        .with("buffer_m", options.buffer_m)
+        .with("clipped_area", options.clipped_area)
+        .with("include_density", options.include_density)
+        .with_option("normalize", &options.normalize)
+        .with_option("containment", &options.containment)
+        .with_option("filter", &options.filter)
+        .with_option("geometry_options", &options.geometry_options)
+        .with("include_metadata_columns", options.include_metadata_columns)
+        .id()
+}
+
+/// A boundary made ready to summarise: repaired, buffered and clamped to the
+/// network's coverage, with the envelope and query area its records are fetched
+/// by.
+struct AreaClip {
+    clip: MultiPolygon<f64>,
+    /// The fraction of the boundary inside the network's coverage, if known.
+    coverage_fraction: Option<f64>,
+    bbox: BBox,
+    query_area: Option<MultiPolygon<f64>>,
+}
+
+impl AreaClip {
+    /// Prepares `boundary` for a summary at `zoom`, checking it against
+    /// `max_hexes` and the tile size (see [`check_tiling`]) and warning if `zoom` is too coarse for it. `label` names the
+    /// boundary in errors and warnings.
+    fn new(
+        py: Python<'_>,
+        boundary: MultiPolygon<f64>,
+        label: &str,
+        zoom: u8,
+        options: &AreaSummaryOptions,
+    ) -> PyResult<Self> {
+        let clip = repaired_clip(py, boundary, label)?;
+        let clip = match options.buffer_m {
+            Some(buffer_m) if buffer_m > 0.0 => buffer_wgs84_multipolygon(&clip, buffer_m)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+            _ => clip,
+        };
+        let (clip, coverage_fraction) =
+            covered_clip(py, clip, label, options.network, options.strict_coverage)?;
+
+        let rect = clip.bounding_rect().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>("Invalid polygon geometry")
+        })?;
+
+        let bbox = BBox::new(rect.min().y, rect.min().x, rect.max().y, rect.max().x);
+        check_hex_budget(&clip, zoom, options.max_hexes)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        check_tiling(&options.http_options, &bbox)?;
+        warn_coarse_clip(py, &clip, label, zoom)?;
+
+        let query_area = hex_cover_query_area(&clip, &bbox, zoom)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        Ok(Self {
+            clip,
+            coverage_fraction,
+            bbox,
+            query_area,
+        })
+    }
+
+    /// Fetches the records of `options.layer` in the area's envelope, filtered to
+    /// its query area when it has one.
+    async fn fetch(
+        &self,
+        options: &AreaSummaryOptions,
+        stats: &TransferStats,
+    ) -> Result<InfraResult<PipelineRecord>, InfraHexError> {
+        match &self.query_area {
+            Some(area) => {
+                options
+                    .network
+                    .fetch_layer_by_polygon(
+                        options.layer,
+                        &self.bbox,
+                        area,
+                        &options.http_options,
+                        stats,
+                    )
+                    .await
+            }
+            None => {
+                options
+                    .network
+                    .fetch_layer_by_bbox(
+                        options.layer,
+                        &self.bbox,
+                        &RecordQuery::default(),
+                        &options.http_options,
+                        stats,
+                        &FeatureBudget::default(),
+                    )
+                    .await
+            }
+        }
+    }
+
+    /// Bins the fetched (and deduplicated, see
+    /// [`AreaSummaryOptions::deduplicate`]) `records` into a summary clipped to the
+    /// area, with the
+    /// columns `options` ask for, `query_id` among them with `include_query_id`.
+    fn summary(
+        &self,
+        records: &[PipelineRecord],
+        zoom: u8,
+        options: &AreaSummaryOptions,
+        query_id: &str,
+    ) -> PyResult<RecordBatch> {
+        let clip = &self.clip;
+        let count_mode = options.count_mode;
+
+        let batch = to_hex_summary_for_multipolygon(
+            records,
+            zoom,
+            options.assignment,
+            clip,
+            options.containment,
+            options.clipped_area,
+        )
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let assignment = options.assignment;
+        let batch = with_count_mode(batch, records, zoom, assignment, count_mode)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let batch = with_weighting(batch, records, zoom, assignment, options.weighting)
+            .and_then(|batch| {
+                with_aggregations(batch, records, zoom, assignment, &options.aggregations)
+            })
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let batch = if options.boundary_pipe_count {
+            with_boundary_pipe_counts(batch, records, zoom, assignment, count_mode)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+        } else {
+            batch
+        };
+        let batch = filter_hex_summary(batch, &options.filter)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let batch = match options.parent_zoom {
+            Some(parent_zoom) => with_parent_hex_ids(batch, parent_zoom)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+            None => batch,
+        };
+        let batch = if options.include_density {
+            with_density(batch, Some(clip))
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+        } else {
+            batch
+        };
+        let batch = with_normalized_density(batch, options.normalize, Some(clip))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let batch = if options.include_metadata_columns {
+            with_metadata_columns(batch, Some(zoom), Some(options.network.name()))
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+        } else {
+            batch
+        };
+        if options.include_query_id {
+            with_query_id_column(batch, query_id)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+        } else {
+            Ok(batch)
+        }
+    }
+}
+
+/// Get a single ONS built-up area boundary, e.g. to plot beneath its hexes or to
+/// check which area an OBJECTID refers to before running a summary.
+///
+/// # Arguments
+/// * `object_id` - The OBJECTID of the built-up area from ONS Open Geography Portal
+/// * `http_options` - Optional dict of HTTP client options (proxy, headers, retries)
+///
+/// # Returns
+/// A dict with `object_id`, `code`, `name`, `geojson` (the WGS84 boundary as a
+/// GeoJSON geometry string), `wkb` (the same geometry as WKB bytes) and `bbox` (its
+/// WGS84 envelope as a `BBox`)
+///
+/// # Errors
+/// Raises `AreaNotFoundError` if no built-up area has `object_id`.
+#[pyfunction]
+#[pyo3(signature = (object_id, *, http_options=None))]
+fn get_built_up_area<'py>(
+    py: Python<'py>,
+    object_id: i64,
+    http_options: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let http_options = extract_http_options(http_options)?;
+
+    let runtime = runtime()?;
+    let area_client = BuiltUpAreaClient::new(&http_options)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    let started = Instant::now();
+    let built_up_area = runtime
+        .block_on(async { area_client.fetch_by_object_id(object_id).await })
+        .map_err(|e| fetch_error(py, &[e], started.elapsed()))?
+        .ok_or_else(|| built_up_area_not_found(py, object_id))?;
+
+    built_up_area_dict(py, built_up_area)
+}
+
+/// Get several ONS built-up area boundaries at once, as from `get_built_up_area`.
+///
+/// The areas are looked up with one query per 100 ids rather than one each,
+/// falling back to separate queries if the service rejects the batched filter.
+///
+/// # Arguments
+/// * `object_ids` - The OBJECTIDs of the built-up areas; repeats are fetched once
+/// * `http_options` - Optional dict of HTTP client options (proxy, headers, retries)
+///
+/// # Returns
+/// A list with one dict per id, in the order given, each as returned by
+/// `get_built_up_area`
+///
+/// # Errors
+/// Raises `AreaNotFoundError` for the first id no built-up area has.
+#[pyfunction]
+#[pyo3(signature = (object_ids, *, http_options=None))]
+fn get_built_up_areas<'py>(
+    py: Python<'py>,
+    object_ids: Vec<i64>,
+    http_options: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    let http_options = extract_http_options(http_options)?;
+
+    let runtime = runtime()?;
+    let area_client = BuiltUpAreaClient::new(&http_options)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    let started = Instant::now();
+    let areas = runtime
+        .block_on(async { area_client.fetch_by_object_ids(&object_ids).await })
+        .map_err(|e| fetch_error(py, &[e], started.elapsed()))?;
+
+    object_ids
+        .iter()
+        .zip(areas)
+        .map(|(&object_id, area)| {
+            let area = area.ok_or_else(|| built_up_area_not_found(py, object_id))?;
+            built_up_area_dict(py, area)
+        })
+        .collect()
+}
+
+/// The dict `get_built_up_area` returns for `built_up_area`.
+fn built_up_area_dict(py: Python<'_>, built_up_area: BuiltUpArea) -> PyResult<Bound<'_, PyDict>> {
+    let rect = built_up_area.geometry.bounding_rect().ok_or_else(|| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+            "Built-up area OBJECTID {} has an empty geometry",
+            built_up_area.object_id
+        ))
+    })?;
+    let bbox = PyBBox {
+        min_lat: rect.min().y,
+        min_lon: rect.min().x,
+        max_lat: rect.max().y,
+        max_lon: rect.max().x,
+    };
+
+    let geometry = geo::Geometry::MultiPolygon(built_up_area.geometry);
+    let geojson = geojson::Geometry::new(geojson::Value::from(&geometry)).to_string();
+    let mut wkb = Vec::new();
+    wkb::writer::write_geometry(&mut wkb, &geometry, &Default::default())
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    let area = PyDict::new(py);
+    area.set_item("object_id", built_up_area.object_id)?;
+    area.set_item("code", built_up_area.code)?;
+    area.set_item("name", built_up_area.name)?;
+    area.set_item("geojson", geojson)?;
+    area.set_item("wkb", PyBytes::new(py, &wkb))?;
+    area.set_item("bbox", bbox)?;
+
+    Ok(area)
+}
+
+fn built_up_area_not_found(py: Python<'_>, object_id: i64) -> PyErr {
+    area_not_found(
+        py,
+        format!("No built-up area found for OBJECTID {}", object_id),
+        Some(object_id),
+    )
+}
+
+/// An `AreaNotFoundError` carrying the requested OBJECTID, if there was one, as
+/// its `object_id` attribute.
+fn area_not_found(py: Python<'_>, message: String, object_id: Option<i64>) -> PyErr {
+    let build = || -> PyResult<PyErr> {
+        let err = PyErr::from_type(area_not_found_error(py)?.clone(), message);
+        err.value(py).setattr("object_id", object_id)?;
+        Ok(err)
+    };
+    build().unwrap_or_else(|e| e)
+}
+
+/// Get a combined hex summary across every registered gas distribution network.
+///
+/// All networks are fetched concurrently. Networks that fail are skipped and listed
+/// in the schema metadata under `failed_networks` (a JSON object of network name to
+/// error message); the call only fails if every network does. Like
+/// `get_hex_summary`, accepts a `BBox` in place of the four corner floats, and
+/// with `deduplicate` counts repeated asset ids within each network once.
+/// `resolution="auto"`, `target_hex_count`, `parent_zoom`, `count_mode`,
+/// `assignment`, `boundary_pipe_count`, `weighting`, `aggregations`, `max_hexes`,
+/// `min_pipe_count`, `top_n`,
+/// `geometry_encoding`, `output`, `output_layout` and `telemetry` behave as in
+/// `get_hex_summary`, with `telemetry` summed across the networks. `provider`
+/// lists the networks that succeeded, comma-separated.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count, count_<network>...,
+/// [resolution, provider,] geometry
+#[pyfunction]
+#[pyo3(signature = (min_lat, min_lon=None, max_lat=None, max_lon=None, resolution=None, *, zoom=None, target_hex_count=summary::DEFAULT_TARGET_HEX_COUNT, parent_zoom=None, deduplicate=true, weighting="count", aggregations=None, count_mode="records", assignment="coverage", boundary_pipe_count=false, max_hexes=summary::DEFAULT_MAX_HEXES, min_pipe_count=None, top_n=None, http_options=None, coordinate_precision=None, geometry_encoding="geoarrow_separated", include_metadata_columns=true, output="arrow", output_layout="wide", max_batch_rows=None, telemetry=false))]
+#[allow(clippy::too_many_arguments)]
+fn get_hex_summary_all_networks(
+    py: Python<'_>,
+    min_lat: &Bound<'_, PyAny>,
+    min_lon: Option<&Bound<'_, PyAny>>,
+    max_lat: Option<&Bound<'_, PyAny>>,
+    max_lon: Option<&Bound<'_, PyAny>>,
+    resolution: Option<&Bound<'_, PyAny>>,
+    zoom: Option<&Bound<'_, PyAny>>,
+    target_hex_count: u64,
+    parent_zoom: Option<i64>,
+    deduplicate: bool,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    count_mode: &str,
+    assignment: &str,
+    boundary_pipe_count: bool,
+    max_hexes: u64,
+    min_pipe_count: Option<i64>,
+    top_n: Option<usize>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    coordinate_precision: Option<i64>,
+    geometry_encoding: &str,
+    include_metadata_columns: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+    telemetry: bool,
+) -> PyResult<Py<PyAny>> {
+    let http_options = extract_http_options(http_options)?;
+    let weighting = extract_weighting(weighting)?;
+    let aggregations = extract_aggregations(aggregations)?;
+    let count_mode = extract_count_mode(count_mode)?;
+    let assignment = extract_assignment(assignment)?;
+    let filter = extract_hex_filter(min_pipe_count, top_n)?;
+    let geometry_options = extract_geometry_options(coordinate_precision, None, geometry_encoding)?;
+    let output = extract_summary_output(output, output_layout, max_batch_rows)?;
+    let resolution = extract_resolution_arg(py, resolution, zoom)?;
+    let (bbox, resolution) = resolve_bbox_args(
+        min_lat,
+        min_lon,
+        max_lat,
+        max_lon,
+        resolution,
+        target_hex_count,
+    )?;
+    let zoom = resolution.zoom();
+    let parent_zoom = extract_parent_zoom(zoom, parent_zoom)?;
+    check_hex_budget(&bbox_polygon(&bbox), zoom, max_hexes)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    check_tiling(&http_options, &bbox)?;
+
+    let runtime = runtime()?;
+
+    let stats = TransferStats::default().with_telemetry(telemetry);
+
+    let fetched = runtime.block_on(futures::future::join_all(
+        Network::ALL
+            .iter()
+            .map(|network| network.fetch_all_by_bbox(&bbox, &http_options, &stats)),
+    ));
+
+    let mut succeeded = Vec::new();
+    let mut failed = HashMap::new();
+    for (network, result) in Network::ALL.iter().zip(fetched) {
+        match result {
+            Ok(mut result) if result.errors.is_empty() => {
+                if count_mode.deduplicate(deduplicate) {
+                    deduplicate_records(&mut result.records, &network.id_field());
+                }
+                succeeded.push((*network, result.records));
+            }
+            Ok(result) => {
+                failed.insert(
+                    network.name(),
+                    format!(
+                        "Fetch had {} errors: {:?}",
+                        result.errors.len(),
+                        result.errors
+                    ),
+                );
+            }
+            Err(e) => {
+                failed.insert(network.name(), e.to_string());
+            }
+        }
+    }
+
+    if succeeded.is_empty() {
+        return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+            "All networks failed: {:?}",
+            failed
+        )));
+    }
+    warn_missing_records(py, &stats)?;
+
+    let results: Vec<_> = succeeded
+        .iter()
+        .map(|(network, records)| (network.name(), records.as_slice()))
+        .collect();
 
+    let batch = to_hex_summary_by_source(&results, zoom, assignment)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_count_mode_by_source(batch, &results, zoom, assignment, count_mode)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_weighting(
+        batch,
+        succeeded.iter().flat_map(|(_, records)| records),
+        zoom,
+        assignment,
+        weighting,
+    )
+    .and_then(|batch| {
+        with_aggregations(
+            batch,
+            succeeded.iter().flat_map(|(_, records)| records),
+            zoom,
+            assignment,
+            &aggregations,
+        )
+    })
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = if boundary_pipe_count {
+        with_boundary_pipe_counts(
+            batch,
+            succeeded.iter().flat_map(|(_, records)| records),
+            zoom,
+            assignment,
+            count_mode,
+        )
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = clip_hex_summary_to_bbox(batch, &bbox, Containment::Intersects)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = filter_hex_summary(batch, &filter)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = match parent_zoom {
+        Some(parent_zoom) => with_parent_hex_ids(batch, parent_zoom)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+        None => batch,
+    };
+    let providers: Vec<_> = succeeded
+        .iter()
+        .map(|(network, _)| network.name())
+        .collect();
+    let batch = if include_metadata_columns {
+        with_metadata_columns(batch, Some(zoom), Some(&providers.join(",")))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+
+    let failed_json = serde_json::to_string(&failed)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let data_bbox = data_bbox_metadata(&batch)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let metadata = std::iter::once(("failed_networks".to_string(), failed_json))
+        .chain(stats.metadata())
+        .chain(data_bbox)
+        .chain(query_metadata(Some(&bbox), None, Some(zoom)))
+        .chain(resolution.metadata())
+        .chain(assignment.metadata());
+    let batch = with_metadata(batch, metadata)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_geometry_options(batch, &geometry_options)
+        .and_then(conform_hex_summary)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    output.to_python(py, batch)
+}
+
+/// Get a hex summary from locally stored GeoJSON, without any network access.
+///
+/// # Arguments
+/// * `path_or_str` - A path to a GeoJSON file, or the GeoJSON document itself
+/// * `resolution` - Hex grid resolution (0-15; `zoom` is a deprecated alias)
+/// * `clip_geojson` - Optional GeoJSON polygon/multipolygon to clip the summary to,
+///   or any object with a `__geo_interface__`
+/// * `parent_zoom` - Add a `parent_hex_id` column at this coarser resolution, as in
+///   `get_hex_summary`
+/// * `coordinate_precision` - Optional number of decimal places to round coordinates to
+/// * `geometry_encoding` - `"geoarrow_separated"` (default), `"geoarrow"` or `"wkb"`,
+///   as in `get_hex_summary`
+/// * `deduplicate` - Count features sharing an `asset_id` property once (default true)
+/// * `id_field` - The property to deduplicate on instead of `asset_id`, as in
+///   `get_hex_summary`
+/// * `count_mode` - `"records"` (default) or `"unique_assets"`, as in `get_hex_summary`
+/// * `assignment` - `"coverage"` (default) or `"midpoint"`, as in `get_hex_summary`
+/// * `boundary_pipe_count` - Add a `boundary_pipe_count` column, as in
+///   `get_hex_summary`
+/// * `weighting` - `"count"` (default) or `"length"` to add a `weighted_count` column
+/// * `aggregations` - Per-hex statistics of record attributes, as in `get_hex_summary`
+/// * `clipped_area` - Add a `clipped_area_m2` column with the area of each hex inside
+///   `clip_geojson` (which must then be given)
+/// * `containment` - Which hexes `clip_geojson` keeps, as in
+///   `get_hex_summary_polygon_area` (default `"intersects"`)
+/// * `hex_area` - Add a `hex_area_m2` column with each whole hex's area, as in
+///   `get_hex_summary`
+/// * `include_density` - Add `cell_area_km2` and `pipe_density_per_km2` columns, as
+///   in `get_hex_summary_polygon_area`, measuring each hex inside `clip_geojson`
+///   when one is given
+/// * `normalize` - `"none"` (default), `"per_km2"` or `"per_km_edge"` to add a
+///   `pipe_density` column, as in `get_hex_summary_polygon_area`
+/// * `include_points` - Also count Point and MultiPoint features, such as
+///   governors and valves, each in the hex containing it (default false)
+/// * `include_metadata_columns` - Add `resolution` and `provider` columns, as in
+///   `get_hex_summary` (default true); `provider` is null, as a file's origin is
+///   unknown
+/// * `output` - `"arrow"` (default), `"pyarrow"`, `"capsule"` or `"polars"`, as in
+///   `get_hex_summary`
+/// * `output_layout` - `"wide"` (default) or `"normalized"`, as in `get_hex_summary`
+/// * `max_batch_rows` - Split the summary into batches of at most this many rows,
+///   as in `get_hex_summary`
+///
+/// Only LineString and MultiLineString features are binned, plus Point and
+/// MultiPoint ones with `include_points`; the number of other features skipped
+/// is recorded under `skipped_features` in the schema metadata.
+/// A legacy `crs` member naming EPSG:27700 or EPSG:4326 (as in WFS output) is
+/// honoured, reprojecting to WGS84 first, and recorded under `source_crs`.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count, [hex_area_m2,]
+/// [cell_area_km2, pipe_density_per_km2,] [pipe_density,] [resolution, provider,]
+/// geometry. A clip that leaves no hexes gives zero rows with the same columns.
 #[pyfunction]
-fn get_hex_summary(
+#[pyo3(signature = (path_or_str, resolution=None, clip_geojson=None, *, zoom=None, parent_zoom=None, coordinate_precision=None, geometry_encoding="geoarrow_separated", deduplicate=true, id_field=None, weighting="count", aggregations=None, count_mode="records", assignment="coverage", boundary_pipe_count=false, clipped_area=false, containment="intersects", hex_area=false, include_density=false, normalize="none", include_points=false, include_metadata_columns=true, output="arrow", output_layout="wide", max_batch_rows=None))]
+#[allow(clippy::too_many_arguments)]
+fn hex_summary_from_geojson(
     py: Python<'_>,
-    min_lat: f64,
-    min_lon: f64,
-    max_lat: f64,
-    max_lon: f64,
-    zoom: u8,
+    path_or_str: &str,
+    resolution: Option<u8>,
+    clip_geojson: Option<&Bound<'_, PyAny>>,
+    zoom: Option<u8>,
+    parent_zoom: Option<i64>,
+    coordinate_precision: Option<i64>,
+    geometry_encoding: &str,
+    deduplicate: bool,
+    id_field: Option<&str>,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    count_mode: &str,
+    assignment: &str,
+    boundary_pipe_count: bool,
+    clipped_area: bool,
+    containment: &str,
+    hex_area: bool,
+    include_density: bool,
+    normalize: &str,
+    include_points: bool,
+    include_metadata_columns: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
 ) -> PyResult<Py<PyAny>> {
-    let runtime = tokio::runtime::Runtime::new()
+    let zoom = extract_resolution(py, resolution, zoom)?;
+    let containment = extract_containment(containment)?;
+    let geometry_options = extract_geometry_options(coordinate_precision, None, geometry_encoding)?;
+    let output = extract_summary_output(output, output_layout, max_batch_rows)?;
+    let weighting = extract_weighting(weighting)?;
+    let aggregations = extract_aggregations(aggregations)?;
+    let count_mode = extract_count_mode(count_mode)?;
+    let assignment = extract_assignment(assignment)?;
+    let normalize = extract_normalize(normalize)?;
+    let parent_zoom = extract_parent_zoom(zoom, parent_zoom)?;
+    let id_field = extract_id_field(id_field, IdField::default())?;
+    if clipped_area && clip_geojson.is_none() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "clipped_area requires clip_geojson",
+        ));
+    }
+
+    let source = if path_or_str.trim_start().starts_with('{') {
+        path_or_str.to_string()
+    } else {
+        std::fs::read_to_string(path_or_str)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyOSError, _>(e.to_string()))?
+    };
+
+    let mut parsed = records_from_geojson(&source, include_points)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let deduplicate = count_mode.deduplicate(deduplicate);
+    if deduplicate {
+        deduplicate_records(&mut parsed.records, &id_field);
+    }
+
+    let clip = clip_geojson
+        .map(|clip| repaired_clip(py, extract_polygon(clip, "clip_geojson")?, "Clip geometry"))
+        .transpose()?;
+    if let Some(clip) = &clip {
+        warn_coarse_clip(py, clip, "Clip geometry", zoom)?;
+    }
+    let batch = match &clip {
+        Some(clip) => to_hex_summary_for_multipolygon(
+            &parsed.records,
+            zoom,
+            assignment,
+            clip,
+            containment,
+            clipped_area,
+        ),
+        None => to_hex_summary(&parsed.records, zoom, assignment),
+    }
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_count_mode(batch, &parsed.records, zoom, assignment, count_mode)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_weighting(batch, &parsed.records, zoom, assignment, weighting)
+        .and_then(|batch| {
+            with_aggregations(batch, &parsed.records, zoom, assignment, &aggregations)
+        })
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = if boundary_pipe_count {
+        with_boundary_pipe_counts(batch, &parsed.records, zoom, assignment, count_mode)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = match parent_zoom {
+        Some(parent_zoom) => with_parent_hex_ids(batch, parent_zoom)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+        None => batch,
+    };
+    let batch = if hex_area {
+        with_hex_areas(batch)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = if include_density {
+        with_density(batch, clip.as_ref())
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = with_normalized_density(batch, normalize, clip.as_ref())
         .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = if include_metadata_columns {
+        with_metadata_columns(batch, Some(zoom), None)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
 
-    let client = CadentClient::new()
+    let metadata = std::iter::once(("skipped_features".to_string(), parsed.skipped.to_string()))
+        .chain(
+            parsed
+                .source_crs
+                .map(|crs| ("source_crs".to_string(), crs.name().to_string())),
+        )
+        .chain(assignment.metadata())
+        .chain(
+            deduplicate
+                .then(|| id_field.metadata(&parsed.records))
+                .into_iter()
+                .flatten(),
+        );
+    let batch = with_metadata(batch, metadata)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_geometry_options(batch, &geometry_options)
+        .and_then(conform_hex_summary)
         .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
-    let bbox = BBox::new(min_lat, min_lon, max_lat, max_lon);
+    output.to_python(py, batch)
+}
 
-    let result = runtime.block_on(async { client.fetch_all_by_bbox(&bbox).await });
+/// Get a hex summary from an Arrow RecordBatch or Table of line geometries.
+///
+/// # Arguments
+/// * `batch` - Any Arrow RecordBatch, Table or stream (e.g. from geopandas or DuckDB)
+/// * `resolution` - Hex grid resolution (0-15; `zoom` is a deprecated alias)
+/// * `geometry_column` - Name of the WKB-encoded geometry column
+/// * `encoding` - Geometry encoding; only `"wkb"` is supported
+/// * `skip_invalid` - Skip non-line or undecodable geometries instead of raising
+/// * `parent_zoom` - Add a `parent_hex_id` column at this coarser resolution, as in
+///   `get_hex_summary`
+/// * `coordinate_precision` - Optional number of decimal places to round coordinates to
+/// * `geometry_encoding` - `"geoarrow_separated"` (default), `"geoarrow"` or `"wkb"`,
+///   as in `get_hex_summary`
+/// * `weighting` - `"count"` (default) or `"length"` to add a `weighted_count` column
+/// * `aggregations` - Per-hex statistics of record attributes, as in `get_hex_summary`
+/// * `assignment` - `"coverage"` (default) or `"midpoint"`, as in `get_hex_summary`
+/// * `boundary_pipe_count` - Add a `boundary_pipe_count` column, as in
+///   `get_hex_summary`
+/// * `include_metadata_columns` - Add `resolution` and a null `provider` column, as
+///   in `hex_summary_from_geojson` (default true)
+/// * `output` - `"arrow"` (default), `"pyarrow"`, `"capsule"` or `"polars"`, as in
+///   `get_hex_summary`
+/// * `output_layout` - `"wide"` (default) or `"normalized"`, as in `get_hex_summary`
+/// * `max_batch_rows` - Split the summary into batches of at most this many rows,
+///   as in `get_hex_summary`
+///
+/// Null geometries are always skipped. Coordinates are expected in WGS84.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count, [resolution, provider,]
+/// geometry
+#[pyfunction]
+#[pyo3(signature = (batch, resolution=None, geometry_column="geometry", encoding="wkb", skip_invalid=false, *, zoom=None, parent_zoom=None, coordinate_precision=None, geometry_encoding="geoarrow_separated", weighting="count", aggregations=None, assignment="coverage", boundary_pipe_count=false, include_metadata_columns=true, output="arrow", output_layout="wide", max_batch_rows=None))]
+#[allow(clippy::too_many_arguments)]
+fn hex_summary_from_arrow(
+    py: Python<'_>,
+    batch: AnyRecordBatch,
+    resolution: Option<u8>,
+    geometry_column: &str,
+    encoding: &str,
+    skip_invalid: bool,
+    zoom: Option<u8>,
+    parent_zoom: Option<i64>,
+    coordinate_precision: Option<i64>,
+    geometry_encoding: &str,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    assignment: &str,
+    boundary_pipe_count: bool,
+    include_metadata_columns: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+) -> PyResult<Py<PyAny>> {
+    let zoom = extract_resolution(py, resolution, zoom)?;
+    let geometry_options = extract_geometry_options(coordinate_precision, None, geometry_encoding)?;
+    let output = extract_summary_output(output, output_layout, max_batch_rows)?;
+    let weighting = extract_weighting(weighting)?;
+    let aggregations = extract_aggregations(aggregations)?;
+    let assignment = extract_assignment(assignment)?;
+    let parent_zoom = extract_parent_zoom(zoom, parent_zoom)?;
 
-    if !result.errors.is_empty() {
-        return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-            "Fetch had {} errors: {:?}",
-            result.errors.len(),
-            result.errors
+    if encoding != "wkb" {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "Unsupported geometry encoding: {:?} (expected \"wkb\")",
+            encoding
         )));
     }
 
-    let batch = to_hex_summary(&result.records, zoom)
+    let reader = batch.into_reader()?;
+    let index = reader.schema().index_of(geometry_column).map_err(|_| {
+        PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "Geometry column {:?} not found",
+            geometry_column
+        ))
+    })?;
+
+    let mut records = Vec::new();
+    for batch in reader {
+        let batch =
+            batch.map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let parsed = records_from_wkb(batch.column(index), skip_invalid)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        records.extend(parsed.records);
+    }
+
+    let batch = to_hex_summary(&records, zoom, assignment)
+        .and_then(|batch| with_metadata(batch, assignment.metadata()))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_weighting(batch, &records, zoom, assignment, weighting)
+        .and_then(|batch| with_aggregations(batch, &records, zoom, assignment, &aggregations))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = if boundary_pipe_count {
+        with_boundary_pipe_counts(batch, &records, zoom, assignment, CountMode::Records)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = match parent_zoom {
+        Some(parent_zoom) => with_parent_hex_ids(batch, parent_zoom)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+        None => batch,
+    };
+    let batch = if include_metadata_columns {
+        with_metadata_columns(batch, Some(zoom), None)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = with_geometry_options(batch, &geometry_options)
+        .and_then(conform_hex_summary)
         .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
-    PyRecordBatch::new(batch)
-        .into_pyarrow(py)
-        .map(|bound| bound.unbind())
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    output.to_python(py, batch)
 }
 
-/// Get hex summary for pipelines within a built-up area polygon.
+/// Get a hex summary from previously fetched pipe records, e.g. archived extracts
+/// or `RegionCache.to_pipe_records()` output.
 ///
 /// # Arguments
-/// * `object_id` - The OBJECTID of the built-up area from ONS Open Geography Portal
-/// * `zoom` - Hex grid zoom level (0-15)
+/// * `records` - Any Arrow RecordBatch, Table or stream with a WKB `geometry`
+///   column (WGS84) and optionally the attribute columns of `to_pipe_records`
+///   (`asset_id`, `pipe_type`, `material`, ...)
+/// * `resolution` - Hex grid resolution (0-15; `zoom` is a deprecated alias)
+/// * `deduplicate` - Count records sharing an `asset_id` once (default true)
+/// * `id_field` - The column to deduplicate on instead of `asset_id`, as in
+///   `get_hex_summary`
+/// * `count_mode` - `"records"` (default) or `"unique_assets"`, as in
+///   `get_hex_summary`
+/// * `assignment` - `"coverage"` (default) or `"midpoint"`, as in `get_hex_summary`
+/// * `weighting` - `"count"` (default) or `"length"` to add a `weighted_count` column
+/// * `aggregations` - Per-hex statistics of record attributes, as in `get_hex_summary`
+/// * `boundary_pipe_count` - Add a `boundary_pipe_count` column, as in
+///   `get_hex_summary`
+/// * `skip_invalid` - Skip non-line or undecodable geometries instead of raising
+/// * `parent_zoom` - Add a `parent_hex_id` column at this coarser resolution
+/// * `min_pipe_count` / `top_n` - Drop sparse hexes / keep only the densest `n`
+/// * `coordinate_precision` - Optional number of decimal places to round coordinates to
+/// * `geometry_encoding` - `"geoarrow_separated"` (default), `"geoarrow"` or `"wkb"`,
+///   as in `get_hex_summary`
+/// * `include_metadata_columns` - Add `resolution` and a null `provider` column
+///   (default true)
+/// * `output` - `"arrow"` (default), `"pyarrow"`, `"capsule"` or `"polars"`
+/// * `output_layout` - `"wide"` (default) or `"normalized"`, as in `get_hex_summary`
+/// * `max_batch_rows` - Split the summary into batches of at most this many rows,
+///   as in `get_hex_summary`
+///
+/// Unlike `hex_summary_from_arrow`, the attribute columns are kept, so the
+/// records are binned exactly as a fetch would bin them. The schema metadata
+/// records `skipped_features`. No network access is needed.
 ///
 /// # Returns
-/// A PyArrow RecordBatch with columns: hex_id, pipe_count, geometry
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count, [resolution, provider,]
+/// geometry
 #[pyfunction]
-fn get_hex_summary_polygon_area(py: Python<'_>, object_id: i64, zoom: u8) -> PyResult<Py<PyAny>> {
-    let runtime = tokio::runtime::Runtime::new()
+#[pyo3(signature = (records, resolution=None, *, zoom=None, deduplicate=true, id_field=None, count_mode="records", assignment="coverage", boundary_pipe_count=false, weighting="count", aggregations=None, skip_invalid=false, parent_zoom=None, min_pipe_count=None, top_n=None, coordinate_precision=None, geometry_encoding="geoarrow_separated", include_metadata_columns=true, output="arrow", output_layout="wide", max_batch_rows=None))]
+#[allow(clippy::too_many_arguments)]
+fn hex_summary_from_records(
+    py: Python<'_>,
+    records: AnyRecordBatch,
+    resolution: Option<u8>,
+    zoom: Option<u8>,
+    deduplicate: bool,
+    id_field: Option<&str>,
+    count_mode: &str,
+    assignment: &str,
+    boundary_pipe_count: bool,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    skip_invalid: bool,
+    parent_zoom: Option<i64>,
+    min_pipe_count: Option<i64>,
+    top_n: Option<usize>,
+    coordinate_precision: Option<i64>,
+    geometry_encoding: &str,
+    include_metadata_columns: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+) -> PyResult<Py<PyAny>> {
+    let zoom = extract_resolution(py, resolution, zoom)?;
+    let geometry_options = extract_geometry_options(coordinate_precision, None, geometry_encoding)?;
+    let output = extract_summary_output(output, output_layout, max_batch_rows)?;
+    let count_mode = extract_count_mode(count_mode)?;
+    let assignment = extract_assignment(assignment)?;
+    let weighting = extract_weighting(weighting)?;
+    let aggregations = extract_aggregations(aggregations)?;
+    let parent_zoom = extract_parent_zoom(zoom, parent_zoom)?;
+    let filter = extract_hex_filter(min_pipe_count, top_n)?;
+    let id_field = extract_id_field(id_field, IdField::default())?;
+
+    let mut parsed = records_from_batch_reader(records, skip_invalid, &id_field)?;
+    let deduplicate = count_mode.deduplicate(deduplicate);
+    if deduplicate {
+        deduplicate_records(&mut parsed.records, &id_field);
+    }
+
+    let batch = to_hex_summary_min_count(&parsed.records, zoom, assignment, filter.min_count())
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_count_mode(batch, &parsed.records, zoom, assignment, count_mode)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_weighting(batch, &parsed.records, zoom, assignment, weighting)
+        .and_then(|batch| {
+            with_aggregations(batch, &parsed.records, zoom, assignment, &aggregations)
+        })
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = if boundary_pipe_count {
+        with_boundary_pipe_counts(batch, &parsed.records, zoom, assignment, count_mode)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = filter_hex_summary(batch, &filter)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = match parent_zoom {
+        Some(parent_zoom) => with_parent_hex_ids(batch, parent_zoom)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+        None => batch,
+    };
+    let batch = if include_metadata_columns {
+        with_metadata_columns(batch, Some(zoom), None)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = with_metadata(
+        batch,
+        [("skipped_features".to_string(), parsed.skipped.to_string())]
+            .into_iter()
+            .chain(assignment.metadata())
+            .chain(
+                deduplicate
+                    .then(|| id_field.metadata(&parsed.records))
+                    .into_iter()
+                    .flatten(),
+            ),
+    )
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_geometry_options(batch, &geometry_options)
+        .and_then(conform_hex_summary)
         .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
-    let area_client = BuiltUpAreaClient::new();
+    output.to_python(py, batch)
+}
 
-    let built_up_area = runtime
-        .block_on(async { area_client.fetch_by_object_id(object_id).await })
+/// Reads every batch of `records` into pipeline records, keeping any `id_field`
+/// column.
+fn records_from_batch_reader(
+    records: AnyRecordBatch,
+    skip_invalid: bool,
+    id_field: &IdField,
+) -> PyResult<ParsedRecords> {
+    let mut parsed = ParsedRecords::default();
+    for batch in records.into_reader()? {
+        let batch =
+            batch.map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let batch = records_from_batch(&batch, skip_invalid, false, id_field)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        parsed.records.extend(batch.records);
+        parsed.skipped += batch.skipped;
+    }
+    Ok(parsed)
+}
+
+/// Fetch the raw pipe records for a bounding box and save them to `path`, so
+/// summaries can be recomputed later with `hex_summary_from_saved`, without
+/// network access and against exactly the same data.
+///
+/// The file is an Arrow IPC file with the columns of
+/// `RegionCache.to_pipe_records()`, readable by `pyarrow.ipc.open_file`. Its
+/// schema metadata holds the bounding box, `network`, layers, fetch time and a
+/// format version. Records from several layers are saved together.
+///
+/// # Arguments
+/// * `path` - File to write (overwritten if it exists)
+/// * `min_lat`, `min_lon`, `max_lat`, `max_lon` - Bounding box, or a `BBox` as
+///   `min_lat`
+/// * `deduplicate`, `layer`, `network`, `http_options` - As for `get_hex_summary`
+/// * `fields` - Cadent field names to fetch and save (e.g. `["material",
+///   "diameter"]`), as for `RegionCache.from_bbox`; `None` saves every field
+///
+/// # Returns
+/// The number of records written
+#[pyfunction]
+#[pyo3(signature = (path, min_lat, min_lon=None, max_lat=None, max_lon=None, *, deduplicate=true, layer=None, network=None, http_options=None, fields=None))]
+#[allow(clippy::too_many_arguments)]
+fn save_raw_fetch(
+    py: Python<'_>,
+    path: &str,
+    min_lat: &Bound<'_, PyAny>,
+    min_lon: Option<&Bound<'_, PyAny>>,
+    max_lat: Option<&Bound<'_, PyAny>>,
+    max_lon: Option<&Bound<'_, PyAny>>,
+    deduplicate: bool,
+    layer: Option<&Bound<'_, PyAny>>,
+    network: Option<&str>,
+    http_options: Option<&Bound<'_, PyDict>>,
+    fields: Option<Vec<String>>,
+) -> PyResult<usize> {
+    let bbox = resolve_bbox(min_lat, min_lon, max_lat, max_lon)?;
+    let layers = extract_layers(layer)?;
+    let network = extract_network(network)?;
+    let http_options = extract_http_options(http_options)?;
+    let query = RecordQuery {
+        fields: extract_record_fields(py, fields, network, &layers, &http_options)?,
+        ..RecordQuery::default()
+    };
+
+    let records: Vec<PipelineRecord> = fetch_layers(
+        py,
+        &bbox,
+        network,
+        &layers,
+        &query,
+        &http_options,
+        deduplicate.then(|| network.id_field()).as_ref(),
+        &TransferStats::default(),
+        &FeatureBudget::default(),
+    )?
+    .into_iter()
+    .flatten()
+    .collect();
+    let metadata = RawFetchMetadata {
+        bbox,
+        network: network.name().to_string(),
+        layers: layers
+            .layers()
+            .iter()
+            .map(|layer| layer.name().to_string())
+            .collect(),
+        fetched_at: httpdate::fmt_http_date(SystemTime::now()),
+        written_by: env!("CARGO_PKG_VERSION").to_string(),
+    };
+
+    let batch = records_to_batch(&records)
+        .and_then(|batch| match &query.fields {
+            Some(fields) => select_record_fields(&batch, fields),
+            None => Ok(batch),
+        })
         .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let file = std::fs::File::create(path)?;
+    write_raw_fetch(&batch, &metadata, std::io::BufWriter::new(file))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyOSError, _>(e.to_string()))?;
+    Ok(records.len())
+}
 
-    let rect = built_up_area.geometry.bounding_rect().ok_or_else(|| {
-        PyErr::new::<pyo3::exceptions::PyValueError, _>("Invalid polygon geometry")
-    })?;
+/// Fetch one page of Cadent records for a bounding box as the raw response body,
+/// for debugging.
+///
+/// This is an escape hatch for when a summary looks wrong and the endpoint
+/// itself is in question, e.g. if records are being dropped silently or the
+/// schema has changed upstream. It sends the same request the first page of a
+/// normal fetch would, and returns the body decompressed but otherwise exactly as
+/// sent (OpenDataSoft JSON for Cadent), without parsing, checking or reprojecting
+/// anything. Transient failures are retried as usual, and an error status raises
+/// `FetchError`. Nothing else in the package consumes the bytes.
+///
+/// # Arguments
+/// * `min_lat`, `min_lon`, `max_lat`, `max_lon` - Bounding box, or a `BBox` as
+///   `min_lat`
+/// * `layer` - Cadent layer to query (default `"gas_pipes"`)
+/// * `limit` - Records in the page (1-100, default 100)
+/// * `offset` - Records to skip, as for the page at that offset (default 0)
+/// * `fields` - Cadent field names to request, as for `save_raw_fetch`; `None`
+///   requests every field
+/// * `http_options` - Optional dict of HTTP client options (proxy, headers, retries)
+///
+/// # Returns
+/// The response body as `bytes`
+#[pyfunction]
+#[pyo3(signature = (min_lat, min_lon=None, max_lat=None, max_lon=None, *, layer="gas_pipes", limit=100, offset=0, fields=None, http_options=None))]
+#[allow(clippy::too_many_arguments)]
+fn fetch_raw_response<'py>(
+    py: Python<'py>,
+    min_lat: &Bound<'py, PyAny>,
+    min_lon: Option<&Bound<'py, PyAny>>,
+    max_lat: Option<&Bound<'py, PyAny>>,
+    max_lon: Option<&Bound<'py, PyAny>>,
+    layer: &str,
+    limit: usize,
+    offset: usize,
+    fields: Option<Vec<String>>,
+    http_options: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let bbox = resolve_bbox(min_lat, min_lon, max_lat, max_lon)?;
+    let layer = extract_layer(layer)?;
+    if !(1..=100).contains(&limit) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "limit must be between 1 and 100, got {}",
+            limit
+        )));
+    }
+    let http_options = extract_http_options(http_options)?;
+    let query = RecordQuery {
+        fields,
+        ..RecordQuery::default()
+    };
+
+    let runtime = runtime()?;
+    let client = CadentClient::new(&http_options)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+        .with_layer(layer)
+        .with_query(&query);
+
+    let started = Instant::now();
+    let body = runtime
+        .block_on(client.fetch_raw_page(&bbox, limit, offset))
+        .map_err(|e| fetch_error(py, &[e], started.elapsed()))?;
+    Ok(PyBytes::new(py, &body))
+}
+
+/// Get a hex summary from a fetch saved with `save_raw_fetch`, without network
+/// access.
+///
+/// The summary is clipped to the saved bounding box, matching `get_hex_summary`
+/// for the same arguments at fetch time, or to `clip_geojson` (a GeoJSON Polygon
+/// or MultiPolygon in WGS84, or an object with a `__geo_interface__`) if given.
+/// The schema metadata records the saved `fetched_at` time. A `UserWarning` is
+/// raised if any saved record lies outside the saved bounding box, i.e. the file
+/// does not hold the fetch its metadata describes.
+///
+/// `deduplicate`, `count_mode`, `assignment`, `weighting`, `aggregations`,
+/// `boundary_pipe_count`, `parent_zoom`,
+/// `min_pipe_count`, `top_n`, `coordinate_precision`, `geometry_encoding`,
+/// `include_metadata_columns`, `output` and `output_layout` are as for
+/// `get_hex_summary`. Per-layer count columns are not
+/// added, even if several layers were saved.
+///
+/// # Errors
+/// Raises `ValueError` if `path` is not a saved fetch, or was written by a newer
+/// release of infra_hex_py in a format this one cannot read.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count, [resolution, provider,]
+/// geometry
+#[pyfunction]
+#[pyo3(signature = (path, resolution=None, clip_geojson=None, *, zoom=None, deduplicate=true, count_mode="records", assignment="coverage", weighting="count", aggregations=None, boundary_pipe_count=false, parent_zoom=None, min_pipe_count=None, top_n=None, coordinate_precision=None, geometry_encoding="geoarrow_separated", include_metadata_columns=true, output="arrow", output_layout="wide", max_batch_rows=None))]
+#[allow(clippy::too_many_arguments)]
+fn hex_summary_from_saved(
+    py: Python<'_>,
+    path: &str,
+    resolution: Option<u8>,
+    clip_geojson: Option<&Bound<'_, PyAny>>,
+    zoom: Option<u8>,
+    deduplicate: bool,
+    count_mode: &str,
+    assignment: &str,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    boundary_pipe_count: bool,
+    parent_zoom: Option<i64>,
+    min_pipe_count: Option<i64>,
+    top_n: Option<usize>,
+    coordinate_precision: Option<i64>,
+    geometry_encoding: &str,
+    include_metadata_columns: bool,
+    output: &str,
+    output_layout: &str,
+    max_batch_rows: Option<usize>,
+) -> PyResult<Py<PyAny>> {
+    let zoom = extract_resolution(py, resolution, zoom)?;
+    let geometry_options = extract_geometry_options(coordinate_precision, None, geometry_encoding)?;
+    let output = extract_summary_output(output, output_layout, max_batch_rows)?;
+    let count_mode = extract_count_mode(count_mode)?;
+    let assignment = extract_assignment(assignment)?;
+    let weighting = extract_weighting(weighting)?;
+    let aggregations = extract_aggregations(aggregations)?;
+    let parent_zoom = extract_parent_zoom(zoom, parent_zoom)?;
+    let filter = extract_hex_filter(min_pipe_count, top_n)?;
 
-    let bbox = BBox::new(rect.min().y, rect.min().x, rect.max().y, rect.max().x);
+    let file = std::fs::File::open(path)?;
+    let (metadata, batches) = read_raw_fetch(std::io::BufReader::new(file))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let id_field = Network::from_name(&metadata.network)
+        .map(|network| network.id_field())
+        .unwrap_or_default();
+    let mut records = Vec::new();
+    for batch in &batches {
+        let parsed = records_from_batch(batch, false, true, &id_field)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        records.extend(parsed.records);
+    }
+
+    let outside = records_outside_bbox(&records, &metadata.bbox);
+    if outside > 0 {
+        let bbox = metadata.bbox;
+        let message = format!(
+            "{} of {} saved records lie outside the saved bbox ({}, {}, {}, {}); the file \
+             does not match its metadata",
+            outside,
+            records.len(),
+            bbox.min_lat,
+            bbox.min_lon,
+            bbox.max_lat,
+            bbox.max_lon
+        );
+        PyErr::warn(
+            py,
+            py.get_type::<pyo3::exceptions::PyUserWarning>().as_any(),
+            &CString::new(message)?,
+            1,
+        )?;
+    }
+    if count_mode.deduplicate(deduplicate) {
+        deduplicate_records(&mut records, &id_field);
+    }
 
-    let cadent_client = CadentClient::new()
+    let batch = match clip_geojson {
+        Some(clip) => {
+            let clip = extract_polygon(clip, "clip_geojson")?;
+            let clip = repaired_clip(py, clip, "Clip geometry")?;
+            warn_coarse_clip(py, &clip, "Clip geometry", zoom)?;
+            to_hex_summary_for_multipolygon(
+                &records,
+                zoom,
+                assignment,
+                &clip,
+                Containment::Intersects,
+                false,
+            )
+        }
+        None => to_hex_summary_min_count(&records, zoom, assignment, filter.min_count()).and_then(
+            |batch| clip_hex_summary_to_bbox(batch, &metadata.bbox, Containment::Intersects),
+        ),
+    }
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_count_mode(batch, &records, zoom, assignment, count_mode)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_weighting(batch, &records, zoom, assignment, weighting)
+        .and_then(|batch| with_aggregations(batch, &records, zoom, assignment, &aggregations))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = if boundary_pipe_count {
+        with_boundary_pipe_counts(batch, &records, zoom, assignment, count_mode)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let batch = filter_hex_summary(batch, &filter)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = match parent_zoom {
+        Some(parent_zoom) => with_parent_hex_ids(batch, parent_zoom)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+        None => batch,
+    };
+    let batch = if include_metadata_columns {
+        with_metadata_columns(batch, Some(zoom), Some(&metadata.network))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+    } else {
+        batch
+    };
+    let data_bbox = data_bbox_metadata(&batch)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let fetched_at = ("fetched_at".to_string(), metadata.fetched_at);
+    let entries = std::iter::once(fetched_at)
+        .chain(data_bbox)
+        .chain(assignment.metadata());
+    let batch = with_metadata(batch, entries)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    let batch = with_geometry_options(batch, &geometry_options)
+        .and_then(conform_hex_summary)
         .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
-    let result = runtime.block_on(async { cadent_client.fetch_all_by_bbox(&bbox).await });
+    output.to_python(py, batch)
+}
 
-    if !result.errors.is_empty() {
+/// Compare two hex summaries, e.g. from two data releases.
+///
+/// # Arguments
+/// * `batch_old`, `batch_new` - Summary RecordBatches or Tables with `hex_id` and
+///   `pipe_count` columns (such as those returned by the other functions)
+///
+/// Hexes present in only one input are treated as zero on the other side. Both
+/// summaries must be at the same zoom (read from their hex ids), otherwise
+/// `ValueError` is raised. No network access is needed.
+///
+/// # Returns
+/// A PyArrow RecordBatch with columns: hex_id, pipe_count_old, pipe_count_new, delta,
+/// geometry, sorted by hex_id
+#[pyfunction]
+fn diff_hex_summaries(
+    py: Python<'_>,
+    batch_old: AnyRecordBatch,
+    batch_new: AnyRecordBatch,
+) -> PyResult<Py<PyAny>> {
+    let (old, _) = batch_old.into_table()?.into_inner();
+    let (new, _) = batch_new.into_table()?.into_inner();
+
+    let batch = diff_summaries(&old, &new)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+
+    OutputFormat::Arrow.to_python(py, batch)
+}
+
+/// Merge hex summaries of neighbouring or overlapping areas, e.g. tiles fetched
+/// side by side, into one summary with a row per hex.
+///
+/// # Arguments
+/// * `batches` - Summary RecordBatches or Tables with the same columns (such as
+///   those returned by the other functions, called with the same options)
+///
+/// Rows are grouped by `hex_id`. `pipe_count`, `boundary_pipe_count`,
+/// `weighted_count` (the pipe length in metres with `weighting="length"`) and the
+/// `count_<layer>` / `count_<network>` columns are summed; every other column,
+/// geometry included, is kept from the first row with that hex. Each tile only
+/// fetches pipes located within it, so the partial counts of a hex on a seam add
+/// up to its full count. Tiles fetched with `snap_to_hex=True` already count
+/// their edge hexes in full, so merging those counts seam hexes twice.
+///
+/// Schema metadata describes single fetches and is dropped. Raises `ValueError`
+/// if `batches` is empty, their columns differ or their hexes are at different
+/// zooms. No network access is needed.
+///
+/// # Returns
+/// A PyArrow RecordBatch with the inputs' columns, one row per hex in order of
+/// first appearance
+#[pyfunction]
+fn merge_hex_summaries(py: Python<'_>, batches: Vec<AnyRecordBatch>) -> PyResult<Py<PyAny>> {
+    let mut summaries = Vec::new();
+    for batch in batches {
+        summaries.extend(batch.into_table()?.into_inner().0);
+    }
+
+    let batch = merge_summaries(&summaries)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+
+    OutputFormat::Arrow.to_python(py, batch)
+}
+
+/// Write a hex summary to `path` as newline-delimited GeoJSON (one Feature per line).
+///
+/// Each feature's geometry is the hex polygon in WGS84 (lon, lat), as BigQuery and
+/// Elasticsearch expect, and every other column (`hex_id`, `pipe_count`, ...) is a
+/// property. Batches are written as they are read, so a stream (e.g. a
+/// `pyarrow.RecordBatchReader`) is never held in memory at once.
+///
+/// # Returns
+/// The number of features written
+#[pyfunction]
+fn write_hex_summary_ndjson(batch: AnyRecordBatch, path: &str) -> PyResult<usize> {
+    let reader = batch.into_reader()?;
+    let file = std::fs::File::create(path)?;
+
+    export::write_ndjson(reader, std::io::BufWriter::new(file)).map_err(|e| match e.kind() {
+        std::io::ErrorKind::InvalidData => {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+        }
+        _ => e.into(),
+    })
+}
+
+/// Write a hex summary to `path` as a FlatGeobuf file with a spatial index.
+///
+/// Geometries and attributes are as for `write_hex_summary_ndjson`: hex polygons
+/// in WGS84 (EPSG:4326, recorded in the header) and every other column as a typed
+/// attribute (`pipe_count` as uint, `hex_id` as string). The packed R-tree index
+/// lets web viewers stream just the hexes in view with HTTP range requests. An
+/// existing file is overwritten, and an empty summary writes a valid file with no
+/// features.
+///
+/// # Returns
+/// The number of features written
+#[pyfunction]
+fn write_hex_summary_flatgeobuf(batch: AnyRecordBatch, path: &str) -> PyResult<usize> {
+    let reader = batch.into_reader()?;
+    let file = std::fs::File::create(path)?;
+
+    export::write_flatgeobuf(reader, std::io::BufWriter::new(file)).map_err(|e| match e.kind() {
+        std::io::ErrorKind::InvalidData => {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+        }
+        _ => e.into(),
+    })
+}
+
+/// Drop the cached HTTP clients, so the next fetch builds new ones.
+///
+/// Clients (and their pooled connections) are reused across calls with the same
+/// `http_options`. Call this after changing `HTTPS_PROXY` or similar environment
+/// variables, or to start a test from fresh connections.
+#[pyfunction]
+fn reset_clients() {
+    client::reset_clients();
+}
+
+/// Answer every request from canned JSON responses on disk instead of the network.
+///
+/// Cadent and ONS requests alike are looked up in `fixtures_dir` by a hash of
+/// their URL and sorted query parameters, so every function runs end-to-end and
+/// deterministically with no network access or API key. A request with no
+/// fixture fails, naming the file it expected. By default the fixtures bundled
+/// with the package are used: they cover `get_hex_summary` for the bbox
+/// `(53.47, -2.26, 53.49, -2.22)` in central Manchester, and
+/// `get_hex_summary_polygon_area` for OBJECTID 1 (a rectangle over the same area)
+/// and 0 (no such area, raising `AreaNotFoundError`).
+///
+/// With `record=True` requests go to the network as usual, and each response is
+/// saved as the fixture for its request, overwriting any already there.
+/// `fixtures_dir` is created if needed. The setting is process-wide and lasts
+/// until `disable_fixture_mode` is called.
+///
+/// # Args
+/// * `fixtures_dir` - Directory of fixture files, or None for the bundled ones
+/// * `record` - Fetch from the network and save the responses, rather than replay
+///
+/// # Errors
+/// Raises `ValueError` if `fixtures_dir` is not a directory when replaying, or if
+/// `record=True` is combined with the bundled fixtures.
+#[pyfunction]
+#[pyo3(signature = (fixtures_dir=None, *, record=false))]
+fn enable_fixture_mode(
+    py: Python<'_>,
+    fixtures_dir: Option<std::path::PathBuf>,
+    record: bool,
+) -> PyResult<()> {
+    let dir = match fixtures_dir {
+        Some(dir) => dir,
+        None if record => {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "record=True needs a fixtures_dir; the bundled fixtures are read-only",
+            ));
+        }
+        None => bundled_fixtures_dir(py)?,
+    };
+    if !record && !dir.is_dir() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "fixtures_dir {} is not a directory",
+            dir.display()
+        )));
+    }
+    fixtures::enable(fixtures::FixtureMode { dir, record });
+    Ok(())
+}
+
+/// The `fixtures` directory inside the installed `infra_hex_py` package.
+fn bundled_fixtures_dir(py: Python<'_>) -> PyResult<std::path::PathBuf> {
+    let init: std::path::PathBuf = py.import("infra_hex_py")?.getattr("__file__")?.extract()?;
+    Ok(init.with_file_name("fixtures"))
+}
+
+/// Send requests to the network again after `enable_fixture_mode`.
+///
+/// # Returns
+/// True if fixture mode was on
+#[pyfunction]
+fn disable_fixture_mode() -> bool {
+    fixtures::disable().is_some()
+}
+
+/// Check that each upstream endpoint is reachable and returns the expected schema.
+///
+/// Issues one lightweight request per endpoint (no records are downloaded and
+/// transient failures are not retried).
+///
+/// # Returns
+/// A dict keyed by endpoint name (`cadent`, `ons_built_up_areas`), each value a dict
+/// with `up` (bool), `status` (HTTP status or None), `url` and `error` (str or None)
+#[pyfunction]
+#[pyo3(signature = (*, http_options=None))]
+fn check_endpoints<'py>(
+    py: Python<'py>,
+    http_options: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let http_options = extract_http_options(http_options)?;
+
+    let report = PyDict::new(py);
+    for (name, status) in ping_endpoints(&http_options)? {
+        let entry = PyDict::new(py);
+        entry.set_item("up", status.up)?;
+        entry.set_item("status", status.http_status)?;
+        entry.set_item("url", status.url)?;
+        entry.set_item("error", status.error)?;
+        report.set_item(name, entry)?;
+    }
+
+    Ok(report)
+}
+
+/// Open connections to the Cadent and ONS hosts ahead of the first fetch.
+///
+/// Sends the same lightweight requests as `check_endpoints`, through the shared
+/// clients that later calls with the same `http_options` reuse, so DNS lookup
+/// and the TLS handshake are done before a user request arrives. Idle
+/// connections are kept for 90 seconds, so a service should not warm up long
+/// before traffic starts.
+///
+/// # Errors
+/// Raises `RuntimeError` naming each endpoint that could not be reached.
+#[pyfunction]
+#[pyo3(signature = (*, http_options=None))]
+fn warmup(http_options: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+    let http_options = extract_http_options(http_options)?;
+
+    let failures: Vec<String> = ping_endpoints(&http_options)?
+        .into_iter()
+        .filter(|(_, status)| !status.up)
+        .map(|(name, status)| {
+            format!(
+                "{} ({}): {}",
+                name,
+                status.url,
+                status.error.unwrap_or_default()
+            )
+        })
+        .collect();
+    if !failures.is_empty() {
         return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-            "Fetch had {} errors: {:?}",
-            result.errors.len(),
-            result.errors
+            "warmup could not reach {}",
+            failures.join("; ")
         )));
     }
+    Ok(())
+}
+
+/// Report how the shared HTTP clients are being used.
+///
+/// Idle pooled connections are not included: the HTTP library does not expose
+/// them.
+///
+/// # Returns
+/// A dict with `clients` (shared clients built, one per distinct proxy, header,
+/// user agent and timeout setting) and `in_flight` (requests currently being sent
+/// or read)
+#[pyfunction]
+fn pool_stats(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let stats = client::pool_stats();
+    let dict = PyDict::new(py);
+    dict.set_item("clients", stats.clients)?;
+    dict.set_item("in_flight", stats.in_flight)?;
+    Ok(dict)
+}
+
+/// Probes the Cadent and ONS built-up area endpoints concurrently, without retries.
+fn ping_endpoints(http_options: &HttpOptions) -> PyResult<[(&'static str, EndpointStatus); 2]> {
+    let runtime = runtime()?;
 
-    let batch = to_hex_summary_for_multipolygon(&result.records, zoom, &built_up_area.geometry)
+    let (cadent, ons) = runtime.block_on(async {
+        let cadent = async {
+            match CadentClient::new(http_options) {
+                Ok(client) => client.ping().await,
+                Err(e) => {
+                    let datasets_url = http_options
+                        .cadent_datasets_url()
+                        .unwrap_or_else(|_| client::CADENT_DATASETS_URL.to_string());
+                    EndpointStatus::unavailable(
+                        &CadentLayer::default().records_url(&datasets_url),
+                        e,
+                    )
+                }
+            }
+        };
+        let ons = async {
+            match BuiltUpAreaClient::new(http_options) {
+                Ok(client) => client.ping().await,
+                Err(e) => {
+                    let url = http_options
+                        .built_up_area_url()
+                        .unwrap_or_else(|_| client::BUILT_UP_AREA_BASE_URL.to_string());
+                    EndpointStatus::unavailable(&url, e)
+                }
+            }
+        };
+        futures::join!(cadent, ons)
+    });
+
+    Ok([("cadent", cadent), ("ons_built_up_areas", ons)])
+}
+
+/// The Arrow schema of the hex summaries the summary functions return with the
+/// given options, which take the same values as in those functions. Every
+/// summary's columns come in this order with these types, so a pipeline can
+/// check incoming batches with `batch.schema.equals(expected)`.
+///
+/// `layer` adds a `count_<layer>` column per layer of a list, `parent_zoom`
+/// (any zoom) a `parent_hex_id` column, `weighting="length"` a `weighted_count`
+/// column, `aggregations` its `<field>_<function>` columns, `k_ring` above 0 the `grid_distance` and `outside_query_area`
+/// columns, `clipped_area` (area summaries) a `clipped_area_m2` column and
+/// `nearest_distance` (`hex_summary_for_cells`) a `nearest_pipe_m` column and
+/// `include_query_id` a `query_id` column.
+/// `get_hex_summary_all_networks` adds a `count_<network>` column for each
+/// network that answered, after `weighted_count`, so its columns depend on the
+/// call. `grid="osgb"` gives the schema of a National Grid square summary,
+/// keyed by `grid_ref` and without a `resolution` column, and `grid="square"`
+/// that of a regular grid summary, keyed by `cell_id`. Raises `ValueError`
+/// for an unknown option value.
+///
+/// # Returns
+/// A `pyarrow.Schema`, with `schema_version` in its metadata.
+#[pyfunction]
+#[pyo3(signature = (*, grid="hex", layer=None, parent_zoom=None, weighting="count", aggregations=None, boundary_pipe_count=false, clipped_area=false, k_ring=0, hex_area=false, include_density=false, normalize="none", nearest_distance=false, include_metadata_columns=true, include_query_id=false, geometry_encoding="geoarrow_separated"))]
+#[allow(clippy::too_many_arguments)]
+fn hex_summary_schema<'py>(
+    py: Python<'py>,
+    grid: &str,
+    layer: Option<&Bound<'py, PyAny>>,
+    parent_zoom: Option<i64>,
+    weighting: &str,
+    aggregations: Option<&Bound<'_, PyDict>>,
+    boundary_pipe_count: bool,
+    clipped_area: bool,
+    k_ring: i64,
+    hex_area: bool,
+    include_density: bool,
+    normalize: &str,
+    nearest_distance: bool,
+    include_metadata_columns: bool,
+    include_query_id: bool,
+    geometry_encoding: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let sources = match extract_layers(layer)? {
+        LayerSelection::Single(_) => Vec::new(),
+        LayerSelection::Tagged(layers) => layers
+            .iter()
+            .map(|layer| layer.name().to_string())
+            .collect(),
+    };
+    let k_ring = validate_k_ring(k_ring)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let columns = SummaryColumns {
+        key: extract_grid_squares(grid, None, None)?.map_or(CellKey::HexId, Squares::key),
+        sources,
+        parent_hex_id: parent_zoom.is_some(),
+        boundary_pipe_count,
+        weighted_count: extract_weighting(weighting)? == Weighting::Length,
+        clipped_area,
+        k_ring: k_ring > 0,
+        hex_area,
+        density: include_density,
+        normalized_density: extract_normalize(normalize)? != Normalize::None,
+        nearest_pipe_distance: nearest_distance,
+        aggregations: extract_aggregations(aggregations)?,
+        metadata_columns: include_metadata_columns,
+        query_id: include_query_id,
+        areas: false,
+        geometry_encoding: extract_geometry_options(None, None, geometry_encoding)?.encoding,
+    };
+    let schema = summary::hex_summary_schema(&columns)
         .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    PySchema::new(Arc::new(schema)).into_pyarrow(py)
+}
+
+/// Version of the `infra-hex-rs` dependency; keep in step with Cargo.toml.
+const INFRA_HEX_RS_VERSION: &str = "0.1.0";
+
+/// Describe this build and the upstream endpoints it queries, for recording how a
+/// dataset was produced. Makes no network requests.
+///
+/// # Returns
+/// A dict with `version` (this package), `infra_hex_rs_version`,
+/// `cadent_datasets_url`, `cadent_layers` (a dict of layer name to records URL),
+/// `networks` (the names accepted by `network=`, including `"mock"` when built with
+/// the `mock` feature) and
+/// `ons_built_up_areas_url` and `ons_local_authority_urls` (a dict of resolution to
+/// URL). The Cadent and built-up area URLs reflect `INFRA_HEX_CADENT_URL` and
+/// `INFRA_HEX_ONS_URL` when set.
+///
+/// # Errors
+/// Raises `ValueError` if either environment variable is not a valid URL.
+#[pyfunction]
+fn build_info(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let defaults = HttpOptions::default();
+    let datasets_url = defaults
+        .cadent_datasets_url()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let built_up_area_url = defaults
+        .built_up_area_url()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let layers = PyDict::new(py);
+    for layer in CadentLayer::ALL {
+        layers.set_item(layer.name(), layer.records_url(&datasets_url))?;
+    }
+
+    let info = PyDict::new(py);
+    info.set_item("version", env!("CARGO_PKG_VERSION"))?;
+    info.set_item("infra_hex_rs_version", INFRA_HEX_RS_VERSION)?;
+    info.set_item("cadent_datasets_url", &datasets_url)?;
+    info.set_item("cadent_layers", layers)?;
+    let networks: Vec<_> = Network::SELECTABLE.iter().map(Network::name).collect();
+    info.set_item("networks", networks)?;
+    info.set_item("ons_built_up_areas_url", built_up_area_url)?;
+    let districts = PyDict::new(py);
+    for resolution in [BoundaryResolution::Generalised, BoundaryResolution::Full] {
+        districts.set_item(resolution.name(), resolution.local_authority_url())?;
+    }
+    info.set_item("ons_local_authority_urls", districts)?;
 
-    PyRecordBatch::new(batch)
-        .into_pyarrow(py)
-        .map(|bound| bound.unbind())
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    Ok(info)
 }
 
 #[pyo3::pymodule]
 mod infra_hex_py {
+    use pyo3::prelude::*;
+
+    #[pymodule_init]
+    fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
+        m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+        m.add(
+            "HEX_SUMMARY_SCHEMA_VERSION",
+            super::HEX_SUMMARY_SCHEMA_VERSION,
+        )?;
+        m.add("FetchTimeoutError", super::fetch_timeout_error(m.py())?)?;
+        m.add("AreaNotFoundError", super::area_not_found_error(m.py())?)
+    }
+
+    #[pymodule_export]
+    use super::FetchError;
+    #[pymodule_export]
+    use super::InfraGeometryError;
+    #[pymodule_export]
+    use super::InfraNetworkError;
+    #[pymodule_export]
+    use super::PaginationError;
+    #[pymodule_export]
+    use super::PyArrowBatch;
+    #[pymodule_export]
+    use super::PyBBox;
+    #[pymodule_export]
+    use super::PyConfig;
+    #[pymodule_export]
+    use super::PyNormalizedSummary;
+    #[pymodule_export]
+    use super::PyRegionCache;
+    #[pymodule_export]
+    use super::build_info;
+    #[pymodule_export]
+    use super::check_endpoints;
+    #[pymodule_export]
+    use super::configure;
+    #[pymodule_export]
+    use super::configure_aggregation;
+    #[pymodule_export]
+    use super::configure_runtime;
+    #[pymodule_export]
+    use super::diff_hex_summaries;
+    #[pymodule_export]
+    use super::disable_fixture_mode;
+    #[pymodule_export]
+    use super::enable_fixture_mode;
+    #[pymodule_export]
+    use super::estimate_fetch;
+    #[pymodule_export]
+    use super::fetch_raw_response;
+    #[pymodule_export]
+    use super::get_built_up_area;
+    #[pymodule_export]
+    use super::get_built_up_areas;
+    #[pymodule_export]
+    use super::get_config;
     #[pymodule_export]
     use super::get_hex_summary;
     #[pymodule_export]
+    use super::get_hex_summary_all_networks;
+    #[pymodule_export]
+    use super::get_hex_summary_around_point;
+    #[pymodule_export]
+    use super::get_hex_summary_bng;
+    #[pymodule_export]
+    use super::get_hex_summary_for_arcgis_feature;
+    #[pymodule_export]
+    use super::get_hex_summary_for_area_names;
+    #[pymodule_export]
+    use super::get_hex_summary_for_polygon;
+    #[pymodule_export]
+    use super::get_hex_summary_local_authority;
+    #[pymodule_export]
     use super::get_hex_summary_polygon_area;
+    #[pymodule_export]
+    use super::get_pipe_cell_traces;
+    #[pymodule_export]
+    use super::get_pipe_hex_assignments;
+    #[pymodule_export]
+    use super::get_pipes_in_cell;
+    #[pymodule_export]
+    use super::get_record_hex_assignments;
+    #[pymodule_export]
+    use super::hex_summary_for_cells;
+    #[pymodule_export]
+    use super::hex_summary_from_arrow;
+    #[pymodule_export]
+    use super::hex_summary_from_geojson;
+    #[pymodule_export]
+    use super::hex_summary_from_records;
+    #[pymodule_export]
+    use super::hex_summary_from_saved;
+    #[pymodule_export]
+    use super::hex_summary_schema;
+    #[pymodule_export]
+    use super::merge_hex_summaries;
+    #[pymodule_export]
+    use super::pool_stats;
+    #[pymodule_export]
+    use super::reset_clients;
+    #[pymodule_export]
+    use super::save_raw_fetch;
+    #[pymodule_export]
+    use super::set_default_network;
+    #[pymodule_export]
+    use super::shutdown_runtime;
+    #[pymodule_export]
+    use super::warmup;
+    #[pymodule_export]
+    use super::write_hex_summary_flatgeobuf;
+    #[pymodule_export]
+    use super::write_hex_summary_ndjson;
 }
@@ -0,0 +1,55 @@
+use infra_hex_rs::{BBox, InfraClient, InfraHexError, InfraResult, PipelineRecord};
+
+use crate::records::records_from_geojson;
+
+/// Synthetic pipes around central Manchester (roughly 53.47 to 53.49N, 2.26 to
+/// 2.22W): long mains crossing many hexes, a main split into two segments sharing
+/// an `asset_id`, a cluster of short services and a pipe with no `asset_id`.
+const MOCK_PIPES_GEOJSON: &str = include_str!("mock_pipes.geojson");
+
+/// An [`InfraClient`] serving a fixed set of synthetic pipeline records, for
+/// tests, demos and CI without an API key or network access.
+///
+/// Like Cadent's `in_bbox` query, a record is returned when its `geo_point_2d`
+/// lies within the bounding box.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MockInfraClient;
+
+impl MockInfraClient {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn records_in(&self, bbox: &BBox) -> Result<Vec<PipelineRecord>, InfraHexError> {
+        let mut records = records_from_geojson(MOCK_PIPES_GEOJSON, false)?.records;
+        records.retain(|record| {
+            let point = &record.geo_point_2d;
+            (bbox.min_lat..=bbox.max_lat).contains(&point.lat)
+                && (bbox.min_lon..=bbox.max_lon).contains(&point.lon)
+        });
+        Ok(records)
+    }
+}
+
+impl InfraClient for MockInfraClient {
+    type Record = PipelineRecord;
+
+    async fn fetch_by_bbox(
+        &self,
+        bbox: &BBox,
+        limit: Option<usize>,
+    ) -> Result<Vec<Self::Record>, InfraHexError> {
+        let mut records = self.records_in(bbox)?;
+        records.truncate(limit.unwrap_or(100));
+        Ok(records)
+    }
+
+    async fn fetch_all_by_bbox(&self, bbox: &BBox) -> InfraResult<Self::Record> {
+        let mut result = InfraResult::new();
+        match self.records_in(bbox) {
+            Ok(records) => result.records = records,
+            Err(e) => result.errors.push(e),
+        }
+        result
+    }
+}
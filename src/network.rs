@@ -0,0 +1,280 @@
+use geo::{MultiPolygon, unary_union};
+use geojson::GeoJson;
+use infra_hex_rs::{BBox, FromGeoJson, InfraClient, InfraHexError, InfraResult, PipelineRecord};
+
+use crate::client::{
+    CadentClient, CadentLayer, FailedPage, FeatureBudget, FetchEstimate, HttpOptions, RecordQuery,
+    TransferStats,
+};
+#[cfg(feature = "mock")]
+use crate::mock::MockInfraClient;
+use crate::records::IdField;
+#[cfg(feature = "mock")]
+use crate::records::RECORD_FIELDS;
+
+/// A coarse, deliberately generous outline of Cadent's four licence areas (North
+/// West, West Midlands, East of England and North London), as WGS84 polygons. It
+/// is drawn to catch queries straying into another network's area, not to place
+/// the boundary to within a few hexes.
+const CADENT_COVERAGE_GEOJSON: &str = include_str!("cadent_coverage.geojson");
+
+/// Gas distribution networks that pipeline records can be fetched from.
+///
+/// Every variant is backed by an [`InfraClient`] implementation yielding
+/// [`PipelineRecord`]s, so records from any network can be binned together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Network {
+    Cadent,
+    /// Synthetic records from an embedded fixture; only built with the `mock`
+    /// feature.
+    #[cfg(feature = "mock")]
+    Mock,
+}
+
+impl Network {
+    /// All registered networks, in output column order.
+    pub const ALL: &'static [Network] = &[Network::Cadent];
+
+    /// Every network that can be selected by name, including the mock one when
+    /// it is compiled in.
+    pub const SELECTABLE: &'static [Network] = &[
+        Network::Cadent,
+        #[cfg(feature = "mock")]
+        Network::Mock,
+    ];
+
+    /// Short lowercase name used in column names (`count_<name>`) and metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Network::Cadent => "cadent",
+            #[cfg(feature = "mock")]
+            Network::Mock => "mock",
+        }
+    }
+
+    /// The attribute holding each pipe's stable id in this network's records,
+    /// used when `id_field=` is left out.
+    pub fn id_field(&self) -> IdField {
+        match self {
+            Network::Cadent => IdField::default(),
+            #[cfg(feature = "mock")]
+            Network::Mock => IdField::default(),
+        }
+    }
+
+    /// Parses a network name as used by `network=` on the fetch functions.
+    ///
+    /// # Errors
+    /// Returns [`InfraHexError::Config`] for an unknown (or not compiled in) name.
+    pub fn from_name(name: &str) -> Result<Self, InfraHexError> {
+        Self::SELECTABLE
+            .iter()
+            .copied()
+            .find(|network| network.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::SELECTABLE.iter().map(Network::name).collect();
+                InfraHexError::Config(format!(
+                    "Unknown network {:?}; expected one of {:?}",
+                    name, names
+                ))
+            })
+    }
+
+    /// A coarse WGS84 outline of where this network has pipes, or `None` if it is
+    /// not known, as for the mock network.
+    pub fn coverage(&self) -> Result<Option<MultiPolygon<f64>>, InfraHexError> {
+        match self {
+            Network::Cadent => {
+                let invalid =
+                    |e: String| InfraHexError::Geometry(format!("Embedded Cadent coverage: {}", e));
+                let GeoJson::FeatureCollection(fc) = CADENT_COVERAGE_GEOJSON
+                    .parse::<GeoJson>()
+                    .map_err(|e| invalid(e.to_string()))?
+                else {
+                    return Err(invalid("not a FeatureCollection".into()));
+                };
+                let areas = fc
+                    .features
+                    .iter()
+                    .map(|feature| {
+                        let geometry = feature
+                            .geometry
+                            .as_ref()
+                            .ok_or_else(|| invalid("feature has no geometry".into()))?;
+                        MultiPolygon::from_geojson(geometry)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Some(unary_union(&areas)))
+            }
+            #[cfg(feature = "mock")]
+            Network::Mock => Ok(None),
+        }
+    }
+
+    /// Fetches every record within the bounding box from this network.
+    ///
+    /// Client construction failures (e.g. a missing API key) are returned as `Err`,
+    /// while page-level failures are collected in the returned `InfraResult`.
+    /// Response sizes are added to `stats`.
+    pub async fn fetch_all_by_bbox(
+        &self,
+        bbox: &BBox,
+        options: &HttpOptions,
+        stats: &TransferStats,
+    ) -> Result<InfraResult<PipelineRecord>, InfraHexError> {
+        self.fetch_layer_by_bbox(
+            CadentLayer::default(),
+            bbox,
+            &RecordQuery::default(),
+            options,
+            stats,
+            &FeatureBudget::default(),
+        )
+        .await
+    }
+
+    /// As [`Network::fetch_all_by_bbox`], but from one Cadent `layer` and as
+    /// restricted by `query`, stopping early once `budget` is spent. The mock
+    /// network serves the same records for every layer, with every field, and
+    /// ignores `budget`.
+    pub async fn fetch_layer_by_bbox(
+        &self,
+        layer: CadentLayer,
+        bbox: &BBox,
+        query: &RecordQuery,
+        options: &HttpOptions,
+        stats: &TransferStats,
+        budget: &FeatureBudget,
+    ) -> Result<InfraResult<PipelineRecord>, InfraHexError> {
+        match self {
+            Network::Cadent => {
+                let client = CadentClient::new(options)?
+                    .with_layer(layer)
+                    .with_query(query)
+                    .with_transfer_stats(stats)
+                    .with_feature_budget(budget);
+                Ok(client.fetch_all_by_bbox(bbox).await)
+            }
+            #[cfg(feature = "mock")]
+            Network::Mock => {
+                let mut result = MockInfraClient::new().fetch_all_by_bbox(bbox).await;
+                if layer.date_field().is_some() {
+                    result
+                        .records
+                        .retain(|record| query.dates.contains(record.inst_date.as_deref()));
+                }
+                Ok(result)
+            }
+        }
+    }
+
+    /// What [`Network::fetch_layer_by_bbox`] with the same arguments would fetch,
+    /// from one count query (see [`CadentClient::estimate`]). The mock network
+    /// makes no requests.
+    pub async fn estimate_layer_by_bbox(
+        &self,
+        layer: CadentLayer,
+        bbox: &BBox,
+        query: &RecordQuery,
+        options: &HttpOptions,
+    ) -> Result<FetchEstimate, InfraHexError> {
+        match self {
+            Network::Cadent => {
+                CadentClient::new(options)?
+                    .with_layer(layer)
+                    .with_query(query)
+                    .estimate(bbox)
+                    .await
+            }
+            #[cfg(feature = "mock")]
+            Network::Mock => {
+                let budget = FeatureBudget::default();
+                let result = self
+                    .fetch_layer_by_bbox(
+                        layer,
+                        bbox,
+                        query,
+                        options,
+                        &TransferStats::default(),
+                        &budget,
+                    )
+                    .await?;
+                Ok(FetchEstimate {
+                    feature_count: result.records.len(),
+                    requests: 0,
+                })
+            }
+        }
+    }
+
+    /// Fetches again just the `failed` parts of an earlier
+    /// [`Network::fetch_layer_by_bbox`] with the same `layer` and `query` (see
+    /// [`CadentClient::refetch`]). The mock network never fails, so has nothing
+    /// to refetch.
+    pub async fn refetch(
+        &self,
+        layer: CadentLayer,
+        failed: &[FailedPage],
+        query: &RecordQuery,
+        options: &HttpOptions,
+        stats: &TransferStats,
+    ) -> Result<InfraResult<PipelineRecord>, InfraHexError> {
+        match self {
+            Network::Cadent => {
+                let client = CadentClient::new(options)?
+                    .with_layer(layer)
+                    .with_query(query)
+                    .with_transfer_stats(stats);
+                Ok(client.refetch(failed).await)
+            }
+            #[cfg(feature = "mock")]
+            Network::Mock => Ok(InfraResult::new()),
+        }
+    }
+
+    /// Names of the fields records of `layer` can carry, from the dataset's
+    /// metadata; for the mock network, the attributes of [`PipelineRecord`].
+    pub async fn field_names(
+        &self,
+        layer: CadentLayer,
+        options: &HttpOptions,
+    ) -> Result<Vec<String>, InfraHexError> {
+        match self {
+            Network::Cadent => {
+                CadentClient::new(options)?
+                    .with_layer(layer)
+                    .field_names()
+                    .await
+            }
+            #[cfg(feature = "mock")]
+            Network::Mock => Ok(RECORD_FIELDS
+                .iter()
+                .map(|field| field.to_string())
+                .collect()),
+        }
+    }
+
+    /// As [`Network::fetch_layer_by_bbox`], unfiltered by date and budget, but
+    /// only records within `bbox` whose pipe passes through the WGS84 `area` (see
+    /// [`CadentClient::fetch_all_by_polygon`]). The mock network ignores `area`
+    /// and serves every record within `bbox`.
+    pub async fn fetch_layer_by_polygon(
+        &self,
+        layer: CadentLayer,
+        bbox: &BBox,
+        area: &MultiPolygon<f64>,
+        options: &HttpOptions,
+        stats: &TransferStats,
+    ) -> Result<InfraResult<PipelineRecord>, InfraHexError> {
+        match self {
+            Network::Cadent => {
+                let client = CadentClient::new(options)?
+                    .with_layer(layer)
+                    .with_transfer_stats(stats);
+                Ok(client.fetch_all_by_polygon(bbox, area).await)
+            }
+            #[cfg(feature = "mock")]
+            Network::Mock => Ok(MockInfraClient::new().fetch_all_by_bbox(bbox).await),
+        }
+    }
+}
@@ -0,0 +1,502 @@
+//! Square cells in British National Grid metres: Ordnance Survey National Grid
+//! squares for a `grid="osgb"` summary, or a regular grid of any size and origin
+//! for `grid="square"`.
+//!
+//! A National Grid square is named by its grid reference: the two letters of its
+//! 100 km square followed by its easting and northing digits within it, as many of
+//! each as the square size needs ("SJ8398" is a 1 km square). A regular square's
+//! `cell_id` gives its size and south-west corner ("250m_E383000_N398250").
+
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray, UInt32Array};
+use arrow_schema::{DataType, Field};
+use geo::{
+    Area, BooleanOps, Coord, Euclidean, Intersects, Length, MultiLineString, Polygon, Rect, coord,
+};
+use infra_hex_rs::{BBox, InfraHexError, PipelineRecord};
+
+use crate::crs::{BNG_EXTENT, Transform, wgs84_multipolygon_to_bng};
+use crate::summary::{
+    Assignment, CellKey, Resolution, Weighting, bbox_polygon, build_polygon_column, line_midpoint,
+    record_lines, summary_schema,
+};
+
+/// Square sizes, in metres, a `grid="osgb"` summary can use.
+pub const GRID_SIZES_M: [u32; 3] = [100, 1_000, 10_000];
+
+/// Square size `grid="osgb"` and `grid="square"` use when `grid_size_m` is not
+/// given.
+pub const DEFAULT_GRID_SIZE_M: u32 = 1_000;
+
+/// The cells a summary bins pipes into, chosen with `grid=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grid {
+    /// n3gb hexes at a resolution, keyed by `hex_id`.
+    Hex(Resolution),
+    /// BNG squares, keyed by `grid_ref` or `cell_id`.
+    Squares(Squares),
+}
+
+impl Grid {
+    /// The hex zoom, for a hex grid.
+    pub fn zoom(self) -> Option<u8> {
+        match self {
+            Self::Hex(resolution) => Some(resolution.zoom()),
+            Self::Squares(_) => None,
+        }
+    }
+
+    /// Schema metadata recording an automatically chosen resolution, or the grid
+    /// and square size (and origin) of a summary of squares.
+    pub fn metadata(self) -> Vec<(String, String)> {
+        match self {
+            Self::Hex(resolution) => resolution.metadata().into_iter().collect(),
+            Self::Squares(Squares::Osgb(size)) => vec![
+                ("grid".to_string(), "osgb".to_string()),
+                ("grid_size_m".to_string(), size.to_string()),
+            ],
+            Self::Squares(Squares::Regular { size_m, origin }) => vec![
+                ("grid".to_string(), "square".to_string()),
+                ("grid_size_m".to_string(), size_m.to_string()),
+                (
+                    "grid_origin".to_string(),
+                    format!("[{}, {}]", origin.0, origin.1),
+                ),
+            ],
+        }
+    }
+}
+
+/// A lattice of BNG squares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Squares {
+    /// National Grid squares this many metres a side, keyed by `grid_ref`.
+    Osgb(u32),
+    /// Squares `size_m` metres a side with a corner at the BNG point `origin`
+    /// (easting, northing), keyed by `cell_id`.
+    Regular { size_m: u32, origin: (i64, i64) },
+}
+
+impl Squares {
+    /// The column a summary of these squares is keyed by.
+    pub fn key(self) -> CellKey {
+        match self {
+            Self::Osgb(_) => CellKey::GridRef,
+            Self::Regular { .. } => CellKey::CellId,
+        }
+    }
+
+    /// The length of a side, in metres.
+    pub fn size_m(self) -> u32 {
+        match self {
+            Self::Osgb(size) | Self::Regular { size_m: size, .. } => size,
+        }
+    }
+
+    fn origin(self) -> Coord<f64> {
+        match self {
+            Self::Osgb(_) => coord! { x: 0.0, y: 0.0 },
+            Self::Regular { origin, .. } => coord! { x: origin.0 as f64, y: origin.1 as f64 },
+        }
+    }
+
+    /// The square in column `col` and row `row`, counting from the origin.
+    fn square(self, col: i64, row: i64) -> Rect<f64> {
+        let size = f64::from(self.size_m());
+        let origin = self.origin();
+        let corner = coord! {
+            x: origin.x + col as f64 * size,
+            y: origin.y + row as f64 * size,
+        };
+        Rect::new(corner, coord! { x: corner.x + size, y: corner.y + size })
+    }
+
+    /// The column and row of the square containing the BNG point `point`.
+    fn index(self, point: Coord<f64>) -> (i64, i64) {
+        let size = f64::from(self.size_m());
+        let origin = self.origin();
+        (
+            ((point.x - origin.x) / size).floor() as i64,
+            ((point.y - origin.y) / size).floor() as i64,
+        )
+    }
+
+    /// The id of `square`, one of these squares.
+    ///
+    /// # Errors
+    /// Returns [`InfraHexError::Geometry`] for a National Grid square outside the
+    /// grid.
+    fn id(self, square: &Rect<f64>) -> Result<String, InfraHexError> {
+        match self {
+            Self::Osgb(size) => grid_ref(square.center(), size),
+            Self::Regular { size_m, .. } => Ok(format!(
+                "{}m_E{}_N{}",
+                size_m,
+                square.min().x as i64,
+                square.min().y as i64
+            )),
+        }
+    }
+}
+
+/// Parses the `grid` (`"hex"`, `"osgb"` or `"square"`), `grid_size_m` and
+/// `grid_origin` arguments into the squares of a summary, or `None` for hexes.
+///
+/// `"osgb"` takes a size of 100, 1000 or 10000 metres and `"square"` any whole
+/// number of metres from 1 to 100 km, both defaulting to [`DEFAULT_GRID_SIZE_M`].
+/// Only `"square"` takes an origin, defaulting to the BNG false origin (0, 0).
+pub fn grid_squares(
+    grid: &str,
+    grid_size_m: Option<i64>,
+    grid_origin: Option<(i64, i64)>,
+) -> Result<Option<Squares>, InfraHexError> {
+    if grid_origin.is_some() && grid != "square" {
+        return Err(InfraHexError::Config(
+            "grid_origin applies to grid=\"square\"".into(),
+        ));
+    }
+    match (grid, grid_size_m) {
+        ("hex", None) => Ok(None),
+        ("hex", Some(_)) => Err(InfraHexError::Config(
+            "grid_size_m applies to grid=\"osgb\" or \"square\"; hex summaries take a \
+             resolution"
+                .into(),
+        )),
+        ("osgb", size) => {
+            let size = size.unwrap_or(DEFAULT_GRID_SIZE_M.into());
+            GRID_SIZES_M
+                .into_iter()
+                .find(|&allowed| i64::from(allowed) == size)
+                .map(|size| Some(Squares::Osgb(size)))
+                .ok_or_else(|| {
+                    InfraHexError::Config(format!(
+                        "grid_size_m must be 100, 1000 or 10000, got {}",
+                        size
+                    ))
+                })
+        }
+        ("square", size) => {
+            let size = size.unwrap_or(DEFAULT_GRID_SIZE_M.into());
+            match u32::try_from(size) {
+                Ok(size_m @ 1..=100_000) => Ok(Some(Squares::Regular {
+                    size_m,
+                    origin: grid_origin.unwrap_or_default(),
+                })),
+                _ => Err(InfraHexError::Config(format!(
+                    "grid_size_m must be between 1 and 100000 metres, got {}",
+                    size
+                ))),
+            }
+        }
+        (other, _) => Err(InfraHexError::Config(format!(
+            "Unknown grid {:?}; expected \"hex\", \"osgb\" or \"square\"",
+            other
+        ))),
+    }
+}
+
+/// The letters of the 100 km square with south-west corner index (`e`, `n`),
+/// counting 100 km squares from the false origin; "I" is not used.
+fn square_letters(e: u32, n: u32) -> [char; 2] {
+    let first = (19 - n) - (19 - n) % 5 + (e + 10) / 5;
+    let second = (19 - n) * 5 % 25 + e % 5;
+    [first, second].map(|index| {
+        let index = if index > 7 { index + 1 } else { index };
+        char::from(b'A' + index as u8)
+    })
+}
+
+/// The grid reference of the `size_m` square containing BNG point `point`.
+///
+/// # Errors
+/// Returns [`InfraHexError::Geometry`] if the point lies outside the National
+/// Grid.
+pub fn grid_ref(point: Coord<f64>, size_m: u32) -> Result<String, InfraHexError> {
+    let [min_e, min_n, max_e, max_n] = BNG_EXTENT;
+    if !(min_e..max_e).contains(&point.x) || !(min_n..max_n).contains(&point.y) {
+        return Err(InfraHexError::Geometry(format!(
+            "({}, {}) is outside the National Grid",
+            point.x, point.y
+        )));
+    }
+    let (e, n) = (point.x as u32, point.y as u32);
+    let [first, second] = square_letters(e / 100_000, n / 100_000);
+    let digits = 5 - size_m.ilog10() as usize;
+    Ok(format!(
+        "{}{}{:0digits$}{:0digits$}",
+        first,
+        second,
+        e % 100_000 / size_m,
+        n % 100_000 / size_m,
+    ))
+}
+
+/// The BNG square a grid reference from [`grid_ref`] names.
+///
+/// # Errors
+/// Returns [`InfraHexError::Geometry`] for anything else.
+pub fn grid_square(grid_ref: &str) -> Result<Rect<f64>, InfraHexError> {
+    let invalid = || InfraHexError::Geometry(format!("Invalid grid reference {:?}", grid_ref));
+    let (letters, digits) = grid_ref.split_at_checked(2).ok_or_else(invalid)?;
+    let index = |letter: u8| match letter {
+        b'A'..=b'H' => Some(u32::from(letter - b'A')),
+        b'J'..=b'Z' => Some(u32::from(letter - b'A') - 1),
+        _ => None,
+    };
+    let [first, second] = [letters.as_bytes()[0], letters.as_bytes()[1]]
+        .map(index)
+        .map(|index| index.ok_or_else(invalid));
+    let (first, second) = (first?, second?);
+    let e100k = (first + 3) % 5 * 5 + second % 5;
+    let n100k = 19u32
+        .checked_sub(first / 5 * 5 + second / 5)
+        .ok_or_else(invalid)?;
+    let half = digits.len() / 2;
+    if !(1..=3).contains(&half)
+        || digits.len() != 2 * half
+        || !digits.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    let size = 10u32.pow(5 - half as u32);
+    let (e, n) = digits.split_at(half);
+    let [e, n] = [e, n].map(|part| part.parse::<u32>().unwrap_or_default() * size);
+    let [_, _, max_e, max_n] = BNG_EXTENT;
+    let min = coord! {
+        x: f64::from(e100k * 100_000 + e),
+        y: f64::from(n100k * 100_000 + n),
+    };
+    if min.x >= max_e || min.y >= max_n {
+        return Err(invalid());
+    }
+    let size = f64::from(size);
+    Ok(Rect::new(min, coord! { x: min.x + size, y: min.y + size }))
+}
+
+/// The BNG square a regular grid `cell_id` from a `grid="square"` summary names.
+///
+/// # Errors
+/// Returns [`InfraHexError::Geometry`] for anything else.
+pub fn square_cell(cell_id: &str) -> Result<Rect<f64>, InfraHexError> {
+    let invalid = || InfraHexError::Geometry(format!("Invalid cell_id {:?}", cell_id));
+    let mut parts = cell_id.split('_');
+    let (Some(size), Some(e), Some(n), None) = (
+        parts.next().and_then(|part| part.strip_suffix('m')),
+        parts.next().and_then(|part| part.strip_prefix('E')),
+        parts.next().and_then(|part| part.strip_prefix('N')),
+        parts.next(),
+    ) else {
+        return Err(invalid());
+    };
+    let size = size.parse::<u32>().map_err(|_| invalid())?;
+    let [e, n] = [e, n].map(|part| part.parse::<i64>());
+    let (e, n) = (e.map_err(|_| invalid())?, n.map_err(|_| invalid())?);
+    let min = coord! { x: e as f64, y: n as f64 };
+    let size = f64::from(size);
+    Ok(Rect::new(min, coord! { x: min.x + size, y: min.y + size }))
+}
+
+/// Fails before any fetch if more than `max_squares` `size_m` squares would
+/// cover `bbox`.
+pub fn check_square_budget(
+    bbox: &BBox,
+    size_m: u32,
+    max_squares: u64,
+) -> Result<(), InfraHexError> {
+    let area = wgs84_multipolygon_to_bng(&bbox_polygon(bbox))?.unsigned_area();
+    let estimate = (area / f64::from(size_m).powi(2)).ceil() as u64;
+    if estimate <= max_squares {
+        return Ok(());
+    }
+    Err(InfraHexError::Config(format!(
+        "Estimated {} grid squares of {} m exceeds max_hexes ({}); use a larger \
+         grid_size_m or a smaller area, or raise max_hexes",
+        estimate, size_m, max_squares
+    )))
+}
+
+fn square_at(point: Coord<f64>, squares: Squares) -> Result<(String, Rect<f64>), InfraHexError> {
+    let (col, row) = squares.index(point);
+    let square = squares.square(col, row);
+    Ok((squares.id(&square)?, square))
+}
+
+/// The unique `squares` the BNG `lines` pass through, like
+/// [`crate::summary::line_hex_cells`] for hexes. A single-point line falls in the
+/// square containing the point.
+fn line_squares(
+    lines: &MultiLineString<f64>,
+    squares: Squares,
+) -> Result<Vec<(String, Rect<f64>)>, InfraHexError> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut crossed = Vec::new();
+    for line in lines {
+        if let [point] = line.0.as_slice() {
+            let (id, square) = square_at(*point, squares)?;
+            if seen.insert(id.clone()) {
+                crossed.push((id, square));
+            }
+            continue;
+        }
+        for segment in line.lines() {
+            let bounds = Rect::new(segment.start, segment.end);
+            let (min_col, min_row) = squares.index(bounds.min());
+            let (max_col, max_row) = squares.index(bounds.max());
+            for col in min_col..=max_col {
+                for row in min_row..=max_row {
+                    let square = squares.square(col, row);
+                    if !square.intersects(&segment) {
+                        continue;
+                    }
+                    let id = squares.id(&square)?;
+                    if seen.insert(id.clone()) {
+                        crossed.push((id, square));
+                    }
+                }
+            }
+        }
+    }
+    Ok(crossed)
+}
+
+/// The squares `assignment` assigns the BNG `lines` to, paired with the share of
+/// the record each gets under [`Weighting::Length`] (see
+/// [`crate::summary::with_weighting`]).
+fn assigned_squares(
+    lines: &MultiLineString<f64>,
+    squares: Squares,
+    assignment: Assignment,
+) -> Result<Vec<(String, Rect<f64>, f64)>, InfraHexError> {
+    if assignment == Assignment::Midpoint {
+        return Ok(match line_midpoint(lines) {
+            Some(midpoint) => {
+                let (id, square) = square_at(midpoint, squares)?;
+                vec![(id, square, 1.0)]
+            }
+            None => Vec::new(),
+        });
+    }
+
+    // Ordered so the float total below is summed the same way on every run.
+    let mut shares: BTreeMap<String, (Rect<f64>, f64)> = BTreeMap::new();
+    for (id, square) in line_squares(lines, squares)? {
+        if let Entry::Vacant(entry) = shares.entry(id) {
+            let length = Euclidean.length(&square.to_polygon().clip(lines, false));
+            entry.insert((square, length));
+        }
+    }
+    let total: f64 = shares.values().map(|(_, length)| length).sum();
+    let count = shares.len();
+    Ok(shares
+        .into_iter()
+        .map(|(id, (square, length))| {
+            // A record with no length, such as a point asset, splits its 1 evenly
+            let share = if total > 0.0 {
+                length / total
+            } else {
+                1.0 / count as f64
+            };
+            (id, square, share)
+        })
+        .collect())
+}
+
+struct SquareCount {
+    square: Rect<f64>,
+    total: u32,
+    weighted: f64,
+    per_source: Vec<u32>,
+}
+
+/// Bins records into a summary of BNG `squares`, the square-grid counterpart of
+/// [`crate::summary::to_hex_summary_by_source`]: one row per square any record is
+/// assigned to by `assignment`, with `grid_ref` (`cell_id` for a regular grid),
+/// `pipe_count`, `weighted_count` under [`Weighting::Length`], a `count_<name>`
+/// column per source when `by_source`, and the square as `geometry` in
+/// EPSG:27700.
+///
+/// Squares with fewer than `min_pipe_count` records are dropped. Rows are sorted
+/// by id ascending.
+pub fn to_grid_summary(
+    sources: &[(&str, &[PipelineRecord])],
+    by_source: bool,
+    squares: Squares,
+    assignment: Assignment,
+    weighting: Weighting,
+    min_pipe_count: u32,
+) -> Result<RecordBatch, InfraHexError> {
+    let to_bng = Transform::wgs84_to_bng()?;
+    let mut counts: HashMap<String, SquareCount> = HashMap::new();
+    for (source_idx, (_, records)) in sources.iter().enumerate() {
+        for record in *records {
+            let lines = record_lines(record).ok_or_else(|| {
+                InfraHexError::Geometry(
+                    "Expected a LineString, MultiLineString, Point or MultiPoint".into(),
+                )
+            })?;
+            for (id, square, share) in
+                assigned_squares(&to_bng.apply(&lines)?, squares, assignment)?
+            {
+                let entry = counts.entry(id).or_insert_with(|| SquareCount {
+                    square,
+                    total: 0,
+                    weighted: 0.0,
+                    per_source: vec![0; sources.len()],
+                });
+                entry.total += 1;
+                entry.weighted += share;
+                entry.per_source[source_idx] += 1;
+            }
+        }
+    }
+
+    let mut sorted: Vec<(String, SquareCount)> = counts
+        .into_iter()
+        .filter(|(_, count)| count.total >= min_pipe_count)
+        .collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut fields = vec![
+        Field::new(squares.key().name(), DataType::Utf8, false),
+        Field::new("pipe_count", DataType::UInt32, false),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            sorted.iter().map(|(id, _)| id),
+        )),
+        Arc::new(UInt32Array::from_iter_values(
+            sorted.iter().map(|(_, count)| count.total),
+        )),
+    ];
+    if weighting == Weighting::Length {
+        fields.push(Field::new("weighted_count", DataType::Float64, false));
+        columns.push(Arc::new(Float64Array::from_iter_values(
+            sorted.iter().map(|(_, count)| count.weighted),
+        )));
+    }
+    if by_source {
+        for (source_idx, (name, _)) in sources.iter().enumerate() {
+            fields.push(Field::new(
+                format!("count_{}", name),
+                DataType::UInt32,
+                false,
+            ));
+            columns.push(Arc::new(UInt32Array::from_iter_values(
+                sorted.iter().map(|(_, count)| count.per_source[source_idx]),
+            )));
+        }
+    }
+    let polygons: Vec<Polygon<f64>> = sorted
+        .iter()
+        .map(|(_, count)| count.square.to_polygon())
+        .collect();
+    let (geometry_array, geometry_field) = build_polygon_column(&polygons);
+    fields.push(geometry_field);
+    columns.push(geometry_array);
+
+    RecordBatch::try_new(Arc::new(summary_schema(fields)), columns)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
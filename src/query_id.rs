@@ -0,0 +1,128 @@
+//! Query ids: short deterministic identifiers for the query a summary answers, so
+//! rows from many calls gathered in one table can be traced back to their call.
+//!
+//! A query is described by its parameters as a JSON object (see
+//! [`QueryDescription`]), written out in a canonical form (see [`canonical_json`])
+//! and hashed with [`stable_hash`]. The package version is part of every
+//! description, so ids from different releases never collide.
+
+use std::fmt::Debug;
+
+use geo::MultiPolygon;
+use serde_json::{Map, Number, Value, json};
+
+use crate::fixtures::stable_hash;
+
+/// The parameters of one query, keyed by name.
+///
+/// Option values are recorded as validated rather than as passed, so spellings
+/// that mean the same query (`resolution=11` and `zoom=11`, or the aggregations
+/// of a dict in another order) give the same id.
+#[derive(Debug, Clone)]
+pub struct QueryDescription {
+    params: Map<String, Value>,
+}
+
+impl QueryDescription {
+    /// A description of a `kind` query, e.g. `"bbox"` or `"built_up_area"`.
+    pub fn new(kind: &str) -> Self {
+        let mut params = Map::new();
+        params.insert("kind".to_string(), Value::from(kind));
+        params.insert(
+            "version".to_string(),
+            Value::from(env!("CARGO_PKG_VERSION")),
+        );
+        Self { params }
+    }
+
+    /// Records the parameter `name` as `value`.
+    pub fn with(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.params.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Records the parameter `name` as the `Debug` form of a validated option.
+    pub fn with_option(self, name: &str, value: &impl Debug) -> Self {
+        self.with(name, format!("{:?}", value))
+    }
+
+    /// The id: the [`stable_hash`] of the description's [`canonical_json`].
+    pub fn id(&self) -> String {
+        stable_hash(&canonical_json(&Value::Object(self.params.clone())))
+    }
+}
+
+/// A WGS84 polygon as a query parameter: the `[lon, lat]` coordinates of each ring
+/// of each polygon, exterior first.
+pub fn multipolygon_value(polygon: &MultiPolygon<f64>) -> Value {
+    let ring = |ring: &geo::LineString<f64>| {
+        Value::Array(ring.coords().map(|c| json!([c.x, c.y])).collect())
+    };
+    polygon
+        .iter()
+        .map(|polygon| {
+            std::iter::once(polygon.exterior())
+                .chain(polygon.interiors())
+                .map(ring)
+                .collect::<Value>()
+        })
+        .collect()
+}
+
+/// `value` as canonical JSON: no whitespace, object keys sorted by their UTF-8
+/// bytes and every number written by [`canonical_number`], so equal values give
+/// the same text whichever order they were built in.
+pub fn canonical_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Number(number) => out.push_str(&canonical_number(number)),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(params) => {
+            let mut keys: Vec<&String> = params.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::from(key.as_str()).to_string());
+                out.push(':');
+                write_canonical(&params[key], out);
+            }
+            out.push('}');
+        }
+        Value::Null | Value::Bool(_) | Value::String(_) => out.push_str(&value.to_string()),
+    }
+}
+
+/// Integers as themselves, and floats in the shortest decimal form that reads
+/// back as the same `f64`, without an exponent or a trailing `.0`, which does not
+/// depend on the platform. So `53` and `53.0` agree, as do `0.0` and `-0.0`.
+pub fn canonical_number(number: &Number) -> String {
+    if let Some(value) = number.as_i64() {
+        return value.to_string();
+    }
+    if let Some(value) = number.as_u64() {
+        return value.to_string();
+    }
+    match number.as_f64() {
+        // Matches -0.0 too
+        Some(0.0) => "0".to_string(),
+        Some(value) => value.to_string(),
+        None => number.to_string(),
+    }
+}
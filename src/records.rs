@@ -0,0 +1,779 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::Float64Type;
+use arrow_array::{Array, ArrayRef, Float64Array, RecordBatch, StringArray};
+use arrow_cast::cast;
+use arrow_schema::{DataType, Field, Schema};
+use geo::{
+    BooleanOps, Centroid, CoordsIter, Geometry, LineString, MultiPolygon, RemoveRepeatedPoints,
+    Validation, coord, unary_union,
+};
+use geo_traits::to_geo::ToGeoGeometry;
+use geoarrow_array::builder::WkbBuilder;
+use geoarrow_array::{GeoArrowArray, IntoArrow};
+use geoarrow_schema::{Crs, Metadata, WkbType};
+use geojson::{Feature, GeoJson, Value as GeoJsonValue};
+use infra_hex_rs::{FromGeoJson, GeoPoint2d, InfraHexError, PipelineRecord};
+use serde_json::{Map, Value};
+
+use crate::crs::{SourceCrs, Wgs84Reprojector};
+use crate::fixtures::stable_hash;
+
+/// Pipeline records parsed from a GeoJSON document, plus how many features were
+/// skipped because they had no line (or, when included, point) geometry.
+#[derive(Debug, Default)]
+pub struct ParsedRecords {
+    pub records: Vec<PipelineRecord>,
+    pub skipped: usize,
+    /// CRS named by the document's `crs` member, if it has one.
+    pub source_crs: Option<SourceCrs>,
+}
+
+/// Parses a GeoJSON document (FeatureCollection, Feature or bare geometry) into
+/// pipeline records.
+///
+/// Only LineString and MultiLineString geometries are kept, plus Point and
+/// MultiPoint ones with `include_points`, for point assets such as governors and
+/// valves; anything else is counted in [`ParsedRecords::skipped`]. Cadent
+/// attribute names (`type`,
+/// `pressure`, `material`, `asset_id`, ...) are read from feature properties when
+/// present.
+///
+/// Coordinates are (lon, lat) WGS84 unless the document carries a legacy `crs`
+/// member, as WFS GeoJSON output does; EPSG:27700 and (lat, lon) EPSG:4326 are
+/// then reprojected before binning. Any other declared CRS is an error.
+pub fn records_from_geojson(
+    source: &str,
+    include_points: bool,
+) -> Result<ParsedRecords, InfraHexError> {
+    let geojson: GeoJson = source
+        .parse()
+        .map_err(|e| InfraHexError::Geometry(format!("Invalid GeoJSON: {}", e)))?;
+
+    let (features, crs) = match geojson {
+        GeoJson::FeatureCollection(fc) => {
+            let crs = declared_crs(fc.foreign_members.as_ref())?;
+            (fc.features, crs)
+        }
+        GeoJson::Feature(feature) => {
+            let crs = declared_crs(feature.foreign_members.as_ref())?;
+            (vec![feature], crs)
+        }
+        GeoJson::Geometry(geometry) => {
+            let crs = declared_crs(geometry.foreign_members.as_ref())?;
+            let feature = Feature {
+                geometry: Some(geometry),
+                ..Default::default()
+            };
+            (vec![feature], crs)
+        }
+    };
+
+    let mut parsed = ParsedRecords {
+        source_crs: crs,
+        ..Default::default()
+    };
+    let mut reprojector = Wgs84Reprojector::default();
+    for mut feature in features {
+        if let (Some(crs), Some(geometry)) = (crs, feature.geometry.as_mut()) {
+            reproject_geometry(geometry, crs, &mut reprojector)?;
+        }
+        match record_from_feature(feature, include_points) {
+            Some(record) => parsed.records.push(record),
+            None => parsed.skipped += 1,
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Reads the CRS named by a legacy GeoJSON `crs` member
+/// (`{"type": "name", "properties": {"name": ...}}`).
+fn declared_crs(
+    foreign_members: Option<&Map<String, Value>>,
+) -> Result<Option<SourceCrs>, InfraHexError> {
+    let Some(crs) = foreign_members.and_then(|members| members.get("crs")) else {
+        return Ok(None);
+    };
+    let name = crs
+        .pointer("/properties/name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| {
+            InfraHexError::Config("GeoJSON crs member has no properties.name".to_string())
+        })?;
+
+    SourceCrs::from_srs_name(name).map(Some)
+}
+
+/// Replaces `geometry` with its WGS84 (lon, lat) equivalent.
+fn reproject_geometry(
+    geometry: &mut geojson::Geometry,
+    crs: SourceCrs,
+    reprojector: &mut Wgs84Reprojector,
+) -> Result<(), InfraHexError> {
+    if crs == SourceCrs::Wgs84 {
+        return Ok(());
+    }
+    let source = Geometry::<f64>::try_from(geometry.clone())
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+    *geometry = geojson::Geometry::new(GeoJsonValue::from(&reprojector.apply(&source, crs)?));
+    Ok(())
+}
+
+/// Reprojects records whose line is not in WGS84 (lon, lat).
+///
+/// Cadent's responses do not name a CRS, so each record's is inferred with
+/// [`SourceCrs::detect`] against its named `geo_point_2d`. Returns how many
+/// records were reprojected from each CRS.
+pub fn normalise_record_crs(
+    records: &mut [PipelineRecord],
+) -> Result<HashMap<SourceCrs, usize>, InfraHexError> {
+    let mut reprojector = Wgs84Reprojector::default();
+    let mut reprojected = HashMap::new();
+    for record in records {
+        let Some(geometry) = record.geo_shape.geometry.as_mut() else {
+            continue;
+        };
+        let Ok(line) = Geometry::<f64>::try_from(geometry.clone()) else {
+            continue;
+        };
+
+        let reference = coord! { x: record.geo_point_2d.lon, y: record.geo_point_2d.lat };
+        let crs = SourceCrs::detect(line.coords_iter(), reference);
+        if crs != SourceCrs::Wgs84 {
+            reproject_geometry(geometry, crs, &mut reprojector)?;
+            *reprojected.entry(crs).or_insert(0) += 1;
+        }
+    }
+    Ok(reprojected)
+}
+
+/// Drops the Z and M values of records whose line or point carries them, so they
+/// are binned like any 2D geometry. Returns how many records were coerced.
+pub fn flatten_record_dimensions(records: &mut [PipelineRecord]) -> usize {
+    records
+        .iter_mut()
+        .filter_map(|record| record.geo_shape.geometry.as_mut())
+        .map(|geometry| match &mut geometry.value {
+            GeoJsonValue::Point(point) => flatten_positions(std::iter::once(point)),
+            GeoJsonValue::MultiPoint(points) | GeoJsonValue::LineString(points) => {
+                flatten_positions(points.iter_mut())
+            }
+            GeoJsonValue::MultiLineString(lines) => flatten_positions(lines.iter_mut().flatten()),
+            _ => false,
+        })
+        .filter(|flattened| *flattened)
+        .count()
+}
+
+/// Truncates each position to (x, y); true if any had more values.
+fn flatten_positions<'a>(positions: impl Iterator<Item = &'a mut geojson::Position>) -> bool {
+    let mut flattened = false;
+    for position in positions.filter(|position| position.len() > 2) {
+        position.truncate(2);
+        flattened = true;
+    }
+    flattened
+}
+
+/// Decodes a WKB geometry column into pipeline records.
+///
+/// Null rows are skipped. Rows that are not LineString or MultiLineString (or whose
+/// WKB cannot be decoded) are an error unless `skip_invalid` is set, in which case
+/// they are counted in [`ParsedRecords::skipped`] along with the nulls.
+pub fn records_from_wkb(
+    column: &dyn Array,
+    skip_invalid: bool,
+) -> Result<ParsedRecords, InfraHexError> {
+    decode_wkb_records(column, skip_invalid, false, |_| None)
+}
+
+/// Attribute columns written by [`records_to_batch`], with the Cadent property
+/// name each is read back into.
+const RECORD_STRING_COLUMNS: [(&str, &str); 9] = [
+    ("asset_id", "asset_id"),
+    ("pipe_type", "type"),
+    ("pressure", "pressure"),
+    ("material", "material"),
+    ("diam_unit", "diam_unit"),
+    ("carr_mat", "carr_mat"),
+    ("carr_di_un", "carr_di_un"),
+    ("ag_ind", "ag_ind"),
+    ("inst_date", "inst_date"),
+];
+const RECORD_NUMBER_COLUMNS: [&str; 3] = ["diameter", "carr_dia", "depth"];
+
+/// Cadent field names of the attributes a [`PipelineRecord`] carries, in the
+/// column order of [`records_to_batch`].
+pub const RECORD_FIELDS: [&str; 12] = [
+    "asset_id",
+    "type",
+    "pressure",
+    "material",
+    "diameter",
+    "diam_unit",
+    "carr_mat",
+    "carr_dia",
+    "carr_di_un",
+    "depth",
+    "ag_ind",
+    "inst_date",
+];
+
+/// The [`records_to_batch`] column holding Cadent field `field`, or `None` if
+/// pipe records do not carry it.
+pub fn record_column_name(field: &str) -> Option<&'static str> {
+    RECORD_STRING_COLUMNS
+        .iter()
+        .find(|(_, property)| *property == field)
+        .map(|(column, _)| *column)
+        .or_else(|| RECORD_NUMBER_COLUMNS.iter().find(|c| **c == field).copied())
+}
+
+/// A record attribute, as [`record_value`] reads it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordValue<'a> {
+    Text(&'a str),
+    Number(f64),
+}
+
+/// Whether Cadent field `field` holds numbers (`diameter`, `carr_dia`, `depth`).
+pub fn is_number_field(field: &str) -> bool {
+    RECORD_NUMBER_COLUMNS.contains(&field)
+}
+
+/// The value of Cadent field `field` (one of [`RECORD_FIELDS`]) of `record`, or
+/// `None` if it is null or not a field records carry.
+pub fn record_value<'a>(record: &'a PipelineRecord, field: &str) -> Option<RecordValue<'a>> {
+    let text = match field {
+        "diameter" => return record.diameter.map(RecordValue::Number),
+        "carr_dia" => return record.carr_dia.map(RecordValue::Number),
+        "depth" => return record.depth.map(RecordValue::Number),
+        "asset_id" => &record.asset_id,
+        "type" => &record.pipe_type,
+        "pressure" => &record.pressure,
+        "material" => &record.material,
+        "diam_unit" => &record.diam_unit,
+        "carr_mat" => &record.carr_mat,
+        "carr_di_un" => &record.carr_di_un,
+        "ag_ind" => &record.ag_ind,
+        "inst_date" => &record.inst_date,
+        _ => return None,
+    };
+    text.as_deref().map(RecordValue::Text)
+}
+
+/// Keeps only the columns of `fields` (Cadent field names, see
+/// [`record_column_name`]), in the order given, and `geometry` of a batch built
+/// by [`records_to_batch`].
+///
+/// # Errors
+/// Returns [`InfraHexError::Config`] for a field pipe records do not carry.
+pub fn select_record_fields(
+    batch: &RecordBatch,
+    fields: &[String],
+) -> Result<RecordBatch, InfraHexError> {
+    let schema = batch.schema();
+    let indices = fields
+        .iter()
+        .map(|field| {
+            record_column_name(field).ok_or_else(|| {
+                InfraHexError::Config(format!(
+                    "Pipe records do not carry field {:?}; they carry: {}",
+                    field,
+                    RECORD_FIELDS.join(", ")
+                ))
+            })
+        })
+        .chain(std::iter::once(Ok("geometry")))
+        .map(|column| {
+            let column = column?;
+            schema
+                .index_of(column)
+                .map_err(|e| InfraHexError::Geometry(e.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    batch
+        .project(&indices)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Decodes a batch of pipe records, as written by [`records_to_batch`], back into
+/// pipeline records.
+///
+/// Only the WKB `geometry` column is required. Whichever attribute columns are
+/// present (`asset_id`, `material`, ...) are carried over, so deduplication and
+/// `count_mode` treat the records exactly as if they had just been fetched.
+/// String attributes may be any Arrow string type and numeric ones any numeric
+/// type. Null and invalid geometries are handled as in [`records_from_wkb`], except
+/// that Point and MultiPoint rows are also decoded when `include_points` is set.
+/// A column named by `id_field` other than those is carried over too, as a
+/// feature property, so [`IdField::id`] can find it.
+pub fn records_from_batch(
+    batch: &RecordBatch,
+    skip_invalid: bool,
+    include_points: bool,
+    id_field: &IdField,
+) -> Result<ParsedRecords, InfraHexError> {
+    let schema = batch.schema();
+    let column = |name: &str, data_type: &DataType| {
+        schema
+            .index_of(name)
+            .ok()
+            .map(|index| {
+                cast(batch.column(index), data_type)
+                    .map_err(|e| InfraHexError::Geometry(format!("Column {:?}: {}", name, e)))
+            })
+            .transpose()
+    };
+
+    let mut strings = Vec::new();
+    for (name, property) in RECORD_STRING_COLUMNS {
+        if let Some(array) = column(name, &DataType::Utf8)? {
+            strings.push((property, array));
+        }
+    }
+    let mut numbers = Vec::new();
+    for name in RECORD_NUMBER_COLUMNS {
+        if let Some(array) = column(name, &DataType::Float64)? {
+            numbers.push((name, array));
+        }
+    }
+    if record_column_name(id_field.name()).is_none()
+        && let Some(array) = column(id_field.name(), &DataType::Utf8)?
+    {
+        strings.push((id_field.name(), array));
+    }
+
+    let geometry = schema
+        .index_of("geometry")
+        .map_err(|_| InfraHexError::Geometry("Geometry column \"geometry\" not found".into()))?;
+
+    decode_wkb_records(
+        batch.column(geometry),
+        skip_invalid,
+        include_points,
+        |row| {
+            let mut properties = Map::new();
+            for (property, array) in &strings {
+                let array = array.as_string::<i32>();
+                if array.is_valid(row) {
+                    properties.insert(property.to_string(), array.value(row).into());
+                }
+            }
+            for (property, array) in &numbers {
+                let array = array.as_primitive::<Float64Type>();
+                if array.is_valid(row) {
+                    properties.insert(property.to_string(), array.value(row).into());
+                }
+            }
+            Some(properties)
+        },
+    )
+}
+
+/// Decodes a WKB column into records, taking each row's feature properties from
+/// `properties`.
+fn decode_wkb_records(
+    column: &dyn Array,
+    skip_invalid: bool,
+    include_points: bool,
+    properties: impl Fn(usize) -> Option<Map<String, Value>>,
+) -> Result<ParsedRecords, InfraHexError> {
+    let values: Vec<Option<&[u8]>> = match column.data_type() {
+        DataType::Binary => column.as_binary::<i32>().iter().collect(),
+        DataType::LargeBinary => column.as_binary::<i64>().iter().collect(),
+        DataType::BinaryView => column.as_binary_view().iter().collect(),
+        other => {
+            return Err(InfraHexError::Geometry(format!(
+                "Expected a binary WKB column, got {}",
+                other
+            )));
+        }
+    };
+
+    let mut parsed = ParsedRecords::default();
+    for (row, value) in values.into_iter().enumerate() {
+        let Some(bytes) = value else {
+            parsed.skipped += 1;
+            continue;
+        };
+
+        let record = match line_from_wkb(bytes, include_points) {
+            Ok(geometry) => record_from_feature(
+                Feature {
+                    geometry: Some(geojson::Geometry::new(GeoJsonValue::from(&geometry))),
+                    properties: properties(row),
+                    ..Default::default()
+                },
+                include_points,
+            ),
+            Err(_) if skip_invalid => None,
+            Err(e) => {
+                return Err(InfraHexError::Geometry(format!("Row {}: {}", row, e)));
+            }
+        };
+
+        match record {
+            Some(record) => parsed.records.push(record),
+            None => parsed.skipped += 1,
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Decodes a single WKB value, requiring a LineString or MultiLineString, or also a
+/// Point or MultiPoint with `include_points`.
+fn line_from_wkb(bytes: &[u8], include_points: bool) -> Result<Geometry<f64>, String> {
+    let wkb = wkb::reader::read_wkb(bytes).map_err(|e| format!("Invalid WKB: {}", e))?;
+    match wkb.try_to_geometry() {
+        Some(geometry @ (Geometry::LineString(_) | Geometry::MultiLineString(_))) => Ok(geometry),
+        Some(geometry @ (Geometry::Point(_) | Geometry::MultiPoint(_))) if include_points => {
+            Ok(geometry)
+        }
+        Some(other) => Err(format!(
+            "Expected LineString or MultiLineString, got {}",
+            geometry_name(&other)
+        )),
+        None => Err("Empty geometry".to_string()),
+    }
+}
+
+fn geometry_name(geometry: &Geometry<f64>) -> &'static str {
+    match geometry {
+        Geometry::Point(_) => "Point",
+        Geometry::Line(_) => "Line",
+        Geometry::LineString(_) => "LineString",
+        Geometry::Polygon(_) => "Polygon",
+        Geometry::MultiPoint(_) => "MultiPoint",
+        Geometry::MultiLineString(_) => "MultiLineString",
+        Geometry::MultiPolygon(_) => "MultiPolygon",
+        Geometry::GeometryCollection(_) => "GeometryCollection",
+        Geometry::Rect(_) => "Rect",
+        Geometry::Triangle(_) => "Triangle",
+    }
+}
+
+/// Prefix of the ids [`IdField::id`] hashes from the geometry of records without
+/// the id attribute.
+pub const GEOMETRY_ID_PREFIX: &str = "geometry-";
+
+/// The record attribute holding each pipe's stable id, used to deduplicate
+/// records and as `pipe_id`.
+///
+/// Cadent's is `asset_id` (the default). Any other of [`RECORD_FIELDS`] can be
+/// named, as can a property of GeoJSON features or a column of a records table
+/// (see [`records_from_batch`]), for data from networks that name it otherwise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdField(String);
+
+impl Default for IdField {
+    fn default() -> Self {
+        Self("asset_id".to_string())
+    }
+}
+
+impl IdField {
+    /// # Errors
+    /// Returns [`InfraHexError::Config`] for an empty or `geometry` name.
+    pub fn new(name: &str) -> Result<Self, InfraHexError> {
+        if name.is_empty() || name == "geometry" {
+            return Err(InfraHexError::Config(format!(
+                "id_field must name an attribute, got {:?}",
+                name
+            )));
+        }
+        Ok(Self(name.to_string()))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// The value of the attribute for `record`, as text, or `None` if it is null
+    /// or missing.
+    fn value(&self, record: &PipelineRecord) -> Option<String> {
+        match record_value(record, &self.0) {
+            Some(RecordValue::Text(text)) => return Some(text.to_string()),
+            Some(RecordValue::Number(number)) => return Some(number.to_string()),
+            None if RECORD_FIELDS.contains(&self.0.as_str()) => return None,
+            None => {}
+        }
+        match record.geo_shape.properties.as_ref()?.get(&self.0)? {
+            Value::String(text) => Some(text.clone()),
+            Value::Number(number) => Some(number.to_string()),
+            _ => None,
+        }
+    }
+
+    /// The id of `record`: its value of the attribute, or for a record without
+    /// one, [`GEOMETRY_ID_PREFIX`] followed by a stable hash of its geometry, so
+    /// that repeats of an unidentified pipe still share an id.
+    pub fn id(&self, record: &PipelineRecord) -> String {
+        self.value(record).unwrap_or_else(|| {
+            let geometry = record
+                .geo_shape
+                .geometry
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default();
+            format!("{}{}", GEOMETRY_ID_PREFIX, stable_hash(&geometry))
+        })
+    }
+
+    /// Schema metadata naming the attribute under `id_field`, and counting the
+    /// `records` without it, identified by geometry instead, under
+    /// `geometry_id_records` if there are any.
+    pub fn metadata<'a>(
+        &self,
+        records: impl IntoIterator<Item = &'a PipelineRecord>,
+    ) -> Vec<(String, String)> {
+        let unidentified = records
+            .into_iter()
+            .filter(|record| self.value(record).is_none())
+            .count();
+        std::iter::once(("id_field".to_string(), self.0.clone()))
+            .chain(
+                (unidentified > 0)
+                    .then(|| ("geometry_id_records".to_string(), unidentified.to_string())),
+            )
+            .collect()
+    }
+}
+
+/// Removes repeated records with the same id under `id_field` (see
+/// [`IdField::id`]), keeping the first.
+///
+/// Overlapping pages or tiles can return the same asset more than once. Records
+/// without the id attribute are only removed as repeats of one with the same
+/// geometry. Returns the number of records removed.
+pub fn deduplicate_records(records: &mut Vec<PipelineRecord>, id_field: &IdField) -> usize {
+    let before = records.len();
+    let mut seen = HashSet::new();
+    records.retain(|record| seen.insert(id_field.id(record)));
+    before - records.len()
+}
+
+/// Copies a record; the upstream [`PipelineRecord`] does not implement `Clone`.
+pub fn clone_record(record: &PipelineRecord) -> PipelineRecord {
+    PipelineRecord {
+        geo_point_2d: record.geo_point_2d,
+        geo_shape: record.geo_shape.clone(),
+        pipe_type: record.pipe_type.clone(),
+        pressure: record.pressure.clone(),
+        material: record.material.clone(),
+        diameter: record.diameter,
+        diam_unit: record.diam_unit.clone(),
+        carr_mat: record.carr_mat.clone(),
+        carr_dia: record.carr_dia,
+        carr_di_un: record.carr_di_un.clone(),
+        asset_id: record.asset_id.clone(),
+        depth: record.depth,
+        ag_ind: record.ag_ind.clone(),
+        inst_date: record.inst_date.clone(),
+    }
+}
+
+/// Builds one row per record: its attributes plus the pipe line as a
+/// `geoarrow.wkb` column in EPSG:4326.
+///
+/// The batch can be fed straight back into `hex_summary_from_records`, or its
+/// geometry column into `hex_summary_from_arrow`.
+pub fn records_to_batch(records: &[PipelineRecord]) -> Result<RecordBatch, InfraHexError> {
+    let strings = |get: fn(&PipelineRecord) -> &Option<String>| -> ArrayRef {
+        Arc::new(
+            records
+                .iter()
+                .map(|r| get(r).as_deref())
+                .collect::<StringArray>(),
+        )
+    };
+    let numbers = |get: fn(&PipelineRecord) -> Option<f64>| -> ArrayRef {
+        Arc::new(records.iter().map(get).collect::<Float64Array>())
+    };
+
+    let (geometry, geometry_field) = records_geometry_column(records)?;
+
+    let columns: Vec<(&str, ArrayRef)> = vec![
+        ("asset_id", strings(|r| &r.asset_id)),
+        ("pipe_type", strings(|r| &r.pipe_type)),
+        ("pressure", strings(|r| &r.pressure)),
+        ("material", strings(|r| &r.material)),
+        ("diameter", numbers(|r| r.diameter)),
+        ("diam_unit", strings(|r| &r.diam_unit)),
+        ("carr_mat", strings(|r| &r.carr_mat)),
+        ("carr_dia", numbers(|r| r.carr_dia)),
+        ("carr_di_un", strings(|r| &r.carr_di_un)),
+        ("depth", numbers(|r| r.depth)),
+        ("ag_ind", strings(|r| &r.ag_ind)),
+        ("inst_date", strings(|r| &r.inst_date)),
+    ];
+    let mut fields: Vec<Field> = columns
+        .iter()
+        .map(|(name, array)| Field::new(*name, array.data_type().clone(), true))
+        .collect();
+    let mut arrays: Vec<ArrayRef> = columns.into_iter().map(|(_, array)| array).collect();
+    fields.push(geometry_field);
+    arrays.push(geometry);
+
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// The pipe lines of `records` as a nullable `geoarrow.wkb` column named
+/// `geometry`, in EPSG:4326.
+pub fn records_geometry_column<'a>(
+    records: impl IntoIterator<Item = &'a PipelineRecord>,
+) -> Result<(ArrayRef, Field), InfraHexError> {
+    let mut geometry = WkbBuilder::<i32>::new(WkbType::new(Arc::new(Metadata::new(
+        Crs::from_authority_code("EPSG:4326".to_string()),
+        None,
+    ))));
+    for record in records {
+        let line = record
+            .geo_shape
+            .geometry
+            .clone()
+            .and_then(|g| Geometry::<f64>::try_from(g).ok());
+        geometry
+            .push_geometry(line.as_ref())
+            .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+    }
+    let geometry = geometry.finish();
+    let field = geometry.data_type().to_field("geometry", true);
+    Ok((Arc::new(geometry.into_arrow()), field))
+}
+
+/// Converts a feature into a [`PipelineRecord`], or `None` if it isn't a line
+/// (or, with `include_points`, a point).
+pub fn record_from_feature(feature: Feature, include_points: bool) -> Option<PipelineRecord> {
+    let geometry = feature.geometry.as_ref()?;
+    let point = match geometry.value {
+        GeoJsonValue::LineString(_) | GeoJsonValue::MultiLineString(_) => {
+            let line = LineString::from_geojson(geometry).ok()?;
+            line.centroid().or_else(|| line.points().next())?
+        }
+        GeoJsonValue::Point(_) | GeoJsonValue::MultiPoint(_) if include_points => {
+            Geometry::<f64>::try_from(geometry.clone())
+                .ok()?
+                .centroid()?
+        }
+        _ => return None,
+    };
+
+    let empty = Map::new();
+    let properties = feature.properties.as_ref().unwrap_or(&empty);
+    let string = |key: &str| {
+        properties
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    let number = |key: &str| properties.get(key).and_then(Value::as_f64);
+
+    Some(PipelineRecord {
+        geo_point_2d: GeoPoint2d {
+            lon: point.x(),
+            lat: point.y(),
+        },
+        pipe_type: string("type"),
+        pressure: string("pressure"),
+        material: string("material"),
+        diameter: number("diameter"),
+        diam_unit: string("diam_unit"),
+        carr_mat: string("carr_mat"),
+        carr_dia: number("carr_dia"),
+        carr_di_un: string("carr_di_un"),
+        asset_id: string("asset_id"),
+        depth: number("depth"),
+        ag_ind: string("ag_ind"),
+        inst_date: string("inst_date"),
+        geo_shape: feature,
+    })
+}
+
+/// Parses a clip polygon from a GeoJSON geometry, Feature or single-feature
+/// FeatureCollection, without repairing it (see [`repair_clip_polygon`]).
+pub fn multipolygon_from_geojson(source: &str) -> Result<MultiPolygon<f64>, InfraHexError> {
+    source
+        .parse()
+        .map_err(|e| InfraHexError::Geometry(format!("Invalid clip GeoJSON: {}", e)))
+        .and_then(multipolygon_from_geojson_object)
+}
+
+/// As [`multipolygon_from_geojson`], for GeoJSON already parsed, e.g. from a
+/// `__geo_interface__` mapping.
+pub fn multipolygon_from_geojson_value(value: Value) -> Result<MultiPolygon<f64>, InfraHexError> {
+    GeoJson::from_json_value(value)
+        .map_err(|e| InfraHexError::Geometry(format!("Invalid clip GeoJSON: {}", e)))
+        .and_then(multipolygon_from_geojson_object)
+}
+
+fn multipolygon_from_geojson_object(geojson: GeoJson) -> Result<MultiPolygon<f64>, InfraHexError> {
+    let geometry = match geojson {
+        GeoJson::Geometry(geometry) => Some(geometry),
+        GeoJson::Feature(feature) => feature.geometry,
+        GeoJson::FeatureCollection(fc) => match fc.features.len() {
+            1 => fc.features.into_iter().next().and_then(|f| f.geometry),
+            n => {
+                return Err(InfraHexError::Geometry(format!(
+                    "Clip FeatureCollection must contain exactly one feature, got {}",
+                    n
+                )));
+            }
+        },
+    };
+
+    let geometry =
+        geometry.ok_or_else(|| InfraHexError::Geometry("Clip feature has no geometry".into()))?;
+    match geometry.value {
+        GeoJsonValue::Polygon(_) | GeoJsonValue::MultiPolygon(_) => {
+            MultiPolygon::from_geojson(&geometry)
+        }
+        other => Err(InfraHexError::Geometry(format!(
+            "Clip geometry must be a Polygon or MultiPolygon, got {}",
+            other.type_name()
+        ))),
+    }
+}
+
+/// Repairs self-intersecting rings and repeated vertices in a clip polygon.
+///
+/// Valid input is returned unchanged. Otherwise each polygon is re-noded with the
+/// even-odd rule (the equivalent of `buffer(0)`) and the pieces are unioned back
+/// together. `source` names the geometry in the error raised if nothing valid is
+/// left.
+pub fn repair_clip_polygon(
+    geometry: MultiPolygon<f64>,
+    source: &str,
+) -> Result<MultiPolygon<f64>, InfraHexError> {
+    if geometry.is_valid() {
+        return Ok(geometry);
+    }
+    log::debug!("{} is not a valid polygon; repairing", source);
+
+    let empty = MultiPolygon::<f64>::new(vec![]);
+    let pieces: Vec<MultiPolygon<f64>> = geometry
+        .remove_repeated_points()
+        .iter()
+        .map(|polygon| polygon.union(&empty))
+        .collect();
+    let repaired = unary_union(&pieces);
+
+    if repaired.0.is_empty() {
+        return Err(InfraHexError::Geometry(format!(
+            "{} is empty after repairing invalid rings",
+            source
+        )));
+    }
+    if !repaired.is_valid() {
+        return Err(InfraHexError::Geometry(format!(
+            "{} is not a valid polygon and could not be repaired",
+            source
+        )));
+    }
+    Ok(repaired)
+}
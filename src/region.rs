@@ -0,0 +1,467 @@
+use std::ffi::CString;
+use std::sync::Arc;
+use std::time::Instant;
+
+use arrow_array::{
+    ArrayRef, Float64Array, RecordBatch, StringArray, StructArray, UInt16Array, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Fields, Schema};
+use infra_hex_rs::{BBox, InfraHexError, PipelineRecord};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::bbox::resolve_bbox;
+use crate::client::{CadentLayer, FailedPage, FeatureBudget, RecordQuery, TransferStats};
+use crate::dataframe::OutputFormat;
+use crate::errors::{fetch_error, warn_missing_records};
+use crate::network::Network;
+use crate::records::{clone_record, deduplicate_records, records_to_batch, select_record_fields};
+use crate::summary::{
+    Containment, clip_hex_summary_to_bbox, conform_hex_summary, to_hex_summary, with_aggregations,
+    with_boundary_pipe_counts, with_count_mode, with_geometry_options, with_metadata,
+    with_metadata_columns, with_parent_hex_ids, with_weighting,
+};
+use crate::{
+    LayerSelection, check_tiling, extract_aggregations, extract_assignment, extract_count_mode,
+    extract_geometry_options, extract_http_options, extract_layer, extract_network,
+    extract_parent_zoom, extract_record_fields, extract_resolution, extract_summary_output,
+    extract_weighting, runtime,
+};
+
+/// Raw Cadent records for a region, downloaded once and re-aggregated locally.
+///
+/// `hex_summary` and `to_pipe_records` only read the cached records, so summaries
+/// at different resolutions or for different materials never refetch.
+#[pyclass(name = "RegionCache", module = "infra_hex_py", frozen)]
+pub struct PyRegionCache {
+    bbox: BBox,
+    network: Network,
+    layer: CadentLayer,
+    query: RecordQuery,
+    deduplicate: bool,
+    records: Vec<PipelineRecord>,
+    /// The parts of the fetch that failed, with `allow_partial=True`.
+    failed: Vec<FailedPage>,
+}
+
+impl PyRegionCache {
+    /// The Cadent fields fetched, or `None` for every field.
+    fn fields(&self) -> Option<&Vec<String>> {
+        self.query.fields.as_ref()
+    }
+}
+
+/// Emits a `UserWarning` that `failed` parts of a fetch are missing from a cache.
+fn warn_failed_pages(py: Python<'_>, failed: usize) -> PyResult<()> {
+    let message = format!(
+        "{} part(s) of the fetch failed and are missing from the cache; see \
+         failed_pages, and call refetch() to fetch just those",
+        failed
+    );
+    PyErr::warn(
+        py,
+        py.get_type::<pyo3::exceptions::PyUserWarning>().as_any(),
+        &CString::new(message)?,
+        1,
+    )
+}
+
+/// `page` as the dict `failed_pages` lists and `refetch` accepts.
+fn failed_page_dict<'py>(py: Python<'py>, page: &FailedPage) -> PyResult<Bound<'py, PyDict>> {
+    let bbox = page.bbox();
+    let dict = PyDict::new(py);
+    dict.set_item(
+        "bbox",
+        (bbox.min_lat, bbox.min_lon, bbox.max_lat, bbox.max_lon),
+    )?;
+    dict.set_item("offset", page.offset())?;
+    dict.set_item("limit", page.limit())?;
+    Ok(dict)
+}
+
+/// `failed` as the RecordBatch `fetch_errors` returns: one row per failed part,
+/// with its `bbox` (a struct of the four corners), `offset` and `limit` as in
+/// `failed_pages`, and the `error_kind`, `message` and `http_status` of its
+/// failure as in `FetchError.errors`.
+fn failed_pages_batch(failed: &[FailedPage]) -> Result<RecordBatch, InfraHexError> {
+    let corner = |value: fn(&BBox) -> f64| -> ArrayRef {
+        Arc::new(Float64Array::from_iter_values(
+            failed.iter().map(|page| value(page.bbox())),
+        ))
+    };
+    let corners = Fields::from(
+        ["min_lat", "min_lon", "max_lat", "max_lon"]
+            .map(|name| Field::new(name, DataType::Float64, false))
+            .to_vec(),
+    );
+    let bbox = StructArray::try_new(
+        corners.clone(),
+        vec![
+            corner(|bbox| bbox.min_lat),
+            corner(|bbox| bbox.min_lon),
+            corner(|bbox| bbox.max_lat),
+            corner(|bbox| bbox.max_lon),
+        ],
+        None,
+    )
+    .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+    let offsets: UInt64Array = failed
+        .iter()
+        .map(|page| page.offset().map(|offset| offset as u64))
+        .collect();
+    let limits: UInt64Array = failed
+        .iter()
+        .map(|page| page.limit().map(|limit| limit as u64))
+        .collect();
+    let kinds = StringArray::from_iter_values(failed.iter().map(|page| page.error().kind));
+    let messages =
+        StringArray::from_iter_values(failed.iter().map(|page| page.error().message.as_str()));
+    let statuses: UInt16Array = failed.iter().map(|page| page.error().status).collect();
+
+    let schema = Schema::new(vec![
+        Field::new("bbox", DataType::Struct(corners), false),
+        Field::new("offset", DataType::UInt64, true),
+        Field::new("limit", DataType::UInt64, true),
+        Field::new("error_kind", DataType::Utf8, false),
+        Field::new("message", DataType::Utf8, false),
+        Field::new("http_status", DataType::UInt16, true),
+    ]);
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(bbox),
+            Arc::new(offsets),
+            Arc::new(limits),
+            Arc::new(kinds),
+            Arc::new(messages),
+            Arc::new(statuses),
+        ],
+    )
+    .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+#[pymethods]
+impl PyRegionCache {
+    /// Fetch and cache every record within a WGS84 bounding box (four corner floats
+    /// or a `BBox`).
+    ///
+    /// `fields` (Cadent field names such as `"material"` or `"type"`) has Cadent
+    /// return only those attributes, and `to_pipe_records()` only their columns
+    /// and `geometry`. A field the layer does not have raises `ValueError` listing
+    /// those it does. `None` fetches every field.
+    ///
+    /// A fetch with failed pages raises `FetchError`. With `allow_partial=True`
+    /// the cache keeps the records that did arrive instead, with a `UserWarning`,
+    /// and lists what is missing in `failed_pages` for `refetch` to repair.
+    #[staticmethod]
+    #[pyo3(signature = (min_lat, min_lon=None, max_lat=None, max_lon=None, *, layer="gas_pipes", network=None, deduplicate=true, http_options=None, fields=None, allow_partial=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn from_bbox(
+        py: Python<'_>,
+        min_lat: &Bound<'_, PyAny>,
+        min_lon: Option<&Bound<'_, PyAny>>,
+        max_lat: Option<&Bound<'_, PyAny>>,
+        max_lon: Option<&Bound<'_, PyAny>>,
+        layer: &str,
+        network: Option<&str>,
+        deduplicate: bool,
+        http_options: Option<&Bound<'_, PyDict>>,
+        fields: Option<Vec<String>>,
+        allow_partial: bool,
+    ) -> PyResult<Self> {
+        let layer = extract_layer(layer)?;
+        let network = extract_network(network)?;
+        let http_options = extract_http_options(http_options)?;
+        let query = RecordQuery {
+            fields: extract_record_fields(
+                py,
+                fields,
+                network,
+                &LayerSelection::Single(layer),
+                &http_options,
+            )?,
+            ..RecordQuery::default()
+        };
+        let bbox = resolve_bbox(min_lat, min_lon, max_lat, max_lon)?;
+        check_tiling(&http_options, &bbox)?;
+
+        let runtime = runtime()?;
+        let stats = TransferStats::default();
+        let started = Instant::now();
+        let mut result = runtime
+            .block_on(network.fetch_layer_by_bbox(
+                layer,
+                &bbox,
+                &query,
+                &http_options,
+                &stats,
+                &FeatureBudget::default(),
+            ))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let failed = stats.failed_pages();
+        // Errors without a failed page to refetch, such as an unpageable bbox,
+        // cannot be repaired
+        if !result.errors.is_empty() && (!allow_partial || result.errors.len() > failed.len()) {
+            return Err(fetch_error(py, &result.errors, started.elapsed()));
+        }
+        if !failed.is_empty() {
+            warn_failed_pages(py, failed.len())?;
+        }
+        warn_missing_records(py, &stats)?;
+        if deduplicate {
+            deduplicate_records(&mut result.records, &network.id_field());
+        }
+
+        Ok(Self {
+            bbox,
+            network,
+            layer,
+            query,
+            deduplicate,
+            records: result.records,
+            failed,
+        })
+    }
+
+    /// The parts of a fetch with `allow_partial=True` that failed, as dicts of
+    /// `bbox` (the `(min_lat, min_lon, max_lat, max_lon)` queried), `offset` and
+    /// `limit` (the failed page, or None if the whole bbox failed). Empty for a
+    /// complete cache.
+    #[getter]
+    fn failed_pages<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.failed
+            .iter()
+            .map(|page| failed_page_dict(py, page))
+            .collect()
+    }
+
+    /// The parts of a fetch with `allow_partial=True` that failed, with why, as a
+    /// PyArrow RecordBatch (an `ArrowBatch` if pyarrow is not installed) for
+    /// logging or monitoring tables: one row per entry of `failed_pages`, with
+    /// columns `bbox` (a struct of `min_lat`, `min_lon`, `max_lat` and `max_lon`),
+    /// `offset`, `limit`, `error_kind` and `message` (the `kind` and `message` of
+    /// `FetchError.errors`) and `http_status` (null without an HTTP response).
+    /// Zero rows for a complete cache.
+    fn fetch_errors(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let batch = failed_pages_batch(&self.failed)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        OutputFormat::Arrow.to_python(py, batch)
+    }
+
+    /// Fetch again just the `failed` entries of `failed_pages` (all of them by
+    /// default), returning a new cache with the recovered records added, so
+    /// summaries can be recomputed without a full refetch.
+    ///
+    /// The same layer, fields and deduplication are used as for this cache, and
+    /// `http_options` is as for `from_bbox`. Parts that fail again stay in the new
+    /// cache's `failed_pages`, with a `UserWarning`.
+    ///
+    /// # Errors
+    /// Raises `ValueError` if an entry of `failed` is not in `failed_pages`.
+    #[pyo3(signature = (failed=None, *, http_options=None))]
+    fn refetch(
+        &self,
+        py: Python<'_>,
+        failed: Option<Vec<Bound<'_, PyDict>>>,
+        http_options: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Self> {
+        let http_options = extract_http_options(http_options)?;
+        let (retry, kept): (Vec<FailedPage>, Vec<FailedPage>) = match failed {
+            None => (self.failed.clone(), Vec::new()),
+            Some(requested) => {
+                let mut chosen = vec![false; self.failed.len()];
+                for dict in &requested {
+                    let index = self
+                        .failed
+                        .iter()
+                        .map(|page| failed_page_dict(py, page))
+                        .position(|page| page.is_ok_and(|page| page.eq(dict).unwrap_or(false)))
+                        .ok_or_else(|| {
+                            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                                "{} is not one of this cache's failed_pages",
+                                dict.repr().map(|r| r.to_string()).unwrap_or_default()
+                            ))
+                        })?;
+                    chosen[index] = true;
+                }
+                let (retry, kept): (Vec<_>, Vec<_>) = self
+                    .failed
+                    .iter()
+                    .zip(chosen)
+                    .partition(|(_, chosen)| *chosen);
+                (
+                    retry.into_iter().map(|(page, _)| page.clone()).collect(),
+                    kept.into_iter().map(|(page, _)| page.clone()).collect(),
+                )
+            }
+        };
+
+        let runtime = runtime()?;
+        let stats = TransferStats::default();
+        let recovered = runtime
+            .block_on(
+                self.network
+                    .refetch(self.layer, &retry, &self.query, &http_options, &stats),
+            )
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        let mut records: Vec<PipelineRecord> = self.records.iter().map(clone_record).collect();
+        records.extend(recovered.records);
+        if self.deduplicate {
+            deduplicate_records(&mut records, &self.network.id_field());
+        }
+        let mut failed = kept;
+        failed.extend(stats.failed_pages());
+        if !failed.is_empty() {
+            warn_failed_pages(py, failed.len())?;
+        }
+        warn_missing_records(py, &stats)?;
+
+        Ok(Self {
+            bbox: self.bbox,
+            network: self.network,
+            layer: self.layer,
+            query: self.query.clone(),
+            deduplicate: self.deduplicate,
+            records,
+            failed,
+        })
+    }
+
+    /// Bin the cached records into a hex summary at `resolution` (0-15; `zoom` is a
+    /// deprecated alias), optionally keeping only records whose `material` matches
+    /// exactly.
+    ///
+    /// `parent_zoom` adds a `parent_hex_id` column, `boundary_pipe_count` a
+    /// `boundary_pipe_count` one, `aggregations` per-hex attribute statistics and
+    /// `include_metadata_columns` the `resolution` and `provider` ones,
+    /// `geometry_encoding` sets the geometry layout, and
+    /// `output="polars"` returns a `polars.DataFrame` and
+    /// `output_layout="normalized"` a `NormalizedSummary` and `max_batch_rows` batches
+    /// of at most that many rows, as in `get_hex_summary`.
+    ///
+    /// `count_mode="unique_assets"` only sees every segment of an asset if the
+    /// cache was built with `deduplicate=False`. `assignment="midpoint"` counts each
+    /// record in the one hex containing its midpoint, as in `get_hex_summary`.
+    ///
+    /// # Returns
+    /// A PyArrow RecordBatch with columns: hex_id, pipe_count, [resolution, provider,]
+    /// geometry
+    #[pyo3(signature = (resolution=None, material=None, *, zoom=None, parent_zoom=None, coordinate_precision=None, geometry_encoding="geoarrow_separated", weighting="count", aggregations=None, count_mode="records", assignment="coverage", boundary_pipe_count=false, include_metadata_columns=true, output="arrow", output_layout="wide", max_batch_rows=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn hex_summary(
+        &self,
+        py: Python<'_>,
+        resolution: Option<u8>,
+        material: Option<&str>,
+        zoom: Option<u8>,
+        parent_zoom: Option<i64>,
+        coordinate_precision: Option<i64>,
+        geometry_encoding: &str,
+        weighting: &str,
+        aggregations: Option<&Bound<'_, PyDict>>,
+        count_mode: &str,
+        assignment: &str,
+        boundary_pipe_count: bool,
+        include_metadata_columns: bool,
+        output: &str,
+        output_layout: &str,
+        max_batch_rows: Option<usize>,
+    ) -> PyResult<Py<PyAny>> {
+        let zoom = extract_resolution(py, resolution, zoom)?;
+        let geometry_options =
+            extract_geometry_options(coordinate_precision, None, geometry_encoding)?;
+        let output = extract_summary_output(output, output_layout, max_batch_rows)?;
+        let weighting = extract_weighting(weighting)?;
+        let aggregations = extract_aggregations(aggregations)?;
+        let count_mode = extract_count_mode(count_mode)?;
+        let assignment = extract_assignment(assignment)?;
+        let parent_zoom = extract_parent_zoom(zoom, parent_zoom)?;
+
+        if material.is_some()
+            && let Some(fields) = self.fields()
+            && !fields.iter().any(|field| field == "material")
+        {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "material= needs the \"material\" field, but the cache was fetched without it",
+            ));
+        }
+
+        let filtered: Vec<PipelineRecord>;
+        let records = match material {
+            Some(material) => {
+                filtered = self
+                    .records
+                    .iter()
+                    .filter(|record| record.material.as_deref() == Some(material))
+                    .map(clone_record)
+                    .collect();
+                &filtered
+            }
+            None => &self.records,
+        };
+
+        let batch = to_hex_summary(records, zoom, assignment)
+            .and_then(|batch| with_metadata(batch, assignment.metadata()))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let batch = with_count_mode(batch, records, zoom, assignment, count_mode)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let batch = with_weighting(batch, records, zoom, assignment, weighting)
+            .and_then(|batch| with_aggregations(batch, records, zoom, assignment, &aggregations))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let batch = if boundary_pipe_count {
+            with_boundary_pipe_counts(batch, records, zoom, assignment, count_mode)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+        } else {
+            batch
+        };
+        let batch = clip_hex_summary_to_bbox(batch, &self.bbox, Containment::Intersects)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let batch = match parent_zoom {
+            Some(parent_zoom) => with_parent_hex_ids(batch, parent_zoom)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?,
+            None => batch,
+        };
+        let batch = if include_metadata_columns {
+            with_metadata_columns(batch, Some(zoom), Some(self.network.name()))
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?
+        } else {
+            batch
+        };
+        let batch = with_geometry_options(batch, &geometry_options)
+            .and_then(conform_hex_summary)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        output.to_python(py, batch)
+    }
+
+    /// The cached records as a PyArrow RecordBatch (an `ArrowBatch` if pyarrow
+    /// is not installed): one row per pipe with its attributes and a WKB
+    /// `geometry` column (EPSG:4326). A cache built with `fields` only has those
+    /// attribute columns, in that order.
+    fn to_pipe_records(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let batch = records_to_batch(&self.records)
+            .and_then(|batch| match self.fields() {
+                Some(fields) => select_record_fields(&batch, fields),
+                None => Ok(batch),
+            })
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        OutputFormat::Arrow.to_python(py, batch)
+    }
+
+    fn __len__(&self) -> usize {
+        self.records.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "RegionCache(min_lat={}, min_lon={}, max_lat={}, max_lon={}, records={})",
+            self.bbox.min_lat,
+            self.bbox.min_lon,
+            self.bbox.max_lat,
+            self.bbox.max_lon,
+            self.records.len()
+        )
+    }
+}
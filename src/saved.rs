@@ -0,0 +1,184 @@
+use std::io::{Read, Seek, Write};
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_ipc::reader::FileReader;
+use arrow_ipc::writer::FileWriter;
+use geo::{BoundingRect, Geometry, Intersects, Rect, coord};
+use infra_hex_rs::{BBox, InfraHexError, PipelineRecord};
+use serde_json::{Value, json};
+
+/// Schema metadata key holding the [`RawFetchMetadata`] of a saved fetch, as JSON.
+pub const RAW_FETCH_METADATA_KEY: &str = "infra_hex_py.raw_fetch";
+
+/// The newest saved-fetch format this release reads and the one it writes.
+/// Bump it whenever the columns or the metadata change meaning.
+pub const RAW_FETCH_FORMAT_VERSION: u64 = 1;
+
+/// What was fetched, and when, for a file written by [`write_raw_fetch`].
+#[derive(Debug, Clone)]
+pub struct RawFetchMetadata {
+    pub bbox: BBox,
+    pub network: String,
+    pub layers: Vec<String>,
+    /// HTTP-date (RFC 9110) of when the fetch finished.
+    pub fetched_at: String,
+    /// The `infra_hex_py` version that wrote the file.
+    pub written_by: String,
+}
+
+impl RawFetchMetadata {
+    fn to_json(&self) -> String {
+        json!({
+            "format_version": RAW_FETCH_FORMAT_VERSION,
+            "bbox": [self.bbox.min_lat, self.bbox.min_lon, self.bbox.max_lat, self.bbox.max_lon],
+            "network": self.network,
+            "layers": self.layers,
+            "fetched_at": self.fetched_at,
+            "written_by": self.written_by,
+        })
+        .to_string()
+    }
+
+    fn from_json(source: &str) -> Result<Self, InfraHexError> {
+        let invalid = |what: &str| {
+            InfraHexError::Config(format!("Saved fetch metadata has no valid {:?}", what))
+        };
+        let value: Value = serde_json::from_str(source)
+            .map_err(|e| InfraHexError::Config(format!("Saved fetch metadata: {}", e)))?;
+
+        let version = value["format_version"]
+            .as_u64()
+            .ok_or_else(|| invalid("format_version"))?;
+        if version > RAW_FETCH_FORMAT_VERSION {
+            return Err(InfraHexError::Config(format!(
+                "Saved fetch has format version {}, but this release of infra_hex_py reads \
+                 up to version {}; upgrade infra_hex_py to read it",
+                version, RAW_FETCH_FORMAT_VERSION
+            )));
+        }
+
+        let bbox = value["bbox"]
+            .as_array()
+            .filter(|corners| corners.len() == 4)
+            .and_then(|corners| {
+                corners
+                    .iter()
+                    .map(Value::as_f64)
+                    .collect::<Option<Vec<_>>>()
+            })
+            .ok_or_else(|| invalid("bbox"))?;
+        let string = |key: &str| {
+            value[key]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| invalid(key))
+        };
+        let layers = value["layers"]
+            .as_array()
+            .and_then(|layers| {
+                layers
+                    .iter()
+                    .map(|layer| layer.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .ok_or_else(|| invalid("layers"))?;
+
+        Ok(Self {
+            bbox: BBox {
+                min_lat: bbox[0],
+                min_lon: bbox[1],
+                max_lat: bbox[2],
+                max_lon: bbox[3],
+            },
+            network: string("network")?,
+            layers,
+            fetched_at: string("fetched_at")?,
+            written_by: string("written_by")?,
+        })
+    }
+}
+
+/// Writes `batch`, pipe records as built by `records_to_batch`, as an Arrow IPC
+/// file with `metadata` under [`RAW_FETCH_METADATA_KEY`] in its schema metadata.
+pub fn write_raw_fetch(
+    batch: &RecordBatch,
+    metadata: &RawFetchMetadata,
+    writer: impl Write,
+) -> Result<(), InfraHexError> {
+    let mut schema_metadata = batch.schema().metadata().clone();
+    schema_metadata.insert(RAW_FETCH_METADATA_KEY.to_string(), metadata.to_json());
+    let schema = Arc::new(
+        batch
+            .schema()
+            .as_ref()
+            .clone()
+            .with_metadata(schema_metadata),
+    );
+    let batch = batch
+        .clone()
+        .with_schema(Arc::clone(&schema))
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+
+    let mut writer =
+        FileWriter::try_new(writer, &schema).map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+    writer
+        .write(&batch)
+        .and_then(|_| writer.finish())
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Reads a file written by [`write_raw_fetch`] back into its metadata and record
+/// batches.
+///
+/// # Errors
+/// Returns [`InfraHexError::Config`] if the file is not a saved fetch, or was
+/// written in a newer format version than [`RAW_FETCH_FORMAT_VERSION`].
+pub fn read_raw_fetch(
+    reader: impl Read + Seek,
+) -> Result<(RawFetchMetadata, Vec<RecordBatch>), InfraHexError> {
+    let reader = FileReader::try_new(reader, None)
+        .map_err(|e| InfraHexError::Config(format!("Not a saved fetch (Arrow IPC file): {}", e)))?;
+    let metadata = reader
+        .schema()
+        .metadata()
+        .get(RAW_FETCH_METADATA_KEY)
+        .ok_or_else(|| {
+            InfraHexError::Config(format!(
+                "Arrow IPC file has no {:?} metadata; was it written by save_raw_fetch?",
+                RAW_FETCH_METADATA_KEY
+            ))
+        })
+        .and_then(|source| RawFetchMetadata::from_json(source))?;
+
+    let batches = reader
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+    Ok((metadata, batches))
+}
+
+/// Counts the `records` whose pipe line lies entirely outside `bbox`.
+///
+/// A fetch only returns records located within its bounding box, so any such
+/// record means the file's contents do not match the bbox in its metadata.
+pub fn records_outside_bbox<'a>(
+    records: impl IntoIterator<Item = &'a PipelineRecord>,
+    bbox: &BBox,
+) -> usize {
+    let bbox = Rect::new(
+        coord! { x: bbox.min_lon, y: bbox.min_lat },
+        coord! { x: bbox.max_lon, y: bbox.max_lat },
+    );
+    records
+        .into_iter()
+        .filter(|record| {
+            record
+                .geo_shape
+                .geometry
+                .clone()
+                .and_then(|g| Geometry::<f64>::try_from(g).ok())
+                .and_then(|line| line.bounding_rect())
+                .is_some_and(|extent| !extent.intersects(&bbox))
+        })
+        .count()
+}
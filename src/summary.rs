@@ -0,0 +1,3459 @@
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use arrow_array::builder::{ListBuilder, StringBuilder};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, Int32Type, UInt8Type, UInt32Type};
+use arrow_array::{
+    ArrayRef, DictionaryArray, Float64Array, Int32Array, Int64Array, RecordBatch, StringArray,
+    UInt8Array, UInt32Array,
+};
+use arrow_array::{BooleanArray, new_null_array};
+use arrow_cast::cast;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use arrow_select::concat::concat_batches;
+use arrow_select::filter::filter_record_batch;
+use arrow_select::take::take_record_batch;
+use geo::{
+    Area, BooleanOps, BoundingRect, Buffer, Closest, ClosestPoint, Coord, CoordsIter, Distance,
+    Euclidean, Geodesic, GeodesicArea, Geometry, Haversine, InterpolatableLine, Intersects, Length,
+    Line, LineString, MapCoordsInPlace, MultiLineString, MultiPolygon, Point, Polygon,
+    PreparedGeometry, Rect, Relate, Simplify, Validation, coord,
+};
+use geoarrow_array::array::PolygonArray;
+use geoarrow_array::builder::PolygonBuilder;
+use geoarrow_array::cast::to_wkb;
+use geoarrow_array::{GeoArrowArray, IntoArrow};
+use geoarrow_schema::{CoordType, Crs, Dimension, Metadata, PolygonType};
+use infra_hex_rs::{BBox, HexCell, InfraHexError, PipelineRecord};
+use rayon::prelude::*;
+use rstar::RTree;
+use serde_json::json;
+
+use crate::crs::{BNG_EXTENT, Transform, bng_extent_to_wgs84, wgs84_multipolygon_to_bng};
+use crate::osgb::{grid_square, square_cell};
+use crate::records::{
+    IdField, RECORD_FIELDS, RecordValue, is_number_field, record_value, records_geometry_column,
+};
+
+/// Highest n3gb zoom level.
+pub const MAX_ZOOM: u8 = 15;
+
+/// Default cap on the estimated number of hexes a fetch may produce.
+pub const DEFAULT_MAX_HEXES: u64 = 2_000_000;
+
+/// Default number of hexes `resolution="auto"` aims for.
+pub const DEFAULT_TARGET_HEX_COUNT: u64 = 5_000;
+
+/// Version of the hex summary layout, recorded under `schema_version` in every
+/// summary's schema metadata. Bump it whenever summary columns are added or
+/// change meaning.
+pub const HEX_SUMMARY_SCHEMA_VERSION: u32 = 1;
+
+/// A summary schema with `fields`, its metadata recording
+/// [`HEX_SUMMARY_SCHEMA_VERSION`].
+pub(crate) fn summary_schema(fields: impl Into<arrow_schema::Fields>) -> Schema {
+    Schema::new_with_metadata(
+        fields,
+        HashMap::from([(
+            "schema_version".to_string(),
+            HEX_SUMMARY_SCHEMA_VERSION.to_string(),
+        )]),
+    )
+}
+
+/// The column a summary is keyed by, which names the kind of cell its rows are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CellKey {
+    /// `hex_id`, for hexes.
+    #[default]
+    HexId,
+    /// `grid_ref`, for National Grid squares (`grid="osgb"`).
+    GridRef,
+    /// `cell_id`, for regular grid squares (`grid="square"`).
+    CellId,
+}
+
+impl CellKey {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::HexId => "hex_id",
+            Self::GridRef => "grid_ref",
+            Self::CellId => "cell_id",
+        }
+    }
+
+    /// The key of a summary with `schema`.
+    pub fn of(schema: &Schema) -> Self {
+        [Self::GridRef, Self::CellId]
+            .into_iter()
+            .find(|key| schema.field_with_name(key.name()).is_ok())
+            .unwrap_or_default()
+    }
+}
+
+/// The optional columns of a hex summary, which with [`hex_summary_schema`] fix
+/// its full schema.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SummaryColumns {
+    /// Keyed by `grid_ref` or `cell_id` rather than `hex_id`, for a summary of
+    /// squares.
+    pub key: CellKey,
+    /// Names of the `count_<name>` columns, one per layer or network.
+    pub sources: Vec<String>,
+    pub parent_hex_id: bool,
+    pub boundary_pipe_count: bool,
+    pub weighted_count: bool,
+    pub clipped_area: bool,
+    /// `grid_distance` and `outside_query_area`, added by `k_ring`.
+    pub k_ring: bool,
+    pub hex_area: bool,
+    /// `cell_area_km2` and `pipe_density_per_km2`.
+    pub density: bool,
+    /// `pipe_density`, added by a `normalize` other than `"none"`.
+    pub normalized_density: bool,
+    pub nearest_pipe_distance: bool,
+    /// The `<field>_<function>` columns added by `aggregations`, in order.
+    pub aggregations: Vec<Aggregation>,
+    /// `resolution` (hex summaries only) and `provider`.
+    pub metadata_columns: bool,
+    /// `query_id`, added by `include_query_id`.
+    pub query_id: bool,
+    /// `area_name` and `object_id`, naming the area of each row of a summary of
+    /// several areas.
+    pub areas: bool,
+    pub geometry_encoding: GeometryEncoding,
+}
+
+impl SummaryColumns {
+    /// The columns of a summary with `schema`, as built by the summary functions.
+    ///
+    /// # Errors
+    /// Returns [`InfraHexError::Geometry`] if the geometry column is missing or
+    /// in no known encoding.
+    pub fn of(schema: &Schema) -> Result<Self, InfraHexError> {
+        let has = |name: &str| schema.field_with_name(name).is_ok();
+        let geometry = schema
+            .field_with_name("geometry")
+            .map_err(|_| InfraHexError::Geometry("Summary has no geometry column".into()))?;
+        let mut geometry_encoding = None;
+        for encoding in GeometryEncoding::ALL {
+            if encoding.field()?.data_type() == geometry.data_type() {
+                geometry_encoding = Some(encoding);
+                break;
+            }
+        }
+        Ok(Self {
+            key: CellKey::of(schema),
+            sources: schema
+                .fields()
+                .iter()
+                .filter_map(|field| field.name().strip_prefix("count_"))
+                .map(str::to_string)
+                .collect(),
+            parent_hex_id: has("parent_hex_id"),
+            boundary_pipe_count: has("boundary_pipe_count"),
+            weighted_count: has("weighted_count"),
+            clipped_area: has("clipped_area_m2"),
+            k_ring: has("grid_distance"),
+            hex_area: has("hex_area_m2"),
+            density: has("cell_area_km2"),
+            normalized_density: has("pipe_density"),
+            nearest_pipe_distance: has("nearest_pipe_m"),
+            aggregations: schema
+                .fields()
+                .iter()
+                .filter_map(|field| Aggregation::from_column_name(field.name()))
+                .collect(),
+            metadata_columns: has("provider"),
+            query_id: has("query_id"),
+            areas: has("area_name"),
+            geometry_encoding: geometry_encoding.ok_or_else(|| {
+                InfraHexError::Geometry(format!(
+                    "Summary geometry column has an unknown type {}",
+                    geometry.data_type()
+                ))
+            })?,
+        })
+    }
+}
+
+/// The schema of a hex summary with `columns`: the single definition of its
+/// column order, types and nullability, which [`conform_hex_summary`] holds every
+/// summary to.
+///
+/// Columns are `hex_id` (Utf8; `grid_ref` or `cell_id` for squares), `parent_hex_id`
+/// (dictionary of Utf8), `pipe_count` (UInt32), `boundary_pipe_count` (UInt32),
+/// `weighted_count` (Float64), `count_<name>` (UInt32), `clipped_area_m2` (Float64),
+/// `grid_distance` (UInt32), `outside_query_area` (Boolean), `hex_area_m2`,
+/// `cell_area_km2`, `pipe_density_per_km2`, `pipe_density` and `nearest_pipe_m`
+/// (Float64), the `<field>_<function>` aggregations (Float64, or UInt32 for
+/// `count_distinct`), `resolution` (UInt8), `provider`, `query_id` and
+/// `area_name` (dictionary of Utf8), `object_id` (Int64) and `geometry`
+/// (`geoarrow.polygon`, or `geoarrow.wkb` Binary), the optional ones only when
+/// `columns` has them. `pipe_density_per_km2`,
+/// `pipe_density`, `nearest_pipe_m`, the aggregations and `provider` are
+/// nullable; no other column is.
+pub fn hex_summary_schema(columns: &SummaryColumns) -> Result<Schema, InfraHexError> {
+    let float = |name: &str, nullable| Field::new(name, DataType::Float64, nullable);
+    let dictionary = |name: &str, nullable| {
+        Field::new_dictionary(name, DataType::Int32, DataType::Utf8, nullable)
+    };
+    let mut fields = vec![Field::new(columns.key.name(), DataType::Utf8, false)];
+    if columns.parent_hex_id {
+        fields.push(dictionary("parent_hex_id", false));
+    }
+    fields.push(Field::new("pipe_count", DataType::UInt32, false));
+    if columns.boundary_pipe_count {
+        fields.push(Field::new("boundary_pipe_count", DataType::UInt32, false));
+    }
+    if columns.weighted_count {
+        fields.push(float("weighted_count", false));
+    }
+    for name in &columns.sources {
+        fields.push(Field::new(
+            format!("count_{}", name),
+            DataType::UInt32,
+            false,
+        ));
+    }
+    if columns.clipped_area {
+        fields.push(float("clipped_area_m2", false));
+    }
+    if columns.k_ring {
+        fields.push(Field::new("grid_distance", DataType::UInt32, false));
+        fields.push(Field::new("outside_query_area", DataType::Boolean, false));
+    }
+    if columns.hex_area {
+        fields.push(float("hex_area_m2", false));
+    }
+    if columns.density {
+        fields.push(float("cell_area_km2", false));
+        fields.push(float("pipe_density_per_km2", true));
+    }
+    if columns.normalized_density {
+        fields.push(float("pipe_density", true));
+    }
+    if columns.nearest_pipe_distance {
+        fields.push(float("nearest_pipe_m", true));
+    }
+    fields.extend(columns.aggregations.iter().map(Aggregation::schema_field));
+    if columns.metadata_columns {
+        if columns.key == CellKey::HexId {
+            fields.push(Field::new("resolution", DataType::UInt8, false));
+        }
+        fields.push(dictionary("provider", true));
+    }
+    if columns.query_id {
+        fields.push(dictionary("query_id", false));
+    }
+    if columns.areas {
+        fields.push(dictionary("area_name", false));
+        fields.push(Field::new("object_id", DataType::Int64, false));
+    }
+    fields.push(columns.geometry_encoding.field()?);
+    Ok(summary_schema(fields))
+}
+
+/// Puts the columns of a summary `batch` in the order of [`hex_summary_schema`],
+/// keeping its schema metadata.
+///
+/// # Errors
+/// Returns [`InfraHexError::Geometry`] if a column is not a summary column, or
+/// does not have the type and nullability the schema gives it.
+pub fn conform_hex_summary(batch: RecordBatch) -> Result<RecordBatch, InfraHexError> {
+    let schema = batch.schema();
+    let expected = hex_summary_schema(&SummaryColumns::of(&schema)?)?;
+    let mut indices = Vec::with_capacity(expected.fields().len());
+    for field in expected.fields() {
+        let index = schema
+            .index_of(field.name())
+            .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+        let actual = schema.field(index);
+        if actual.data_type() != field.data_type() || actual.is_nullable() != field.is_nullable() {
+            return Err(InfraHexError::Geometry(format!(
+                "Summary column {} is {} (nullable: {}), expected {} (nullable: {})",
+                field.name(),
+                actual.data_type(),
+                actual.is_nullable(),
+                field.data_type(),
+                field.is_nullable()
+            )));
+        }
+        indices.push(index);
+    }
+    if indices.len() < schema.fields().len() {
+        let unexpected: Vec<&str> = schema
+            .fields()
+            .iter()
+            .filter(|field| expected.field_with_name(field.name()).is_err())
+            .map(|field| field.name().as_str())
+            .collect();
+        return Err(InfraHexError::Geometry(format!(
+            "Summary has columns outside its schema: {}",
+            unexpected.join(", ")
+        )));
+    }
+    batch
+        .project(&indices)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// A hex resolution, either given explicitly or chosen with `resolution="auto"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Fixed(u8),
+    Auto(u8),
+}
+
+impl Resolution {
+    pub fn zoom(self) -> u8 {
+        match self {
+            Self::Fixed(zoom) | Self::Auto(zoom) => zoom,
+        }
+    }
+
+    /// Schema metadata recording an automatically chosen resolution.
+    pub fn metadata(self) -> Option<(String, String)> {
+        match self {
+            Self::Auto(zoom) => Some(("resolution".to_string(), zoom.to_string())),
+            Self::Fixed(_) => None,
+        }
+    }
+}
+
+/// Beyond this many decimal places rounding has no effect on an `f64` coordinate.
+pub const MAX_COORDINATE_PRECISION: u32 = 15;
+
+fn bng_metadata() -> Arc<Metadata> {
+    let crs = Crs::from_authority_code("EPSG:27700".to_string());
+    Arc::new(Metadata::new(crs, None))
+}
+
+/// Builds the geoarrow polygon column for the given cells, in EPSG:27700.
+pub fn build_polygon_geometry(cells: &[&HexCell]) -> (ArrayRef, Field) {
+    let polygons: Vec<_> = cells.iter().map(|c| c.to_polygon()).collect();
+    build_polygon_column(&polygons)
+}
+
+pub(crate) fn build_polygon_column(polygons: &[Polygon<f64>]) -> (ArrayRef, Field) {
+    let poly_type = PolygonType::new(Dimension::XY, bng_metadata());
+    let geometry_array = PolygonBuilder::from_polygons(polygons, poly_type).finish();
+    let geometry_field = geometry_array.extension_type().to_field("geometry", false);
+    (Arc::new(geometry_array.into_arrow()), geometry_field)
+}
+
+/// Bins records into a hex summary: one row per hex any pipe is assigned to by
+/// `assignment`, with the number of pipes assigned to it as `pipe_count`. Under
+/// [`Assignment::Coverage`] that is every hex the pipe passes through (see
+/// [`line_hex_cells`]): every part of a MultiLineString pipe is binned, and a pipe
+/// whose parts cross the same hex counts once there. A point asset, such as a
+/// governor or valve, counts in the single hex containing it (each hex of a
+/// MultiPoint's points).
+///
+/// Rows are sorted by `hex_id` ascending.
+pub fn to_hex_summary(
+    records: &[PipelineRecord],
+    zoom: u8,
+    assignment: Assignment,
+) -> Result<RecordBatch, InfraHexError> {
+    to_hex_summary_min_count(records, zoom, assignment, 0)
+}
+
+/// [`to_hex_summary`] without the hexes fewer than `min_pipe_count` records
+/// cross, dropped before their polygons are built.
+///
+/// Recounting by [`CountMode`] and clipping only ever lower `pipe_count`, so
+/// [`filter_hex_summary`] would drop these rows later anyway.
+pub fn to_hex_summary_min_count(
+    records: &[PipelineRecord],
+    zoom: u8,
+    assignment: Assignment,
+    min_pipe_count: u32,
+) -> Result<RecordBatch, InfraHexError> {
+    let counts = count_record_hex_cells(records, zoom, assignment)?;
+
+    let mut sorted: Vec<(HexCell, u32)> = counts
+        .into_values()
+        .filter(|(_, count)| *count >= min_pipe_count)
+        .collect();
+    sorted.sort_by(|a, b| a.0.id.cmp(&b.0.id));
+
+    let hex_ids: StringArray = sorted
+        .iter()
+        .map(|(cell, _)| Some(cell.id.as_str()))
+        .collect();
+    let pipe_counts: UInt32Array = sorted.iter().map(|(_, count)| Some(*count)).collect();
+    let cells: Vec<&HexCell> = sorted.iter().map(|(cell, _)| cell).collect();
+    let (geometry_array, geometry_field) = build_polygon_geometry(&cells);
+
+    let schema = summary_schema(vec![
+        Field::new("hex_id", DataType::Utf8, false),
+        Field::new("pipe_count", DataType::UInt32, false),
+        geometry_field,
+    ]);
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(hex_ids), Arc::new(pipe_counts), geometry_array],
+    )
+    .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Records per parallel task in [`count_record_hex_cells`]; each task sets up its
+/// own PROJ transform, so tasks must be large enough to amortise that.
+const RECORDS_PER_TASK: usize = 4096;
+
+/// Counts, for each hex at `zoom`, the records assigned to it by `assignment`.
+///
+/// Records are split into chunks traced on rayon's thread pool (see
+/// `configure_aggregation`), and the per-chunk counts are summed, so the result
+/// does not depend on how the work was scheduled.
+fn count_record_hex_cells(
+    records: &[PipelineRecord],
+    zoom: u8,
+    assignment: Assignment,
+) -> Result<HashMap<String, (HexCell, u32)>, InfraHexError> {
+    records
+        .par_chunks(RECORDS_PER_TASK)
+        .map(|chunk| {
+            let to_bng = Transform::wgs84_to_bng()?;
+            let mut counts: HashMap<String, (HexCell, u32)> = HashMap::new();
+            for record in chunk {
+                for cell in record_hex_cells(record, zoom, assignment, &to_bng)? {
+                    counts.entry(cell.id.clone()).or_insert((cell, 0)).1 += 1;
+                }
+            }
+            Ok(counts)
+        })
+        .try_reduce(HashMap::new, |mut counts, mut other| {
+            if other.len() > counts.len() {
+                std::mem::swap(&mut counts, &mut other);
+            }
+            for (id, (cell, count)) in other {
+                counts.entry(id).or_insert((cell, 0)).1 += count;
+            }
+            Ok(counts)
+        })
+}
+
+/// The unique hexes at `zoom` that the BNG `lines` pass through.
+///
+/// n3gb's line binning samples each segment every half cell radius, which misses
+/// hexes a segment only clips at a corner. Every hex a segment intersects is a
+/// sampled hex or a neighbour of one, so the neighbours are tested against the
+/// segment too. A single-point line falls in the hex containing the point.
+pub fn line_hex_cells(
+    lines: &MultiLineString<f64>,
+    zoom: u8,
+) -> Result<Vec<HexCell>, InfraHexError> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut cells: Vec<HexCell> = Vec::new();
+    let mut push = |cell: HexCell, cells: &mut Vec<HexCell>| {
+        if seen.insert(cell.id.clone()) {
+            cells.push(cell);
+        }
+    };
+
+    for line in lines {
+        if let [point] = line.0.as_slice() {
+            push(HexCell::from_bng(&(point.x, point.y), zoom)?, &mut cells);
+            continue;
+        }
+        for segment in line.lines() {
+            let path = LineString::new(vec![segment.start, segment.end]);
+            for cell in HexCell::from_line_string_bng(&path, zoom)? {
+                for neighbour in hex_neighbours(&cell)? {
+                    if neighbour.to_polygon().intersects(&segment) {
+                        push(neighbour, &mut cells);
+                    }
+                }
+                push(cell, &mut cells);
+            }
+        }
+    }
+
+    Ok(cells)
+}
+
+/// The hexes at `zoom` that the BNG `lines` pass through, in the order they pass
+/// through them: each segment's hexes (see [`line_hex_cells`]) sorted along it
+/// (see [`segment_position`]), with consecutive repeats merged. A hex that a line
+/// leaves and later re-enters is listed again, and the parts of a multi-line
+/// follow one another.
+pub fn line_hex_trace(
+    lines: &MultiLineString<f64>,
+    zoom: u8,
+) -> Result<Vec<HexCell>, InfraHexError> {
+    let mut trace: Vec<HexCell> = Vec::new();
+    let push = |cell: HexCell, trace: &mut Vec<HexCell>| {
+        if trace.last().is_none_or(|last| last.id != cell.id) {
+            trace.push(cell);
+        }
+    };
+
+    for line in lines {
+        if let [point] = line.0.as_slice() {
+            push(HexCell::from_bng(&(point.x, point.y), zoom)?, &mut trace);
+            continue;
+        }
+        for segment in line.lines() {
+            let path =
+                MultiLineString::new(vec![LineString::new(vec![segment.start, segment.end])]);
+            let mut cells: Vec<((i64, f64), HexCell)> = line_hex_cells(&path, zoom)?
+                .into_iter()
+                .map(|cell| (segment_position(&segment, &cell), cell))
+                .collect();
+            cells.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+            for (_, cell) in cells {
+                push(cell, &mut trace);
+            }
+        }
+    }
+
+    Ok(trace)
+}
+
+/// Where the foot of `cell`'s centre lies along `segment`, in whole millimetres
+/// from its start (negative before it), then how far the centre is to its left.
+///
+/// A line crossing from one hex into its neighbour moves towards the neighbour's
+/// centre, so the hexes a segment passes through come in the order of the first
+/// distance. The second only orders hexes side by side across a segment running
+/// along their shared edge. Reversing the segment reverses both, and so the
+/// order. Ordering by where the segment first meets each hex polygon would be
+/// less robust: n3gb assigns some points near a boundary to the hex across it,
+/// and those hexes need not meet the segment at all.
+fn segment_position(segment: &Line<f64>, cell: &HexCell) -> (i64, f64) {
+    let length = Euclidean.length(segment);
+    if length == 0.0 {
+        return (0, 0.0);
+    }
+    let delta = segment.delta();
+    let offset = cell.center.0 - segment.start;
+    let along = (offset.x * delta.x + offset.y * delta.y) / length;
+    let left = (delta.x * offset.y - delta.y * offset.x) / length;
+    ((along * 1000.0).round() as i64, left)
+}
+
+/// The six hexes sharing an edge with `cell`: the cell reflected across each edge.
+fn hex_neighbours(cell: &HexCell) -> Result<Vec<HexCell>, InfraHexError> {
+    let centre = cell.center;
+    cell.to_polygon()
+        .exterior()
+        .lines()
+        .map(|edge| {
+            let x = edge.start.x + edge.end.x - centre.x();
+            let y = edge.start.y + edge.end.y - centre.y();
+            Ok(HexCell::from_bng(&(x, y), cell.zoom_level)?)
+        })
+        .collect()
+}
+
+/// Which hexes each record counts in, chosen with `assignment=`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Assignment {
+    /// Every hex the record's line passes through (see [`line_hex_cells`]); also
+    /// named `"all_intersecting"`.
+    #[default]
+    Coverage,
+    /// Only the hex containing the point halfway along the record's line, so each
+    /// record counts exactly once and `pipe_count` sums to the number of records.
+    Midpoint,
+}
+
+impl Assignment {
+    pub fn from_name(name: &str) -> Result<Self, InfraHexError> {
+        match name {
+            "coverage" | "all_intersecting" => Ok(Self::Coverage),
+            "midpoint" => Ok(Self::Midpoint),
+            other => Err(InfraHexError::Config(format!(
+                "Unknown assignment {:?}; expected \"coverage\" (or \"all_intersecting\") \
+                 or \"midpoint\"",
+                other
+            ))),
+        }
+    }
+
+    /// Schema metadata recording an assignment other than the default.
+    pub fn metadata(self) -> Option<(String, String)> {
+        match self {
+            Self::Coverage => None,
+            Self::Midpoint => Some(("assignment".to_string(), "midpoint".to_string())),
+        }
+    }
+
+    /// The hexes at `zoom` the BNG `lines` are assigned to.
+    fn hex_cells(
+        self,
+        lines: &MultiLineString<f64>,
+        zoom: u8,
+    ) -> Result<Vec<HexCell>, InfraHexError> {
+        match self {
+            Self::Coverage => line_hex_cells(lines, zoom),
+            Self::Midpoint => Ok(midpoint_hex_cell(lines, zoom)?.into_iter().collect()),
+        }
+    }
+}
+
+/// The hex at `zoom` containing the [`line_midpoint`] of the BNG `lines`.
+fn midpoint_hex_cell(
+    lines: &MultiLineString<f64>,
+    zoom: u8,
+) -> Result<Option<HexCell>, InfraHexError> {
+    line_midpoint(lines)
+        .map(|point| Ok(HexCell::from_bng(&(point.x, point.y), zoom)?))
+        .transpose()
+}
+
+/// The point halfway along the total length of the BNG `lines`, or their first
+/// point if they have no length.
+pub(crate) fn line_midpoint(lines: &MultiLineString<f64>) -> Option<Coord<f64>> {
+    let mut remaining = Euclidean.length(lines) / 2.0;
+    let mut midpoint = lines.iter().flat_map(|line| line.coords()).next().copied();
+    for line in lines.iter().filter(|line| Euclidean.length(*line) > 0.0) {
+        // Clamped to the line's end, so rounding cannot run past the last part
+        midpoint = line
+            .point_at_distance_from_start(&Euclidean, remaining)
+            .map(|point| point.0);
+        remaining -= Euclidean.length(line);
+        if remaining <= 0.0 {
+            break;
+        }
+    }
+    midpoint
+}
+
+/// The hexes at `zoom` that `assignment` assigns the record's WGS84 line geometry
+/// to, or that contain its points (see [`line_hex_cells`]).
+///
+/// # Errors
+/// Returns [`InfraHexError::Geometry`] if the record has no line or point
+/// geometry.
+fn record_hex_cells(
+    record: &PipelineRecord,
+    zoom: u8,
+    assignment: Assignment,
+    to_bng: &Transform,
+) -> Result<Vec<HexCell>, InfraHexError> {
+    assignment.hex_cells(&record_bng_lines(record, to_bng)?, zoom)
+}
+
+/// The record's line (or point) geometry, projected to BNG with `to_bng`.
+///
+/// # Errors
+/// Returns [`InfraHexError::Geometry`] if the record has no line or point
+/// geometry.
+fn record_bng_lines(
+    record: &PipelineRecord,
+    to_bng: &Transform,
+) -> Result<MultiLineString<f64>, InfraHexError> {
+    let lines = record_lines(record).ok_or_else(|| {
+        InfraHexError::Geometry(match &record.geo_shape.geometry {
+            Some(geometry) => format!(
+                "Expected a LineString, MultiLineString, Point or MultiPoint, got {}",
+                geometry.value.type_name()
+            ),
+            None => "Feature has no geometry".to_string(),
+        })
+    })?;
+    to_bng.apply(&lines)
+}
+
+/// Keeps the records whose line passes through `cell` (see [`line_hex_cells`]),
+/// in order: exactly the pipes [`to_hex_summary`] counts in that hex.
+///
+/// # Errors
+/// Returns [`InfraHexError::Geometry`] if a record has no line geometry.
+pub fn records_in_cell(
+    records: Vec<PipelineRecord>,
+    cell: &HexCell,
+) -> Result<Vec<PipelineRecord>, InfraHexError> {
+    let to_bng = Transform::wgs84_to_bng()?;
+    let mut kept = Vec::new();
+    for record in records {
+        if record_hex_cells(&record, cell.zoom_level, Assignment::Coverage, &to_bng)?
+            .iter()
+            .any(|other| other.id == cell.id)
+        {
+            kept.push(record);
+        }
+    }
+    Ok(kept)
+}
+
+/// Maps each record to the hexes its line passes through: one row per (pipe, hex)
+/// pair, the long form that [`to_hex_summary`] aggregates. Only hexes overlapping
+/// the WGS84 `area` are kept, as in [`clip_hex_summary`].
+///
+/// The columns are `pipe_id` (the record's id under `id_field`, see
+/// [`IdField::id`]) and `hex_id`, both dictionary encoded, and the pipe's
+/// `geometry`, repeated on each of its rows. Rows follow the order of `records`,
+/// then `hex_id`.
+pub fn to_pipe_hex_assignments(
+    records: &[PipelineRecord],
+    zoom: u8,
+    area: &MultiPolygon<f64>,
+    id_field: &IdField,
+) -> Result<RecordBatch, InfraHexError> {
+    let to_bng = Transform::wgs84_to_bng()?;
+    let area = PreparedGeometry::from(wgs84_multipolygon_to_bng(area)?);
+    let mut overlaps: HashMap<String, bool> = HashMap::new();
+    let mut rows: Vec<(&PipelineRecord, String)> = Vec::new();
+    for record in records {
+        let mut cells = record_hex_cells(record, zoom, Assignment::Coverage, &to_bng)?;
+        cells.sort_by(|a, b| a.id.cmp(&b.id));
+        for cell in cells {
+            let overlap = *overlaps.entry(cell.id.clone()).or_insert_with(|| {
+                let relation = area.relate(&cell.to_polygon());
+                relation.is_intersects() && !relation.is_touches()
+            });
+            if overlap {
+                rows.push((record, cell.id));
+            }
+        }
+    }
+
+    // Both ids repeat across rows (a hex holds many pipes, a pipe crosses many
+    // hexes), so each is dictionary encoded
+    let ids: Vec<String> = rows.iter().map(|(record, _)| id_field.id(record)).collect();
+    let pipe_ids: DictionaryArray<Int32Type> = ids.iter().map(String::as_str).collect();
+    let hex_ids: DictionaryArray<Int32Type> =
+        rows.iter().map(|(_, id)| Some(id.as_str())).collect();
+    let (geometry, geometry_field) =
+        records_geometry_column(rows.iter().map(|(record, _)| *record))?;
+
+    let schema = Schema::new(vec![
+        Field::new_dictionary("pipe_id", DataType::Int32, DataType::Utf8, false),
+        Field::new_dictionary("hex_id", DataType::Int32, DataType::Utf8, false),
+        geometry_field,
+    ]);
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(pipe_ids), Arc::new(hex_ids), geometry],
+    )
+    .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Traces each record through the hexes at `zoom`: one row per record, with its
+/// `pipe_id` (see [`IdField::id`]) and a `cell_sequence` list of the hex ids its
+/// line passes through, in order (see [`line_hex_trace`]).
+///
+/// Unlike [`to_pipe_hex_assignments`], no hexes are clipped away, so every trace
+/// runs the whole length of its pipe. Rows follow the order of `records`.
+pub fn to_pipe_cell_traces(
+    records: &[PipelineRecord],
+    zoom: u8,
+    id_field: &IdField,
+) -> Result<RecordBatch, InfraHexError> {
+    let to_bng = Transform::wgs84_to_bng()?;
+    let mut pipe_ids: Vec<String> = Vec::with_capacity(records.len());
+    let cell_field = Arc::new(Field::new_list_field(DataType::Utf8, false));
+    let mut sequences = ListBuilder::new(StringBuilder::new()).with_field(cell_field.clone());
+    for record in records {
+        for cell in line_hex_trace(&record_bng_lines(record, &to_bng)?, zoom)? {
+            sequences.values().append_value(cell.id);
+        }
+        sequences.append(true);
+        pipe_ids.push(id_field.id(record));
+    }
+
+    let sequences = sequences.finish();
+    let schema = Schema::new(vec![
+        Field::new("pipe_id", DataType::Utf8, false),
+        Field::new("cell_sequence", DataType::List(cell_field), false),
+    ]);
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(StringArray::from(pipe_ids)), Arc::new(sequences)],
+    )
+    .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Prefix of the ids [`to_record_hex_assignments`] gives records without an
+/// `asset_id`.
+pub const UNIDENTIFIED_ASSET_PREFIX: &str = "unidentified-";
+
+/// Maps each asset to the hexes it falls in: one row per (asset, hex) pair, for
+/// joining hex-level results back onto asset tables.
+///
+/// Records sharing an `asset_id` make up one asset. Records without one are each
+/// their own, with the id [`UNIDENTIFIED_ASSET_PREFIX`] followed by its index
+/// among them in order of their coordinates, so the ids do not depend on the
+/// order the records were fetched in. Under [`Assignment::Coverage`] the rows
+/// cover every hex an asset passes through, with a Float64 `fraction_of_length`
+/// column holding the share of the asset's BNG length inside each hex, in the
+/// same way as [`with_weighting`]: an asset's fractions sum to 1. Under
+/// [`Assignment::Midpoint`] each record's midpoint hex is listed, without
+/// fractions. Unlike [`to_pipe_hex_assignments`], no hexes are clipped away, so
+/// no part of an asset is lost.
+///
+/// `asset_id` and `hex_id` are dictionary encoded. Rows are ordered by
+/// `asset_id`, then `hex_id`.
+pub fn to_record_hex_assignments(
+    records: &[PipelineRecord],
+    zoom: u8,
+    assignment: Assignment,
+) -> Result<RecordBatch, InfraHexError> {
+    let to_bng = Transform::wgs84_to_bng()?;
+    let coords_key = |lines: &MultiLineString<f64>| -> Vec<(u64, u64)> {
+        lines
+            .iter()
+            .flat_map(|line| line.coords())
+            .map(|c| (c.x.to_bits(), c.y.to_bits()))
+            .collect()
+    };
+
+    let mut identified = Vec::new();
+    let mut unidentified = Vec::new();
+    for record in records {
+        let Some(lines) = record_lines(record) else {
+            continue;
+        };
+        let lines = to_bng.apply(&lines)?;
+        match &record.asset_id {
+            Some(id) => identified.push((id.clone(), lines)),
+            None => unidentified.push((coords_key(&lines), lines)),
+        }
+    }
+    unidentified.sort_by(|a, b| a.0.cmp(&b.0));
+    let assets = identified.into_iter().chain(
+        unidentified
+            .into_iter()
+            .enumerate()
+            .map(|(i, (_, lines))| (format!("{}{}", UNIDENTIFIED_ASSET_PREFIX, i), lines)),
+    );
+
+    // Each asset's length in each hex, or just its midpoint hexes
+    let mut shares: BTreeMap<String, BTreeMap<String, f64>> = BTreeMap::new();
+    let mut starts: HashMap<String, Coord<f64>> = HashMap::new();
+    for (id, lines) in assets {
+        let asset = shares.entry(id.clone()).or_default();
+        if assignment == Assignment::Midpoint {
+            if let Some(cell) = midpoint_hex_cell(&lines, zoom)? {
+                asset.insert(cell.id, 1.0);
+            }
+            continue;
+        }
+        for (cell, length) in hex_lengths(&lines, zoom)? {
+            *asset.entry(cell).or_default() += length;
+        }
+        if let Some(start) = lines.iter().flat_map(|line| line.coords()).next() {
+            starts.entry(id).or_insert(*start);
+        }
+    }
+
+    let mut rows: Vec<(String, String, f64)> = Vec::new();
+    for (id, lengths) in shares {
+        let total: f64 = lengths.values().sum();
+        if assignment == Assignment::Midpoint {
+            rows.extend(lengths.into_keys().map(|cell| (id.clone(), cell, 1.0)));
+        } else if total > 0.0 {
+            rows.extend(
+                lengths
+                    .into_iter()
+                    .map(|(cell, length)| (id.clone(), cell, length / total)),
+            );
+        } else if let Some(start) = starts.get(&id) {
+            // A zero-length asset lies wholly in the hex containing it
+            let cell = HexCell::from_bng(&(start.x, start.y), zoom)?;
+            rows.push((id, cell.id, 1.0));
+        }
+    }
+
+    let asset_ids: DictionaryArray<Int32Type> =
+        rows.iter().map(|(id, _, _)| Some(id.as_str())).collect();
+    let hex_ids: DictionaryArray<Int32Type> =
+        rows.iter().map(|(_, id, _)| Some(id.as_str())).collect();
+    let mut fields = vec![
+        Field::new_dictionary("asset_id", DataType::Int32, DataType::Utf8, false),
+        Field::new_dictionary("hex_id", DataType::Int32, DataType::Utf8, false),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(asset_ids), Arc::new(hex_ids)];
+    if assignment == Assignment::Coverage {
+        fields.push(Field::new("fraction_of_length", DataType::Float64, false));
+        columns.push(Arc::new(Float64Array::from_iter_values(
+            rows.iter().map(|(_, _, fraction)| *fraction),
+        )));
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Bins records into a hex summary clipped to the WGS84 `polygon` by `containment`
+/// (see [`clip_hex_summary`]), with a deterministic row order (see
+/// [`sort_hex_rows`]).
+///
+/// The schema metadata records `covered_area_fraction` (see
+/// [`covered_area_fraction`]) and, unless they are the defaults, `containment` and
+/// `assignment`.
+pub fn to_hex_summary_for_multipolygon(
+    records: &[PipelineRecord],
+    zoom: u8,
+    assignment: Assignment,
+    polygon: &MultiPolygon<f64>,
+    containment: Containment,
+    clipped_area: bool,
+) -> Result<RecordBatch, InfraHexError> {
+    let batch = clip_hex_summary(
+        to_hex_summary(records, zoom, assignment)?,
+        polygon,
+        containment,
+    )?;
+    let fraction = covered_area_fraction(&batch, polygon)?;
+    let batch = with_metadata(
+        batch,
+        [("covered_area_fraction".to_string(), fraction.to_string())]
+            .into_iter()
+            .chain(containment.metadata())
+            .chain(assignment.metadata()),
+    )?;
+    if clipped_area {
+        with_clipped_area(batch, polygon)
+    } else {
+        Ok(batch)
+    }
+}
+
+/// `batch` with the `added` columns inserted in order at `index`, keeping its
+/// schema metadata.
+fn insert_columns(
+    batch: &RecordBatch,
+    index: usize,
+    added: impl IntoIterator<Item = (Field, ArrayRef)>,
+) -> Result<RecordBatch, InfraHexError> {
+    let schema = batch.schema();
+    let (added_fields, added_columns): (Vec<Field>, Vec<ArrayRef>) = added.into_iter().unzip();
+    let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    let mut columns = batch.columns().to_vec();
+    fields.splice(index..index, added_fields);
+    columns.splice(index..index, added_columns);
+
+    let schema = Schema::new_with_metadata(fields, schema.metadata().clone());
+    RecordBatch::try_new(Arc::new(schema), columns)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// `batch` with the `added` columns inserted in order just before its `geometry`
+/// column, or at the end if it has none (see [`insert_columns`]).
+fn insert_before_geometry(
+    batch: &RecordBatch,
+    added: impl IntoIterator<Item = (Field, ArrayRef)>,
+) -> Result<RecordBatch, InfraHexError> {
+    let schema = batch.schema();
+    let index = schema.index_of("geometry").unwrap_or(schema.fields().len());
+    insert_columns(batch, index, added)
+}
+
+/// Adds a Float64 `clipped_area_m2` column, before `geometry`, holding the BNG area
+/// of each hex that lies within the WGS84 `area`.
+///
+/// Hexes wholly inside `area` report the full cell area; boundary hexes report the
+/// area of their intersection, so counts can be normalised by covered area.
+pub fn with_clipped_area(
+    batch: RecordBatch,
+    area: &MultiPolygon<f64>,
+) -> Result<RecordBatch, InfraHexError> {
+    let area = wgs84_multipolygon_to_bng(area)?;
+    let clipped: Float64Array = clipped_hex_areas(&batch, &area)?
+        .into_iter()
+        .map(Some)
+        .collect();
+
+    insert_before_geometry(
+        &batch,
+        [(
+            Field::new("clipped_area_m2", DataType::Float64, false),
+            Arc::new(clipped) as ArrayRef,
+        )],
+    )
+}
+
+/// Adds a Float64 `hex_area_m2` column, before `geometry`, holding the ground
+/// area of each row's whole hex, for densities such as pipes per km².
+///
+/// Hexes have one size on the BNG plane, but the projection's scale factor
+/// varies across the country, so each hex is measured on the WGS84 ellipsoid
+/// instead; the two differ by up to about 0.1%.
+pub fn with_hex_areas(batch: RecordBatch) -> Result<RecordBatch, InfraHexError> {
+    let transform = Transform::bng_to_wgs84()?;
+    let hex_ids = batch
+        .column_by_name("hex_id")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no hex_id column".into()))?
+        .as_string::<i32>();
+    let areas = hex_ids
+        .iter()
+        .map(|id| {
+            let cell = HexCell::from_hex_id(id.unwrap_or_default())?.to_polygon();
+            Ok(Some(transform.apply(&cell)?.geodesic_area_unsigned()))
+        })
+        .collect::<Result<Float64Array, InfraHexError>>()?;
+
+    insert_before_geometry(
+        &batch,
+        [(
+            Field::new("hex_area_m2", DataType::Float64, false),
+            Arc::new(areas) as ArrayRef,
+        )],
+    )
+}
+
+/// Smallest share of its whole hex a clipped cell must keep to be given a
+/// `pipe_density_per_km2`; below it the density is null rather than the huge
+/// (or infinite) value a sliver's tiny area would give.
+pub const MIN_DENSITY_AREA_FRACTION: f64 = 0.01;
+
+/// Adds a Float64 `cell_area_km2` column and a nullable Float64
+/// `pipe_density_per_km2` column, before `geometry`.
+///
+/// `cell_area_km2` is each hex's ground area on the WGS84 ellipsoid, as in
+/// [`with_hex_areas`], or with a WGS84 `clip` the area of the hex's part inside
+/// it. The density divides `weighted_count`, when the batch has one, or else
+/// `pipe_count` by that area, and is null for cells keeping less than
+/// [`MIN_DENSITY_AREA_FRACTION`] of their hex.
+pub fn with_density(
+    batch: RecordBatch,
+    clip: Option<&MultiPolygon<f64>>,
+) -> Result<RecordBatch, InfraHexError> {
+    let counts = density_counts(&batch)?;
+    let mut areas = Vec::with_capacity(batch.num_rows());
+    let mut densities = Vec::with_capacity(batch.num_rows());
+    for (cell, count) in cell_ground_areas(&batch, clip)?.into_iter().zip(&counts) {
+        areas.push(cell.area_m2 / 1e6);
+        densities.push(cell.per_km2(count.unwrap_or_default()));
+    }
+
+    insert_before_geometry(
+        &batch,
+        [
+            (
+                Field::new("cell_area_km2", DataType::Float64, false),
+                Arc::new(Float64Array::from(areas)) as ArrayRef,
+            ),
+            (
+                Field::new("pipe_density_per_km2", DataType::Float64, true),
+                Arc::new(Float64Array::from(densities)),
+            ),
+        ],
+    )
+}
+
+/// What a density divides: `weighted_count` when the batch has one, else
+/// `pipe_count`.
+fn density_counts(batch: &RecordBatch) -> Result<Float64Array, InfraHexError> {
+    let counts = match batch.column_by_name("weighted_count") {
+        Some(weighted) => Arc::clone(weighted),
+        None => batch
+            .column_by_name("pipe_count")
+            .ok_or_else(|| InfraHexError::Geometry("Summary has no pipe_count column".into()))?
+            .clone(),
+    };
+    let counts =
+        cast(&counts, &DataType::Float64).map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+    Ok(counts.as_primitive::<Float64Type>().clone())
+}
+
+/// A hex's ground measurements on the WGS84 ellipsoid.
+struct CellGround {
+    /// The area kept by the clip, or the whole hex's.
+    area_m2: f64,
+    whole_area_m2: f64,
+    perimeter_m: f64,
+}
+
+impl CellGround {
+    /// `count` per km² of the kept area, or None for a cell keeping less than
+    /// [`MIN_DENSITY_AREA_FRACTION`] of its hex.
+    fn per_km2(&self, count: f64) -> Option<f64> {
+        (self.area_m2 > 0.0 && self.area_m2 >= MIN_DENSITY_AREA_FRACTION * self.whole_area_m2)
+            .then(|| count / (self.area_m2 / 1e6))
+    }
+
+    /// `count` per km of the hex's mean edge length, a sixth of its perimeter.
+    fn per_km_edge(&self, count: f64) -> f64 {
+        count / (self.perimeter_m / 6.0 / 1e3)
+    }
+}
+
+/// The ground measurements of each row's hex, its area clipped to `clip` when
+/// given (a WGS84 area).
+fn cell_ground_areas(
+    batch: &RecordBatch,
+    clip: Option<&MultiPolygon<f64>>,
+) -> Result<Vec<CellGround>, InfraHexError> {
+    let transform = Transform::bng_to_wgs84()?;
+    let clip = clip
+        .map(|clip| {
+            let clip = wgs84_multipolygon_to_bng(clip)?;
+            Ok::<_, InfraHexError>((PreparedGeometry::from(clip.clone()), clip))
+        })
+        .transpose()?;
+    let hex_ids = batch
+        .column_by_name("hex_id")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no hex_id column".into()))?
+        .as_string::<i32>();
+
+    hex_ids
+        .iter()
+        .map(|id| {
+            let cell = HexCell::from_hex_id(id.unwrap_or_default())?.to_polygon();
+            let ground = transform.apply(&cell)?;
+            let whole_area_m2 = ground.geodesic_area_unsigned();
+            let area_m2 = match &clip {
+                Some((prepared, clip)) if !prepared.relate(&cell).is_covers() => transform
+                    .apply(&cell.intersection(clip))?
+                    .geodesic_area_unsigned(),
+                _ => whole_area_m2,
+            };
+            Ok(CellGround {
+                area_m2,
+                whole_area_m2,
+                perimeter_m: Geodesic.length(ground.exterior()),
+            })
+        })
+        .collect()
+}
+
+/// How [`with_normalized_density`] scales each cell's count.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Normalize {
+    /// No `pipe_density` column.
+    #[default]
+    None,
+    /// Per km² of the cell's ground area.
+    PerKm2,
+    /// Per km of the hex's edge length.
+    PerKmEdge,
+}
+
+impl Normalize {
+    pub fn from_name(name: &str) -> Result<Self, InfraHexError> {
+        match name {
+            "none" => Ok(Self::None),
+            "per_km2" => Ok(Self::PerKm2),
+            "per_km_edge" => Ok(Self::PerKmEdge),
+            other => Err(InfraHexError::Config(format!(
+                "Unknown normalize {:?}; expected \"none\", \"per_km2\" or \"per_km_edge\"",
+                other
+            ))),
+        }
+    }
+}
+
+/// Adds a nullable Float64 `pipe_density` column before `geometry`, holding each
+/// cell's count (`weighted_count` when the batch has one, else `pipe_count`)
+/// scaled by `normalize`. [`Normalize::None`] returns the batch unchanged.
+///
+/// With `N` that count and the hex measured on the WGS84 ellipsoid:
+/// - [`Normalize::PerKm2`]: `N / A`, `A` the `cell_area_km2` of [`with_density`]
+///   (the part inside a WGS84 `clip`, when given), and null for cells keeping
+///   less than [`MIN_DENSITY_AREA_FRACTION`] of their hex;
+/// - [`Normalize::PerKmEdge`]: `N / (P / 6)`, `P` the whole hex's geodesic
+///   perimeter in km, so `P / 6` is its mean edge length.
+pub fn with_normalized_density(
+    batch: RecordBatch,
+    normalize: Normalize,
+    clip: Option<&MultiPolygon<f64>>,
+) -> Result<RecordBatch, InfraHexError> {
+    if normalize == Normalize::None {
+        return Ok(batch);
+    }
+    let counts = density_counts(&batch)?;
+    let densities: Float64Array = cell_ground_areas(&batch, clip)?
+        .into_iter()
+        .zip(&counts)
+        .map(|(cell, count)| {
+            let count = count.unwrap_or_default();
+            match normalize {
+                Normalize::PerKmEdge => Some(cell.per_km_edge(count)),
+                _ => cell.per_km2(count),
+            }
+        })
+        .collect();
+
+    insert_before_geometry(
+        &batch,
+        [(
+            Field::new("pipe_density", DataType::Float64, true),
+            Arc::new(densities) as ArrayRef,
+        )],
+    )
+}
+
+/// Adds a nullable Float64 `nearest_pipe_m` column before `geometry`: for each hex
+/// with a `pipe_count` of 0, the geodesic distance in metres from its centre to
+/// the nearest of `records`' lines, and null for populated hexes or when there
+/// are no records.
+///
+/// The lines' BNG segments go in an R-tree, so each hex costs one nearest-neighbour
+/// query. The nearest segment is found in BNG, then the distance to its closest
+/// point measured on the ellipsoid.
+pub fn with_nearest_pipe_distances<'a>(
+    batch: RecordBatch,
+    records: impl IntoIterator<Item = &'a PipelineRecord>,
+) -> Result<RecordBatch, InfraHexError> {
+    let to_bng = Transform::wgs84_to_bng()?;
+    let to_wgs84 = Transform::bng_to_wgs84()?;
+    let mut segments = Vec::new();
+    for record in records {
+        if let Some(lines) = record_lines(record) {
+            for line in &to_bng.apply(&lines)? {
+                match line.0.as_slice() {
+                    [point] => segments.push(Line::new(*point, *point)),
+                    _ => segments.extend(line.lines()),
+                }
+            }
+        }
+    }
+    let segments = RTree::bulk_load(segments);
+
+    let hex_ids = batch
+        .column_by_name("hex_id")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no hex_id column".into()))?
+        .as_string::<i32>();
+    let pipe_counts = batch
+        .column_by_name("pipe_count")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no pipe_count column".into()))?
+        .as_primitive::<UInt32Type>();
+    let distances = hex_ids
+        .iter()
+        .zip(pipe_counts)
+        .map(|(id, count)| {
+            if count != Some(0) {
+                return Ok(None);
+            }
+            let centre = HexCell::from_hex_id(id.unwrap_or_default())?.center;
+            let Some(nearest) = segments.nearest_neighbor(&centre) else {
+                return Ok(None);
+            };
+            let closest = match nearest.closest_point(&centre) {
+                Closest::Intersection(point) | Closest::SinglePoint(point) => point,
+                Closest::Indeterminate => nearest.start_point(),
+            };
+            let [centre, closest] = [centre, closest].map(|point| to_wgs84.apply(&point));
+            Ok(Some(Geodesic.distance(centre?, closest?)))
+        })
+        .collect::<Result<Float64Array, InfraHexError>>()?;
+
+    insert_before_geometry(
+        &batch,
+        [(
+            Field::new("nearest_pipe_m", DataType::Float64, true),
+            Arc::new(distances) as ArrayRef,
+        )],
+    )
+}
+
+/// The fraction of the WGS84 `area` covered by the batch's hexes that hold at
+/// least one pipe: the summed area of their parts inside `area` over the area of
+/// `area`, both in BNG. 0 for an area with no size.
+pub fn covered_area_fraction(
+    batch: &RecordBatch,
+    area: &MultiPolygon<f64>,
+) -> Result<f64, InfraHexError> {
+    let area = wgs84_multipolygon_to_bng(area)?;
+    let total = area.unsigned_area();
+    if total <= 0.0 {
+        return Ok(0.0);
+    }
+    let pipe_counts = batch
+        .column_by_name("pipe_count")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no pipe_count column".into()))?;
+    let pipe_counts =
+        cast(pipe_counts, &DataType::UInt32).map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+    let covered: f64 = clipped_hex_areas(batch, &area)?
+        .into_iter()
+        .zip(pipe_counts.as_primitive::<UInt32Type>())
+        .filter(|(_, count)| count.is_some_and(|count| count > 0))
+        .map(|(clipped, _)| clipped)
+        .sum();
+    Ok((covered / total).min(1.0))
+}
+
+/// The BNG area of each row's hex that lies within the BNG `area`.
+fn clipped_hex_areas(
+    batch: &RecordBatch,
+    area: &MultiPolygon<f64>,
+) -> Result<Vec<f64>, InfraHexError> {
+    let prepared = PreparedGeometry::from(area.clone());
+    let hex_ids = batch
+        .column_by_name("hex_id")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no hex_id column".into()))?
+        .as_string::<i32>();
+
+    hex_ids
+        .iter()
+        .map(|id| {
+            let cell = HexCell::from_hex_id(id.unwrap_or_default())?.to_polygon();
+            Ok(if prepared.relate(&cell).is_covers() {
+                cell.unsigned_area()
+            } else {
+                cell.intersection(area).unsigned_area()
+            })
+        })
+        .collect()
+}
+
+/// Which hexes a summary clipped to an area keeps, chosen with `containment=`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Containment {
+    /// Every hex whose interior overlaps the area's interior.
+    #[default]
+    Intersects,
+    /// Hexes whose centre lies strictly inside the area, so tiles sharing an edge
+    /// split its hexes between them.
+    Centroid,
+    /// Only hexes lying entirely inside the area; a hex sharing part of its
+    /// outline with the area's boundary still counts.
+    Within,
+    /// Hexes with more than half of their area inside the area, so a hex goes to
+    /// whichever side of a boundary holds most of it.
+    Majority,
+}
+
+impl Containment {
+    pub fn from_name(name: &str) -> Result<Self, InfraHexError> {
+        match name {
+            "intersects" => Ok(Self::Intersects),
+            "centroid" => Ok(Self::Centroid),
+            "within" => Ok(Self::Within),
+            "majority" => Ok(Self::Majority),
+            other => Err(InfraHexError::Config(format!(
+                "Unknown containment {:?}; expected \"intersects\", \"centroid\", \"within\" \
+                 or \"majority\"",
+                other
+            ))),
+        }
+    }
+
+    /// Schema metadata recording a containment other than the default.
+    pub fn metadata(self) -> Option<(String, String)> {
+        let name = match self {
+            Self::Intersects => return None,
+            Self::Centroid => "centroid",
+            Self::Within => "within",
+            Self::Majority => "majority",
+        };
+        Some(("containment".to_string(), name.to_string()))
+    }
+
+    /// Whether the cell with BNG `polygon` and `centre` is kept by a clip to
+    /// `area`, a BNG polygon.
+    fn keeps(
+        self,
+        area: &PreparedGeometry<'_, MultiPolygon<f64>>,
+        polygon: &Polygon<f64>,
+        centre: Point<f64>,
+    ) -> bool {
+        match self {
+            Self::Intersects => {
+                let relation = area.relate(polygon);
+                relation.is_intersects() && !relation.is_touches()
+            }
+            Self::Centroid => area.relate(&centre).is_contains(),
+            Self::Within => area.relate(polygon).is_covers(),
+            Self::Majority => {
+                let relation = area.relate(polygon);
+                relation.is_covers()
+                    || (relation.is_intersects()
+                        && polygon.intersection(area.geometry()).unsigned_area()
+                            > 0.5 * polygon.unsigned_area())
+            }
+        }
+    }
+}
+
+/// The BNG outline and centre of a summary row's cell.
+struct SummaryCell {
+    polygon: Polygon<f64>,
+    centre: Point<f64>,
+}
+
+/// The cell of each row of a summary batch: its hex, or its square in a summary
+/// keyed by `grid_ref` or `cell_id`.
+fn summary_cells(batch: &RecordBatch) -> Result<Vec<SummaryCell>, InfraHexError> {
+    let key = CellKey::of(&batch.schema());
+    batch
+        .column_by_name(key.name())
+        .ok_or_else(|| InfraHexError::Geometry(format!("Summary has no {} column", key.name())))?
+        .as_string::<i32>()
+        .iter()
+        .map(|id| {
+            let id = id.unwrap_or_default();
+            let square = match key {
+                CellKey::HexId => {
+                    let cell = HexCell::from_hex_id(id)?;
+                    return Ok(SummaryCell {
+                        polygon: cell.to_polygon(),
+                        centre: cell.center,
+                    });
+                }
+                CellKey::GridRef => grid_square(id)?,
+                CellKey::CellId => square_cell(id)?,
+            };
+            Ok(SummaryCell {
+                polygon: square.to_polygon(),
+                centre: square.center().into(),
+            })
+        })
+        .collect()
+}
+
+/// Keeps the rows of a summary batch whose hex is inside the WGS84 `area` by
+/// `containment`, or whose square is for a summary of squares.
+///
+/// Hexes are tested in BNG against the exact hex polygon. Under
+/// [`Containment::Intersects`] a hex counts when its interior overlaps the
+/// interior of `area`. Interior rings are respected: a hex inside a hole is
+/// dropped, and so is one that only shares an edge or vertex with the area's
+/// boundary (outer or hole), while a hex straddling a hole edge is kept. Row order
+/// and schema metadata are preserved.
+pub fn clip_hex_summary(
+    batch: RecordBatch,
+    area: &MultiPolygon<f64>,
+    containment: Containment,
+) -> Result<RecordBatch, InfraHexError> {
+    let area = PreparedGeometry::from(wgs84_multipolygon_to_bng(area)?);
+    let keep: BooleanArray = summary_cells(&batch)?
+        .iter()
+        .map(|cell| Some(containment.keeps(&area, &cell.polygon, cell.centre)))
+        .collect();
+
+    filter_record_batch(&batch, &keep).map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// [`clip_hex_summary`] against a WGS84 bounding box.
+pub fn clip_hex_summary_to_bbox(
+    batch: RecordBatch,
+    bbox: &BBox,
+    containment: Containment,
+) -> Result<RecordBatch, InfraHexError> {
+    clip_hex_summary(batch, &bbox_polygon(bbox), containment)
+}
+
+/// A WGS84 bounding box as a (lon/lat) polygon.
+pub fn bbox_polygon(bbox: &BBox) -> MultiPolygon<f64> {
+    let rect = Rect::new(
+        coord! { x: bbox.min_lon, y: bbox.min_lat },
+        coord! { x: bbox.max_lon, y: bbox.max_lat },
+    );
+    MultiPolygon::new(vec![rect.to_polygon()])
+}
+
+/// Grows the WGS84 `bbox` outward to whole hex cells at `zoom`, so it covers in
+/// full every hex that overlaps it.
+///
+/// The box's BNG extent is widened by one cell's extent on each side (clamped to
+/// [`BNG_EXTENT`]) and converted back to WGS84. Fetching the grown box means the
+/// counts of hexes along the original edges no longer depend on where the edge
+/// falls within them.
+pub fn snap_bbox_to_hexes(bbox: &BBox, zoom: u8) -> Result<BBox, InfraHexError> {
+    let extent = wgs84_multipolygon_to_bng(&bbox_polygon(bbox))?
+        .bounding_rect()
+        .ok_or_else(|| InfraHexError::Config("Empty bounding box".into()))?;
+    let centre = extent.center();
+    let cell = HexCell::from_bng(&(centre.x, centre.y), zoom)?
+        .to_polygon()
+        .bounding_rect()
+        .ok_or_else(|| InfraHexError::Geometry("Empty hex cell polygon".into()))?;
+    let (cell_width, cell_height) = (cell.width(), cell.height());
+
+    let [bng_min_e, bng_min_n, bng_max_e, bng_max_n] = BNG_EXTENT;
+    bng_extent_to_wgs84(
+        (extent.min().x - cell_width).max(bng_min_e),
+        (extent.min().y - cell_height).max(bng_min_n),
+        (extent.max().x + cell_width).min(bng_max_e),
+        (extent.max().y + cell_height).min(bng_max_n),
+    )
+}
+
+/// Estimates how many hexes at `zoom` cover the WGS84 `area`: its BNG area divided
+/// by the area of one cell.
+pub fn estimate_hex_count(area: &MultiPolygon<f64>, zoom: u8) -> Result<u64, InfraHexError> {
+    let area = wgs84_multipolygon_to_bng(area)?;
+    let Some(centre) = area.bounding_rect().map(|rect| rect.center()) else {
+        return Ok(0);
+    };
+    let cell_area = HexCell::from_bng(&(centre.x, centre.y), zoom)?
+        .to_polygon()
+        .unsigned_area();
+    Ok((area.unsigned_area() / cell_area).ceil() as u64)
+}
+
+/// The part of the WGS84 `area` inside the WGS84 `coverage`, with the fraction of
+/// `area` (by BNG area) it makes up. An `area` wholly inside is returned as it is,
+/// with a fraction of exactly 1, so clamping it changes nothing downstream.
+pub fn clamp_to_coverage(
+    area: &MultiPolygon<f64>,
+    coverage: &MultiPolygon<f64>,
+) -> Result<(MultiPolygon<f64>, f64), InfraHexError> {
+    let total = wgs84_multipolygon_to_bng(area)?.unsigned_area();
+    let covered = area.intersection(coverage);
+    let fraction = match total {
+        0.0 => 1.0,
+        total => wgs84_multipolygon_to_bng(&covered)?.unsigned_area() / total,
+    };
+    if fraction >= 1.0 - 1e-9 {
+        return Ok((area.clone(), 1.0));
+    }
+    Ok((covered, fraction))
+}
+
+/// Most vertices in a [`hex_cover_query_area`], which keeps the query URL it is
+/// sent in well under common 8 KB limits.
+pub const MAX_QUERY_AREA_VERTICES: usize = 200;
+
+/// A simplified WGS84 area containing every hex at `zoom` that overlaps the
+/// WGS84 `area`, to filter a fetch server-side to the pipes a summary clipped to
+/// `area` can count.
+///
+/// `area` is buffered by a hex's width plus twice the simplification tolerance,
+/// so simplifying never cuts into those hexes, and the tolerance doubles until
+/// there are at most [`MAX_QUERY_AREA_VERTICES`]. Returns `None` when the result
+/// covers most of `bbox`, the envelope otherwise fetched, so filtering by it
+/// would save little.
+pub fn hex_cover_query_area(
+    area: &MultiPolygon<f64>,
+    bbox: &BBox,
+    zoom: u8,
+) -> Result<Option<MultiPolygon<f64>>, InfraHexError> {
+    let area = wgs84_multipolygon_to_bng(area)?;
+    let Some(centre) = area.bounding_rect().map(|rect| rect.center()) else {
+        return Ok(None);
+    };
+    let cell = HexCell::from_bng(&(centre.x, centre.y), zoom)?;
+    let circumradius = cell
+        .to_polygon()
+        .exterior()
+        .coords()
+        .map(|vertex| Euclidean.distance(cell.center, Point::from(*vertex)))
+        .fold(0.0, f64::max);
+    let envelope_area = wgs84_multipolygon_to_bng(&bbox_polygon(bbox))?.unsigned_area();
+
+    let mut tolerance = circumradius / 2.0;
+    for _ in 0..16 {
+        let query_area = area
+            .buffer(2.0 * circumradius + 2.0 * tolerance)
+            .simplify(tolerance);
+        if query_area.coords_count() <= MAX_QUERY_AREA_VERTICES {
+            if query_area.unsigned_area() >= 0.8 * envelope_area {
+                return Ok(None);
+            }
+            return Transform::bng_to_wgs84()?.apply(&query_area).map(Some);
+        }
+        tolerance *= 2.0;
+    }
+    Ok(None)
+}
+
+/// Chooses the resolution at which the WGS84 `area` is covered by closest to
+/// `target_hex_count` hexes, comparing estimates (see [`estimate_hex_count`]) on a
+/// log scale. The result is always within 0 to [`MAX_ZOOM`].
+///
+/// # Errors
+/// Returns [`InfraHexError::Config`] if `target_hex_count` is 0 or `area` has no
+/// area.
+pub fn auto_resolution(
+    area: &MultiPolygon<f64>,
+    target_hex_count: u64,
+) -> Result<u8, InfraHexError> {
+    if target_hex_count == 0 {
+        return Err(InfraHexError::Config(
+            "target_hex_count must be at least 1".into(),
+        ));
+    }
+    let area = wgs84_multipolygon_to_bng(area)?;
+    let extent = area.unsigned_area();
+    let centre = area
+        .bounding_rect()
+        .filter(|_| extent > 0.0)
+        .map(|rect| rect.center())
+        .ok_or_else(|| {
+            InfraHexError::Config("resolution=\"auto\" needs a query area larger than zero".into())
+        })?;
+
+    let target = target_hex_count as f64;
+    let mut best = (0, f64::INFINITY);
+    for zoom in 0..=MAX_ZOOM {
+        let cell_area = HexCell::from_bng(&(centre.x, centre.y), zoom)?
+            .to_polygon()
+            .unsigned_area();
+        let miss = (extent / cell_area / target).ln().abs();
+        if miss < best.1 {
+            best = (zoom, miss);
+        }
+    }
+    Ok(best.0)
+}
+
+/// Fails before any fetch if the estimated output for `area` at `zoom` is larger
+/// than `max_hexes`, suggesting the finest zoom that would fit.
+pub fn check_hex_budget(
+    area: &MultiPolygon<f64>,
+    zoom: u8,
+    max_hexes: u64,
+) -> Result<(), InfraHexError> {
+    let estimate = estimate_hex_count(area, zoom)?;
+    if estimate <= max_hexes {
+        return Ok(());
+    }
+
+    let mut suggestion = None;
+    for coarser in (0..zoom).rev() {
+        if estimate_hex_count(area, coarser)? <= max_hexes {
+            suggestion = Some(coarser);
+            break;
+        }
+    }
+    let hint = match suggestion {
+        Some(coarser) => format!("use resolution {} or coarser", coarser),
+        None => "use a smaller area".to_string(),
+    };
+    Err(InfraHexError::Config(format!(
+        "Estimated {} hexes at resolution {} exceeds max_hexes ({}); {}, or raise max_hexes",
+        estimate, zoom, max_hexes, hint
+    )))
+}
+
+/// Fewest hexes a clip polygon can be estimated to cover (see
+/// [`estimate_hex_count`]) before [`check_clip_resolution`] reports its resolution
+/// as likely too coarse.
+pub const MIN_CLIP_HEXES: u64 = 4;
+
+/// Checks whether the WGS84 `area` covers so few hexes at `zoom` that the summary
+/// would show next to nothing, returning a message naming the area by `label` and
+/// the coarsest finer resolution that covers it with at least [`MIN_CLIP_HEXES`].
+pub fn check_clip_resolution(
+    area: &MultiPolygon<f64>,
+    label: &str,
+    zoom: u8,
+) -> Result<Option<String>, InfraHexError> {
+    let estimate = estimate_hex_count(area, zoom)?;
+    if estimate >= MIN_CLIP_HEXES {
+        return Ok(None);
+    }
+
+    let mut suggestion = None;
+    for finer in zoom + 1..=MAX_ZOOM {
+        if estimate_hex_count(area, finer)? >= MIN_CLIP_HEXES {
+            suggestion = Some(finer);
+            break;
+        }
+    }
+    let hint = match suggestion {
+        Some(finer) => format!(
+            "use resolution {} or finer for a more detailed summary",
+            finer
+        ),
+        None => format!("resolution {} is the finest available", MAX_ZOOM),
+    };
+    Ok(Some(format!(
+        "{} is estimated to cover only {} hex{} at resolution {}, which is likely too \
+         coarse; {}",
+        label,
+        estimate,
+        if estimate == 1 { "" } else { "es" },
+        zoom,
+        hint
+    )))
+}
+
+/// Reorders a summary batch by `resolution`, then `provider` (nulls first), then
+/// `hex_id` (or `grid_ref` / `cell_id`), all ascending, so repeated runs come out
+/// identical. `resolution` and `provider` are only sorted on when the batch has
+/// them (see [`with_metadata_columns`]); without them this is the order every
+/// summary is built in.
+fn sort_hex_rows(batch: RecordBatch) -> Result<RecordBatch, InfraHexError> {
+    let utf8 = |name: &str| {
+        batch
+            .column_by_name(name)
+            .map(|array| {
+                cast(array, &DataType::Utf8).map_err(|e| InfraHexError::Geometry(e.to_string()))
+            })
+            .transpose()
+    };
+    let key = CellKey::of(&batch.schema()).name();
+    let hex_ids = utf8(key)?
+        .ok_or_else(|| InfraHexError::Geometry(format!("Summary has no {} column", key)))?;
+    let hex_ids = hex_ids.as_string::<i32>();
+    let providers = utf8("provider")?;
+    let providers: Option<Vec<Option<&str>>> = providers
+        .as_ref()
+        .map(|array| array.as_string::<i32>().iter().collect());
+    let resolutions = batch
+        .column_by_name("resolution")
+        .map(|array| array.as_primitive::<UInt8Type>());
+
+    let sort_key = |row: u32| {
+        let row = row as usize;
+        (
+            resolutions.map(|array| array.value(row)),
+            providers.as_ref().map(|providers| providers[row]),
+            hex_ids.value(row),
+        )
+    };
+    let mut indices: Vec<u32> = (0..batch.num_rows() as u32).collect();
+    indices.sort_by(|&a, &b| sort_key(a).cmp(&sort_key(b)));
+
+    take_record_batch(&batch, &UInt32Array::from(indices))
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// The `n` rows of `batch` with the highest `pipe_count`, kept in batch order. Ties
+/// at the cut-off go to the rows that come first.
+fn densest_hex_rows(batch: &RecordBatch, n: usize) -> Result<RecordBatch, InfraHexError> {
+    let pipe_counts = batch
+        .column_by_name("pipe_count")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no pipe_count column".into()))?
+        .as_primitive::<UInt32Type>();
+
+    let mut rows: Vec<u32> = (0..batch.num_rows() as u32).collect();
+    rows.sort_by_key(|&row| std::cmp::Reverse(pipe_counts.value(row as usize)));
+    rows.truncate(n);
+    rows.sort_unstable();
+
+    take_record_batch(batch, &UInt32Array::from(rows))
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Row filters applied to a finished summary, e.g. to target surveys at the
+/// densest hexes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HexFilter {
+    /// Drop hexes with fewer pipes than this.
+    pub min_pipe_count: Option<u32>,
+    /// Keep only this many hexes, the densest.
+    pub top_n: Option<usize>,
+}
+
+impl HexFilter {
+    /// # Errors
+    /// Returns [`InfraHexError::Config`] for a negative `min_pipe_count` or a
+    /// zero `top_n`.
+    pub fn new(min_pipe_count: Option<i64>, top_n: Option<usize>) -> Result<Self, InfraHexError> {
+        if top_n == Some(0) {
+            return Err(InfraHexError::Config(
+                "top_n must be at least 1".to_string(),
+            ));
+        }
+        let min_pipe_count = min_pipe_count
+            .map(|count| {
+                u32::try_from(count).map_err(|_| {
+                    InfraHexError::Config(format!(
+                        "min_pipe_count must be between 0 and {}, got {}",
+                        u32::MAX,
+                        count
+                    ))
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            min_pipe_count,
+            top_n,
+        })
+    }
+
+    /// The `pipe_count` below which hexes can be left out of a summary as soon as
+    /// it is binned (see [`to_hex_summary_min_count`]).
+    pub fn min_count(&self) -> u32 {
+        self.min_pipe_count.unwrap_or(0)
+    }
+
+    fn is_noop(&self) -> bool {
+        self.min_pipe_count.is_none() && self.top_n.is_none()
+    }
+}
+
+/// Applies a [`HexFilter`] to a summary batch, after any recounting or clipping
+/// so `pipe_count` is final.
+///
+/// Rows come out sorted by `hex_id` (see [`sort_hex_rows`]); `top_n` keeps the `n`
+/// with the highest `pipe_count`, breaking ties at the cut-off by `hex_id`. Schema
+/// metadata is preserved, with the threshold used recorded as `min_pipe_count`.
+pub fn filter_hex_summary(
+    batch: RecordBatch,
+    filter: &HexFilter,
+) -> Result<RecordBatch, InfraHexError> {
+    if filter.is_noop() {
+        return Ok(batch);
+    }
+
+    let batch = sort_hex_rows(batch)?;
+    let batch = match filter.min_pipe_count {
+        Some(min_pipe_count) => {
+            let pipe_counts = batch
+                .column_by_name("pipe_count")
+                .ok_or_else(|| InfraHexError::Geometry("Summary has no pipe_count column".into()))?
+                .as_primitive::<UInt32Type>();
+            let keep: BooleanArray = pipe_counts
+                .iter()
+                .map(|count| Some(count.unwrap_or(0) >= min_pipe_count))
+                .collect();
+            let batch = filter_record_batch(&batch, &keep)
+                .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+            with_metadata(
+                batch,
+                [("min_pipe_count".to_string(), min_pipe_count.to_string())],
+            )?
+        }
+        None => batch,
+    };
+
+    Ok(match filter.top_n {
+        Some(top_n) => densest_hex_rows(&batch, top_n)?,
+        None => batch,
+    })
+}
+
+/// How the hex polygons are laid out in a summary's `geometry` column, chosen
+/// with `geometry_encoding=`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GeometryEncoding {
+    /// A `geoarrow.polygon` column with coordinates in separate x and y arrays.
+    #[default]
+    GeoArrowSeparated,
+    /// A `geoarrow.polygon` column with interleaved `xyxy...` coordinates, the
+    /// layout GeoArrow renderers such as lonboard upload without conversion.
+    GeoArrow,
+    /// A `geoarrow.wkb` binary column.
+    Wkb,
+}
+
+impl GeometryEncoding {
+    pub const ALL: [Self; 3] = [Self::GeoArrowSeparated, Self::GeoArrow, Self::Wkb];
+
+    pub fn from_name(name: &str) -> Result<Self, InfraHexError> {
+        match name {
+            "geoarrow_separated" => Ok(Self::GeoArrowSeparated),
+            "geoarrow" => Ok(Self::GeoArrow),
+            "wkb" => Ok(Self::Wkb),
+            other => Err(InfraHexError::Config(format!(
+                "Unknown geometry_encoding {:?}; expected \"geoarrow_separated\", \"geoarrow\" \
+                 or \"wkb\"",
+                other
+            ))),
+        }
+    }
+
+    /// The `geometry` field of a summary in this encoding.
+    fn field(self) -> Result<Field, InfraHexError> {
+        let (column, field) = build_polygon_column(&[]);
+        self.encode(&column, &field).map(|(_, field)| field)
+    }
+
+    /// Re-encodes `column`, a `geoarrow.polygon` array described by `field`.
+    fn encode(self, column: &ArrayRef, field: &Field) -> Result<(ArrayRef, Field), InfraHexError> {
+        let polygons = PolygonArray::try_from((column.as_ref(), field))
+            .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+        let polygons = match self {
+            Self::GeoArrowSeparated => polygons.into_coord_type(CoordType::Separated),
+            Self::GeoArrow => polygons.into_coord_type(CoordType::Interleaved),
+            Self::Wkb => {
+                let wkb =
+                    to_wkb::<i32>(&polygons).map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+                let field = wkb.data_type().to_field(field.name(), field.is_nullable());
+                return Ok((wkb.to_array_ref(), field));
+            }
+        };
+        let field = polygons
+            .extension_type()
+            .to_field(field.name(), field.is_nullable());
+        Ok((polygons.into_array_ref(), field))
+    }
+}
+
+/// Output controls applied to the hex geometry column of a summary.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GeometryOptions {
+    /// Round coordinates to this many decimal places (0 rounds to whole metres).
+    pub coordinate_precision: Option<u32>,
+    /// Douglas-Peucker tolerance in metres (EPSG:27700 units).
+    pub simplify_tolerance_m: Option<f64>,
+    pub encoding: GeometryEncoding,
+}
+
+impl GeometryOptions {
+    pub fn new(
+        coordinate_precision: Option<i64>,
+        simplify_tolerance_m: Option<f64>,
+        encoding: GeometryEncoding,
+    ) -> Result<Self, InfraHexError> {
+        let coordinate_precision = coordinate_precision
+            .map(|precision| {
+                u32::try_from(precision)
+                    .ok()
+                    .filter(|precision| *precision <= MAX_COORDINATE_PRECISION)
+                    .ok_or_else(|| {
+                        InfraHexError::Config(format!(
+                            "coordinate_precision must be between 0 and {} decimal places, got {}",
+                            MAX_COORDINATE_PRECISION, precision
+                        ))
+                    })
+            })
+            .transpose()?;
+        if let Some(tolerance) = simplify_tolerance_m
+            && !(tolerance.is_finite() && tolerance >= 0.0)
+        {
+            return Err(InfraHexError::Config(format!(
+                "simplify_tolerance_m must be a non-negative number, got {}",
+                tolerance
+            )));
+        }
+        Ok(Self {
+            coordinate_precision,
+            simplify_tolerance_m,
+            encoding,
+        })
+    }
+
+    fn apply(&self, mut polygon: Polygon<f64>) -> Polygon<f64> {
+        if let Some(tolerance) = self.simplify_tolerance_m {
+            // Fall back to the original ring if simplification degenerates it
+            let simplified = polygon.simplify(tolerance);
+            if simplified.exterior().0.len() >= 4 && simplified.is_valid() {
+                polygon = simplified;
+            }
+        }
+        if let Some(precision) = self.coordinate_precision {
+            let factor = 10f64.powi(precision as i32);
+            polygon.map_coords_in_place(|c| {
+                coord! {
+                    x: (c.x * factor).round() / factor,
+                    y: (c.y * factor).round() / factor,
+                }
+            });
+        }
+        polygon
+    }
+}
+
+/// Rebuilds the geometry column of a summary batch with `options` applied.
+///
+/// When rounding or simplifying, hex polygons are regenerated from `hex_id` (and
+/// squares from `grid_ref` or `cell_id`), so
+/// this works for any batch built by the summary functions. The column is then
+/// re-encoded as `options.encoding`. Returns the batch unchanged when no option
+/// is set.
+pub fn with_geometry_options(
+    batch: RecordBatch,
+    options: &GeometryOptions,
+) -> Result<RecordBatch, InfraHexError> {
+    let reshape = options.coordinate_precision.is_some() || options.simplify_tolerance_m.is_some();
+    if !reshape && options.encoding == GeometryEncoding::default() {
+        return Ok(batch);
+    }
+
+    let schema = batch.schema();
+    let geometry_idx = schema
+        .index_of("geometry")
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+    let (geometry_array, geometry_field) = if reshape {
+        let polygons: Vec<Polygon<f64>> = summary_cells(&batch)?
+            .into_iter()
+            .map(|cell| options.apply(cell.polygon))
+            .collect();
+        build_polygon_column(&polygons)
+    } else {
+        (
+            Arc::clone(batch.column(geometry_idx)),
+            schema.field(geometry_idx).clone(),
+        )
+    };
+    let (geometry_array, geometry_field) =
+        options.encoding.encode(&geometry_array, &geometry_field)?;
+
+    let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    let mut columns = batch.columns().to_vec();
+    fields[geometry_idx] = geometry_field;
+    columns[geometry_idx] = geometry_array;
+
+    let schema = Schema::new_with_metadata(fields, schema.metadata().clone());
+    RecordBatch::try_new(Arc::new(schema), columns)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Checks that `resolution` is a hex resolution, 0 (coarsest) to [`MAX_ZOOM`].
+pub fn validate_resolution(resolution: u8) -> Result<u8, InfraHexError> {
+    if resolution > MAX_ZOOM {
+        return Err(InfraHexError::Config(format!(
+            "resolution must be between 0 and {}, got {}",
+            MAX_ZOOM, resolution
+        )));
+    }
+    Ok(resolution)
+}
+
+/// Checks that `parent_zoom` is a valid level coarser than `zoom`.
+pub fn validate_parent_zoom(zoom: u8, parent_zoom: i64) -> Result<u8, InfraHexError> {
+    validate_resolution(zoom)?;
+    if parent_zoom < 0 || parent_zoom >= zoom as i64 {
+        return Err(InfraHexError::Config(format!(
+            "parent_zoom must be between 0 and resolution - 1 ({}), got {}",
+            zoom as i64 - 1,
+            parent_zoom
+        )));
+    }
+    Ok(parent_zoom as u8)
+}
+
+/// Checks that `k_ring` is not negative.
+pub fn validate_k_ring(k_ring: i64) -> Result<u32, InfraHexError> {
+    u32::try_from(k_ring).map_err(|_| {
+        InfraHexError::Config(format!(
+            "k_ring must be a non-negative grid distance, got {}",
+            k_ring
+        ))
+    })
+}
+
+/// Appends a row for every hex within grid distance `k` of a hex in the batch,
+/// and adds UInt32 `grid_distance` and Boolean `outside_query_area` columns before
+/// `geometry`.
+///
+/// Added rows come after the existing ones, nearest first and then by `hex_id`,
+/// with 0 in every count column. `grid_distance` is the number of steps to the
+/// nearest original hex (0 for those), and `outside_query_area` marks hexes that
+/// do not overlap the WGS84 `area`, as tested by [`clip_hex_summary`].
+pub fn with_k_ring(
+    batch: RecordBatch,
+    k: u32,
+    area: &MultiPolygon<f64>,
+) -> Result<RecordBatch, InfraHexError> {
+    let hex_ids = batch
+        .column_by_name("hex_id")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no hex_id column".into()))?
+        .as_string::<i32>();
+
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut frontier: Vec<HexCell> = Vec::new();
+    for id in hex_ids.iter().flatten() {
+        seen.insert(id.to_string());
+        frontier.push(HexCell::from_hex_id(id)?);
+    }
+    let mut ring: Vec<(u32, HexCell)> = Vec::new();
+    for distance in 1..=k {
+        let mut next = Vec::new();
+        for cell in &frontier {
+            for neighbour in hex_neighbours(cell)? {
+                if seen.insert(neighbour.id.clone()) {
+                    next.push(neighbour);
+                }
+            }
+        }
+        next.sort_by(|a, b| a.id.cmp(&b.id));
+        ring.extend(next.iter().cloned().map(|cell| (distance, cell)));
+        frontier = next;
+    }
+
+    let schema = batch.schema();
+    let ring_cells: Vec<&HexCell> = ring.iter().map(|(_, cell)| cell).collect();
+    let ring_batch = zero_hex_rows(&schema, &ring_cells)?;
+    let batch = concat_batches(&schema, [&batch, &ring_batch])
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+
+    let area = PreparedGeometry::from(wgs84_multipolygon_to_bng(area)?);
+    let grid_distance: UInt32Array = std::iter::repeat_n(0, batch.num_rows() - ring.len())
+        .chain(ring.iter().map(|(distance, _)| *distance))
+        .map(Some)
+        .collect();
+    let outside = batch
+        .column_by_name("hex_id")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no hex_id column".into()))?
+        .as_string::<i32>()
+        .iter()
+        .map(|id| {
+            let cell = HexCell::from_hex_id(id.unwrap_or_default())?;
+            let relation = area.relate(&cell.to_polygon());
+            Ok(Some(!relation.is_intersects() || relation.is_touches()))
+        })
+        .collect::<Result<BooleanArray, InfraHexError>>()?;
+
+    insert_before_geometry(
+        &batch,
+        [
+            (
+                Field::new("grid_distance", DataType::UInt32, false),
+                Arc::new(grid_distance) as ArrayRef,
+            ),
+            (
+                Field::new("outside_query_area", DataType::Boolean, false),
+                Arc::new(outside),
+            ),
+        ],
+    )
+}
+
+/// Rows for `cells` in a batch with `schema`, with 0 in every count column and
+/// null aggregations.
+fn zero_hex_rows(schema: &SchemaRef, cells: &[&HexCell]) -> Result<RecordBatch, InfraHexError> {
+    let mut columns = Vec::with_capacity(schema.fields().len());
+    for field in schema.fields() {
+        let column: ArrayRef = match field.name().as_str() {
+            "hex_id" => Arc::new(
+                cells
+                    .iter()
+                    .map(|cell| Some(cell.id.as_str()))
+                    .collect::<StringArray>(),
+            ),
+            "geometry" => build_polygon_geometry(cells).0,
+            name if Aggregation::from_column_name(name).is_some() => {
+                new_null_array(field.data_type(), cells.len())
+            }
+            _ => {
+                let zeros: ArrayRef = Arc::new(UInt32Array::from(vec![0; cells.len()]));
+                cast(&zeros, field.data_type()).map_err(|e| {
+                    InfraHexError::Geometry(format!(
+                        "Cannot fill empty hex rows of {}: {}",
+                        field.name(),
+                        e
+                    ))
+                })?
+            }
+        };
+        columns.push(column);
+    }
+    RecordBatch::try_new(schema.clone(), columns)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Parses a list of hex ids, all at one resolution (`resolution`, if given),
+/// keeping the first occurrence of each. Returns the cells in input order and the
+/// number of duplicates dropped.
+///
+/// # Errors
+/// Returns [`InfraHexError::Config`] if the list is empty, an id is not a valid
+/// hex id, or the ids mix resolutions or are not at `resolution`.
+pub fn parse_hex_cells(
+    ids: &[String],
+    resolution: Option<u8>,
+) -> Result<(Vec<HexCell>, usize), InfraHexError> {
+    let mut seen = HashSet::new();
+    let mut cells: Vec<HexCell> = Vec::with_capacity(ids.len());
+    for id in ids {
+        if !seen.insert(id.as_str()) {
+            continue;
+        }
+        let cell = HexCell::from_hex_id(id)
+            .map_err(|e| InfraHexError::Config(format!("Invalid hex id {:?}: {}", id, e)))?;
+        if let Some(resolution) = resolution
+            && cell.zoom_level != resolution
+        {
+            return Err(InfraHexError::Config(format!(
+                "cell {} is at resolution {}, expected resolution {}",
+                cell.id, cell.zoom_level, resolution
+            )));
+        }
+        if let Some(first) = cells.first()
+            && first.zoom_level != cell.zoom_level
+        {
+            return Err(InfraHexError::Config(format!(
+                "cells mix resolutions {} ({}) and {} ({}); pass cells of a single resolution",
+                first.zoom_level, first.id, cell.zoom_level, cell.id
+            )));
+        }
+        cells.push(cell);
+    }
+    if cells.is_empty() {
+        return Err(InfraHexError::Config("cells must not be empty".into()));
+    }
+    let duplicates = ids.len() - cells.len();
+    Ok((cells, duplicates))
+}
+
+/// The WGS84 bounding box of the hex polygons of `cells`.
+pub fn hex_cells_bbox(cells: &[HexCell]) -> Result<BBox, InfraHexError> {
+    let polygons: MultiPolygon<f64> = cells.iter().map(|cell| cell.to_polygon()).collect();
+    let extent = polygons
+        .bounding_rect()
+        .ok_or_else(|| InfraHexError::Config("cells must not be empty".into()))?;
+    bng_extent_to_wgs84(
+        extent.min().x,
+        extent.min().y,
+        extent.max().x,
+        extent.max().y,
+    )
+}
+
+/// Schema metadata recording `data_bbox`, the WGS84 bounding box of the hex
+/// polygons a summary batch returns, as a JSON `[min_lat, min_lon, max_lat,
+/// max_lon]` array.
+///
+/// Unlike the requested bbox it grows to whole hexes along the edges and shrinks
+/// where no pipes were returned. A batch with no rows has no `data_bbox`.
+pub fn data_bbox_metadata(batch: &RecordBatch) -> Result<Option<(String, String)>, InfraHexError> {
+    let polygons: MultiPolygon<f64> = summary_cells(batch)?
+        .into_iter()
+        .map(|cell| cell.polygon)
+        .collect();
+    let Some(extent) = polygons.bounding_rect() else {
+        return Ok(None);
+    };
+    let bbox = bng_extent_to_wgs84(
+        extent.min().x,
+        extent.min().y,
+        extent.max().x,
+        extent.max().y,
+    )?;
+    let corners = json!([bbox.min_lat, bbox.min_lon, bbox.max_lat, bbox.max_lon]);
+    Ok(Some(("data_bbox".to_string(), corners.to_string())))
+}
+
+/// Schema metadata recording the query a batch answers, so that it (and any file
+/// written from it) describes its own provenance: `query_bbox`, the WGS84 box
+/// fetched, as a JSON `[min_lat, min_lon, max_lat, max_lon]` array;
+/// `query_object_id`, the OBJECTID of a boundary looked up by one; and `zoom`, the
+/// hex resolution. Each is left out when `None`.
+pub fn query_metadata(
+    bbox: Option<&BBox>,
+    object_id: Option<i64>,
+    zoom: Option<u8>,
+) -> Vec<(String, String)> {
+    let bbox = bbox.map(|bbox| {
+        let corners = json!([bbox.min_lat, bbox.min_lon, bbox.max_lat, bbox.max_lon]);
+        ("query_bbox".to_string(), corners.to_string())
+    });
+    let object_id = object_id.map(|id| ("query_object_id".to_string(), id.to_string()));
+    let zoom = zoom.map(|zoom| ("zoom".to_string(), zoom.to_string()));
+    bbox.into_iter().chain(object_id).chain(zoom).collect()
+}
+
+/// Reorders a summary batch to one row per cell of `cells`, in that order, adding
+/// rows with 0 in every count column for cells the batch has no pipes in and
+/// dropping hexes not listed. Schema metadata is preserved.
+pub fn select_hex_cells(
+    batch: RecordBatch,
+    cells: &[HexCell],
+) -> Result<RecordBatch, InfraHexError> {
+    let hex_ids = batch
+        .column_by_name("hex_id")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no hex_id column".into()))?
+        .as_string::<i32>();
+    let rows: HashMap<&str, u32> = hex_ids
+        .iter()
+        .enumerate()
+        .filter_map(|(row, id)| Some((id?, row as u32)))
+        .collect();
+
+    let missing: Vec<&HexCell> = cells
+        .iter()
+        .filter(|cell| !rows.contains_key(cell.id.as_str()))
+        .collect();
+    let mut next_missing = batch.num_rows() as u32;
+    let indices: UInt32Array = cells
+        .iter()
+        .map(|cell| {
+            Some(rows.get(cell.id.as_str()).copied().unwrap_or_else(|| {
+                next_missing += 1;
+                next_missing - 1
+            }))
+        })
+        .collect();
+
+    let schema = batch.schema();
+    let empty = zero_hex_rows(&schema, &missing)?;
+    let batch = concat_batches(&schema, [&batch, &empty])
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+    take_record_batch(&batch, &indices).map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Adds a `parent_hex_id` column (after `hex_id`) giving each hex's ancestor at
+/// `parent_zoom`.
+///
+/// n3gb cells do not nest exactly, so the parent is the `parent_zoom` cell that
+/// contains the child's centre point. Every child therefore has exactly one parent.
+/// The column is dictionary encoded, since siblings repeat their parent's id.
+pub fn with_parent_hex_ids(
+    batch: RecordBatch,
+    parent_zoom: u8,
+) -> Result<RecordBatch, InfraHexError> {
+    let hex_ids = batch
+        .column_by_name("hex_id")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no hex_id column".into()))?
+        .as_string::<i32>();
+
+    let parent_ids = hex_ids
+        .iter()
+        .flatten()
+        .map(|id| {
+            let cell = HexCell::from_hex_id(id)?;
+            Ok(HexCell::from_bng(&cell.center, parent_zoom)?.id)
+        })
+        .collect::<Result<Vec<_>, InfraHexError>>()?;
+    // Many hexes share each parent, so the ids are stored once in a dictionary
+    let parent_ids: DictionaryArray<Int32Type> =
+        parent_ids.iter().map(|id| Some(id.as_str())).collect();
+
+    let insert_at = batch
+        .schema()
+        .index_of("hex_id")
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?
+        + 1;
+    insert_columns(
+        &batch,
+        insert_at,
+        [(
+            Field::new_dictionary("parent_hex_id", DataType::Int32, DataType::Utf8, false),
+            Arc::new(parent_ids) as ArrayRef,
+        )],
+    )
+}
+
+/// Adds `resolution` (UInt8, the hex zoom) and `provider` (dictionary-encoded
+/// string, the network the records came from) columns before `geometry`, so
+/// summaries concatenated from several calls keep their provenance. A summary
+/// of grid squares has no zoom and gets only `provider`.
+///
+/// `provider` is `None` for records read from a file or Arrow data, whose origin
+/// is unknown, and is then null in every row.
+pub fn with_metadata_columns(
+    batch: RecordBatch,
+    zoom: Option<u8>,
+    provider: Option<&str>,
+) -> Result<RecordBatch, InfraHexError> {
+    let rows = batch.num_rows();
+    let keys = Int32Array::from(vec![provider.map(|_| 0); rows]);
+    let values = StringArray::from_iter_values(provider);
+    let provider = DictionaryArray::<Int32Type>::try_new(keys, Arc::new(values))
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+
+    let mut added = vec![(
+        Field::new_dictionary("provider", DataType::Int32, DataType::Utf8, true),
+        Arc::new(provider) as ArrayRef,
+    )];
+    if let Some(zoom) = zoom {
+        added.insert(
+            0,
+            (
+                Field::new("resolution", DataType::UInt8, false),
+                Arc::new(UInt8Array::from(vec![zoom; rows])),
+            ),
+        );
+    }
+    insert_before_geometry(&batch, added)
+}
+
+/// Adds a `query_id` column (dictionary-encoded string) before `geometry`, holding
+/// `query_id` in every row, so rows of summaries gathered in one table can be
+/// traced back to the query each came from.
+pub fn with_query_id_column(
+    batch: RecordBatch,
+    query_id: &str,
+) -> Result<RecordBatch, InfraHexError> {
+    let keys = Int32Array::from(vec![0; batch.num_rows()]);
+    let values = StringArray::from(vec![query_id]);
+    let column = DictionaryArray::<Int32Type>::try_new(keys, Arc::new(values))
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+
+    insert_before_geometry(
+        &batch,
+        [(
+            Field::new_dictionary("query_id", DataType::Int32, DataType::Utf8, false),
+            Arc::new(column) as ArrayRef,
+        )],
+    )
+}
+
+/// Adds `area_name` (dictionary-encoded string) and `object_id` (Int64) columns
+/// before `geometry`, naming the area, `name` with OBJECTID `object_id`, that
+/// every row of its summary belongs to, so that summaries of several areas can be
+/// stacked with [`stack_area_summaries`].
+pub fn with_area_columns(
+    batch: RecordBatch,
+    name: &str,
+    object_id: i64,
+) -> Result<RecordBatch, InfraHexError> {
+    let rows = batch.num_rows();
+    let keys = Int32Array::from(vec![0; rows]);
+    let names =
+        DictionaryArray::<Int32Type>::try_new(keys, Arc::new(StringArray::from(vec![name])))
+            .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+
+    insert_before_geometry(
+        &batch,
+        [
+            (
+                Field::new_dictionary("area_name", DataType::Int32, DataType::Utf8, false),
+                Arc::new(names) as ArrayRef,
+            ),
+            (
+                Field::new("object_id", DataType::Int64, false),
+                Arc::new(Int64Array::from(vec![object_id; rows])),
+            ),
+        ],
+    )
+}
+
+/// Stacks the summaries of several areas, each with [`with_area_columns`], into
+/// one batch, keeping their rows in order. The result has no schema metadata, as
+/// each area's describes that area alone.
+///
+/// # Errors
+/// Returns [`InfraHexError::Config`] if `batches` is empty, and
+/// [`InfraHexError::Geometry`] if their columns differ.
+pub fn stack_area_summaries(batches: &[RecordBatch]) -> Result<RecordBatch, InfraHexError> {
+    let first = batches
+        .first()
+        .ok_or_else(|| InfraHexError::Config("No area summaries to stack".into()))?;
+    let schema = Arc::new(Schema::new(first.schema().fields().clone()));
+    let batches = batches
+        .iter()
+        .map(|batch| RecordBatch::try_new(Arc::clone(&schema), batch.columns().to_vec()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+    concat_batches(&schema, &batches).map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// What `pipe_count` counts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CountMode {
+    /// Every record counts once per hex it crosses.
+    #[default]
+    Records,
+    /// Records sharing an `asset_id` count once per hex between them, so a main
+    /// split into several segments is one pipe. Records without an id count
+    /// individually.
+    UniqueAssets,
+}
+
+impl CountMode {
+    pub fn from_name(name: &str) -> Result<Self, InfraHexError> {
+        match name {
+            "records" => Ok(Self::Records),
+            "unique_assets" => Ok(Self::UniqueAssets),
+            other => Err(InfraHexError::Config(format!(
+                "Unknown count_mode {:?}; expected \"records\" or \"unique_assets\"",
+                other
+            ))),
+        }
+    }
+
+    /// Whether to drop repeated asset ids before counting when `requested`.
+    ///
+    /// [`CountMode::UniqueAssets`] already counts a repeated id once per hex, and
+    /// needs every segment of an asset to find all the hexes it crosses.
+    pub fn deduplicate(self, requested: bool) -> bool {
+        requested && self == Self::Records
+    }
+}
+
+/// Applies `mode` to a summary built from `records`, recounting `pipe_count` in
+/// place. [`CountMode::Records`] returns the batch unchanged.
+pub fn with_count_mode(
+    batch: RecordBatch,
+    records: &[PipelineRecord],
+    zoom: u8,
+    assignment: Assignment,
+    mode: CountMode,
+) -> Result<RecordBatch, InfraHexError> {
+    with_count_mode_by_source(batch, &[("", records)], zoom, assignment, mode)
+}
+
+/// [`with_count_mode`] for a [`to_hex_summary_by_source`] batch, also recounting
+/// each `count_<name>` column.
+pub fn with_count_mode_by_source(
+    batch: RecordBatch,
+    sources: &[(&str, &[PipelineRecord])],
+    zoom: u8,
+    assignment: Assignment,
+    mode: CountMode,
+) -> Result<RecordBatch, InfraHexError> {
+    if mode == CountMode::Records {
+        return Ok(batch);
+    }
+
+    let schema = batch.schema();
+    let hex_ids = batch
+        .column_by_name("hex_id")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no hex_id column".into()))?
+        .as_string::<i32>();
+    let mut columns = batch.columns().to_vec();
+
+    let mut recount = |name: &str, counts: HashMap<String, u32>| {
+        if let Ok(index) = schema.index_of(name) {
+            let counts: UInt32Array = hex_ids
+                .iter()
+                .map(|id| Some(id.and_then(|id| counts.get(id)).copied().unwrap_or(0)))
+                .collect();
+            columns[index] = Arc::new(counts);
+        }
+    };
+    recount(
+        "pipe_count",
+        unique_asset_counts(
+            sources.iter().flat_map(|(_, records)| *records),
+            zoom,
+            assignment,
+        )?,
+    );
+    if sources.len() > 1 {
+        for (name, records) in sources {
+            recount(
+                &format!("count_{}", name),
+                unique_asset_counts(*records, zoom, assignment)?,
+            );
+        }
+    }
+
+    RecordBatch::try_new(schema.clone(), columns)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Counts, per hex, the distinct `asset_id`s among the records assigned to it;
+/// records without an id count once each.
+fn unique_asset_counts<'a>(
+    records: impl IntoIterator<Item = &'a PipelineRecord>,
+    zoom: u8,
+    assignment: Assignment,
+) -> Result<HashMap<String, u32>, InfraHexError> {
+    let to_bng = Transform::wgs84_to_bng()?;
+    let mut assets: HashMap<String, HashSet<&str>> = HashMap::new();
+    let mut counts: HashMap<String, u32> = HashMap::new();
+
+    for record in records {
+        for id in record_hex_cells(record, zoom, assignment, &to_bng)?
+            .into_iter()
+            .map(|cell| cell.id)
+        {
+            let first_sighting = match record.asset_id.as_deref() {
+                Some(asset_id) => assets.entry(id.clone()).or_default().insert(asset_id),
+                None => true,
+            };
+            if first_sighting {
+                *counts.entry(id).or_default() += 1;
+            }
+        }
+    }
+
+    Ok(counts)
+}
+
+/// Adds a UInt32 `boundary_pipe_count` column after `pipe_count`: how many of each
+/// hex's counted pipes also cross other hexes, so their assignment to this hex
+/// depends on the binning rule.
+///
+/// Pipes are counted as `mode` counts them, so under [`CountMode::UniqueAssets`]
+/// an asset whose segments together span several hexes is on a boundary even if
+/// each segment lies within one.
+pub fn with_boundary_pipe_counts<'a>(
+    batch: RecordBatch,
+    records: impl IntoIterator<Item = &'a PipelineRecord>,
+    zoom: u8,
+    assignment: Assignment,
+    mode: CountMode,
+) -> Result<RecordBatch, InfraHexError> {
+    let counts = boundary_pipe_counts(records, zoom, assignment, mode)?;
+    let hex_ids = batch
+        .column_by_name("hex_id")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no hex_id column".into()))?
+        .as_string::<i32>();
+    let boundary: UInt32Array = hex_ids
+        .iter()
+        .map(|id| Some(id.and_then(|id| counts.get(id)).copied().unwrap_or(0)))
+        .collect();
+
+    let insert_at = batch
+        .schema()
+        .index_of("pipe_count")
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?
+        + 1;
+    insert_columns(
+        &batch,
+        insert_at,
+        [(
+            Field::new("boundary_pipe_count", DataType::UInt32, false),
+            Arc::new(boundary) as ArrayRef,
+        )],
+    )
+}
+
+/// Counts, per hex, the pipes assigned to it that are assigned to at least one
+/// other hex.
+fn boundary_pipe_counts<'a>(
+    records: impl IntoIterator<Item = &'a PipelineRecord>,
+    zoom: u8,
+    assignment: Assignment,
+    mode: CountMode,
+) -> Result<HashMap<String, u32>, InfraHexError> {
+    let to_bng = Transform::wgs84_to_bng()?;
+    let mut pipes: Vec<HashSet<String>> = Vec::new();
+    let mut assets: HashMap<&str, usize> = HashMap::new();
+
+    for record in records {
+        let cells = record_hex_cells(record, zoom, assignment, &to_bng)?
+            .into_iter()
+            .map(|cell| cell.id);
+        let asset = match (mode, record.asset_id.as_deref()) {
+            (CountMode::UniqueAssets, Some(asset_id)) => Some(asset_id),
+            _ => None,
+        };
+        match asset.and_then(|asset_id| assets.get(asset_id)) {
+            Some(&index) => pipes[index].extend(cells),
+            None => {
+                if let Some(asset_id) = asset {
+                    assets.insert(asset_id, pipes.len());
+                }
+                pipes.push(cells.collect());
+            }
+        }
+    }
+
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for cells in pipes.into_iter().filter(|cells| cells.len() > 1) {
+        for id in cells {
+            *counts.entry(id).or_default() += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/// How each asset contributes to the hexes it passes through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Weighting {
+    /// One per asset per hex, as in `pipe_count`.
+    #[default]
+    Count,
+    /// Each asset's length split across its hexes, in a `weighted_count` column.
+    Length,
+}
+
+impl Weighting {
+    pub fn from_name(name: &str) -> Result<Self, InfraHexError> {
+        match name {
+            "count" => Ok(Self::Count),
+            "length" => Ok(Self::Length),
+            other => Err(InfraHexError::Config(format!(
+                "Unknown weighting {:?}; expected \"count\" or \"length\"",
+                other
+            ))),
+        }
+    }
+}
+
+/// Applies `weighting` to a summary built from `records`.
+///
+/// [`Weighting::Length`] adds a Float64 `weighted_count` column after `pipe_count`:
+/// each asset contributes the fraction of its BNG length that falls inside each
+/// hex, so an asset's contributions sum to 1. Zero-length assets count 1 towards
+/// the hex containing them, and under [`Assignment::Midpoint`] every asset counts
+/// 1 towards its one hex. [`Weighting::Count`] returns the batch unchanged.
+pub fn with_weighting<'a>(
+    batch: RecordBatch,
+    records: impl IntoIterator<Item = &'a PipelineRecord>,
+    zoom: u8,
+    assignment: Assignment,
+    weighting: Weighting,
+) -> Result<RecordBatch, InfraHexError> {
+    if weighting == Weighting::Count {
+        return Ok(batch);
+    }
+
+    let weights = length_weights(records, zoom, assignment)?;
+    let hex_ids = batch
+        .column_by_name("hex_id")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no hex_id column".into()))?
+        .as_string::<i32>();
+    let weighted: Float64Array = hex_ids
+        .iter()
+        .map(|id| Some(id.and_then(|id| weights.get(id)).copied().unwrap_or(0.0)))
+        .collect();
+
+    let insert_at = batch
+        .schema()
+        .index_of("pipe_count")
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?
+        + 1;
+    insert_columns(
+        &batch,
+        insert_at,
+        [(
+            Field::new("weighted_count", DataType::Float64, false),
+            Arc::new(weighted) as ArrayRef,
+        )],
+    )
+}
+
+/// Sums each asset's per-hex length fractions, keyed by hex id.
+fn length_weights<'a>(
+    records: impl IntoIterator<Item = &'a PipelineRecord>,
+    zoom: u8,
+    assignment: Assignment,
+) -> Result<HashMap<String, f64>, InfraHexError> {
+    let to_bng = Transform::wgs84_to_bng()?;
+    let mut weights: HashMap<String, f64> = HashMap::new();
+
+    for record in records {
+        let Some(lines) = record_lines(record) else {
+            continue;
+        };
+        let lines = to_bng.apply(&lines)?;
+        if assignment == Assignment::Midpoint {
+            if let Some(cell) = midpoint_hex_cell(&lines, zoom)? {
+                *weights.entry(cell.id).or_default() += 1.0;
+            }
+            continue;
+        }
+
+        let shares = hex_lengths(&lines, zoom)?;
+        let total: f64 = shares.values().sum();
+        if total > 0.0 {
+            for (id, length) in shares {
+                *weights.entry(id).or_default() += length / total;
+            }
+        } else if let Some(start) = lines.iter().flat_map(|line| line.coords()).next() {
+            let cell = HexCell::from_bng(&(start.x, start.y), zoom)?;
+            *weights.entry(cell.id).or_default() += 1.0;
+        }
+    }
+
+    Ok(weights)
+}
+
+/// The length of the BNG `lines` inside each hex at `zoom` they pass through,
+/// ordered by hex id so that totals over it are summed the same way on every run.
+fn hex_lengths(
+    lines: &MultiLineString<f64>,
+    zoom: u8,
+) -> Result<BTreeMap<String, f64>, InfraHexError> {
+    let mut lengths: BTreeMap<String, f64> = BTreeMap::new();
+    for cell in line_hex_cells(lines, zoom)? {
+        if let Entry::Vacant(entry) = lengths.entry(cell.id.clone()) {
+            entry.insert(Euclidean.length(&cell.to_polygon().clip(lines, false)));
+        }
+    }
+    Ok(lengths)
+}
+
+/// A statistic computed per hex over a record attribute, requested with
+/// `aggregations=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Min,
+    Max,
+    Mean,
+    Sum,
+    /// The number of distinct values, of any field.
+    CountDistinct,
+}
+
+impl AggregateFunction {
+    const ALL: [Self; 5] = [
+        Self::Min,
+        Self::Max,
+        Self::Mean,
+        Self::Sum,
+        Self::CountDistinct,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Mean => "mean",
+            Self::Sum => "sum",
+            Self::CountDistinct => "count_distinct",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, InfraHexError> {
+        Self::ALL
+            .into_iter()
+            .find(|function| function.name() == name)
+            .ok_or_else(|| {
+                InfraHexError::Config(format!(
+                    "Unknown aggregation {:?}; expected one of: {}",
+                    name,
+                    Self::ALL.map(Self::name).join(", ")
+                ))
+            })
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            Self::CountDistinct => DataType::UInt32,
+            _ => DataType::Float64,
+        }
+    }
+
+    /// The statistic over a hex's non-null `values`, of which there is at least
+    /// one.
+    fn apply(self, values: &[RecordValue<'_>]) -> f64 {
+        let numbers = values.iter().filter_map(|value| match value {
+            RecordValue::Number(number) => Some(*number),
+            RecordValue::Text(_) => None,
+        });
+        match self {
+            Self::Min => numbers.fold(f64::INFINITY, f64::min),
+            Self::Max => numbers.fold(f64::NEG_INFINITY, f64::max),
+            Self::Sum => numbers.sum(),
+            Self::Mean => numbers.sum::<f64>() / values.len() as f64,
+            Self::CountDistinct => {
+                // Numbers by their bits, with -0.0 the same as 0.0
+                let distinct: HashSet<Result<&str, u64>> = values
+                    .iter()
+                    .map(|value| match value {
+                        RecordValue::Text(text) => Ok(*text),
+                        RecordValue::Number(number) => Err((number + 0.0).to_bits()),
+                    })
+                    .collect();
+                distinct.len() as f64
+            }
+        }
+    }
+}
+
+/// A `<field>_<function>` summary column, e.g. `diameter_max`, requested with
+/// `aggregations=`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aggregation {
+    /// The Cadent field name, one of [`RECORD_FIELDS`].
+    pub field: &'static str,
+    pub function: AggregateFunction,
+}
+
+impl Aggregation {
+    /// # Errors
+    /// Returns [`InfraHexError::Config`] if pipe records do not carry `field`,
+    /// `function` is unknown, or it is numeric and `field` is not.
+    pub fn new(field: &str, function: &str) -> Result<Self, InfraHexError> {
+        let function = AggregateFunction::from_name(function)?;
+        let field = RECORD_FIELDS
+            .into_iter()
+            .find(|name| *name == field)
+            .ok_or_else(|| {
+                InfraHexError::Config(format!(
+                    "Pipe records do not carry field {:?}; they carry: {}",
+                    field,
+                    RECORD_FIELDS.join(", ")
+                ))
+            })?;
+        if function != AggregateFunction::CountDistinct && !is_number_field(field) {
+            return Err(InfraHexError::Config(format!(
+                "Cannot take the {} of {:?}, which is not numeric; only count_distinct \
+                 applies to it",
+                function.name(),
+                field
+            )));
+        }
+        Ok(Self { field, function })
+    }
+
+    pub fn column_name(&self) -> String {
+        format!("{}_{}", self.field, self.function.name())
+    }
+
+    /// The aggregation a summary column named `name` holds, if it is one.
+    pub fn from_column_name(name: &str) -> Option<Self> {
+        RECORD_FIELDS.into_iter().find_map(|field| {
+            let function = name.strip_prefix(field)?.strip_prefix('_')?;
+            let function = AggregateFunction::from_name(function).ok()?;
+            Some(Self { field, function })
+        })
+    }
+
+    fn schema_field(&self) -> Field {
+        Field::new(self.column_name(), self.function.data_type(), true)
+    }
+}
+
+/// Adds a column for each of `aggregations` to a summary built from `records`,
+/// before `geometry`, holding the statistic over the values of the records
+/// assigned to each hex. Null values are skipped, and a hex where every value is
+/// null gets null. `count_distinct` columns are UInt32, the rest Float64.
+pub fn with_aggregations<'a>(
+    batch: RecordBatch,
+    records: impl IntoIterator<Item = &'a PipelineRecord>,
+    zoom: u8,
+    assignment: Assignment,
+    aggregations: &[Aggregation],
+) -> Result<RecordBatch, InfraHexError> {
+    if aggregations.is_empty() {
+        return Ok(batch);
+    }
+
+    let mut fields: Vec<&str> = aggregations.iter().map(|a| a.field).collect();
+    fields.sort_unstable();
+    fields.dedup();
+    let to_bng = Transform::wgs84_to_bng()?;
+    // The non-null values of each of `fields` among each hex's records
+    let mut values: HashMap<String, Vec<Vec<RecordValue<'a>>>> = HashMap::new();
+    for record in records {
+        let record_values: Vec<Option<RecordValue<'a>>> = fields
+            .iter()
+            .map(|field| record_value(record, field))
+            .collect();
+        if record_values.iter().all(Option::is_none) {
+            continue;
+        }
+        for cell in record_hex_cells(record, zoom, assignment, &to_bng)? {
+            let hex = values
+                .entry(cell.id)
+                .or_insert_with(|| vec![Vec::new(); fields.len()]);
+            for (slot, value) in hex.iter_mut().zip(&record_values) {
+                slot.extend(*value);
+            }
+        }
+    }
+
+    let schema = batch.schema();
+    let hex_ids = batch
+        .column_by_name("hex_id")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no hex_id column".into()))?
+        .as_string::<i32>();
+    let insert_at = schema.index_of("geometry").unwrap_or(schema.fields().len());
+    let mut added_fields = Vec::with_capacity(aggregations.len());
+    let mut added_columns: Vec<ArrayRef> = Vec::with_capacity(aggregations.len());
+    for aggregation in aggregations {
+        let index = fields
+            .iter()
+            .position(|field| *field == aggregation.field)
+            .unwrap_or_default();
+        let statistics = hex_ids.iter().map(|id| {
+            id.and_then(|id| values.get(id))
+                .map(|hex| hex[index].as_slice())
+                .filter(|values| !values.is_empty())
+                .map(|values| aggregation.function.apply(values))
+        });
+        added_columns.push(match aggregation.function {
+            AggregateFunction::CountDistinct => Arc::new(
+                statistics
+                    .map(|count| count.map(|count| count as u32))
+                    .collect::<UInt32Array>(),
+            ),
+            _ => Arc::new(statistics.collect::<Float64Array>()),
+        });
+        added_fields.push(aggregation.schema_field());
+    }
+
+    insert_columns(
+        &batch,
+        insert_at,
+        added_fields.into_iter().zip(added_columns),
+    )
+}
+
+/// How pipe lengths are measured, chosen with `length_method=`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LengthMethod {
+    /// Along the WGS84 ellipsoid (Karney's algorithm), the most accurate.
+    #[default]
+    Geodesic,
+    /// Along a sphere of mean Earth radius, within about 0.5% of geodesic.
+    Haversine,
+    /// Euclidean length in BNG (EPSG:27700) metres. The projection's scale factor
+    /// varies from 0.9996 to about 1.0007 across Great Britain.
+    Projected27700,
+}
+
+impl LengthMethod {
+    pub fn from_name(name: &str) -> Result<Self, InfraHexError> {
+        match name {
+            "geodesic" => Ok(Self::Geodesic),
+            "haversine" => Ok(Self::Haversine),
+            "projected_27700" => Ok(Self::Projected27700),
+            other => Err(InfraHexError::Config(format!(
+                "Unknown length_method {:?}; expected \"geodesic\", \"haversine\" or \
+                 \"projected_27700\"",
+                other
+            ))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Geodesic => "geodesic",
+            Self::Haversine => "haversine",
+            Self::Projected27700 => "projected_27700",
+        }
+    }
+
+    /// The schema metadata entry recording this method.
+    pub fn metadata(self) -> (String, String) {
+        ("length_method".to_string(), self.name().to_string())
+    }
+}
+
+/// The total length of the records' line geometry in metres, measured with
+/// `method`.
+pub fn total_pipe_length_m<'a>(
+    records: impl IntoIterator<Item = &'a PipelineRecord>,
+    method: LengthMethod,
+) -> Result<f64, InfraHexError> {
+    let to_bng = Transform::wgs84_to_bng()?;
+    let mut total = 0.0;
+    for record in records {
+        if let Some(lines) = record_lines(record) {
+            total += match method {
+                LengthMethod::Geodesic => Geodesic.length(&lines),
+                LengthMethod::Haversine => Haversine.length(&lines),
+                LengthMethod::Projected27700 => Euclidean.length(&to_bng.apply(&lines)?),
+            };
+        }
+    }
+    Ok(total)
+}
+
+/// Totals describing a summary, as returned with `with_stats=True`.
+///
+/// Every field is 0 for an empty summary. `total_pipe_length_m` is only computed
+/// for [`Weighting::Length`] summaries, with the summary's [`LengthMethod`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SummaryStats {
+    /// Records binned, after deduplication.
+    pub total_records: usize,
+    /// Hexes with at least one pipe.
+    pub hex_count: usize,
+    pub max_pipe_count: u32,
+    /// Mean `pipe_count` over the populated hexes.
+    pub mean_pipe_count: f64,
+    pub total_pipe_length_m: Option<f64>,
+    /// Wall-clock time spent fetching.
+    pub fetch_seconds: f64,
+}
+
+impl SummaryStats {
+    /// Reads the hex statistics from the `pipe_count` column of `batch`, leaving
+    /// the record and fetch fields at 0.
+    pub fn from_summary(batch: &RecordBatch) -> Result<Self, InfraHexError> {
+        let counts = batch
+            .column_by_name("pipe_count")
+            .ok_or_else(|| InfraHexError::Geometry("Summary has no pipe_count column".into()))?
+            .as_primitive_opt::<UInt32Type>()
+            .ok_or_else(|| InfraHexError::Geometry("pipe_count must be UInt32".into()))?;
+        let populated: Vec<u32> = counts.iter().flatten().filter(|&count| count > 0).collect();
+        let total: u64 = populated.iter().map(|&count| count as u64).sum();
+
+        Ok(Self {
+            hex_count: populated.len(),
+            max_pipe_count: populated.iter().copied().max().unwrap_or(0),
+            mean_pipe_count: if populated.is_empty() {
+                0.0
+            } else {
+                total as f64 / populated.len() as f64
+            },
+            ..Self::default()
+        })
+    }
+}
+
+/// The record's line geometry, keeping MultiLineString parts separate. Points
+/// become single-point lines, which [`line_hex_cells`] bins into the hex
+/// containing them and which have no length.
+pub(crate) fn record_lines(record: &PipelineRecord) -> Option<MultiLineString<f64>> {
+    let geometry = record.geo_shape.geometry.clone()?;
+    match Geometry::<f64>::try_from(geometry).ok()? {
+        Geometry::LineString(line) => Some(MultiLineString::new(vec![line])),
+        Geometry::MultiLineString(lines) => Some(lines),
+        Geometry::Point(point) => Some(MultiLineString::new(vec![LineString::new(vec![point.0])])),
+        Geometry::MultiPoint(points) => Some(
+            points
+                .into_iter()
+                .map(|point| LineString::new(vec![point.0]))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Returns the batch with `entries` merged into its schema metadata.
+pub fn with_metadata(
+    batch: RecordBatch,
+    entries: impl IntoIterator<Item = (String, String)>,
+) -> Result<RecordBatch, InfraHexError> {
+    let schema = batch.schema();
+    let mut metadata = schema.metadata().clone();
+    metadata.extend(entries);
+    let schema = Schema::new_with_metadata(schema.fields().clone(), metadata);
+    batch
+        .with_schema(Arc::new(schema))
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+struct SourceHexCount {
+    cell: HexCell,
+    total: usize,
+    per_source: Vec<usize>,
+}
+
+/// Aggregates records from several sources (networks or Cadent layers) into a
+/// single hex summary.
+///
+/// Each record is tagged with the name of the source it was fetched from, so
+/// alongside the combined `pipe_count` the batch has one `count_<name>` column per
+/// entry in `results`. Rows are sorted by `hex_id` ascending.
+pub fn to_hex_summary_by_source(
+    results: &[(&str, &[PipelineRecord])],
+    zoom: u8,
+    assignment: Assignment,
+) -> Result<RecordBatch, InfraHexError> {
+    let mut counts: HashMap<String, SourceHexCount> = HashMap::new();
+
+    for (source_idx, (_, records)) in results.iter().enumerate() {
+        for (id, (cell, count)) in count_record_hex_cells(records, zoom, assignment)? {
+            let entry = counts.entry(id).or_insert_with(|| SourceHexCount {
+                cell,
+                total: 0,
+                per_source: vec![0; results.len()],
+            });
+            entry.total += count as usize;
+            entry.per_source[source_idx] += count as usize;
+        }
+    }
+
+    let mut sorted: Vec<SourceHexCount> = counts.into_values().collect();
+    sorted.sort_by(|a, b| a.cell.id.cmp(&b.cell.id));
+
+    let hex_ids: StringArray = sorted.iter().map(|h| Some(h.cell.id.as_str())).collect();
+    let pipe_counts: UInt32Array = sorted.iter().map(|h| Some(h.total as u32)).collect();
+
+    let mut fields = vec![
+        Field::new("hex_id", DataType::Utf8, false),
+        Field::new("pipe_count", DataType::UInt32, false),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(hex_ids), Arc::new(pipe_counts)];
+
+    for (source_idx, (name, _)) in results.iter().enumerate() {
+        let source_counts: UInt32Array = sorted
+            .iter()
+            .map(|h| Some(h.per_source[source_idx] as u32))
+            .collect();
+        fields.push(Field::new(
+            format!("count_{}", name),
+            DataType::UInt32,
+            false,
+        ));
+        columns.push(Arc::new(source_counts));
+    }
+
+    let cells: Vec<&HexCell> = sorted.iter().map(|h| &h.cell).collect();
+    let (geometry_array, geometry_field) = build_polygon_geometry(&cells);
+    fields.push(geometry_field);
+    columns.push(geometry_array);
+
+    RecordBatch::try_new(Arc::new(summary_schema(fields)), columns)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Reads `hex_id` -> `pipe_count` from summary batches, rejecting duplicate ids.
+fn read_pipe_counts(
+    batches: &[RecordBatch],
+    label: &str,
+) -> Result<BTreeMap<String, u32>, InfraHexError> {
+    let mut counts = BTreeMap::new();
+    for batch in batches {
+        let column = |name: &str, data_type: &DataType| {
+            let array = batch.column_by_name(name).ok_or_else(|| {
+                InfraHexError::Geometry(format!("{} summary has no {} column", label, name))
+            })?;
+            cast(array, data_type).map_err(|e| {
+                InfraHexError::Geometry(format!("{} summary {} column: {}", label, name, e))
+            })
+        };
+        let hex_ids = column("hex_id", &DataType::Utf8)?;
+        let pipe_counts = column("pipe_count", &DataType::UInt32)?;
+        let hex_ids = hex_ids.as_string::<i32>();
+        let pipe_counts = pipe_counts.as_primitive::<UInt32Type>();
+
+        for (hex_id, pipe_count) in hex_ids.iter().zip(pipe_counts.iter()) {
+            let (Some(hex_id), Some(pipe_count)) = (hex_id, pipe_count) else {
+                return Err(InfraHexError::Geometry(format!(
+                    "{} summary has a null or negative hex_id/pipe_count",
+                    label
+                )));
+            };
+            if counts.insert(hex_id.to_string(), pipe_count).is_some() {
+                return Err(InfraHexError::Geometry(format!(
+                    "Duplicate hex_id {:?} in {} summary",
+                    hex_id, label
+                )));
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// Compares two hex summaries, keyed by `hex_id`.
+///
+/// Hexes present in only one input count as zero on the other side. The batch has
+/// columns `hex_id`, `pipe_count_old`, `pipe_count_new`, `delta` (new - old) and
+/// `geometry`, sorted by `hex_id`.
+///
+/// # Errors
+/// Returns [`InfraHexError::Config`] if the summaries are at different zooms (or
+/// either mixes zooms), as read from their hex ids.
+pub fn diff_hex_summaries(
+    old: &[RecordBatch],
+    new: &[RecordBatch],
+) -> Result<RecordBatch, InfraHexError> {
+    let old = read_pipe_counts(old, "old")?;
+    let mut new = read_pipe_counts(new, "new")?;
+
+    let zoom_of = |counts: &BTreeMap<String, u32>| {
+        counts
+            .keys()
+            .next()
+            .map(|hex_id| HexCell::from_hex_id(hex_id).map(|cell| cell.zoom_level))
+            .transpose()
+    };
+    let zoom = match (zoom_of(&old)?, zoom_of(&new)?) {
+        (Some(old_zoom), Some(new_zoom)) if old_zoom != new_zoom => {
+            return Err(InfraHexError::Config(format!(
+                "Cannot diff summaries at different zooms: old is zoom {}, new is zoom {}",
+                old_zoom, new_zoom
+            )));
+        }
+        (old_zoom, new_zoom) => old_zoom.or(new_zoom),
+    };
+
+    let mut rows: BTreeMap<String, (u32, u32)> = BTreeMap::new();
+    for (hex_id, old_count) in old {
+        let new_count = new.remove(&hex_id).unwrap_or(0);
+        rows.insert(hex_id, (old_count, new_count));
+    }
+    for (hex_id, new_count) in new {
+        rows.insert(hex_id, (0, new_count));
+    }
+
+    let cells = rows
+        .keys()
+        .map(|hex_id| HexCell::from_hex_id(hex_id))
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(cell) = cells.iter().find(|cell| Some(cell.zoom_level) != zoom) {
+        return Err(InfraHexError::Config(format!(
+            "Cannot diff summaries with mixed zooms: hex {:?} is zoom {}, expected {}",
+            cell.id,
+            cell.zoom_level,
+            zoom.unwrap_or_default()
+        )));
+    }
+
+    let hex_ids: StringArray = rows.keys().map(|id| Some(id.as_str())).collect();
+    let old_counts: UInt32Array = rows.values().map(|(old, _)| Some(*old)).collect();
+    let new_counts: UInt32Array = rows.values().map(|(_, new)| Some(*new)).collect();
+    let deltas: Int64Array = rows
+        .values()
+        .map(|(old, new)| Some(*new as i64 - *old as i64))
+        .collect();
+
+    let cell_refs: Vec<&HexCell> = cells.iter().collect();
+    let (geometry_array, geometry_field) = build_polygon_geometry(&cell_refs);
+
+    let schema = Schema::new(vec![
+        Field::new("hex_id", DataType::Utf8, false),
+        Field::new("pipe_count_old", DataType::UInt32, false),
+        Field::new("pipe_count_new", DataType::UInt32, false),
+        Field::new("delta", DataType::Int64, false),
+        geometry_field,
+    ]);
+
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(hex_ids),
+            Arc::new(old_counts),
+            Arc::new(new_counts),
+            Arc::new(deltas),
+            geometry_array,
+        ],
+    )
+    .map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Whether [`merge_hex_summaries`] adds up a column across summaries.
+fn is_additive_column(name: &str) -> bool {
+    matches!(
+        name,
+        "pipe_count" | "boundary_pipe_count" | "weighted_count"
+    ) || name.starts_with("count_")
+}
+
+/// Merges hex summaries of overlapping areas into one, keyed by `hex_id`.
+///
+/// `pipe_count`, `boundary_pipe_count`, `weighted_count` and `count_<source>`
+/// columns are summed per hex; every other column, geometry included, keeps its
+/// value from the first row with that hex. Rows come out in order of first
+/// appearance, with only `schema_version` in the schema metadata.
+///
+/// # Errors
+/// Returns [`InfraHexError::Config`] if there are no summaries, their columns
+/// differ, or their hexes are at different zooms.
+pub fn merge_hex_summaries(batches: &[RecordBatch]) -> Result<RecordBatch, InfraHexError> {
+    let Some(first) = batches.first() else {
+        return Err(InfraHexError::Config(
+            "merge_hex_summaries needs at least one summary".into(),
+        ));
+    };
+    let schema = Arc::new(summary_schema(first.schema().fields().clone()));
+    let columns_of = |batch: &RecordBatch| -> Vec<(String, DataType)> {
+        batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| (field.name().clone(), field.data_type().clone()))
+            .collect()
+    };
+    let expected = columns_of(first);
+    let batches = batches
+        .iter()
+        .map(|batch| {
+            if columns_of(batch) != expected {
+                return Err(InfraHexError::Config(format!(
+                    "Cannot merge summaries with different columns: {:?} and {:?}",
+                    expected.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+                    batch
+                        .schema()
+                        .fields()
+                        .iter()
+                        .map(|f| f.name())
+                        .collect::<Vec<_>>()
+                )));
+            }
+            RecordBatch::try_new(Arc::clone(&schema), batch.columns().to_vec())
+                .map_err(|e| InfraHexError::Geometry(e.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let combined =
+        concat_batches(&schema, &batches).map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+
+    let hex_ids = combined
+        .column_by_name("hex_id")
+        .ok_or_else(|| InfraHexError::Geometry("Summary has no hex_id column".into()))
+        .and_then(|array| {
+            cast(array, &DataType::Utf8).map_err(|e| InfraHexError::Geometry(e.to_string()))
+        })?;
+    let hex_ids = hex_ids.as_string::<i32>();
+
+    // Output row of each hex, the input row it was first seen at, and each input
+    // row's output row
+    let mut rows: HashMap<&str, usize> = HashMap::new();
+    let mut first_rows: Vec<u32> = Vec::new();
+    let mut groups: Vec<usize> = Vec::with_capacity(combined.num_rows());
+    let mut zoom = None;
+    for (row, hex_id) in hex_ids.iter().enumerate() {
+        let hex_id =
+            hex_id.ok_or_else(|| InfraHexError::Geometry("Summary has a null hex_id".into()))?;
+        let group = match rows.get(hex_id) {
+            Some(&group) => group,
+            None => {
+                let cell_zoom = HexCell::from_hex_id(hex_id)?.zoom_level;
+                if let Some(zoom) = zoom
+                    && zoom != cell_zoom
+                {
+                    return Err(InfraHexError::Config(format!(
+                        "Cannot merge summaries at different zooms: hex {:?} is zoom {}, \
+                         expected {}",
+                        hex_id, cell_zoom, zoom
+                    )));
+                }
+                zoom = Some(cell_zoom);
+                rows.insert(hex_id, first_rows.len());
+                first_rows.push(row as u32);
+                first_rows.len() - 1
+            }
+        };
+        groups.push(group);
+    }
+
+    let merged = take_record_batch(&combined, &UInt32Array::from(first_rows))
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+    let columns = schema
+        .fields()
+        .iter()
+        .zip(merged.columns())
+        .zip(combined.columns())
+        .map(|((field, kept), all)| {
+            if is_additive_column(field.name()) {
+                sum_by_group(field.name(), all, &groups, merged.num_rows())
+            } else {
+                Ok(Arc::clone(kept))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    RecordBatch::try_new(schema, columns).map_err(|e| InfraHexError::Geometry(e.to_string()))
+}
+
+/// Sums `column` into `len` rows, adding each row into the output row `groups`
+/// gives for it. Nulls count as zero.
+fn sum_by_group(
+    name: &str,
+    column: &ArrayRef,
+    groups: &[usize],
+    len: usize,
+) -> Result<ArrayRef, InfraHexError> {
+    match column.data_type() {
+        DataType::UInt32 => {
+            let mut sums = vec![0u32; len];
+            for (value, group) in column.as_primitive::<UInt32Type>().iter().zip(groups) {
+                sums[*group] = sums[*group].saturating_add(value.unwrap_or_default());
+            }
+            Ok(Arc::new(UInt32Array::from(sums)))
+        }
+        DataType::Float64 => {
+            let mut sums = vec![0.0; len];
+            for (value, group) in column.as_primitive::<Float64Type>().iter().zip(groups) {
+                sums[*group] += value.unwrap_or_default();
+            }
+            Ok(Arc::new(Float64Array::from(sums)))
+        }
+        other => Err(InfraHexError::Config(format!(
+            "Cannot sum {} column of type {}",
+            name, other
+        ))),
+    }
+}
+
+/// Splits a summary into a `cells` batch, holding `hex_id`, `resolution` and
+/// `geometry` once per distinct hex in order of first appearance, and a `summary`
+/// batch with every other column, so each hex polygon is stored only once.
+///
+/// Looking each `summary` row's hex up in `cells` gives back `batch`. The schema
+/// metadata stays with `summary`.
+pub fn normalise_hex_summary(
+    batch: &RecordBatch,
+) -> Result<(RecordBatch, RecordBatch), InfraHexError> {
+    let schema = batch.schema();
+    let hex_id = schema
+        .index_of("hex_id")
+        .map_err(|_| InfraHexError::Geometry("Summary has no hex_id column".into()))?;
+    let geometry = schema
+        .index_of("geometry")
+        .map_err(|_| InfraHexError::Geometry("Summary has no geometry column".into()))?;
+    let hex_ids = cast(batch.column(hex_id), &DataType::Utf8)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut first_rows: Vec<u32> = Vec::new();
+    let mut zooms: Vec<u8> = Vec::new();
+    for (row, id) in hex_ids.as_string::<i32>().iter().enumerate() {
+        let id = id.ok_or_else(|| InfraHexError::Geometry("Summary has a null hex_id".into()))?;
+        if seen.insert(id) {
+            first_rows.push(row as u32);
+            zooms.push(HexCell::from_hex_id(id)?.zoom_level);
+        }
+    }
+    let first_rows = UInt32Array::from(first_rows);
+    let take = |index: usize| {
+        arrow_select::take::take(batch.column(index).as_ref(), &first_rows, None)
+            .map_err(|e| InfraHexError::Geometry(e.to_string()))
+    };
+    let cells = RecordBatch::try_new(
+        Arc::new(Schema::new(vec![
+            schema.field(hex_id).clone(),
+            Field::new("resolution", DataType::UInt8, false),
+            schema.field(geometry).clone(),
+        ])),
+        vec![
+            take(hex_id)?,
+            Arc::new(UInt8Array::from(zooms)),
+            take(geometry)?,
+        ],
+    )
+    .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+
+    let measures: Vec<usize> = (0..schema.fields().len())
+        .filter(|&index| index != geometry && schema.field(index).name() != "resolution")
+        .collect();
+    let summary = batch
+        .project(&measures)
+        .map_err(|e| InfraHexError::Geometry(e.to_string()))?;
+    Ok((cells, summary))
+}
@@ -0,0 +1,363 @@
+"""A local HTTP server standing in for Cadent's OpenDataSoft API in tests.
+
+Point a fetch at it with `http_options={"cadent_url": server.cadent_url, ...}`.
+Like wiremock, it answers from canned data and records every request, and can be
+told to misbehave: reply 429 a number of times first, fail given pages once,
+answer given pages slowly, unordered, shifted or with no records, or ignore
+`offset`. Records are filtered by the `in_bbox`, `<field> >= date'...'` /
+`<field> < date'...'` and `intersects(geo_shape,geom'<WKT>')` conditions of
+`where`, sorted by the field in `order_by`, and trimmed to the fields in
+`select`. `/datasets/<id>` lists the fields the records have.
+`server.ons_url` is an ArcGIS feature layer: count queries answer 0 and feature
+queries return `ons_features` (none by default), narrowed to the ids of an
+`OBJECTID IN (...)` condition or the name of an `UPPER(BUA24NM)='...'` one, as
+GeoJSON for `f=geojson` and Esri JSON for `f=json`, enough for `check_endpoints`,
+`warmup` and built-up area lookups.
+`RecordingProxy` is a forward proxy for plain HTTP requests that records what
+reaches it, and `RedirectServer` redirects every request to another server.
+"""
+
+import http.server
+import json
+import re
+import threading
+import time
+import urllib.error
+import urllib.parse
+import urllib.request
+
+import shapely
+
+IN_BBOX = re.compile(r"in_bbox\(geo_point_2d,([^,]+),([^,]+),([^,]+),([^,]+)\)")
+DATE_BOUND = re.compile(r"(\w+) (>=|<) date'([^']+)'")
+INTERSECTS = re.compile(r"intersects\(geo_shape,geom'([^']+)'\)")
+OBJECTID_IN = re.compile(r"OBJECTID IN \(([^)]*)\)")
+NAME_EQUALS = re.compile(r"UPPER\(BUA24NM\)='((?:[^']|'')*)'")
+
+
+def esri_feature(feature):
+    """Convert a GeoJSON polygon feature to Esri JSON, clockwise outer rings first."""
+    shape = shapely.geometry.shape(feature["geometry"])
+    rings = []
+    for polygon in getattr(shape, "geoms", [shape]):
+        polygon = shapely.geometry.polygon.orient(polygon, sign=-1.0)
+        for ring in [polygon.exterior, *polygon.interiors]:
+            rings.append([list(point) for point in ring.coords])
+    return {"attributes": feature["properties"], "geometry": {"rings": rings}}
+
+
+def cadent_record(feature):
+    """Convert a GeoJSON pipe feature to a Cadent record, located at its centroid."""
+    centroid = shapely.geometry.shape(feature["geometry"]).centroid
+    return {
+        **feature["properties"],
+        "geo_point_2d": {"lon": centroid.x, "lat": centroid.y},
+        "geo_shape": {
+            "type": "Feature",
+            "geometry": feature["geometry"],
+            "properties": {},
+        },
+    }
+
+
+class FixtureServer:
+    """Serves `features` as the records of every Cadent dataset.
+
+    `rate_limited` is how many requests get a 429 with `Retry-After: 0` before
+    any succeeds. The first request for each offset in `fail_offsets` gets a
+    500, and every request for an offset in `slow_offsets` is answered after
+    `page_delay` seconds. Pages at an offset in `unordered_offsets` ignore
+    `order_by`, taking their records from the reversed order, and those in
+    `shifted_offsets` start one record early, as if a record had moved between
+    requests. `empty_pages` is how many record pages come back
+    empty, with the true `total_count`, as Cadent sometimes answers under load.
+    With `ignore_offset`, every page starts at the first matching record, as on
+    servers that do not support paging. `ons_features` are returned for every
+    ONS feature query, whatever its `where` unless it lists OBJECTIDs with `IN`,
+    in any of the `ons_formats` (`f=` values); other formats get ArcGIS's 200
+    with an error object, as do `IN` queries with `reject_in_filters`. With
+    `reject_polygons`, Cadent queries with an `intersects` condition get a 400,
+    as from a server without polygon filters. `requests` lists the parsed query
+    of each request received, `records_served` counts the records returned
+    across every page, and `bytes_served` the bytes of every successful
+    response body.
+    """
+
+    def __init__(
+        self,
+        features,
+        *,
+        rate_limited=0,
+        empty_pages=0,
+        ignore_offset=False,
+        ons_features=(),
+        reject_polygons=False,
+        fail_offsets=(),
+        ons_formats=("geojson", "json"),
+        reject_in_filters=False,
+        slow_offsets=(),
+        page_delay=0.0,
+        unordered_offsets=(),
+        shifted_offsets=(),
+    ):
+        self.records = [cadent_record(feature) for feature in features]
+        self.ons_features = list(ons_features)
+        self.ons_formats = set(ons_formats)
+        self.rate_limited = rate_limited
+        self.empty_pages = empty_pages
+        self.ignore_offset = ignore_offset
+        self.reject_polygons = reject_polygons
+        self.reject_in_filters = reject_in_filters
+        self.fail_offsets = {int(offset) for offset in fail_offsets}
+        self.slow_offsets = {int(offset) for offset in slow_offsets}
+        self.page_delay = page_delay
+        self.unordered_offsets = {int(offset) for offset in unordered_offsets}
+        self.shifted_offsets = {int(offset) for offset in shifted_offsets}
+        self.requests = []
+        self.records_served = 0
+        self.bytes_served = 0
+        self._lock = threading.Lock()
+        self._server = http.server.ThreadingHTTPServer(
+            ("127.0.0.1", 0), self._handler()
+        )
+
+    @property
+    def cadent_url(self):
+        return "http://127.0.0.1:{}/datasets".format(self._server.server_port)
+
+    @property
+    def ons_url(self):
+        return "http://127.0.0.1:{}/ons/FeatureServer/0".format(
+            self._server.server_port
+        )
+
+    def __enter__(self):
+        threading.Thread(target=self._server.serve_forever, daemon=True).start()
+        return self
+
+    def __exit__(self, *exc):
+        self._server.shutdown()
+        self._server.server_close()
+
+    def _respond(self, path, query):
+        with self._lock:
+            self.requests.append(query)
+            if self.rate_limited > 0:
+                self.rate_limited -= 1
+                return 429, {"Retry-After": "0"}, {"error": "rate limited"}
+            if "offset" in query and int(query["offset"]) in self.fail_offsets:
+                self.fail_offsets.discard(int(query["offset"]))
+                return 500, {}, {"error": "internal error"}
+        if "offset" in query and int(query["offset"]) in self.slow_offsets:
+            time.sleep(self.page_delay)
+
+        if path == "/ons/FeatureServer/0/query":
+            if query.get("returnCountOnly"):
+                return 200, {}, {"count": 0}
+            if query.get("f") not in self.ons_formats:
+                return 200, {}, {"error": {"code": 400, "message": "Invalid format"}}
+            features = self.ons_features
+            ids = OBJECTID_IN.search(query.get("where", ""))
+            if ids and self.reject_in_filters:
+                return 200, {}, {"error": {"code": 400, "message": "Invalid query"}}
+            if ids:
+                wanted = {int(i) for i in ids.group(1).split(",")}
+                features = [
+                    f for f in features if f["properties"]["OBJECTID"] in wanted
+                ]
+            name = NAME_EQUALS.search(query.get("where", ""))
+            if name:
+                wanted = name.group(1).replace("''", "'")
+                features = [
+                    f
+                    for f in features
+                    if wanted
+                    in (
+                        f["properties"].get("BUA24NM", "").upper(),
+                        f["properties"].get("BUA24NMW", "").upper(),
+                    )
+                ]
+            if query["f"] == "json":
+                features = [esri_feature(feature) for feature in features]
+                reference = {"wkid": 4326, "latestWkid": 4326}
+                return 200, {}, {"spatialReference": reference, "features": features}
+            return 200, {}, {"type": "FeatureCollection", "features": features}
+        dataset = re.fullmatch(r"/datasets/([^/]+)", path)
+        if dataset:
+            names = sorted({name for record in self.records for name in record})
+            fields = [{"name": name} for name in names]
+            return 200, {}, {"dataset_id": dataset.group(1), "fields": fields}
+        if not re.fullmatch(r"/datasets/[^/]+/records", path):
+            return 404, {}, {"error": "unknown path {}".format(path)}
+
+        records = self.records
+        match = IN_BBOX.search(query.get("where", ""))
+        if match:
+            min_lat, min_lon, max_lat, max_lon = map(float, match.groups())
+            records = [
+                r
+                for r in records
+                if min_lat <= r["geo_point_2d"]["lat"] <= max_lat
+                and min_lon <= r["geo_point_2d"]["lon"] <= max_lon
+            ]
+        intersects = INTERSECTS.search(query.get("where", ""))
+        if intersects and self.reject_polygons:
+            return 400, {}, {"error": "polygon filters are not supported"}
+        if intersects:
+            area = shapely.from_wkt(intersects.group(1))
+            records = [
+                r
+                for r in records
+                if shapely.geometry.shape(r["geo_shape"]["geometry"]).intersects(area)
+            ]
+        for field, op, date in DATE_BOUND.findall(query.get("where", "")):
+            records = [
+                r
+                for r in records
+                if r.get(field)
+                and (r[field][:10] >= date if op == ">=" else r[field][:10] < date)
+            ]
+
+        if "order_by" in query:
+            field = query["order_by"]
+            records = sorted(
+                records, key=lambda r: (r.get(field) is None, r.get(field))
+            )
+        limit = int(query.get("limit", 10))
+        offset = 0 if self.ignore_offset else int(query.get("offset", 0))
+        if offset in self.unordered_offsets:
+            records = records[::-1]
+        if offset in self.shifted_offsets:
+            offset -= 1
+        page = records[offset : offset + limit]
+        with self._lock:
+            if "offset" in query and self.empty_pages > 0:
+                self.empty_pages -= 1
+                page = []
+            self.records_served += len(page)
+        if "select" in query:
+            selected = query["select"].split(",")
+            page = [{k: v for k, v in r.items() if k in selected} for r in page]
+        return 200, {}, {"total_count": len(records), "results": page}
+
+    def _handler(self):
+        server = self
+
+        class Handler(http.server.BaseHTTPRequestHandler):
+            def do_GET(self):
+                url = urllib.parse.urlsplit(self.path)
+                query = dict(urllib.parse.parse_qsl(url.query))
+                status, headers, body = server._respond(url.path, query)
+
+                payload = json.dumps(body).encode()
+                if status == 200:
+                    with server._lock:
+                        server.bytes_served += len(payload)
+                self.send_response(status)
+                self.send_header("Content-Type", "application/json")
+                self.send_header("Content-Length", str(len(payload)))
+                for name, value in headers.items():
+                    self.send_header(name, value)
+                self.end_headers()
+                try:
+                    self.wfile.write(payload)
+                except (BrokenPipeError, ConnectionResetError):
+                    pass  # the client cancelled a slow page
+
+            def log_message(self, format, *args):
+                pass
+
+        return Handler
+
+
+class RecordingProxy:
+    """Forwards plain HTTP requests to their target, without any proxy of its own.
+
+    `requests` lists the `(url, proxy_authorization)` of each request received,
+    the second None when no `Proxy-Authorization` header was sent.
+    """
+
+    def __init__(self):
+        self.requests = []
+        self._lock = threading.Lock()
+        self._opener = urllib.request.build_opener(urllib.request.ProxyHandler({}))
+        self._server = http.server.ThreadingHTTPServer(
+            ("127.0.0.1", 0), self._handler()
+        )
+
+    @property
+    def url(self):
+        return "http://127.0.0.1:{}".format(self._server.server_port)
+
+    def __enter__(self):
+        threading.Thread(target=self._server.serve_forever, daemon=True).start()
+        return self
+
+    def __exit__(self, *exc):
+        self._server.shutdown()
+        self._server.server_close()
+
+    def _handler(self):
+        proxy = self
+
+        class Handler(http.server.BaseHTTPRequestHandler):
+            def do_GET(self):
+                with proxy._lock:
+                    proxy.requests.append(
+                        (self.path, self.headers.get("Proxy-Authorization"))
+                    )
+                try:
+                    response = proxy._opener.open(self.path)
+                except urllib.error.HTTPError as e:
+                    response = e
+                payload = response.read()
+                self.send_response(response.status)
+                for name, value in response.headers.items():
+                    if name.lower() not in ("connection", "transfer-encoding"):
+                        self.send_header(name, value)
+                self.end_headers()
+                self.wfile.write(payload)
+
+            def log_message(self, format, *args):
+                pass
+
+        return Handler
+
+
+class RedirectServer:
+    """Answers every request with a `status` redirect to the same path and query
+    under `target`, as a mirror that moved would."""
+
+    def __init__(self, target, status=301):
+        self.target = target.rstrip("/")
+        self.status = status
+        self._server = http.server.ThreadingHTTPServer(
+            ("127.0.0.1", 0), self._handler()
+        )
+
+    @property
+    def url(self):
+        return "http://127.0.0.1:{}".format(self._server.server_port)
+
+    def __enter__(self):
+        threading.Thread(target=self._server.serve_forever, daemon=True).start()
+        return self
+
+    def __exit__(self, *exc):
+        self._server.shutdown()
+        self._server.server_close()
+
+    def _handler(self):
+        redirect = self
+
+        class Handler(http.server.BaseHTTPRequestHandler):
+            def do_GET(self):
+                self.send_response(redirect.status)
+                self.send_header("Location", redirect.target + self.path)
+                self.send_header("Content-Length", "0")
+                self.end_headers()
+
+            def log_message(self, format, *args):
+                pass
+
+        return Handler
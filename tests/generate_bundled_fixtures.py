@@ -0,0 +1,142 @@
+"""Regenerates the fixtures bundled for `enable_fixture_mode()`.
+
+They answer the requests `get_hex_summary(53.47, -2.26, 53.49, -2.22)` and
+`get_hex_summary_polygon_area(1)` / `(0)` make with default arguments, from the
+synthetic pipes of `src/mock_pipes.geojson` and a made-up built-up area covering
+the same rectangle. Run it from the repository root after changing what those
+requests look like; fixtures for real data are better recorded with
+`enable_fixture_mode(fixtures_dir, record=True)`.
+"""
+
+import json
+import math
+import pathlib
+import urllib.parse
+
+ROOT = pathlib.Path(__file__).resolve().parent.parent
+FIXTURES_DIR = ROOT / "python" / "infra_hex_py" / "fixtures"
+CADENT_RECORDS_URL = (
+    "https://cadentgas.opendatasoft.com/api/explore/v2.1/catalog/datasets/"
+    "gas-pipe-infrastructure-gpi_open/records"
+)
+BUILT_UP_AREA_URL = (
+    "https://services1.arcgis.com/ESMARspQHYMw9BZ9/arcgis/rest/services/"
+    "main_ONS_BUA_2024_EW/FeatureServer/0/query"
+)
+BBOX = (53.47, -2.26, 53.49, -2.22)
+
+
+def canonical_request(url):
+    """As `fixtures::canonical_request`: the URL without its query, then each
+    decoded query parameter as `name=value`, sorted, one per line."""
+    parts = urllib.parse.urlsplit(url)
+    pairs = sorted(urllib.parse.parse_qsl(parts.query, keep_blank_values=True))
+    base = urllib.parse.urlunsplit((parts.scheme, parts.netloc, parts.path, "", ""))
+    return "\n".join(["GET " + base] + ["{}={}".format(k, v) for k, v in pairs])
+
+
+def request_key(url):
+    """As `fixtures::request_key`: the 64-bit FNV-1a hash of the canonical request."""
+    hash = 0xCBF29CE484222325
+    for byte in canonical_request(url).encode():
+        hash = ((hash ^ byte) * 0x100000001B3) % 2**64
+    return "{:016x}".format(hash)
+
+
+def query(**params):
+    """A query string encoded as the Rust clients encode theirs."""
+    return "&".join(
+        "{}={}".format(k, urllib.parse.quote(str(v), safe="-_.~"))
+        for k, v in params.items()
+    )
+
+
+def centroid(line):
+    """The length-weighted centroid of a LineString's coordinates."""
+    total = x = y = 0.0
+    for (x0, y0), (x1, y1) in zip(line, line[1:]):
+        length = math.hypot(x1 - x0, y1 - y0)
+        total += length
+        x += length * (x0 + x1) / 2
+        y += length * (y0 + y1) / 2
+    return x / total, y / total
+
+
+def cadent_records():
+    """The mock pipes within `BBOX` as Cadent records, ordered by `asset_id`."""
+    features = json.loads((ROOT / "src" / "mock_pipes.geojson").read_text())
+    min_lat, min_lon, max_lat, max_lon = BBOX
+    records = []
+    for feature in features["features"]:
+        lon, lat = centroid(feature["geometry"]["coordinates"])
+        if min_lat <= lat <= max_lat and min_lon <= lon <= max_lon:
+            records.append(
+                {
+                    **feature["properties"],
+                    "geo_point_2d": {"lon": lon, "lat": lat},
+                    "geo_shape": {
+                        "type": "Feature",
+                        "geometry": feature["geometry"],
+                        "properties": {},
+                    },
+                }
+            )
+    records.sort(key=lambda r: (r.get("asset_id") is None, r.get("asset_id")))
+    return records
+
+
+def built_up_area():
+    min_lat, min_lon, max_lat, max_lon = BBOX
+    ring = [
+        [min_lon, min_lat],
+        [max_lon, min_lat],
+        [max_lon, max_lat],
+        [min_lon, max_lat],
+        [min_lon, min_lat],
+    ]
+    return {
+        "type": "Feature",
+        "properties": {
+            "OBJECTID": 1,
+            "BUA24CD": "E63999999",
+            "BUA24NM": "Fixture Town",
+            "areahectar": 588.0,
+        },
+        "geometry": {"type": "Polygon", "coordinates": [ring]},
+    }
+
+
+def responses():
+    """(url, body) of each bundled fixture."""
+    where = "in_bbox(geo_point_2d,{},{},{},{})".format(*BBOX)
+    records = cadent_records()
+    yield (
+        "{}?{}".format(CADENT_RECORDS_URL, query(where=where, limit=1)),
+        {"total_count": len(records), "results": records[:1]},
+    )
+    page = query(where=where, order_by="asset_id", limit=100, offset=0)
+    yield (
+        "{}?{}".format(CADENT_RECORDS_URL, page),
+        {"total_count": len(records), "results": records},
+    )
+    for object_id, features in [(1, [built_up_area()]), (0, [])]:
+        params = query(
+            where="OBJECTID={}".format(object_id), outFields="*", outSR=4326
+        )
+        yield (
+            "{}?{}&f=geojson".format(BUILT_UP_AREA_URL, params),
+            {"type": "FeatureCollection", "features": features},
+        )
+
+
+def main():
+    FIXTURES_DIR.mkdir(parents=True, exist_ok=True)
+    for url, body in responses():
+        fixture = {"body": body, "status": 200, "url": url}
+        path = FIXTURES_DIR / "{}.json".format(request_key(url))
+        path.write_text(json.dumps(fixture, indent=2, sort_keys=True) + "\n")
+        print(path.relative_to(ROOT), url)
+
+
+if __name__ == "__main__":
+    main()
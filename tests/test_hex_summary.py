@@ -1,6 +1,7 @@
 import geopandas as gpd
 import pyarrow as pa
 import pytest
+import shapely
 
 import infra_hex_py
 
@@ -39,8 +40,9 @@ def test_hex_summary_returns_geodataframe(hex_summary_result):
 
 def test_hex_summary_has_expected_columns(hex_summary_result):
     """Test that the GeoDataFrame has the expected columns."""
-    expected_columns = {"hex_id", "pipe_count", "geometry"}
+    expected_columns = {"hex_id", "pipe_count", "resolution", "provider", "geometry"}
     assert set(hex_summary_result.columns) == expected_columns
+    assert set(hex_summary_result["provider"]) == {"cadent"}
 
 
 def test_hex_summary_has_rows(hex_summary_result):
@@ -104,5 +106,449 @@ def test_hex_summary_with_different_zoom_levels():
     )
 
 
+def test_all_networks_has_per_network_counts(test_bbox):
+    """Test that the combined summary breaks pipe counts down by network."""
+    result = infra_hex_py.get_hex_summary_all_networks(
+        test_bbox["min_lat"],
+        test_bbox["min_lon"],
+        test_bbox["max_lat"],
+        test_bbox["max_lon"],
+        test_bbox["zoom"],
+    )
+    gdf = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([result]))
+
+    count_columns = [c for c in gdf.columns if c.startswith("count_")]
+    assert "count_cadent" in count_columns
+    assert (gdf[count_columns].sum(axis=1) == gdf["pipe_count"]).all(), (
+        "Per-network counts should sum to pipe_count"
+    )
+
+
+def test_all_networks_reports_failures_in_metadata(test_bbox):
+    """Test that failed networks are reported in the schema metadata."""
+    result = infra_hex_py.get_hex_summary_all_networks(
+        test_bbox["min_lat"],
+        test_bbox["min_lon"],
+        test_bbox["max_lat"],
+        test_bbox["max_lon"],
+        test_bbox["zoom"],
+    )
+    assert b"failed_networks" in result.schema.metadata
+
+
+def test_invalid_proxy_url_raises_value_error(test_bbox):
+    """Test that a malformed proxy URL is rejected before any request is made."""
+    with pytest.raises(ValueError, match="proxy"):
+        infra_hex_py.get_hex_summary(
+            test_bbox["min_lat"],
+            test_bbox["min_lon"],
+            test_bbox["max_lat"],
+            test_bbox["max_lon"],
+            test_bbox["zoom"],
+            http_options={"proxy_url": "not a url"},
+        )
+
+
+def test_unknown_http_option_raises_value_error(test_bbox):
+    """Test that unsupported http_options keys are rejected."""
+    with pytest.raises(ValueError, match="Unknown http_options key"):
+        infra_hex_py.get_hex_summary(
+            test_bbox["min_lat"],
+            test_bbox["min_lon"],
+            test_bbox["max_lat"],
+            test_bbox["max_lon"],
+            test_bbox["zoom"],
+            http_options={"proxy": "http://proxy:8080"},
+        )
+
+
+def test_hex_summary_reports_transfer_bytes(test_bbox):
+    """Test that transferred and decoded byte counts are recorded in metadata."""
+    args = (
+        test_bbox["min_lat"],
+        test_bbox["min_lon"],
+        test_bbox["max_lat"],
+        test_bbox["max_lon"],
+        test_bbox["zoom"],
+    )
+    compressed = infra_hex_py.get_hex_summary(*args).schema.metadata
+    uncompressed = infra_hex_py.get_hex_summary(
+        *args, http_options={"compression": False}
+    ).schema.metadata
+
+    assert int(compressed[b"bytes_decoded"]) > 0
+    assert int(compressed[b"bytes_transferred"]) <= int(compressed[b"bytes_decoded"])
+    assert uncompressed[b"bytes_transferred"] == uncompressed[b"bytes_decoded"], (
+        "Without compression nothing should be decoded"
+    )
+
+
+def test_hex_summary_accepts_bbox(test_bbox):
+    """Test that a BBox instance gives the same result as the four floats."""
+    bbox = infra_hex_py.BBox.from_corners(
+        min_lat=test_bbox["min_lat"],
+        min_lon=test_bbox["min_lon"],
+        max_lat=test_bbox["max_lat"],
+        max_lon=test_bbox["max_lon"],
+    )
+    from_bbox = infra_hex_py.get_hex_summary(bbox, test_bbox["zoom"])
+    from_floats = infra_hex_py.get_hex_summary(
+        test_bbox["min_lat"],
+        test_bbox["min_lon"],
+        test_bbox["max_lat"],
+        test_bbox["max_lon"],
+        test_bbox["zoom"],
+    )
+
+    assert from_bbox.num_rows == from_floats.num_rows
+
+
+def test_min_pipe_count_and_top_n_filter_hexes(test_bbox):
+    """Test that the density filters keep the densest hexes above the threshold."""
+    args = (
+        test_bbox["min_lat"],
+        test_bbox["min_lon"],
+        test_bbox["max_lat"],
+        test_bbox["max_lon"],
+        test_bbox["zoom"],
+    )
+    full = infra_hex_py.get_hex_summary(*args)
+    counts = sorted(full.column("pipe_count").to_pylist(), reverse=True)
+    threshold = counts[len(counts) // 2]
+
+    dense = infra_hex_py.get_hex_summary(*args, min_pipe_count=threshold)
+    assert dense.num_rows == sum(c >= threshold for c in counts)
+    assert min(dense.column("pipe_count").to_pylist()) >= threshold
+
+    top = infra_hex_py.get_hex_summary(*args, min_pipe_count=threshold, top_n=3)
+    assert top.column("pipe_count").to_pylist() == counts[: min(3, dense.num_rows)]
+
+    with pytest.raises(ValueError, match="top_n"):
+        infra_hex_py.get_hex_summary(*args, top_n=0)
+
+
+def test_get_built_up_area_returns_boundary():
+    """Test that a built-up area's geometry comes back as GeoJSON and WKB."""
+    area = infra_hex_py.get_built_up_area(1310)
+
+    assert area["object_id"] == 1310
+    assert area["name"]
+    from_wkb = shapely.from_wkb(area["wkb"])
+    from_geojson = shapely.from_geojson(area["geojson"])
+    assert from_wkb.geom_type in {"Polygon", "MultiPolygon"}
+    assert from_wkb.equals(from_geojson)
+
+    bbox = area["bbox"]
+    minx, miny, maxx, maxy = from_wkb.bounds
+    assert (bbox.min_lon, bbox.min_lat, bbox.max_lon, bbox.max_lat) == pytest.approx(
+        (minx, miny, maxx, maxy)
+    )
+
+
+def test_unknown_built_up_area_raises_area_not_found():
+    """Test that an unknown OBJECTID raises AreaNotFoundError, not RuntimeError."""
+    with pytest.raises(infra_hex_py.AreaNotFoundError, match="OBJECTID"):
+        infra_hex_py.get_built_up_area(999_999_999)
+    with pytest.raises(infra_hex_py.AreaNotFoundError):
+        infra_hex_py.get_hex_summary_polygon_area(999_999_999, 10)
+
+
+def test_local_authority_code_and_objectid_agree():
+    """Test that a district looked up by code or OBJECTID gives the same summary."""
+    by_code = infra_hex_py.get_hex_summary_local_authority("E08000003", 8)
+    assert by_code.num_rows > 0
+
+    url = infra_hex_py.build_info()["ons_local_authority_urls"]["generalised"]
+    district = gpd.read_file(
+        f"{url}?where=LAD24CD%3D%27E08000003%27&outFields=OBJECTID&f=geojson"
+    )
+    object_id = int(district["OBJECTID"].iloc[0])
+    by_id = infra_hex_py.get_hex_summary_local_authority(object_id, 8)
+
+    assert by_id.column("hex_id").to_pylist() == by_code.column("hex_id").to_pylist()
+
+
+@pytest.mark.parametrize(
+    "kwargs, error",
+    [
+        ({"code_or_objectid": "Manchester"}, ValueError),
+        (
+            {"code_or_objectid": "E08000003", "boundary_resolution": "coarse"},
+            ValueError,
+        ),
+        ({"code_or_objectid": 1.5}, TypeError),
+    ],
+)
+def test_local_authority_invalid_arguments(kwargs, error):
+    """Test that malformed codes and resolutions are rejected before fetching."""
+    with pytest.raises(error):
+        infra_hex_py.get_hex_summary_local_authority(resolution=8, **kwargs)
+
+
+def test_unknown_local_authority_raises_area_not_found():
+    """Test that a well-formed but unknown code raises AreaNotFoundError."""
+    with pytest.raises(infra_hex_py.AreaNotFoundError, match="E99999999"):
+        infra_hex_py.get_hex_summary_local_authority("E99999999", 8)
+
+
+def test_arcgis_feature_matches_local_authority():
+    """Test that an arbitrary layer URL and where clause clip like the LAD helper."""
+    url = infra_hex_py.build_info()["ons_local_authority_urls"]["generalised"]
+    by_layer = infra_hex_py.get_hex_summary_for_arcgis_feature(
+        url.removesuffix("/query"), "LAD24CD = 'E08000003'", 8
+    )
+    by_code = infra_hex_py.get_hex_summary_local_authority("E08000003", 8)
+
+    assert by_layer.column("hex_id").to_pylist() == by_code.column("hex_id").to_pylist()
+
+
+def test_arcgis_feature_requires_exactly_one_match():
+    """Test that where clauses matching no or several features are rejected."""
+    url = infra_hex_py.build_info()["ons_local_authority_urls"]["generalised"]
+    with pytest.raises(infra_hex_py.AreaNotFoundError):
+        infra_hex_py.get_hex_summary_for_arcgis_feature(url, "LAD24CD = 'E99999999'", 8)
+    with pytest.raises(ValueError, match="found [0-9]+"):
+        infra_hex_py.get_hex_summary_for_arcgis_feature(url, "LAD24CD LIKE 'E08%'", 8)
+
+
+def test_polygon_area_simplify_never_degenerates_hexes():
+    """Test that an oversized tolerance falls back to the unsimplified hexes."""
+    plain = infra_hex_py.get_hex_summary_polygon_area(1310, 10)
+    simplified = infra_hex_py.get_hex_summary_polygon_area(
+        1310, 10, simplify_tolerance_m=1_000_000.0
+    )
+
+    plain_gdf = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([plain]))
+    simplified_gdf = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([simplified]))
+    assert simplified_gdf.geometry.is_valid.all()
+    assert simplified_gdf.geometry.geom_equals(plain_gdf.geometry).all()
+
+
+def test_polygon_area_buffer_adds_hexes():
+    """Test that buffer_m keeps every unbuffered hex and adds ones beyond the edge."""
+    plain = infra_hex_py.get_hex_summary_polygon_area(1310, 10)
+    buffered = infra_hex_py.get_hex_summary_polygon_area(1310, 10, buffer_m=500.0)
+    zero = infra_hex_py.get_hex_summary_polygon_area(1310, 10, buffer_m=0.0)
+
+    plain_ids = set(plain.column("hex_id").to_pylist())
+    buffered_ids = set(buffered.column("hex_id").to_pylist())
+    assert plain_ids < buffered_ids, "Buffered area should be a strict superset"
+    assert set(zero.column("hex_id").to_pylist()) == plain_ids
+
+
+@pytest.mark.parametrize("buffer_m", [-10.0, float("nan")])
+def test_polygon_area_invalid_buffer_raises_value_error(buffer_m):
+    """Test that a negative or non-finite buffer is rejected before fetching."""
+    with pytest.raises(ValueError, match="buffer_m"):
+        infra_hex_py.get_hex_summary_polygon_area(1310, 10, buffer_m=buffer_m)
+
+
+def test_max_hexes_fails_before_fetching(test_bbox):
+    """Test that an oversized request is rejected up front with the estimate."""
+    with pytest.raises(
+        ValueError, match=r"Estimated \d+ hexes at resolution 15"
+    ) as excinfo:
+        infra_hex_py.get_hex_summary(
+            test_bbox["min_lat"],
+            test_bbox["min_lon"],
+            test_bbox["max_lat"],
+            test_bbox["max_lon"],
+            15,
+            max_hexes=1000,
+            http_options={"proxy_url": "http://127.0.0.1:9"},
+        )
+    assert "max_hexes (1000)" in str(excinfo.value)
+    assert "use resolution" in str(excinfo.value), "Should suggest a coarser one"
+
+
+@pytest.fixture
+def region_cache(test_bbox):
+    """Records for the test bounding box, fetched once."""
+    return infra_hex_py.RegionCache.from_bbox(
+        test_bbox["min_lat"],
+        test_bbox["min_lon"],
+        test_bbox["max_lat"],
+        test_bbox["max_lon"],
+    )
+
+
+def test_region_cache_matches_get_hex_summary(test_bbox, region_cache):
+    """Test that summarising cached records matches a direct fetch."""
+    direct = infra_hex_py.get_hex_summary(
+        test_bbox["min_lat"],
+        test_bbox["min_lon"],
+        test_bbox["max_lat"],
+        test_bbox["max_lon"],
+        test_bbox["zoom"],
+    )
+    cached = region_cache.hex_summary(test_bbox["zoom"])
+
+    assert cached.column("hex_id").to_pylist() == direct.column("hex_id").to_pylist()
+    assert (
+        cached.column("pipe_count").to_pylist()
+        == direct.column("pipe_count").to_pylist()
+    )
+
+
+def test_region_cache_material_filter(region_cache):
+    """Test that a material filter only ever reduces counts."""
+    total = sum(region_cache.hex_summary(11).column("pipe_count").to_pylist())
+    pe = sum(region_cache.hex_summary(11, "PE").column("pipe_count").to_pylist())
+    assert 0 < pe <= total
+    assert region_cache.hex_summary(11, "NOT-A-MATERIAL").num_rows == 0
+
+
+def test_region_cache_to_pipe_records(region_cache):
+    """Test that raw records come back one row per pipe with WKB geometry."""
+    records = region_cache.to_pipe_records()
+    assert records.num_rows == len(region_cache)
+    assert {"asset_id", "material", "geometry"} <= set(records.schema.names)
+
+    summary = infra_hex_py.hex_summary_from_arrow(records, 11)
+    assert summary.num_rows > 0
+
+
+def test_unknown_layer_raises_value_error(test_bbox):
+    """Test that an unknown Cadent layer is rejected with the supported names."""
+    with pytest.raises(ValueError, match="supported layers: gas_pipes"):
+        infra_hex_py.get_hex_summary(
+            test_bbox["min_lat"],
+            test_bbox["min_lon"],
+            test_bbox["max_lat"],
+            test_bbox["max_lon"],
+            test_bbox["zoom"],
+            layer="governors",
+        )
+
+
+def test_layer_list_adds_per_layer_counts(test_bbox):
+    """Test that a list of layers tags counts with a count_<layer> column."""
+    result = infra_hex_py.get_hex_summary(
+        test_bbox["min_lat"],
+        test_bbox["min_lon"],
+        test_bbox["max_lat"],
+        test_bbox["max_lon"],
+        test_bbox["zoom"],
+        layer=["gas_pipes"],
+    )
+
+    assert "count_gas_pipes" in result.schema.names
+    assert result.column("count_gas_pipes") == result.column("pipe_count")
+
+
+def test_parent_hex_ids_roll_up_to_parent_zoom(test_bbox):
+    """Test that grouping by parent_hex_id approximates a summary at parent_zoom.
+
+    n3gb cells don't nest exactly and a pipe crossing several children of the same
+    parent is counted once per child, so the rollup is only checked for overlap and
+    as an upper bound on the direct total.
+    """
+    bbox = (
+        test_bbox["min_lat"],
+        test_bbox["min_lon"],
+        test_bbox["max_lat"],
+        test_bbox["max_lon"],
+    )
+    child = infra_hex_py.get_hex_summary(*bbox, 11, parent_zoom=9)
+    parent = infra_hex_py.get_hex_summary(*bbox, 9)
+
+    assert child.schema.names[:3] == ["hex_id", "parent_hex_id", "pipe_count"]
+
+    rollup = child.to_pandas().groupby("parent_hex_id")["pipe_count"].sum()
+    direct = parent.to_pandas().set_index("hex_id")["pipe_count"]
+
+    overlap = set(rollup.index) & set(direct.index)
+    assert len(overlap) >= 0.9 * len(rollup), "Most parents should match direct hexes"
+    assert rollup.sum() >= direct.sum()
+
+
+@pytest.mark.parametrize("parent_zoom", [11, 12, -1])
+def test_invalid_parent_zoom_raises_value_error(test_bbox, parent_zoom):
+    """Test that parent_zoom must be strictly coarser than zoom."""
+    with pytest.raises(ValueError, match="parent_zoom"):
+        infra_hex_py.get_hex_summary(
+            test_bbox["min_lat"],
+            test_bbox["min_lon"],
+            test_bbox["max_lat"],
+            test_bbox["max_lon"],
+            11,
+            parent_zoom=parent_zoom,
+        )
+
+
+def test_negative_retry_after_cap_raises_value_error(test_bbox):
+    """Test that a negative max_retry_after_seconds is rejected up-front."""
+    with pytest.raises(ValueError, match="max_retry_after_seconds"):
+        infra_hex_py.get_hex_summary(
+            test_bbox["min_lat"],
+            test_bbox["min_lon"],
+            test_bbox["max_lat"],
+            test_bbox["max_lon"],
+            test_bbox["zoom"],
+            http_options={"max_retry_after_seconds": -1.0},
+        )
+
+
+@pytest.mark.parametrize("key", ["connect_timeout_secs", "read_timeout_secs"])
+@pytest.mark.parametrize("value", [0.0, -1.0, float("inf")])
+def test_non_positive_timeouts_raise_value_error(test_bbox, key, value):
+    """Test that connect and read timeouts must be positive finite seconds."""
+    with pytest.raises(ValueError, match=key):
+        infra_hex_py.get_hex_summary(
+            test_bbox["min_lat"],
+            test_bbox["min_lon"],
+            test_bbox["max_lat"],
+            test_bbox["max_lon"],
+            test_bbox["zoom"],
+            http_options={key: value},
+        )
+
+
+def test_connect_and_read_timeouts_accepted(test_bbox):
+    """Test that generous connect and read timeouts leave a fetch unaffected."""
+    result = infra_hex_py.get_hex_summary(
+        test_bbox["min_lat"],
+        test_bbox["min_lon"],
+        test_bbox["max_lat"],
+        test_bbox["max_lon"],
+        test_bbox["zoom"],
+        http_options={"connect_timeout_secs": 30.0, "read_timeout_secs": 60.0},
+    )
+    assert result.num_rows > 0
+
+
+def test_check_endpoints_reports_each_endpoint():
+    """Test that check_endpoints reports status for every upstream endpoint."""
+    report = infra_hex_py.check_endpoints()
+
+    assert set(report) == {"cadent", "ons_built_up_areas"}
+    for name, status in report.items():
+        assert status["up"], f"{name} should be reachable: {status['error']}"
+        assert status["status"] == 200
+
+
+def test_bng_bbox_matches_wgs84_bbox():
+    """Test that a BNG extent around the test area returns hexes."""
+    result = infra_hex_py.get_hex_summary_bng(383000, 397500, 385500, 399500, 11)
+    gdf = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([result]))
+
+    assert len(gdf) > 0
+    assert gdf.crs.to_epsg() == 27700
+
+
+@pytest.mark.parametrize(
+    "extent",
+    [
+        (-10, 397500, 385500, 399500),
+        (383000, 397500, 800000, 399500),
+        (385500, 397500, 383000, 399500),
+    ],
+)
+def test_bng_bbox_outside_domain_raises_value_error(extent):
+    """Test that out-of-domain or inverted BNG extents raise ValueError."""
+    with pytest.raises(ValueError):
+        infra_hex_py.get_hex_summary_bng(*extent, 11)
+
+
 if __name__ == "__main__":
     pytest.main([__file__, "-vv", "-s"])
@@ -0,0 +1,4046 @@
+import asyncio
+import base64
+import concurrent.futures
+import datetime
+import json
+import math
+import os
+import pathlib
+import socket
+import subprocess
+import sys
+import time
+
+import geopandas as gpd
+import pyarrow as pa
+import pytest
+import shapely
+
+import infra_hex_py
+from fixture_server import (
+    IN_BBOX,
+    FixtureServer,
+    RecordingProxy,
+    RedirectServer,
+    cadent_record,
+)
+
+
+def _line_feature(coords, **properties):
+    return {
+        "type": "Feature",
+        "geometry": {"type": "LineString", "coordinates": coords},
+        "properties": properties,
+    }
+
+
+@pytest.fixture
+def pipes_geojson():
+    """Two short pipes in central Manchester plus a point feature to be skipped."""
+    return json.dumps(
+        {
+            "type": "FeatureCollection",
+            "features": [
+                _line_feature(
+                    [
+                        [-2.248423716278411, 53.4804537960769],
+                        [-2.248817614533952, 53.480510340167925],
+                        [-2.249255070278722, 53.480573578320396],
+                    ],
+                    asset_id="TEST-001",
+                    material="PE",
+                ),
+                _line_feature(
+                    [
+                        [-2.2401, 53.4801],
+                        [-2.2412, 53.4809],
+                    ],
+                    asset_id="TEST-002",
+                ),
+                {
+                    "type": "Feature",
+                    "geometry": {"type": "Point", "coordinates": [-2.24, 53.48]},
+                    "properties": {},
+                },
+            ],
+        }
+    )
+
+
+@pytest.fixture
+def clip_polygon():
+    """A small square around the first pipe only."""
+    return json.dumps(
+        {
+            "type": "Polygon",
+            "coordinates": [
+                [
+                    [-2.2500, 53.4800],
+                    [-2.2480, 53.4800],
+                    [-2.2480, 53.4810],
+                    [-2.2500, 53.4810],
+                    [-2.2500, 53.4800],
+                ]
+            ],
+        }
+    )
+
+
+def test_from_geojson_string(pipes_geojson):
+    """Test that an inline GeoJSON string is summarised."""
+    result = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    gdf = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([result]))
+
+    assert set(gdf.columns) == {
+        "hex_id",
+        "pipe_count",
+        "resolution",
+        "provider",
+        "geometry",
+    }
+    assert len(gdf) > 0
+
+
+def test_from_geojson_file(tmp_path, pipes_geojson):
+    """Test that a GeoJSON file path gives the same result as the string."""
+    path = tmp_path / "pipes.geojson"
+    path.write_text(pipes_geojson)
+
+    from_file = infra_hex_py.hex_summary_from_geojson(str(path), 11)
+    from_str = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+
+    assert from_file.num_rows == from_str.num_rows
+
+
+def test_from_geojson_counts_skipped_features(pipes_geojson):
+    """Test that non-line features are skipped and counted in metadata."""
+    result = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    assert result.schema.metadata[b"skipped_features"] == b"1"
+
+
+def test_from_geojson_with_clip(pipes_geojson, clip_polygon):
+    """Test that clipping never adds hexes."""
+    unclipped = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    clipped = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 11, clip_geojson=clip_polygon
+    )
+    assert 0 < clipped.num_rows <= unclipped.num_rows
+
+
+FIXTURES = pathlib.Path(__file__).parent / "fixtures"
+
+
+def test_from_geojson_repairs_invalid_clip(pipes_geojson):
+    """Test that a self-intersecting clip ring is repaired rather than rejected."""
+    invalid = (FIXTURES / "invalid_ring.geojson").read_text()
+    # The two lobes of the bowtie, as a valid MultiPolygon
+    valid = json.dumps(
+        {
+            "type": "MultiPolygon",
+            "coordinates": [
+                [
+                    [
+                        [-2.252, 53.478],
+                        [-2.244, 53.481],
+                        [-2.252, 53.484],
+                        [-2.252, 53.478],
+                    ]
+                ],
+                [
+                    [
+                        [-2.236, 53.478],
+                        [-2.236, 53.484],
+                        [-2.244, 53.481],
+                        [-2.236, 53.478],
+                    ]
+                ],
+            ],
+        }
+    )
+
+    with pytest.warns(UserWarning, match="repaired it before clipping"):
+        repaired = infra_hex_py.hex_summary_from_geojson(
+            pipes_geojson, 12, clip_geojson=invalid
+        )
+    expected = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 12, clip_geojson=valid
+    )
+
+    assert repaired.num_rows > 0
+    assert sorted(repaired.column("hex_id").to_pylist()) == sorted(
+        expected.column("hex_id").to_pylist()
+    ), "Repaired clip should cover the same hexes as its valid equivalent"
+
+
+def test_from_geojson_degenerate_clip_raises_geometry_error(pipes_geojson):
+    """Test that a clip with no area left after repair raises InfraGeometryError."""
+    degenerate = json.dumps(
+        {
+            "type": "Polygon",
+            "coordinates": [
+                [[-2.25, 53.48], [-2.24, 53.49], [-2.23, 53.50], [-2.25, 53.48]]
+            ],
+        }
+    )
+    with pytest.raises(infra_hex_py.InfraGeometryError, match="empty after repair"):
+        infra_hex_py.hex_summary_from_geojson(
+            pipes_geojson, 11, clip_geojson=degenerate
+        )
+
+
+@pytest.fixture
+def pipe_hexes(pipes_geojson):
+    """The unclipped zoom-10 hexes of pipes_geojson, in BNG."""
+    result = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 10)
+    gdf = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([result]))
+    return gdf.set_index("hex_id").geometry
+
+
+def _clip_from_bng(geometry):
+    """Reproject a shapely geometry in BNG metres to a WGS84 GeoJSON clip."""
+    return gpd.GeoSeries([geometry], crs=27700).to_crs(4326).to_json()
+
+
+def _clipped_hex_ids(pipes_geojson, geometry):
+    result = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 10, clip_geojson=_clip_from_bng(geometry)
+    )
+    return set(result.column("hex_id").to_pylist())
+
+
+def test_clip_excludes_hex_inside_hole(pipes_geojson, pipe_hexes):
+    """Test that a hex lying wholly inside an interior ring is dropped."""
+    target = pipe_hexes.index[0]
+    hole = pipe_hexes[target].buffer(2, join_style="mitre")
+    outer = shapely.box(*pipe_hexes.total_bounds).buffer(500)
+    area = shapely.Polygon(outer.exterior, [hole.exterior])
+
+    kept = _clipped_hex_ids(pipes_geojson, area)
+    assert target not in kept, "Hex inside the hole should be excluded"
+    assert kept == set(pipe_hexes.index) - {target}
+
+
+def test_clip_keeps_hex_straddling_hole_edge(pipes_geojson, pipe_hexes):
+    """Test that a hex partly inside the area and partly inside a hole is kept."""
+    target = pipe_hexes.index[0]
+    minx, miny, maxx, maxy = pipe_hexes[target].bounds
+    # A hole covering the western half of the target hex only
+    hole = shapely.box(minx - 2, miny - 2, (minx + maxx) / 2, maxy + 2)
+    outer = shapely.box(*pipe_hexes.total_bounds).buffer(500)
+    area = shapely.Polygon(outer.exterior, [hole.exterior])
+
+    assert target in _clipped_hex_ids(pipes_geojson, area)
+
+
+def test_clip_keeps_island_inside_hole(pipes_geojson, pipe_hexes):
+    """Test that a MultiPolygon part nested inside another part's hole is honoured."""
+    target = pipe_hexes.index[0]
+    island = pipe_hexes[target].buffer(2, join_style="mitre")
+    hole = island.buffer(50, join_style="mitre")
+    outer = shapely.box(*pipe_hexes.total_bounds).buffer(500)
+    area = shapely.MultiPolygon(
+        [shapely.Polygon(outer.exterior, [hole.exterior]), island]
+    )
+
+    kept = _clipped_hex_ids(pipes_geojson, area)
+    assert target in kept, "Hex on the island should be included"
+
+
+def _clipped_areas(pipes_geojson, geometry):
+    result = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 10, clip_geojson=_clip_from_bng(geometry), clipped_area=True
+    )
+    hex_ids = result.column("hex_id").to_pylist()
+    return dict(zip(hex_ids, result.column("clipped_area_m2").to_pylist()))
+
+
+def test_clipped_area_is_full_cell_for_interior_hex(pipes_geojson, pipe_hexes):
+    """Test that a hex wholly inside the clip reports its full cell area."""
+    target = pipe_hexes.index[0]
+    areas = _clipped_areas(pipes_geojson, pipe_hexes[target].buffer(5))
+    assert areas[target] == pytest.approx(pipe_hexes[target].area)
+
+
+def test_clipped_area_of_boundary_hex(pipes_geojson, pipe_hexes):
+    """Test that a hex cut in half by the clip reports half its area."""
+    target = pipe_hexes.index[0]
+    hexagon = pipe_hexes[target]
+    minx, miny, maxx, maxy = hexagon.bounds
+    west_half = shapely.box(minx - 10, miny - 10, (minx + maxx) / 2, maxy + 10)
+
+    areas = _clipped_areas(pipes_geojson, west_half)
+    assert areas[target] == pytest.approx(hexagon.area / 2, rel=1e-3)
+
+
+def test_clipped_area_requires_clip(pipes_geojson):
+    """Test that clipped_area without clip_geojson raises ValueError."""
+    with pytest.raises(ValueError, match="clip_geojson"):
+        infra_hex_py.hex_summary_from_geojson(pipes_geojson, 10, clipped_area=True)
+
+
+def test_from_geojson_invalid_raises_value_error():
+    """Test that malformed GeoJSON raises ValueError."""
+    with pytest.raises(ValueError):
+        infra_hex_py.hex_summary_from_geojson('{"type": "Nope"}', 11)
+
+
+def _with_crs(collection, name):
+    """Add a legacy GeoJSON crs member, as WFS servers emit."""
+    collection["crs"] = {"type": "name", "properties": {"name": name}}
+    return json.dumps(collection)
+
+
+def _summary_counts(result):
+    hex_ids = result.column("hex_id").to_pylist()
+    return dict(zip(hex_ids, result.column("pipe_count").to_pylist()))
+
+
+def test_from_geojson_reprojects_bng_response(pipes_geojson):
+    """Test that a WFS response in EPSG:27700 lands in the same hexes as WGS84."""
+    lines = gpd.read_file(pipes_geojson).set_crs(4326, allow_override=True)
+    bng = json.loads(lines.to_crs(27700).to_json())
+    wfs_response = _with_crs(bng, "urn:ogc:def:crs:EPSG::27700")
+
+    expected = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 12)
+    result = infra_hex_py.hex_summary_from_geojson(wfs_response, 12)
+
+    assert _summary_counts(result) == _summary_counts(expected)
+    assert result.schema.metadata[b"source_crs"] == b"EPSG:27700"
+
+
+def test_from_geojson_swaps_lat_lon_axis_order(pipes_geojson):
+    """Test that URN EPSG:4326 is read in its (lat, lon) authority axis order."""
+    collection = json.loads(pipes_geojson)
+    for feature in collection["features"]:
+        if feature["geometry"]["type"] == "LineString":
+            coords = feature["geometry"]["coordinates"]
+            feature["geometry"]["coordinates"] = [[lat, lon] for lon, lat in coords]
+    wfs_response = _with_crs(collection, "urn:ogc:def:crs:EPSG::4326")
+
+    expected = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 12)
+    result = infra_hex_py.hex_summary_from_geojson(wfs_response, 12)
+    assert _summary_counts(result) == _summary_counts(expected)
+
+
+def test_from_geojson_unsupported_crs_raises_value_error(pipes_geojson):
+    """Test that a declared CRS other than WGS84 or BNG is rejected."""
+    source = _with_crs(json.loads(pipes_geojson), "EPSG:3857")
+    with pytest.raises(ValueError, match="Unsupported CRS"):
+        infra_hex_py.hex_summary_from_geojson(source, 12)
+
+
+def test_from_geojson_is_deterministic(pipes_geojson):
+    """Test that repeated runs over the same data produce byte-identical batches."""
+    first = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 12)
+    second = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 12)
+    assert first.serialize().to_pybytes() == second.serialize().to_pybytes()
+
+
+def test_from_geojson_rows_sorted_by_hex_id(pipes_geojson):
+    """Test that rows are ordered by hex_id ascending, whatever their counts."""
+    result = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 12)
+    hex_ids = result.column("hex_id").to_pylist()
+    assert hex_ids == sorted(hex_ids)
+
+
+def test_top_n_keeps_densest_rows_in_hex_id_order(pipes_table):
+    """Test that top_n keeps the densest hexes but leaves them sorted by hex_id."""
+    records = pa.concat_tables([pipes_table, pipes_table.slice(1, 1)])
+    full = infra_hex_py.hex_summary_from_records(records, 12, deduplicate=False)
+    top = infra_hex_py.hex_summary_from_records(
+        records, 12, deduplicate=False, top_n=2
+    )
+
+    assert len(set(full.column("pipe_count").to_pylist())) > 1
+    densest = sorted(
+        zip(full.column("pipe_count").to_pylist(), full.column("hex_id").to_pylist()),
+        key=lambda row: (-row[0], row[1]),
+    )[:2]
+    assert top.column("hex_id").to_pylist() == sorted(hex_id for _, hex_id in densest)
+
+
+def test_from_geojson_deduplicates_by_asset_id(pipes_geojson):
+    """Test that repeated asset ids are counted once unless deduplicate=False."""
+    collection = json.loads(pipes_geojson)
+    collection["features"] = collection["features"] * 2
+    duplicated = json.dumps(collection)
+
+    original = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    deduped = infra_hex_py.hex_summary_from_geojson(duplicated, 11)
+    doubled = infra_hex_py.hex_summary_from_geojson(duplicated, 11, deduplicate=False)
+
+    original_total = sum(original.column("pipe_count").to_pylist())
+    assert sum(deduped.column("pipe_count").to_pylist()) == original_total, (
+        "Duplicated records should be counted once"
+    )
+    assert sum(doubled.column("pipe_count").to_pylist()) == 2 * original_total, (
+        "deduplicate=False should count every record"
+    )
+
+
+def test_from_geojson_keeps_records_without_asset_id():
+    """Test that records lacking an asset_id are never deduplicated."""
+    feature = _line_feature([[-2.2401, 53.4801], [-2.2412, 53.4809]])
+    source = json.dumps({"type": "FeatureCollection", "features": [feature, feature]})
+
+    result = infra_hex_py.hex_summary_from_geojson(source, 11)
+    assert sum(result.column("pipe_count").to_pylist()) == 2
+
+
+def _segmented_main(n_segments, start=(-2.2401, 53.4801), step=0.0004, **properties):
+    """A straight main split into consecutive segment features."""
+    lon, lat = start
+    return [
+        _line_feature(
+            [[lon + i * step, lat], [lon + (i + 1) * step, lat]], **properties
+        )
+        for i in range(n_segments)
+    ]
+
+
+def test_unique_assets_counts_segmented_main_once():
+    """Test that segments sharing an asset_id count once per hex."""
+    features = _segmented_main(5, asset_id="MAIN-001")
+    source = json.dumps({"type": "FeatureCollection", "features": features})
+
+    records = infra_hex_py.hex_summary_from_geojson(source, 8, deduplicate=False)
+    unique = infra_hex_py.hex_summary_from_geojson(
+        source, 8, count_mode="unique_assets"
+    )
+
+    assert records.column("pipe_count").to_pylist() == [5]
+    assert unique.column("pipe_count").to_pylist() == [1]
+
+
+def test_unique_assets_keeps_every_hex_of_a_long_main():
+    """Test that unique_assets still reaches every hex the segments cross."""
+    features = _segmented_main(20, step=0.002, asset_id="MAIN-001")
+    source = json.dumps({"type": "FeatureCollection", "features": features})
+
+    records = infra_hex_py.hex_summary_from_geojson(source, 11, deduplicate=False)
+    unique = infra_hex_py.hex_summary_from_geojson(
+        source, 11, count_mode="unique_assets"
+    )
+
+    assert unique.num_rows > 1
+    assert set(unique.column("hex_id").to_pylist()) == set(
+        records.column("hex_id").to_pylist()
+    )
+    assert set(unique.column("pipe_count").to_pylist()) == {1}
+
+
+def test_unique_assets_counts_records_without_id_individually():
+    """Test that segments lacking an asset_id fall back to per-record counting."""
+    features = _segmented_main(3, asset_id="MAIN-001") + _segmented_main(2)
+    source = json.dumps({"type": "FeatureCollection", "features": features})
+
+    result = infra_hex_py.hex_summary_from_geojson(
+        source, 8, count_mode="unique_assets"
+    )
+    assert result.column("pipe_count").to_pylist() == [3]
+
+
+def test_unknown_count_mode_raises_value_error(pipes_geojson):
+    """Test that an unrecognised count_mode raises ValueError."""
+    with pytest.raises(ValueError, match="Unknown count_mode"):
+        infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11, count_mode="pipes")
+
+
+def test_length_weighting_sums_to_one_per_pipe(pipes_geojson):
+    """Test that weighted_count splits each pipe's length across its hexes."""
+    result = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 12, weighting="length"
+    )
+    names = result.schema.names
+    assert names.index("weighted_count") == names.index("pipe_count") + 1
+
+    weights = result.column("weighted_count").to_pylist()
+    assert sum(weights) == pytest.approx(2.0), "Each of the two pipes sums to one"
+    assert all(0.0 < w <= 1.0 for w in weights)
+
+
+def test_count_weighting_has_no_weighted_column(pipes_geojson):
+    """Test that the default weighting leaves the schema unchanged."""
+    result = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    assert "weighted_count" not in result.schema.names
+
+
+def test_length_weighting_zero_length_pipe():
+    """Test that a zero-length pipe counts fully in the hex containing it."""
+    feature = _line_feature([[-2.2401, 53.4801], [-2.2401, 53.4801]])
+    source = json.dumps({"type": "FeatureCollection", "features": [feature]})
+
+    result = infra_hex_py.hex_summary_from_geojson(source, 11, weighting="length")
+    assert result.column("weighted_count").to_pylist() == [1.0]
+
+
+def test_unknown_weighting_raises_value_error(pipes_geojson):
+    """Test that an unrecognised weighting name raises ValueError."""
+    with pytest.raises(ValueError, match="Unknown weighting"):
+        infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11, weighting="area")
+
+
+def test_coordinate_precision_rounds_geometry(pipes_geojson):
+    """Test that coordinate_precision rounds every hex vertex."""
+    result = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 11, coordinate_precision=1
+    )
+    gdf = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([result]))
+    coords = shapely.get_coordinates(gdf.geometry.values)
+
+    assert len(coords) > 0
+    assert (coords.round(1) == coords).all(), "All coordinates should have 1 dp"
+
+
+def test_coordinate_precision_zero_rounds_to_whole_metres(pipes_geojson):
+    """Test that coordinate_precision=0 rounds every hex vertex to an integer."""
+    result = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 11, coordinate_precision=0
+    )
+    gdf = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([result]))
+    coords = shapely.get_coordinates(gdf.geometry.values)
+
+    assert (coords.round() == coords).all(), "All coordinates should be whole metres"
+
+
+@pytest.mark.parametrize("precision", [-2, 16])
+def test_coordinate_precision_out_of_range(pipes_geojson, precision):
+    """Test that out-of-range coordinate_precision raises ValueError."""
+    with pytest.raises(ValueError, match="coordinate_precision"):
+        infra_hex_py.hex_summary_from_geojson(
+            pipes_geojson, 11, coordinate_precision=precision
+        )
+
+
+@pytest.fixture
+def pipes_table(pipes_geojson):
+    """The line features from pipes_geojson as a WKB Arrow table, plus a null row."""
+    lines = [
+        shapely.geometry.shape(f["geometry"])
+        for f in json.loads(pipes_geojson)["features"]
+        if f["geometry"]["type"] == "LineString"
+    ]
+    return pa.table({"geometry": pa.array(shapely.to_wkb(lines + [None]).tolist())})
+
+
+def test_from_arrow_matches_geojson(pipes_geojson, pipes_table):
+    """Test that WKB input gives the same summary and schema as GeoJSON input."""
+    from_arrow = infra_hex_py.hex_summary_from_arrow(pipes_table, 11)
+    from_geojson = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+
+    assert from_arrow.schema.equals(from_geojson.schema), "Schemas should match"
+    assert from_arrow.column("hex_id") == from_geojson.column("hex_id")
+    assert from_arrow.column("pipe_count") == from_geojson.column("pipe_count")
+
+
+def test_from_arrow_accepts_record_batch(pipes_table):
+    """Test that a single RecordBatch is accepted as well as a Table."""
+    batch = pipes_table.to_batches()[0]
+    result = infra_hex_py.hex_summary_from_arrow(batch, 11)
+    assert result.num_rows > 0
+
+
+def test_from_arrow_non_line_raises_value_error(pipes_table):
+    """Test that non-line geometries raise unless skip_invalid is set."""
+    point = shapely.to_wkb(shapely.Point(-2.24, 53.48))
+    table = pa.concat_tables(
+        [pipes_table, pa.table({"geometry": pa.array([point])})]
+    )
+
+    with pytest.raises(ValueError, match="Point"):
+        infra_hex_py.hex_summary_from_arrow(table, 11)
+
+    skipped = infra_hex_py.hex_summary_from_arrow(table, 11, skip_invalid=True)
+    unskipped = infra_hex_py.hex_summary_from_arrow(pipes_table, 11)
+    assert skipped.num_rows == unskipped.num_rows
+
+
+def test_from_records_matches_geojson(pipes_geojson, pipes_table):
+    """Test that a pipe records table bins exactly like the same GeoJSON."""
+    records = pipes_table.append_column(
+        "asset_id", pa.array(["TEST-001", "TEST-002", None])
+    )
+    from_records = infra_hex_py.hex_summary_from_records(records, 11)
+    from_geojson = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+
+    assert from_records.schema.equals(from_geojson.schema)
+    assert from_records.column("hex_id") == from_geojson.column("hex_id")
+    assert from_records.column("pipe_count") == from_geojson.column("pipe_count")
+    assert from_records.schema.metadata[b"skipped_features"] == b"1"
+
+
+def test_from_records_deduplicates_on_asset_id(pipes_table):
+    """Test that asset_id is carried over, so repeated records count once."""
+    records = pa.concat_tables([pipes_table, pipes_table]).append_column(
+        "asset_id", pa.array(["A", "B", None] * 2)
+    )
+    single = infra_hex_py.hex_summary_from_records(pipes_table, 11)
+    deduplicated = infra_hex_py.hex_summary_from_records(records, 11)
+    everything = infra_hex_py.hex_summary_from_records(records, 11, deduplicate=False)
+
+    assert deduplicated.column("pipe_count") == single.column("pipe_count")
+    assert sum(everything.column("pipe_count").to_pylist()) == 2 * sum(
+        single.column("pipe_count").to_pylist()
+    )
+
+
+def test_from_arrow_missing_column_raises_value_error(pipes_table):
+    """Test that an unknown geometry column raises ValueError."""
+    with pytest.raises(ValueError, match="not found"):
+        infra_hex_py.hex_summary_from_arrow(pipes_table, 11, geometry_column="geom")
+
+
+def test_diff_hex_summaries(pipes_geojson, clip_polygon):
+    """Test that hexes dropped by clipping show up as losses in the diff."""
+    old = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    new = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 11, clip_geojson=clip_polygon
+    )
+    diff = infra_hex_py.diff_hex_summaries(old, new).to_pydict()
+
+    assert set(diff["hex_id"]) == set(old.column("hex_id").to_pylist())
+    assert diff["hex_id"] == sorted(diff["hex_id"])
+    for old_count, new_count, delta in zip(
+        diff["pipe_count_old"], diff["pipe_count_new"], diff["delta"]
+    ):
+        assert delta == new_count - old_count
+        assert delta <= 0, "Clipping can only remove pipes"
+
+
+def test_diff_hex_summaries_of_identical_inputs_is_zero(pipes_geojson):
+    """Test that diffing a summary against itself gives all-zero deltas."""
+    summary = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    diff = infra_hex_py.diff_hex_summaries(summary, summary)
+
+    assert diff.num_rows == summary.num_rows
+    assert set(diff.column("delta").to_pylist()) == {0}
+
+
+def test_diff_hex_summaries_at_different_zooms_raises_value_error(pipes_geojson):
+    """Test that summaries at different zooms cannot be diffed."""
+    fine = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    coarse = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 10)
+
+    with pytest.raises(ValueError, match="different zooms"):
+        infra_hex_py.diff_hex_summaries(coarse, fine)
+
+
+def test_bbox_constructors_agree():
+    """Test that corner, bounds and GeoJSON constructors produce the same box."""
+    corners = infra_hex_py.BBox.from_corners(
+        min_lat=53.47, min_lon=-2.26, max_lat=53.49, max_lon=-2.22
+    )
+    bounds = infra_hex_py.BBox.from_bounds(-2.26, 53.47, -2.22, 53.49)
+    geojson = infra_hex_py.BBox.from_geojson(
+        json.dumps(shapely.geometry.mapping(shapely.box(-2.26, 53.47, -2.22, 53.49)))
+    )
+
+    for bbox in (bounds, geojson):
+        assert repr(bbox) == repr(corners)
+
+
+@pytest.mark.parametrize(
+    "kwargs, name",
+    [
+        ({"min_lat": 95.0, "min_lon": -2.26, "max_lat": 96.0, "max_lon": -2.22}, "min_lat"),
+        ({"min_lat": 53.47, "min_lon": -200.0, "max_lat": 53.49, "max_lon": -2.22}, "min_lon"),
+        ({"min_lat": 53.49, "min_lon": -2.26, "max_lat": 53.47, "max_lon": -2.22}, "min_lat"),
+    ],
+)
+def test_bbox_invalid_raises_value_error(kwargs, name):
+    """Test that out-of-range or inverted corners raise ValueError naming the parameter."""
+    with pytest.raises(ValueError, match=name):
+        infra_hex_py.BBox.from_corners(**kwargs)
+
+
+def test_bbox_area_and_split():
+    """Test that split tiles cover the box and their areas sum to the whole."""
+    bbox = infra_hex_py.BBox.from_bounds(-2.26, 53.47, -2.22, 53.49)
+    tiles = bbox.split(3)
+
+    assert len(tiles) == 9
+    assert tiles[0].min_lat == bbox.min_lat and tiles[0].min_lon == bbox.min_lon
+    assert tiles[-1].max_lat == bbox.max_lat and tiles[-1].max_lon == bbox.max_lon
+    assert sum(t.area_km2 for t in tiles) == pytest.approx(bbox.area_km2)
+    assert 5 < bbox.area_km2 < 7, "Roughly 2.65km x 2.2km"
+
+
+def test_build_info_describes_package_and_endpoints():
+    """Test that build_info reports versions and endpoints without network access."""
+    info = infra_hex_py.build_info()
+
+    assert info["version"] == infra_hex_py.__version__
+    assert info["infra_hex_rs_version"]
+    assert "gas_pipes" in info["cadent_layers"]
+    assert "cadent" in info["networks"]
+    for url in [*info["cadent_layers"].values(), info["ons_built_up_areas_url"]]:
+        assert url.startswith("https://")
+    json.dumps(info)
+
+
+def test_bbox_crossing_antimeridian_raises_value_error():
+    """Test that min_lon > max_lon is reported as an unsupported antimeridian box."""
+    with pytest.raises(ValueError, match="antimeridian"):
+        infra_hex_py.BBox.from_bounds(170.0, -20.0, -170.0, -10.0)
+    with pytest.raises(ValueError, match="antimeridian"):
+        infra_hex_py.get_hex_summary(-20.0, 170.0, -10.0, -170.0, 8)
+
+
+@pytest.mark.parametrize(
+    "bounds",
+    [
+        (-10.0, 85.0, 10.0, 89.0),
+        (139.0, 35.0, 140.0, 36.0),
+    ],
+)
+def test_bbox_outside_bng_area_raises_value_error(bounds):
+    """Test that boxes the BNG hex grid cannot cover are rejected before fetching."""
+    bbox = infra_hex_py.BBox.from_bounds(*bounds)
+    with pytest.raises(ValueError, match="British National Grid area of use"):
+        infra_hex_py.get_hex_summary(bbox, 8)
+    with pytest.raises(ValueError, match="British National Grid area of use"):
+        infra_hex_py.RegionCache.from_bbox(bbox)
+
+
+def test_write_hex_summary_ndjson(tmp_path, pipes_geojson):
+    """Test that each hex is written as one WGS84 GeoJSON Feature per line."""
+    result = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    path = tmp_path / "hexes.ndjson"
+
+    written = infra_hex_py.write_hex_summary_ndjson(result, str(path))
+
+    lines = path.read_text().splitlines()
+    assert written == len(lines) == result.num_rows
+    features = [json.loads(line) for line in lines]
+    assert [f["properties"]["hex_id"] for f in features] == result.column(
+        "hex_id"
+    ).to_pylist()
+    assert [f["properties"]["pipe_count"] for f in features] == result.column(
+        "pipe_count"
+    ).to_pylist()
+    for feature in features:
+        assert feature["type"] == "Feature"
+        assert feature["geometry"]["type"] == "Polygon"
+        lon, lat = feature["geometry"]["coordinates"][0][0]
+        assert -3.0 < lon < -2.0 and 53.0 < lat < 54.0
+        assert "geometry" not in feature["properties"]
+
+
+@pytest.mark.parametrize(
+    "layer_url",
+    [
+        "https://services1.arcgis.com/x/arcgis/rest/services/Wards/MapServer/0",
+        "https://services1.arcgis.com/x/arcgis/rest/services/Wards/FeatureServer",
+        "ftp://services1.arcgis.com/x/arcgis/rest/services/Wards/FeatureServer/0",
+        "https://services1.arcgis.com/x/FeatureServer/0/query?where=1%3D1",
+    ],
+)
+def test_arcgis_feature_rejects_non_layer_urls(layer_url):
+    """Test that URLs that are not FeatureServer layers fail before any request."""
+    with pytest.raises(ValueError, match="feature service layer"):
+        infra_hex_py.get_hex_summary_for_arcgis_feature(layer_url, "1=1", 8)
+
+
+@pytest.fixture
+def silent_proxy():
+    """A local proxy that accepts connections but never answers them."""
+    server = socket.socket()
+    server.bind(("127.0.0.1", 0))
+    server.listen()
+    yield "http://127.0.0.1:{}".format(server.getsockname()[1])
+    server.close()
+
+
+def test_fetch_timeout_raises_timeout_error(silent_proxy):
+    """Test that a fetch that only timed out raises a TimeoutError naming the URL."""
+    with pytest.raises(TimeoutError) as excinfo:
+        infra_hex_py.get_built_up_area(
+            1310,
+            http_options={
+                "proxy_url": silent_proxy,
+                "connect_timeout_secs": 0.2,
+                "max_retries": 0,
+            },
+        )
+
+    error = excinfo.value
+    assert isinstance(error, infra_hex_py.FetchTimeoutError)
+    assert isinstance(error, infra_hex_py.FetchError)
+    assert isinstance(error, RuntimeError)
+    assert error.url.startswith(infra_hex_py.build_info()["ons_built_up_areas_url"])
+    assert error.elapsed_secs >= 0.2
+    assert [e["kind"] for e in error.errors] == ["timeout"]
+
+
+def test_reset_clients_rebuilds_after_failure(silent_proxy):
+    """Test that failed fetches don't poison the client cache across resets."""
+    http_options = {
+        "proxy_url": silent_proxy,
+        "connect_timeout_secs": 0.2,
+        "max_retries": 0,
+    }
+    for _ in range(2):
+        with pytest.raises(TimeoutError):
+            infra_hex_py.get_built_up_area(1310, http_options=http_options)
+        assert infra_hex_py.reset_clients() is None
+
+
+def _column_bytes(batch):
+    return [
+        buffer.to_pybytes()
+        for column in batch.columns
+        for buffer in column.buffers()
+        if buffer is not None
+    ]
+
+
+def test_repeated_summaries_are_byte_identical(pipes_geojson):
+    """Test that the same input gives identical rows, column bytes and metadata."""
+    first, second = (
+        infra_hex_py.hex_summary_from_geojson(
+            pipes_geojson, 11, weighting="length", count_mode="unique_assets"
+        )
+        for _ in range(2)
+    )
+
+    assert first.schema.names == second.schema.names
+    assert first.schema.metadata == second.schema.metadata
+    assert _column_bytes(first) == _column_bytes(second)
+
+
+def test_summary_rows_sorted_by_resolution_provider_and_hex_id(pipes_table):
+    """Test that rows come out ordered by (resolution, provider, hex_id)."""
+    records = pa.concat_tables([pipes_table, pipes_table.slice(1, 1)])
+    for top_n in (None, 3):
+        result = infra_hex_py.hex_summary_from_records(
+            records, 12, deduplicate=False, top_n=top_n
+        )
+        keys = list(
+            zip(
+                result.column("resolution").to_pylist(),
+                result.column("provider").to_pylist(),
+                result.column("hex_id").to_pylist(),
+            )
+        )
+        assert len(keys) > 1
+        assert keys == sorted(keys, key=lambda k: (k[0], k[1] or "", k[2]))
+
+
+def test_from_geojson_parent_zoom_rolls_up(pipes_geojson):
+    """Test that parent_hex_id joins a fine summary onto a coarser one."""
+    fine = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11, parent_zoom=8)
+    coarse = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 8)
+
+    assert fine.schema.names[:2] == ["hex_id", "parent_hex_id"]
+    assert set(fine.column("parent_hex_id").to_pylist()) <= set(
+        coarse.column("hex_id").to_pylist()
+    )
+
+
+@pytest.mark.parametrize("parent_zoom", [11, 12, -1])
+def test_parent_zoom_not_coarser_raises_value_error(pipes_geojson, parent_zoom):
+    """Test that a parent zoom at or finer than the data zoom is rejected."""
+    with pytest.raises(ValueError, match="parent_zoom"):
+        infra_hex_py.hex_summary_from_geojson(
+            pipes_geojson, 11, parent_zoom=parent_zoom
+        )
+
+
+requires_mock = pytest.mark.skipif(
+    "mock" not in infra_hex_py.build_info()["networks"],
+    reason="built without the mock feature (maturin develop --features mock)",
+)
+MOCK_BBOX = infra_hex_py.BBox.from_corners(
+    min_lat=53.47, min_lon=-2.26, max_lat=53.49, max_lon=-2.22
+)
+
+
+@requires_mock
+def test_mock_network_summary_spans_hexes():
+    """Test that the mock network bins its fixture pipes across several hexes."""
+    result = infra_hex_py.get_hex_summary(MOCK_BBOX, 11, network="mock")
+    pipes = infra_hex_py.RegionCache.from_bbox(
+        MOCK_BBOX, network="mock", deduplicate=False
+    ).to_pipe_records()
+
+    assert result.num_rows > 1
+    assert sum(result.column("pipe_count").to_pylist()) > pipes.num_rows
+
+
+@requires_mock
+def test_fetch_inside_running_asyncio_loop():
+    """Test that a fetch called from a coroutine in a worker thread succeeds."""
+
+    async def fetch():
+        return infra_hex_py.get_hex_summary(MOCK_BBOX, 11, network="mock")
+
+    with concurrent.futures.ThreadPoolExecutor(max_workers=1) as pool:
+        result = pool.submit(asyncio.run, fetch()).result(timeout=60)
+
+    expected = infra_hex_py.get_hex_summary(MOCK_BBOX, 11, network="mock")
+    assert result.equals(expected)
+
+
+@requires_mock
+def test_mock_network_deduplicates_split_main():
+    """Test that the fixture's two segments of one main are deduplicated by default."""
+    cache = infra_hex_py.RegionCache.from_bbox(MOCK_BBOX, network="mock")
+    everything = infra_hex_py.RegionCache.from_bbox(
+        MOCK_BBOX, network="mock", deduplicate=False
+    )
+
+    assert cache.to_pipe_records().num_rows == everything.to_pipe_records().num_rows - 1
+
+
+@requires_mock
+def test_mock_network_filters_by_bbox():
+    """Test that a bbox away from the fixture returns no mock pipes."""
+    result = infra_hex_py.get_hex_summary(51.50, -0.13, 51.51, -0.12, 11, network="mock")
+
+    assert result.num_rows == 0
+
+
+def test_unknown_network_raises_value_error():
+    """Test that an unknown network name is rejected before any fetch."""
+    with pytest.raises(ValueError, match="Unknown network"):
+        infra_hex_py.get_hex_summary(MOCK_BBOX, 11, network="national_gas")
+
+
+def test_metadata_columns_record_resolution(pipes_geojson):
+    """Test that resolution and provider columns sit before geometry and can be dropped."""
+    result = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11, parent_zoom=8)
+
+    assert result.schema.names[-3:] == ["resolution", "provider", "geometry"]
+    assert result.schema.field("resolution").type == pa.uint8()
+    assert pa.types.is_dictionary(result.schema.field("provider").type)
+    assert set(result.column("resolution").to_pylist()) == {11}
+    assert set(result.column("provider").to_pylist()) == {None}
+
+    bare = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 11, parent_zoom=8, include_metadata_columns=False
+    )
+    assert bare.schema.names == ["hex_id", "parent_hex_id", "pipe_count", "geometry"]
+
+
+@requires_mock
+def test_metadata_columns_survive_concatenation():
+    """Test that summaries at two zooms concatenate with their provenance intact."""
+    tables = [
+        pa.Table.from_batches(
+            [infra_hex_py.get_hex_summary(MOCK_BBOX, zoom, network="mock")]
+        )
+        for zoom in (10, 11)
+    ]
+    combined = pa.concat_tables(tables)
+
+    assert set(combined.column("resolution").to_pylist()) == {10, 11}
+    assert set(combined.column("provider").to_pylist()) == {"mock"}
+
+
+def test_polars_output_matches_arrow(pipes_geojson):
+    """Test that output="polars" gives the same rows with WKB geometry."""
+    pl = pytest.importorskip("polars")
+    batch = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    df = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11, output="polars")
+
+    assert isinstance(df, pl.DataFrame)
+    assert df.columns == batch.schema.names
+    assert df["hex_id"].to_list() == batch.column("hex_id").to_pylist()
+    assert df["pipe_count"].to_list() == batch.column("pipe_count").to_pylist()
+    assert df.schema["resolution"] == pl.UInt8
+    assert df.schema["geometry"] == pl.Binary
+    polygons = shapely.from_wkb(df["geometry"].to_list())
+    assert all(polygon.geom_type == "Polygon" for polygon in polygons)
+
+
+def test_unknown_output_raises_value_error(pipes_geojson):
+    """Test that an unknown output format is rejected."""
+    with pytest.raises(ValueError, match="Unknown output"):
+        infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11, output="pandas")
+
+
+def test_invalid_api_key_header_raises_value_error():
+    """Test that a bad api_key_header is rejected before any request."""
+    with pytest.raises(ValueError, match="api_key_header"):
+        infra_hex_py.get_built_up_area(
+            1310, http_options={"api_key": "secret", "api_key_header": "bad header"}
+        )
+
+
+def test_invalid_api_key_is_not_echoed():
+    """Test that an api_key unusable as a header value is rejected without leaking it."""
+    with pytest.raises(ValueError) as excinfo:
+        infra_hex_py.get_built_up_area(1310, http_options={"api_key": "s3cr3t\nkey"})
+
+    assert "s3cr3t" not in str(excinfo.value)
+
+
+def test_geometry_field_carries_geoarrow_crs(pipes_geojson):
+    """Test that the geometry field is tagged so from_arrow needs no setup."""
+    result = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    metadata = result.schema.field("geometry").metadata
+
+    assert metadata[b"ARROW:extension:name"] == b"geoarrow.polygon"
+    assert "27700" in json.loads(metadata[b"ARROW:extension:metadata"])["crs"]
+
+
+@requires_mock
+def test_get_hex_summary_geodataframe():
+    """Test that the GeoDataFrame variant has the summary's rows, CRS and geometry."""
+    batch = infra_hex_py.get_hex_summary(MOCK_BBOX, 11, network="mock")
+    gdf = infra_hex_py.get_hex_summary_geodataframe(MOCK_BBOX, 11, network="mock")
+
+    assert isinstance(gdf, gpd.GeoDataFrame)
+    assert len(gdf) == batch.num_rows
+    assert gdf.crs.to_epsg() == 27700
+    assert gdf.geometry.name == "geometry"
+    assert isinstance(gdf.geometry.dtype, gpd.array.GeometryDtype)
+    assert (gdf.geom_type == "Polygon").all()
+    assert gdf["hex_id"].tolist() == batch.column("hex_id").to_pylist()
+
+
+def test_boundary_pipe_count():
+    """Test that only pipes crossing several hexes count towards boundary_pipe_count."""
+    long_main = [[-2.25 + i * 0.0005, 53.48] for i in range(20)]
+    short_service = [[-2.23, 53.485], [-2.23, 53.485001]]
+    geojson = json.dumps(
+        {
+            "type": "FeatureCollection",
+            "features": [
+                _line_feature(long_main, asset_id="MAIN"),
+                _line_feature(short_service, asset_id="SERVICE"),
+            ],
+        }
+    )
+    result = infra_hex_py.hex_summary_from_geojson(
+        geojson, 13, boundary_pipe_count=True
+    ).to_pydict()
+
+    assert list(result)[:3] == ["hex_id", "pipe_count", "boundary_pipe_count"]
+    counts = sorted(zip(result["pipe_count"], result["boundary_pipe_count"]))
+    assert counts[0] == (1, 0), "The short service lies within one hex"
+    assert len(counts) > 2
+    assert set(counts[1:]) == {(1, 1)}, "Every hex of the main is a boundary hex"
+
+
+def test_long_segment_counts_every_hex_it_crosses():
+    """Test that a two-vertex line is counted in every hex it passes through."""
+    line = shapely.LineString([(383000, 398000), (387000, 401000)])  # 5 km, in BNG
+    wgs84 = gpd.GeoSeries([line], crs=27700).to_crs(4326)
+    feature = _line_feature(shapely.get_coordinates(wgs84.iloc[0]).tolist())
+    source = json.dumps({"type": "FeatureCollection", "features": [feature]})
+
+    result = infra_hex_py.hex_summary_from_geojson(source, 9)
+    hexes = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([result])).geometry
+
+    # Zoom-9 hexes are 346 m across, so the endpoints' hexes are far from adjacent
+    assert len(hexes) > line.length / 346
+    assert hexes.intersects(line).all()
+    assert line.difference(hexes.union_all()).length < 1e-6
+    assert set(result.column("pipe_count").to_pylist()) == {1}
+
+
+def test_configure_runtime_rejects_zero_threads():
+    """Test that configure_runtime requires at least one worker thread."""
+    with pytest.raises(ValueError, match="at least 1"):
+        infra_hex_py.configure_runtime(0)
+
+
+@requires_mock
+def test_configure_runtime_after_first_fetch_raises():
+    """Test that configure_runtime fails once a fetch has started the runtime."""
+    infra_hex_py.get_hex_summary(MOCK_BBOX, 11, network="mock")
+    with pytest.raises(RuntimeError, match="before the first fetch"):
+        infra_hex_py.configure_runtime(2)
+
+
+FIXTURE_BBOX = (53.47, -2.26, 53.49, -2.22)
+MOCK_PIPES = pathlib.Path(__file__).parents[1] / "src" / "mock_pipes.geojson"
+
+
+def _mock_pipe_features():
+    return json.loads(MOCK_PIPES.read_text())["features"]
+
+
+def _expected_counts(features, zoom):
+    """Counts for the features Cadent would return for FIXTURE_BBOX."""
+    min_lat, min_lon, max_lat, max_lon = FIXTURE_BBOX
+    inside = [
+        f
+        for f in features
+        if min_lat <= cadent_record(f)["geo_point_2d"]["lat"] <= max_lat
+        and min_lon <= cadent_record(f)["geo_point_2d"]["lon"] <= max_lon
+    ]
+    source = json.dumps({"type": "FeatureCollection", "features": inside})
+    return _summary_counts(infra_hex_py.hex_summary_from_geojson(source, zoom))
+
+
+def _fixture_summary(server, zoom, **http_options):
+    return infra_hex_py.get_hex_summary(
+        *FIXTURE_BBOX,
+        resolution=zoom,
+        http_options={
+            "cadent_url": server.cadent_url,
+            "api_key": "test",
+            **http_options,
+        },
+    )
+
+
+def test_cadent_url_runs_full_summary_against_fixture_server():
+    """Test that get_hex_summary fetches from a cadent_url override end to end."""
+    features = _mock_pipe_features()
+    with FixtureServer(features) as server:
+        result = _fixture_summary(server, 11)
+
+    assert result.num_rows > 1
+    assert _summary_counts(result) == _expected_counts(features, 11)
+    assert all("in_bbox" in request["where"] for request in server.requests)
+
+
+def test_cadent_url_environment_variable(monkeypatch):
+    """Test that INFRA_HEX_CADENT_URL redirects fetches and shows in build_info."""
+    features = _mock_pipe_features()
+    with FixtureServer(features) as server:
+        monkeypatch.setenv("INFRA_HEX_CADENT_URL", server.cadent_url)
+        result = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution=11, http_options={"api_key": "test"}
+        )
+        info = infra_hex_py.build_info()
+
+    assert _summary_counts(result) == _expected_counts(features, 11)
+    assert info["cadent_datasets_url"] == server.cadent_url
+    assert info["cadent_layers"]["gas_pipes"].startswith(server.cadent_url + "/")
+
+
+def test_ons_url_environment_variable_in_build_info(monkeypatch):
+    """Test that INFRA_HEX_ONS_URL replaces the built-up areas layer URL."""
+    layer = "http://127.0.0.1:9/arcgis/rest/services/bua/FeatureServer/0"
+    monkeypatch.setenv("INFRA_HEX_ONS_URL", layer)
+    assert infra_hex_py.build_info()["ons_built_up_areas_url"] == layer + "/query"
+
+
+@pytest.mark.parametrize(
+    "key, url",
+    [
+        ("cadent_url", "ftp://mirror.internal/datasets"),
+        ("cadent_url", "http://mirror.internal/datasets?apikey=1"),
+        ("ons_url", "file:///srv/bua/FeatureServer/0"),
+        ("ons_url", "https://mirror.internal/bua/MapServer/0"),
+    ],
+)
+def test_invalid_endpoint_url_raises_value_error(key, url):
+    """Test that endpoint overrides must be http(s) URLs of the right shape."""
+    with pytest.raises(ValueError, match="{}|feature service layer".format(key)):
+        infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution=11, http_options={key: url, "api_key": "test"}
+        )
+
+
+def test_rate_limited_fetch_waits_for_retry_after():
+    """Test that 429 responses are retried and the fetch still completes."""
+    features = _mock_pipe_features()
+    with FixtureServer(features, rate_limited=2) as server:
+        result = _fixture_summary(server, 11, backoff_base_seconds=0)
+
+    assert _summary_counts(result) == _expected_counts(features, 11)
+    assert len(server.requests) > 2
+
+
+def test_server_ignoring_offset_is_fetched_by_quadrant():
+    """Test that a server repeating its first page still yields every record."""
+    min_lat, min_lon, max_lat, max_lon = FIXTURE_BBOX
+    # 256 short pipes, more than one 100-record page, kept off the quadrant edges
+    features = []
+    for row in range(16):
+        for col in range(16):
+            lat = min_lat + (row + 0.3) * (max_lat - min_lat) / 16
+            lon = min_lon + (col + 0.3) * (max_lon - min_lon) / 16
+            features.append(
+                _line_feature(
+                    [[lon, lat], [lon + 0.0004, lat + 0.0002]],
+                    asset_id="GRID-{}-{}".format(row, col),
+                )
+            )
+    with FixtureServer(features, ignore_offset=True) as server:
+        result = _fixture_summary(server, 12)
+
+    assert _summary_counts(result) == _expected_counts(features, 12)
+
+
+def test_clip_records_covered_area_fraction(pipes_geojson, pipe_hexes):
+    """Test that covered_area_fraction is the hexes' share of the clip's area."""
+    area = shapely.box(*pipe_hexes.total_bounds).buffer(200)
+    result = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 10, clip_geojson=_clip_from_bng(area)
+    )
+
+    fraction = float(result.schema.metadata[b"covered_area_fraction"])
+    expected = pipe_hexes.union_all().area / area.area
+    assert 0 < fraction < 1
+    assert fraction == pytest.approx(expected, rel=1e-3)
+    assert b"covered_area_fraction" not in infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 10
+    ).schema.metadata
+
+
+def test_negative_k_ring_raises_value_error():
+    """Test that a negative k_ring is rejected before any fetch."""
+    with pytest.raises(ValueError, match="k_ring"):
+        infra_hex_py.get_hex_summary(MOCK_BBOX, 11, k_ring=-1)
+
+
+@requires_mock
+def test_k_ring_pads_populated_hexes():
+    """Test that k_ring adds empty neighbours and flags those outside the bbox."""
+    plain = infra_hex_py.get_hex_summary(MOCK_BBOX, 11, network="mock")
+    padded = infra_hex_py.get_hex_summary_geodataframe(
+        MOCK_BBOX, 11, network="mock", k_ring=1
+    )
+    bbox = (
+        gpd.GeoSeries([shapely.box(-2.26, 53.47, -2.22, 53.49)], crs=4326)
+        .to_crs(27700)
+        .iloc[0]
+    )
+
+    original = padded[padded["grid_distance"] == 0]
+    ring = padded[padded["grid_distance"] == 1]
+    assert original["hex_id"].tolist() == plain.column("hex_id").to_pylist()
+    assert len(ring) > 0
+    assert (ring["pipe_count"] == 0).all()
+    assert set(padded["grid_distance"]) == {0, 1}
+    outside = ~(padded.intersects(bbox) & ~padded.touches(bbox))
+    assert padded["outside_query_area"].tolist() == outside.tolist()
+
+
+def test_hex_summary_for_cells_rejects_mixed_zooms(pipes_geojson):
+    """Test that cells at different zoom levels are rejected before any fetch."""
+    cells = [
+        infra_hex_py.hex_summary_from_geojson(pipes_geojson, zoom)
+        .column("hex_id")[0]
+        .as_py()
+        for zoom in (10, 11)
+    ]
+    with pytest.raises(ValueError, match="mix resolutions"):
+        infra_hex_py.hex_summary_for_cells(cells)
+
+
+def test_hex_summary_for_cells_returns_requested_cells_in_order():
+    """Test that each requested cell gets one row in input order, zeros included."""
+    features = _mock_pipe_features()
+    expected = _summary_counts(
+        infra_hex_py.hex_summary_from_geojson(
+            json.dumps({"type": "FeatureCollection", "features": features}), 11
+        )
+    )
+    london = json.dumps(
+        {
+            "type": "FeatureCollection",
+            "features": [_line_feature([[-0.1300, 51.5000], [-0.1301, 51.5001]])],
+        }
+    )
+    empty = (
+        infra_hex_py.hex_summary_from_geojson(london, 11).column("hex_id")[0].as_py()
+    )
+    populated = sorted(expected)
+    cells = [empty, *reversed(populated), populated[0]]
+
+    with FixtureServer(features) as server:
+        with pytest.warns(UserWarning, match="1 duplicate"):
+            result = infra_hex_py.hex_summary_for_cells(
+                cells,
+                http_options={"cadent_url": server.cadent_url, "api_key": "test"},
+            )
+
+    assert result.column("hex_id").to_pylist() == cells[:-1]
+    assert _summary_counts(result) == {**expected, empty: 0}
+
+
+def test_zoom_keyword_is_deprecated_alias_for_resolution(pipes_geojson):
+    """Test that zoom= still works, with a DeprecationWarning pointing at resolution."""
+    with pytest.warns(DeprecationWarning, match="pass resolution instead"):
+        old = infra_hex_py.hex_summary_from_geojson(pipes_geojson, zoom=10)
+    new = infra_hex_py.hex_summary_from_geojson(pipes_geojson, resolution=10)
+
+    assert old.equals(new)
+
+
+def test_resolution_and_zoom_together_raise_type_error(pipes_geojson):
+    """Test that a resolution given both ways is rejected."""
+    with pytest.raises(TypeError, match="deprecated alias zoom"):
+        infra_hex_py.hex_summary_from_geojson(pipes_geojson, 10, zoom=10)
+
+
+def test_resolution_above_15_raises_value_error():
+    """Test that resolutions finer than the grid's 15 are rejected before any fetch."""
+    with pytest.raises(ValueError, match="resolution must be between 0 and 15"):
+        infra_hex_py.get_hex_summary(MOCK_BBOX, 16)
+
+
+def test_hex_summary_for_cells_rejects_cells_at_another_resolution(pipes_geojson):
+    """Test that cells not at the requested resolution are rejected before fetching."""
+    cells = (
+        infra_hex_py.hex_summary_from_geojson(pipes_geojson, 10)
+        .column("hex_id")
+        .to_pylist()
+    )
+    with pytest.raises(ValueError, match="expected resolution 11"):
+        infra_hex_py.hex_summary_for_cells(cells, 11)
+
+
+def test_with_stats_returns_summary_statistics():
+    """Test that with_stats=True returns totals matching the summary itself."""
+    with FixtureServer(_mock_pipe_features()) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        result, stats = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX,
+            resolution=11,
+            weighting="length",
+            with_stats=True,
+            http_options=http_options,
+        )
+        cache = infra_hex_py.RegionCache.from_bbox(
+            *FIXTURE_BBOX, http_options=http_options
+        )
+
+    counts = result.column("pipe_count").to_pylist()
+    assert stats["total_records"] == cache.to_pipe_records().num_rows
+    assert stats["hex_count"] == result.num_rows
+    assert stats["max_pipe_count"] == max(counts)
+    assert stats["mean_pipe_count"] == pytest.approx(sum(counts) / len(counts))
+    assert stats["total_pipe_length_m"] > 0
+    assert stats["fetch_seconds"] >= 0
+
+
+def test_with_stats_for_empty_summary_is_zeros():
+    """Test that an empty summary's stats are zeros rather than NaNs."""
+    with FixtureServer([]) as server:
+        result, stats = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX,
+            resolution=11,
+            with_stats=True,
+            http_options={"cadent_url": server.cadent_url, "api_key": "test"},
+        )
+
+    assert result.num_rows == 0
+    assert stats == {
+        "total_records": 0,
+        "hex_count": 0,
+        "max_pipe_count": 0,
+        "mean_pipe_count": 0.0,
+        "total_pipe_length_m": None,
+        "fetch_seconds": stats["fetch_seconds"],
+    }
+
+
+def test_warmup_opens_shared_client():
+    """Test that warmup reaches both hosts through a client later fetches reuse."""
+    infra_hex_py.reset_clients()
+    assert infra_hex_py.pool_stats() == {"clients": 0, "in_flight": 0}
+
+    with FixtureServer(_mock_pipe_features()) as server:
+        http_options = {
+            "cadent_url": server.cadent_url,
+            "ons_url": server.ons_url,
+            "api_key": "test",
+        }
+        infra_hex_py.warmup(http_options=http_options)
+        assert len(server.requests) == 2
+        _fixture_summary(server, 11)
+
+    assert infra_hex_py.pool_stats() == {"clients": 1, "in_flight": 0}
+
+
+def test_warmup_raises_when_a_host_is_unreachable():
+    """Test that warmup names the endpoint it could not reach."""
+    with FixtureServer([]) as server:
+        with pytest.raises(RuntimeError, match="ons_built_up_areas"):
+            infra_hex_py.warmup(
+                http_options={
+                    "cadent_url": server.cadent_url,
+                    "ons_url": "http://127.0.0.1:9/ons/FeatureServer/0",
+                    "api_key": "test",
+                    "connect_timeout_secs": 1,
+                }
+            )
+
+
+def test_hex_summary_from_saved_matches_live_summary(tmp_path):
+    """Test that a saved fetch summarises offline exactly as the live fetch did."""
+    path = str(tmp_path / "fetch.arrow")
+    with FixtureServer(_mock_pipe_features()) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        live = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution=11, http_options=http_options
+        )
+        written = infra_hex_py.save_raw_fetch(
+            path, *FIXTURE_BBOX, http_options=http_options
+        )
+
+    saved = infra_hex_py.hex_summary_from_saved(path, 11)
+
+    assert written == pa.ipc.open_file(path).read_all().num_rows
+    assert _summary_counts(saved) == _summary_counts(live)
+    assert saved.column("provider").to_pylist() == ["cadent"] * saved.num_rows
+    assert b"fetched_at" in saved.schema.metadata
+
+
+def _resave_with_metadata(path, **changes):
+    """Rewrite a saved fetch with some of its raw fetch metadata replaced."""
+    table = pa.ipc.open_file(path).read_all()
+    metadata = json.loads(table.schema.metadata[b"infra_hex_py.raw_fetch"])
+    metadata.update(changes)
+    table = table.replace_schema_metadata(
+        {"infra_hex_py.raw_fetch": json.dumps(metadata)}
+    )
+    with pa.ipc.new_file(path, table.schema) as writer:
+        writer.write_table(table)
+
+
+def test_hex_summary_from_saved_checks_metadata(tmp_path):
+    """Test that a mismatched bbox warns and a newer format version is refused."""
+    path = str(tmp_path / "fetch.arrow")
+    with FixtureServer(_mock_pipe_features()) as server:
+        infra_hex_py.save_raw_fetch(
+            path,
+            *FIXTURE_BBOX,
+            http_options={"cadent_url": server.cadent_url, "api_key": "test"},
+        )
+
+    _resave_with_metadata(path, bbox=[51.50, -0.13, 51.51, -0.12])
+    with pytest.warns(UserWarning, match="outside the saved bbox"):
+        infra_hex_py.hex_summary_from_saved(path, 11)
+
+    _resave_with_metadata(path, format_version=99)
+    with pytest.raises(ValueError, match="format version 99"):
+        infra_hex_py.hex_summary_from_saved(path, 11)
+
+
+def test_capsule_output_is_read_zero_copy_by_pyarrow(pipes_geojson):
+    """Test that output="capsule" exports the same batch through both capsules."""
+    expected = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    result = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 11, output="capsule"
+    )
+
+    assert type(result).__name__ == "ArrowBatch"
+    assert len(result) == result.num_rows == expected.num_rows
+    assert result.column_names == expected.schema.names
+    assert pa.table(result).to_batches()[0].equals(expected)
+    assert pa.record_batch(result).equals(expected)
+    assert pa.table(result).schema.metadata == expected.schema.metadata
+
+
+def test_import_without_pyarrow(tmp_path, pipes_geojson):
+    """Test that the module imports and summarises with pyarrow unavailable."""
+    path = tmp_path / "pipes.geojson"
+    path.write_text(pipes_geojson)
+    script = (
+        "import sys\n"
+        "sys.modules['pyarrow'] = None\n"
+        "import infra_hex_py\n"
+        f"path = {str(path)!r}\n"
+        "result = infra_hex_py.hex_summary_from_geojson(path, 11)\n"
+        "assert type(result).__name__ == 'ArrowBatch', type(result)\n"
+        "assert 'arrow_array_stream' in repr(result.__arrow_c_stream__())\n"
+        "try:\n"
+        "    infra_hex_py.hex_summary_from_geojson(path, 11, output='pyarrow')\n"
+        "except ImportError as e:\n"
+        "    assert 'pyarrow' in str(e)\n"
+        "else:\n"
+        "    raise AssertionError('output=pyarrow did not raise ImportError')\n"
+    )
+
+    subprocess.run([sys.executable, "-c", script], check=True)
+
+
+def test_snap_to_hex_fetches_whole_edge_hexes():
+    """Test that snap_to_hex counts a pipe just beyond the edge in its edge hex."""
+    pipe = _line_feature([[-2.2401, 53.48], [-2.2399, 53.48]])
+    with FixtureServer([pipe]) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        hexes = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution=8, http_options=http_options
+        )
+        hex_bounds = (
+            gpd.GeoDataFrame.from_arrow(pa.table(hexes)).to_crs(4326).total_bounds
+        )
+        # Cut the tile between the bottom of the pipe's hex and the pipe itself
+        edge = (hex_bounds[1] + 53.48) / 2
+        tile = (53.47, -2.26, edge, -2.22)
+        clipped = infra_hex_py.get_hex_summary(
+            *tile, resolution=8, http_options=http_options
+        )
+        snapped = infra_hex_py.get_hex_summary(
+            *tile, resolution=8, snap_to_hex=True, http_options=http_options
+        )
+
+    assert _summary_counts(hexes) == {hexes.column("hex_id")[0].as_py(): 1}
+    assert clipped.num_rows == 0
+    assert _summary_counts(snapped) == _summary_counts(hexes)
+
+
+def test_pipe_hex_assignments_aggregate_to_summary():
+    """Test that counting assignment rows per hex reproduces get_hex_summary."""
+    with FixtureServer(_mock_pipe_features()) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        summary = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution=11, http_options=http_options
+        )
+        rows = infra_hex_py.get_pipe_hex_assignments(
+            *FIXTURE_BBOX, resolution=11, http_options=http_options
+        )
+
+    assert rows.schema.names == ["pipe_id", "hex_id", "geometry"]
+    counts = {}
+    for hex_id in rows.column("hex_id").to_pylist():
+        counts[hex_id] = counts.get(hex_id, 0) + 1
+    assert counts == _summary_counts(summary)
+
+    # A main crossing many hexes has one row per hex, all with its geometry
+    pipe_ids = rows.column("pipe_id").to_pylist()
+    longest = max(set(pipe_ids) - {None}, key=pipe_ids.count)
+    assert pipe_ids.count(longest) > 1
+    geometries = {
+        rows.column("geometry")[i].as_py()
+        for i, pipe_id in enumerate(pipe_ids)
+        if pipe_id == longest
+    }
+    assert len(geometries) == 1
+
+
+def test_auto_resolution_aims_for_target_hex_count():
+    """Test that resolution="auto" records its choice and coarsens for fewer hexes."""
+    with FixtureServer(_mock_pipe_features()) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        fine, coarse = (
+            infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX,
+                resolution="auto",
+                target_hex_count=target,
+                http_options=http_options,
+            )
+            for target in (5000, 50)
+        )
+
+    for summary in (fine, coarse):
+        chosen = int(summary.schema.metadata[b"resolution"])
+        assert 0 <= chosen <= 15
+        assert set(summary.column("resolution").to_pylist()) == {chosen}
+    assert int(coarse.schema.metadata[b"resolution"]) < int(
+        fine.schema.metadata[b"resolution"]
+    )
+
+    with pytest.raises(ValueError, match="target_hex_count"):
+        infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution="auto", target_hex_count=0, network="mock"
+        )
+    with pytest.raises(ValueError, match="zero"):
+        infra_hex_py.get_hex_summary(
+            53.47, -2.24, 53.49, -2.24, resolution="auto", network="mock"
+        )
+    with pytest.raises(ValueError, match='"auto"'):
+        infra_hex_py.get_hex_summary(*FIXTURE_BBOX, resolution="fine")
+
+
+def _grid_features(n):
+    """`n` x `n` short pipes spread over FIXTURE_BBOX, off the quadrant edges."""
+    min_lat, min_lon, max_lat, max_lon = FIXTURE_BBOX
+    features = []
+    for row in range(n):
+        for col in range(n):
+            lat = min_lat + (row + 0.3) * (max_lat - min_lat) / n
+            lon = min_lon + (col + 0.3) * (max_lon - min_lon) / n
+            features.append(
+                _line_feature(
+                    [[lon, lat], [lon + 0.0004, lat + 0.0002]],
+                    asset_id="GRID-{}-{}".format(row, col),
+                )
+            )
+    return features
+
+
+def test_max_total_features_stops_the_fetch_early():
+    """Test that max_total_features truncates the fetch and skips later queries."""
+    features = _grid_features(16)
+    for ignore_offset in (False, True):
+        with FixtureServer(features, ignore_offset=ignore_offset) as server:
+            http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+            full, full_stats = infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX,
+                resolution=11,
+                max_total_features=1000,
+                with_stats=True,
+                http_options=http_options,
+            )
+            full_requests = len(server.requests)
+            with pytest.warns(UserWarning, match="max_total_features=150"):
+                capped, capped_stats = infra_hex_py.get_hex_summary(
+                    *FIXTURE_BBOX,
+                    resolution=11,
+                    max_total_features=150,
+                    with_stats=True,
+                    http_options=http_options,
+                )
+            capped_requests = len(server.requests) - full_requests
+
+        assert full.schema.metadata[b"truncated"] == b"false"
+        assert full_stats["total_records"] == len(features)
+        assert capped.schema.metadata[b"truncated"] == b"true"
+        if ignore_offset:
+            # Repeated pages count against the cap before deduplication
+            assert capped_stats["total_records"] <= 150
+            assert capped_requests < full_requests
+        else:
+            assert capped_stats["total_records"] == 150
+
+    with pytest.raises(ValueError, match="max_total_features"):
+        infra_hex_py.get_hex_summary(MOCK_BBOX, 11, max_total_features=0)
+
+
+def test_single_threaded_aggregation_matches_default(tmp_path, pipes_geojson):
+    """Test that configure_aggregation(1) gives the same summary as the default."""
+    path = tmp_path / "pipes.geojson"
+    path.write_text(pipes_geojson)
+    expected = infra_hex_py.hex_summary_from_geojson(str(path), 11)
+    script = (
+        "import infra_hex_py\n"
+        "infra_hex_py.configure_aggregation(1)\n"
+        f"result = infra_hex_py.hex_summary_from_geojson({str(path)!r}, 11)\n"
+        "print(result.column('hex_id').to_pylist())\n"
+        "print(result.column('pipe_count').to_pylist())\n"
+        "try:\n"
+        "    infra_hex_py.configure_aggregation(2)\n"
+        "except RuntimeError:\n"
+        "    pass\n"
+        "else:\n"
+        "    raise AssertionError('configure_aggregation did not raise')\n"
+    )
+
+    output = subprocess.run(
+        [sys.executable, "-c", script], check=True, capture_output=True, text=True
+    ).stdout.splitlines()
+
+    assert output == [
+        str(expected.column("hex_id").to_pylist()),
+        str(expected.column("pipe_count").to_pylist()),
+    ]
+    with pytest.raises(ValueError, match="num_threads"):
+        infra_hex_py.configure_aggregation(0)
+
+
+def test_empty_page_is_retried_against_the_count():
+    """Test that a page emptier than total_count promised is fetched again."""
+    features = _mock_pipe_features()
+    with FixtureServer(features, empty_pages=1) as server:
+        result = _fixture_summary(server, 11, backoff_base_seconds=0)
+    assert _summary_counts(result) == _expected_counts(features, 11)
+
+    with FixtureServer(features, empty_pages=10) as server:
+        with pytest.warns(UserWarning, match="more records than it returned"):
+            result = _fixture_summary(
+                server, 11, max_retries=2, backoff_base_seconds=0
+            )
+    assert result.num_rows == 0
+
+    with FixtureServer(features, empty_pages=1) as server:
+        result = _fixture_summary(server, 11, verify_counts=False)
+    assert result.num_rows == 0
+
+
+def test_repeated_id_columns_are_dictionary_encoded():
+    """Test that parent and assignment ids store each distinct id only once."""
+    features = _grid_features(16)
+    fine = infra_hex_py.hex_summary_from_geojson(
+        json.dumps({"type": "FeatureCollection", "features": features}),
+        11,
+        parent_zoom=6,
+    )
+    with FixtureServer(features) as server:
+        rows = infra_hex_py.get_pipe_hex_assignments(
+            *FIXTURE_BBOX,
+            resolution=6,
+            http_options={"cadent_url": server.cadent_url, "api_key": "test"},
+        )
+
+    for column in (fine.column("parent_hex_id"), rows.column("hex_id")):
+        assert pa.types.is_dictionary(column.type)
+        assert len(column.dictionary) < len(column) / 2
+        decoded = column.cast(pa.string())
+        assert decoded.nbytes >= 2 * column.nbytes
+    assert pa.types.is_dictionary(rows.column("pipe_id").type)
+
+
+def test_empty_bbox_keeps_the_full_schema():
+    """Test that a bbox with no pipes gives zero rows with the populated schema."""
+    options = dict(
+        resolution=11,
+        parent_zoom=9,
+        boundary_pipe_count=True,
+        k_ring=1,
+        with_stats=True,
+    )
+    with FixtureServer(_mock_pipe_features()) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        full, _ = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, http_options=http_options, **options
+        )
+        empty, stats = infra_hex_py.get_hex_summary(
+            51.50, -0.13, 51.51, -0.12, http_options=http_options, **options
+        )
+
+    assert full.num_rows > 0
+    assert empty.num_rows == 0
+    assert empty.schema.remove_metadata() == full.schema.remove_metadata()
+    assert stats["total_records"] == 0
+
+
+def test_clip_removing_every_hex_keeps_the_full_schema(pipes_geojson):
+    """Test that a clip away from every pipe gives zero rows, not an error."""
+    around = gpd.GeoSeries([shapely.box(-2.26, 53.47, -2.23, 53.49)], crs=4326)
+    elsewhere = _clip_from_bng(shapely.box(530000, 180000, 531000, 181000))
+    full, empty = (
+        infra_hex_py.hex_summary_from_geojson(
+            pipes_geojson, 11, clip, parent_zoom=9, clipped_area=True
+        )
+        for clip in (around.to_json(), elsewhere)
+    )
+
+    assert full.num_rows > 0
+    assert empty.num_rows == 0
+    assert empty.schema.remove_metadata() == full.schema.remove_metadata()
+    assert float(empty.schema.metadata[b"covered_area_fraction"]) == 0
+
+
+def test_hex_area_is_each_hexs_ground_area(pipes_geojson):
+    """Test that hex_area_m2 is each hex's ellipsoidal area, not the BNG constant."""
+    import pyproj
+
+    result = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11, hex_area=True)
+    hexes = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([result])).geometry
+    geod = pyproj.Geod(ellps="WGS84")
+    ground = [
+        abs(geod.geometry_area_perimeter(cell)[0]) for cell in hexes.to_crs(4326)
+    ]
+    areas = result.column("hex_area_m2").to_pylist()
+
+    assert result.schema.names[-4:-3] == ["hex_area_m2"]
+    assert areas == pytest.approx(ground, rel=1e-6)
+    for area, bng_area in zip(areas, hexes.area):
+        assert area == pytest.approx(bng_area, rel=2e-3)
+        assert area != pytest.approx(bng_area, rel=1e-6)
+    assert "hex_area_m2" not in infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 11
+    ).schema.names
+
+
+def test_unknown_object_id_raises_area_not_found_offline():
+    """Test that an empty ONS response for an OBJECTID raises AreaNotFoundError."""
+    with FixtureServer([]) as server:
+        http_options = {"ons_url": server.ons_url}
+        with pytest.raises(
+            infra_hex_py.AreaNotFoundError,
+            match="No built-up area found for OBJECTID 999999",
+        ) as excinfo:
+            infra_hex_py.get_built_up_area(999999, http_options=http_options)
+        assert isinstance(excinfo.value, LookupError)
+        assert excinfo.value.object_id == 999999
+        with pytest.raises(ValueError, match="No built-up area found"):
+            infra_hex_py.get_built_up_area(999999, http_options=http_options)
+        with pytest.raises(infra_hex_py.AreaNotFoundError, match="999999"):
+            infra_hex_py.get_hex_summary_polygon_area(
+                999999, 9, http_options={**http_options, "api_key": "test"}
+            )
+
+    assert server.requests[0]["where"] == "OBJECTID=999999"
+
+
+def test_3d_and_measured_lines_are_binned_as_2d():
+    """Test that records with Z or M values are counted, with the coercions noted."""
+    features = _mock_pipe_features()
+    lifted = json.loads(json.dumps(features))
+    for feature in lifted:
+        for position in feature["geometry"]["coordinates"]:
+            position.append(-1.2)
+    with FixtureServer(lifted) as server:
+        # Records share their geometry with `lifted`, so this adds M after the
+        # server has located each record from its XYZ line
+        for position in lifted[0]["geometry"]["coordinates"]:
+            position.append(7.0)
+        min_lat, min_lon, max_lat, max_lon = FIXTURE_BBOX
+        served = sum(
+            min_lat <= r["geo_point_2d"]["lat"] <= max_lat
+            and min_lon <= r["geo_point_2d"]["lon"] <= max_lon
+            for r in server.records
+        )
+        result = _fixture_summary(server, 11)
+    with FixtureServer(features) as server:
+        flat = _fixture_summary(server, 11)
+
+    assert served > 0
+    assert _summary_counts(result) == _expected_counts(features, 11)
+    assert result.schema.metadata[b"coerced_to_2d"] == str(served).encode()
+    assert flat.schema.metadata[b"coerced_to_2d"] == b"0"
+
+
+def test_summary_for_polygon_fetches_without_a_boundary_lookup():
+    """Test that a supplied polygon is fetched and clipped like a looked-up one."""
+    features = _mock_pipe_features()
+    polygon = shapely.box(-2.25, 53.47, -2.23, 53.485)
+    with FixtureServer(features) as server:
+        result = infra_hex_py.get_hex_summary_for_polygon(
+            shapely.to_geojson(polygon),
+            11,
+            http_options={"cadent_url": server.cadent_url, "api_key": "test"},
+        )
+
+    assert server.requests
+    assert all("in_bbox" in request["where"] for request in server.requests)
+    served = [
+        f
+        for f in features
+        if polygon.covers(shapely.Point(*cadent_record(f)["geo_point_2d"].values()))
+    ]
+    expected = infra_hex_py.hex_summary_from_geojson(
+        json.dumps({"type": "FeatureCollection", "features": served}),
+        11,
+        clip_geojson=shapely.to_geojson(polygon),
+    )
+    assert result.num_rows > 0
+    assert _summary_counts(result) == _summary_counts(expected)
+
+
+def _built_up_area_feature(object_id, name):
+    boundary = shapely.box(-2.25, 53.47, -2.23, 53.49)
+    return {
+        "type": "Feature",
+        "properties": {"OBJECTID": object_id, "BUA24CD": "E63000000", "BUA24NM": name},
+        "geometry": json.loads(shapely.to_geojson(boundary)),
+    }
+
+
+@pytest.mark.parametrize(
+    ("ons_features", "message"),
+    [
+        (
+            [_built_up_area_feature(42, "Here"), _built_up_area_feature(42, "There")],
+            "2 built-up areas for OBJECTID 42",
+        ),
+        ([_built_up_area_feature(7, "Elsewhere")], "built-up area 7 .*OBJECTID 42"),
+    ],
+)
+def test_ambiguous_built_up_area_is_an_error(ons_features, message):
+    """Test that several areas, or the wrong one, for an OBJECTID are not guessed."""
+    with FixtureServer([], ons_features=ons_features) as server:
+        http_options = {"ons_url": server.ons_url}
+        with pytest.raises(infra_hex_py.FetchError, match=message):
+            infra_hex_py.get_built_up_area(42, http_options=http_options)
+
+
+@requires_mock
+def test_set_default_network_applies_when_network_is_omitted():
+    """Test that set_default_network picks the network used without network=."""
+    previous = infra_hex_py.set_default_network("mock")
+    try:
+        result = infra_hex_py.get_hex_summary(MOCK_BBOX, 11)
+        cache = infra_hex_py.RegionCache.from_bbox(MOCK_BBOX)
+    finally:
+        assert infra_hex_py.set_default_network(previous) == "mock"
+
+    assert previous == "cadent"
+    assert result.equals(infra_hex_py.get_hex_summary(MOCK_BBOX, 11, network="mock"))
+    assert len(cache) > 0
+
+
+def test_set_default_network_rejects_unknown_names():
+    """Test that an unknown default network raises ValueError and changes nothing."""
+    with pytest.raises(ValueError, match="Unknown network \"sgn\""):
+        infra_hex_py.set_default_network("sgn")
+    assert infra_hex_py.set_default_network("cadent") == "cadent"
+
+
+def _haversine_m(start, end, radius=6_371_008.8):
+    """Great-circle distance between two (lon, lat) points on a sphere."""
+    lon1, lat1, lon2, lat2 = map(math.radians, (*start, *end))
+    a = (
+        math.sin((lat2 - lat1) / 2) ** 2
+        + math.cos(lat1) * math.cos(lat2) * math.sin((lon2 - lon1) / 2) ** 2
+    )
+    return 2 * radius * math.asin(math.sqrt(a))
+
+
+def test_length_method_measures_a_1km_line():
+    """Test each length_method against reference lengths of a 1 km geodesic line."""
+    import pyproj
+
+    start = (-2.24, 53.475)
+    lon, lat, _ = pyproj.Geod(ellps="WGS84").fwd(*start, 45, 1000)
+    end = (lon, lat)
+    to_bng = pyproj.Transformer.from_crs(4326, 27700, always_xy=True)
+    (x1, x2), (y1, y2) = to_bng.transform([start[0], end[0]], [start[1], end[1]])
+    expected = {
+        "geodesic": 1000.0,
+        "haversine": _haversine_m(start, end),
+        "projected_27700": math.hypot(x2 - x1, y2 - y1),
+    }
+
+    feature = _line_feature([list(start), list(end)], asset_id="KM-1")
+    with FixtureServer([feature]) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        for method, length in expected.items():
+            result, stats = infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX,
+                resolution=11,
+                weighting="length",
+                with_stats=True,
+                length_method=method,
+                http_options=http_options,
+            )
+            assert stats["total_pipe_length_m"] == pytest.approx(length, rel=1e-6)
+            assert result.schema.metadata[b"length_method"] == method.encode()
+
+        default, stats = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX,
+            resolution=11,
+            weighting="length",
+            with_stats=True,
+            http_options=http_options,
+        )
+        with pytest.raises(ValueError, match="length_method"):
+            infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX,
+                resolution=11,
+                length_method="planar",
+                http_options=http_options,
+            )
+
+    assert stats["total_pipe_length_m"] == pytest.approx(1000.0, rel=1e-6)
+    assert default.schema.metadata[b"length_method"] == b"geodesic"
+    # BNG's scale factor and the sphere both shorten the line measurably here
+    assert expected["projected_27700"] < 999.8
+    assert expected["haversine"] != pytest.approx(1000.0, rel=1e-3)
+
+
+def test_geometry_encoding_sets_the_geometry_layout(pipes_geojson):
+    """Test that each geometry_encoding gives the same polygons in its own layout."""
+    default = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    encoded = {
+        encoding: infra_hex_py.hex_summary_from_geojson(
+            pipes_geojson, 11, geometry_encoding=encoding
+        )
+        for encoding in ("geoarrow_separated", "geoarrow", "wkb")
+    }
+
+    def extension(batch):
+        metadata = batch.schema.field("geometry").metadata
+        return metadata[b"ARROW:extension:name"], json.loads(
+            metadata[b"ARROW:extension:metadata"]
+        )
+
+    assert encoded["geoarrow_separated"] == default
+    name, metadata = extension(encoded["geoarrow"])
+    assert name == b"geoarrow.polygon"
+    assert "27700" in json.dumps(metadata["crs"])
+    # Interleaved coordinates are a fixed-size list of [x, y] pairs
+    coords = encoded["geoarrow"].schema.field("geometry").type.value_type.value_type
+    assert pa.types.is_fixed_size_list(coords) and coords.list_size == 2
+    assert extension(encoded["wkb"])[0] == b"geoarrow.wkb"
+    assert pa.types.is_binary(encoded["wkb"].schema.field("geometry").type)
+
+    expected = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([default])).geometry
+    for batch in encoded.values():
+        assert batch.drop_columns(["geometry"]) == default.drop_columns(["geometry"])
+        geometry = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([batch])).geometry
+        assert geometry.crs == expected.crs
+        assert geometry.geom_equals_exact(expected, tolerance=0).all()
+    with pytest.raises(ValueError, match="geometry_encoding"):
+        infra_hex_py.hex_summary_from_geojson(
+            pipes_geojson, 11, geometry_encoding="geojson"
+        )
+
+
+def test_write_hex_summary_flatgeobuf(tmp_path, pipes_geojson):
+    """Test that hexes are written as an indexed WGS84 FlatGeobuf file."""
+    import pyogrio
+
+    result = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11)
+    path = tmp_path / "hexes.fgb"
+    path.write_bytes(b"stale contents to overwrite")
+
+    written = infra_hex_py.write_hex_summary_flatgeobuf(result, str(path))
+
+    info = pyogrio.read_info(path)
+    assert written == info["features"] == result.num_rows
+    assert info["geometry_type"] == "Polygon"
+    assert info["crs"] == "EPSG:4326"
+    assert info["capabilities"]["fast_spatial_filter"]
+    fields = dict(zip(info["fields"], info["dtypes"]))
+    assert fields["hex_id"] == "object"
+    assert fields["pipe_count"].startswith("int")
+    assert "geometry" not in fields
+
+    # Same features as the NDJSON writer, reordered by the spatial index
+    ndjson = tmp_path / "hexes.ndjson"
+    infra_hex_py.write_hex_summary_ndjson(result, str(ndjson))
+    expected = gpd.read_file(ndjson).sort_values("hex_id").reset_index(drop=True)
+    gdf = gpd.read_file(path).sort_values("hex_id").reset_index(drop=True)
+    assert gdf["hex_id"].tolist() == expected["hex_id"].tolist()
+    assert gdf["pipe_count"].tolist() == expected["pipe_count"].tolist()
+    assert gdf.geometry.geom_equals_exact(expected.geometry, tolerance=1e-12).all()
+
+
+def test_write_hex_summary_flatgeobuf_empty(tmp_path, pipes_geojson):
+    """Test that an empty summary still writes a readable FlatGeobuf file."""
+    result = infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11).slice(0, 0)
+    path = tmp_path / "empty.fgb"
+
+    assert infra_hex_py.write_hex_summary_flatgeobuf(result, str(path)) == 0
+
+    gdf = gpd.read_file(path)
+    assert len(gdf) == 0
+    assert {"hex_id", "pipe_count"} <= set(gdf.columns)
+
+
+class _GeoInterface:
+    """Stands in for a shapely geometry or Fiona feature."""
+
+    def __init__(self, mapping):
+        self.__geo_interface__ = mapping
+
+
+def test_clip_accepts_geo_interface(pipes_geojson, clip_polygon):
+    """Test that objects with __geo_interface__ clip like the equivalent GeoJSON."""
+    expected = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 11, clip_geojson=clip_polygon
+    )
+    # shapely's interface nests tuples rather than lists
+    rings = tuple(
+        tuple(tuple(point) for point in ring)
+        for ring in json.loads(clip_polygon)["coordinates"]
+    )
+    polygon = {"type": "Polygon", "coordinates": rings}
+    multipolygon = {"type": "MultiPolygon", "coordinates": (rings,)}
+    feature = {"type": "Feature", "geometry": polygon, "properties": {"id": 1}}
+
+    for mapping in (polygon, multipolygon, feature):
+        result = infra_hex_py.hex_summary_from_geojson(
+            pipes_geojson, 11, clip_geojson=_GeoInterface(mapping)
+        )
+        assert result == expected
+
+    line = {"type": "LineString", "coordinates": ((-2.25, 53.48), (-2.24, 53.48))}
+    with pytest.raises(ValueError, match="got LineString"):
+        infra_hex_py.hex_summary_from_geojson(
+            pipes_geojson, 11, clip_geojson=_GeoInterface(line)
+        )
+    with pytest.raises(TypeError, match="__geo_interface__"):
+        infra_hex_py.hex_summary_from_geojson(pipes_geojson, 11, clip_geojson=42)
+
+
+def test_date_range_filters_the_fetch():
+    """Test that date_from/date_to send a date filter and count only that subset."""
+    features = _mock_pipe_features()
+    old = [f for f in features if f["properties"].get("inst_date", "9") < "1980"]
+    assert 0 < len(old) < len(features)
+    with FixtureServer(features) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        result = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX,
+            resolution=11,
+            date_to=datetime.date(1980, 1, 1),
+            http_options=http_options,
+        )
+        with pytest.raises(ValueError, match="YYYY-MM-DD"):
+            infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX,
+                resolution=11,
+                date_from="1980",
+                http_options=http_options,
+            )
+        with pytest.raises(ValueError, match="before date_to"):
+            infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX,
+                resolution=11,
+                date_from="2000-01-01",
+                date_to="1990-01-01",
+                http_options=http_options,
+            )
+
+    assert _summary_counts(result) == _expected_counts(old, 11)
+    assert result.schema.metadata[b"date_to"] == b"1980-01-01"
+    assert b"date_from" not in result.schema.metadata
+    assert all(
+        "inst_date < date'1980-01-01'" in request["where"]
+        for request in server.requests
+    )
+
+
+def test_merge_hex_summaries_sums_seam_hexes():
+    """Test that merging tile summaries matches a summary of the whole area."""
+    features = _mock_pipe_features()
+
+    def summary(tile):
+        collection = {"type": "FeatureCollection", "features": tile}
+        return infra_hex_py.hex_summary_from_geojson(
+            json.dumps(collection), 11, deduplicate=False, weighting="length"
+        )
+
+    # Long mains in each half cross many of the same hexes, like tiles at a seam
+    first = summary(features[::2])
+    second = summary(features[1::2])
+    full = summary(features)
+    merged = infra_hex_py.merge_hex_summaries([first, pa.Table.from_batches([second])])
+
+    first_ids = set(first.column("hex_id").to_pylist())
+    assert first_ids & set(second.column("hex_id").to_pylist())
+    assert merged.num_rows == full.num_rows
+    assert _summary_counts(merged) == _summary_counts(full)
+
+    def lengths(result):
+        return dict(
+            zip(
+                result.column("hex_id").to_pylist(),
+                result.column("weighted_count").to_pylist(),
+            )
+        )
+
+    assert lengths(merged) == pytest.approx(lengths(full))
+
+    counts_only = pa.Table.from_batches([full]).drop_columns(["weighted_count"])
+    with pytest.raises(ValueError, match="different columns"):
+        infra_hex_py.merge_hex_summaries([first, counts_only])
+    with pytest.raises(ValueError, match="at lsecond one"):
+        infra_hex_py.merge_hex_summaries([])
+
+
+def test_polygon_summary_filters_the_fetch_by_polygon():
+    """Test that an L-shaped clip fetches fewer records but gives the same summary."""
+    min_lat, min_lon, max_lat, max_lon = FIXTURE_BBOX
+    arm_lat = min_lat + 0.2 * (max_lat - min_lat)
+    arm_lon = min_lon + 0.2 * (max_lon - min_lon)
+    l_shape = json.dumps(
+        {
+            "type": "Polygon",
+            "coordinates": [
+                [
+                    [min_lon, min_lat],
+                    [max_lon, min_lat],
+                    [max_lon, arm_lat],
+                    [arm_lon, arm_lat],
+                    [arm_lon, max_lat],
+                    [min_lon, max_lat],
+                    [min_lon, min_lat],
+                ]
+            ],
+        }
+    )
+    features = _grid_features(16)
+
+    def summary(**server_options):
+        with FixtureServer(features, **server_options) as server:
+            result = infra_hex_py.get_hex_summary_for_polygon(
+                l_shape,
+                11,
+                http_options={"cadent_url": server.cadent_url, "api_key": "test"},
+            )
+        return result, server
+
+    filtered, filtered_server = summary()
+    fallback, fallback_server = summary(reject_polygons=True)
+
+    assert any("intersects(" in r["where"] for r in filtered_server.requests)
+    assert filtered_server.records_served < fallback_server.records_served
+    assert filtered.num_rows == fallback.num_rows > 0
+    assert _summary_counts(filtered) == _summary_counts(fallback)
+    assert filtered.column("geometry") == fallback.column("geometry")
+
+
+def test_fields_select_the_fetched_attributes():
+    """Test that fields= is sent as select and shapes the cached record columns."""
+    features = _mock_pipe_features()
+    with FixtureServer(features) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        cache = infra_hex_py.RegionCache.from_bbox(
+            *FIXTURE_BBOX, fields=["type", "diameter"], http_options=http_options
+        )
+        fetches = [r for r in server.requests if "where" in r]
+        with pytest.raises(ValueError, match="available fields: .*material"):
+            infra_hex_py.RegionCache.from_bbox(
+                *FIXTURE_BBOX, fields=["colour"], http_options=http_options
+            )
+        everything = infra_hex_py.RegionCache.from_bbox(
+            *FIXTURE_BBOX, http_options=http_options
+        )
+
+    assert fetches
+    assert all(
+        r["select"] == "geo_point_2d,geo_shape,asset_id,type,diameter" for r in fetches
+    )
+    records = cache.to_pipe_records()
+    assert records.schema.names == ["pipe_type", "diameter", "geometry"]
+    assert records.num_rows == len(everything)
+    assert cache.hex_summary(11) == everything.hex_summary(11)
+    with pytest.raises(ValueError, match="without it"):
+        cache.hex_summary(11, material="PE")
+
+
+def test_summaries_record_the_schema_version():
+    """Test that summaries carry schema_version matching the module constant."""
+    version = str(infra_hex_py.HEX_SUMMARY_SCHEMA_VERSION).encode()
+    assert infra_hex_py.HEX_SUMMARY_SCHEMA_VERSION >= 1
+    collection = json.dumps(
+        {"type": "FeatureCollection", "features": _mock_pipe_features()}
+    )
+    summary = infra_hex_py.hex_summary_from_geojson(collection, 11, parent_zoom=8)
+    bare = infra_hex_py.hex_summary_from_geojson(
+        collection, 11, include_metadata_columns=False
+    )
+    merged = infra_hex_py.merge_hex_summaries([summary, summary])
+
+    for result in (summary, bare, merged):
+        assert result.schema.metadata[b"schema_version"] == version
+
+
+def test_get_pipes_in_cell_returns_the_pipes_through_one_hex():
+    """Test that only pipes crossing the hex come back, with an empty batch if none."""
+    inside = _line_feature([[-2.24000, 53.48000], [-2.24001, 53.48001]], asset_id="IN")
+    elsewhere = _line_feature([[-2.2300, 53.4850], [-2.2301, 53.4851]], asset_id="OUT")
+    features = [inside, elsewhere]
+    summary = infra_hex_py.hex_summary_from_geojson(
+        json.dumps({"type": "FeatureCollection", "features": [inside]}), 11
+    )
+    hex_id = summary.column("hex_id")[0].as_py()
+    london = infra_hex_py.hex_summary_from_geojson(
+        json.dumps(
+            {
+                "type": "FeatureCollection",
+                "features": [_line_feature([[-0.1300, 51.5000], [-0.1301, 51.5001]])],
+            }
+        ),
+        11,
+    )
+
+    with FixtureServer(features) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        pipes = infra_hex_py.get_pipes_in_cell(hex_id, http_options=http_options)
+        empty = infra_hex_py.get_pipes_in_cell(
+            london.column("hex_id")[0].as_py(), 11, http_options=http_options
+        )
+        with pytest.raises(ValueError, match="expected resolution 10"):
+            infra_hex_py.get_pipes_in_cell(hex_id, 10, http_options=http_options)
+
+    assert pipes.column("asset_id").to_pylist() == ["IN"]
+    assert pipes.schema.names[-1] == "geometry"
+    assert empty.num_rows == 0
+    assert empty.schema.remove_metadata() == pipes.schema.remove_metadata()
+
+
+def test_normalized_output_layout_joins_back_to_the_wide_batch():
+    """Test that cells and summary tables reproduce the wide summary exactly."""
+    collection = json.dumps(
+        {"type": "FeatureCollection", "features": _mock_pipe_features()}
+    )
+    wide = infra_hex_py.hex_summary_from_geojson(
+        collection, 11, parent_zoom=8, weighting="length"
+    )
+    result = infra_hex_py.hex_summary_from_geojson(
+        collection, 11, parent_zoom=8, weighting="length", output_layout="normalized"
+    )
+
+    cells = pa.Table.from_batches([result.cells])
+    summary = pa.Table.from_batches([result.summary])
+    assert cells.schema.names == ["hex_id", "resolution", "geometry"]
+    assert "geometry" not in summary.schema.names
+    assert summary.schema.metadata == wide.schema.metadata
+
+    row = {hex_id: i for i, hex_id in enumerate(cells.column("hex_id").to_pylist())}
+    assert len(row) == cells.num_rows
+    hex_ids = summary.column("hex_id").to_pylist()
+    joined = cells.take([row[hex_id] for hex_id in hex_ids])
+    columns = {name: summary.column(name) for name in summary.schema.names}
+    columns.update({name: joined.column(name) for name in ("resolution", "geometry")})
+    rebuilt = pa.table(
+        [columns[name] for name in wide.schema.names], schema=wide.schema
+    )
+    assert rebuilt.equals(pa.Table.from_batches([wide]))
+
+    with pytest.raises(ValueError, match="output_layout"):
+        infra_hex_py.hex_summary_from_geojson(collection, 11, output_layout="long")
+
+
+def test_region_cache_refetches_only_the_failed_pages():
+    """Test that a partial cache lists its failed page and refetch repairs it."""
+    features = _grid_features(16)
+    http_options = {"api_key": "test", "max_retries": 0}
+    with FixtureServer(features) as server:
+        clean = infra_hex_py.RegionCache.from_bbox(
+            *FIXTURE_BBOX,
+            http_options={**http_options, "cadent_url": server.cadent_url},
+        )
+    with FixtureServer(features, fail_offsets=[0]) as server:
+        with pytest.raises(infra_hex_py.FetchError):
+            infra_hex_py.RegionCache.from_bbox(
+                *FIXTURE_BBOX,
+                http_options={**http_options, "cadent_url": server.cadent_url},
+            )
+
+    with FixtureServer(features, fail_offsets=[100]) as server:
+        options = {**http_options, "cadent_url": server.cadent_url}
+        with pytest.warns(UserWarning, match="1 part"):
+            partial = infra_hex_py.RegionCache.from_bbox(
+                *FIXTURE_BBOX, http_options=options, allow_partial=True
+            )
+        failed = partial.failed_pages
+        assert failed == [{"bbox": FIXTURE_BBOX, "offset": 100, "limit": 100}]
+        assert len(partial) == len(clean) - 100
+        with pytest.raises(ValueError, match="failed_pages"):
+            partial.refetch([{**failed[0], "offset": 200}], http_options=options)
+
+        before = len(server.requests)
+        repaired = partial.refetch(failed, http_options=options)
+        assert [r.get("offset") for r in server.requests[before:]] == ["100"]
+
+    assert repaired.failed_pages == []
+    assert len(repaired) == len(clean)
+    assert repaired.hex_summary(11) == clean.hex_summary(11)
+
+
+@pytest.mark.parametrize(
+    ("ons_formats", "output_format", "requested"),
+    [
+        (("geojson", "json"), None, ["geojson"]),
+        (("json",), None, ["geojson", "json"]),
+        (("geojson", "json"), "json", ["json"]),
+        (("geojson",), "geojson", ["geojson"]),
+    ],
+)
+def test_arcgis_queries_negotiate_the_output_format(
+    ons_formats, output_format, requested
+):
+    """Test that ONS lookups fall back to Esri JSON, or use the format asked for."""
+    boundary = shapely.Polygon(
+        [(-2.25, 53.47), (-2.23, 53.47), (-2.23, 53.49), (-2.25, 53.49)],
+        [[(-2.245, 53.475), (-2.235, 53.475), (-2.235, 53.485), (-2.245, 53.485)]],
+    )
+    feature = _built_up_area_feature(42, "Here")
+    feature["geometry"] = json.loads(shapely.to_geojson(boundary))
+
+    with FixtureServer([], ons_features=[feature], ons_formats=ons_formats) as server:
+        http_options = {"ons_url": server.ons_url}
+        if output_format:
+            http_options["arcgis_output_format"] = output_format
+        area = infra_hex_py.get_built_up_area(42, http_options=http_options)
+
+    assert [query["f"] for query in server.requests] == requested
+    assert area["name"] == "Here"
+    assert shapely.from_wkb(area["wkb"]).normalize().equals(boundary.normalize())
+
+    with FixtureServer([], ons_features=[feature], ons_formats=["geojson"]) as server:
+        http_options = {"ons_url": server.ons_url, "arcgis_output_format": "json"}
+        with pytest.raises(infra_hex_py.FetchError, match="Invalid format"):
+            infra_hex_py.get_built_up_area(42, http_options=http_options)
+    with pytest.raises(ValueError, match="Unknown arcgis_output_format"):
+        infra_hex_py.get_built_up_area(42, http_options={"arcgis_output_format": "gml"})
+
+
+def test_multilinestring_pipes_count_in_all_its_parts():
+    """Test that each part of a MultiLineString pipe is binned, not just the first."""
+    parts = [
+        [[-2.2500, 53.4750], [-2.2495, 53.4751]],
+        [[-2.2300, 53.4850], [-2.2295, 53.4851]],
+    ]
+    multi = {
+        "type": "Feature",
+        "geometry": {"type": "MultiLineString", "coordinates": parts},
+        "properties": {"asset_id": "multi"},
+    }
+
+    def summary(features):
+        with FixtureServer(features) as server:
+            return infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX,
+                resolution=11,
+                http_options={"cadent_url": server.cadent_url, "api_key": "test"},
+            )
+
+    first, second = (summary([_line_feature(part)]) for part in parts)
+    result = summary([multi])
+
+    first_hexes = set(first.column("hex_id").to_pylist())
+    second_hexes = set(second.column("hex_id").to_pylist())
+    assert first_hexes and second_hexes and first_hexes.isdisjoint(second_hexes)
+    hexes = first_hexes | second_hexes
+    assert set(result.column("hex_id").to_pylist()) == hexes
+    assert result.column("pipe_count").to_pylist() == [1] * len(hexes)
+
+
+def test_min_pipe_count_drops_sparse_hexes_and_is_recorded():
+    """Test that min_pipe_count keeps the denser hexes and records its threshold."""
+    shared = [[-2.2500, 53.4750], [-2.2498, 53.4750]]
+    features = [
+        _line_feature(shared),
+        _line_feature(shared),
+        _line_feature([[-2.2300, 53.4850], [-2.2298, 53.4850]]),
+    ]
+    with FixtureServer(features) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        full = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution=11, http_options=http_options
+        )
+        dense = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution=11, min_pipe_count=2, http_options=http_options
+        )
+
+    counts = _summary_counts(full)
+    expected = {hex_id: count for hex_id, count in counts.items() if count >= 2}
+    assert expected and len(expected) < len(counts)
+    assert _summary_counts(dense) == expected
+    assert dense.schema.metadata[b"min_pipe_count"] == b"2"
+    assert b"min_pipe_count" not in full.schema.metadata
+
+    with pytest.raises(ValueError, match="min_pipe_count must be between 0"):
+        infra_hex_py.get_hex_summary(*FIXTURE_BBOX, resolution=11, min_pipe_count=-1)
+
+
+def test_nearest_distance_measures_empty_cells_to_the_closest_pipe():
+    """Test that empty cells get the distance to the nearest pipe, and others null."""
+    pipe = [[-2.25000, 53.4800], [-2.24999, 53.4800]]
+    probe = [[-2.23500, 53.4800], [-2.23499, 53.4800]]
+    (pipe_hex,) = _summary_counts(
+        infra_hex_py.hex_summary_from_geojson(json.dumps(_line_feature(pipe)), 11)
+    )
+    (empty_hex,) = _summary_counts(
+        infra_hex_py.hex_summary_from_geojson(json.dumps(_line_feature(probe)), 11)
+    )
+
+    with FixtureServer([_line_feature(pipe)]) as server:
+        result = infra_hex_py.hex_summary_for_cells(
+            [pipe_hex, empty_hex],
+            nearest_distance=True,
+            http_options={"cadent_url": server.cadent_url, "api_key": "test"},
+        )
+
+    assert result["pipe_count"].to_pylist() == [1, 0]
+    nearest, distance = result["nearest_pipe_m"].to_pylist()
+    assert nearest is None
+    assert distance == pytest.approx(_haversine_m(pipe[1], probe[0]), abs=100)
+    assert result.schema.field("nearest_pipe_m").nullable
+
+
+def test_session_shuts_down_the_runtime_and_clients_on_exit():
+    """Test that leaving session() stops the runtime, and fetches start a new one."""
+    with FixtureServer(_mock_pipe_features()) as server:
+        with infra_hex_py.session():
+            first = _fixture_summary(server, 11)
+            assert infra_hex_py.pool_stats()["clients"] >= 1
+            with pytest.raises(RuntimeError, match="the runtime is running"):
+                infra_hex_py.configure_runtime(2)
+
+        assert infra_hex_py.pool_stats()["clients"] == 0
+        infra_hex_py.configure_runtime(os.cpu_count())
+        assert _fixture_summary(server, 11).equals(first)
+
+
+@pytest.mark.parametrize(("error_mode", "errors"), [("collect", 3), ("fail_fast", 1)])
+def test_error_mode_collects_every_failure_or_stops_at_the_first(error_mode, errors):
+    """Test that error_mode="fail_fast" raises the first failed page on its own."""
+    with FixtureServer(_grid_features(16), fail_offsets=[0, 100, 200]) as server:
+        with pytest.raises(infra_hex_py.FetchError) as raised:
+            infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX,
+                resolution=11,
+                error_mode=error_mode,
+                http_options={
+                    "cadent_url": server.cadent_url,
+                    "api_key": "test",
+                    "max_retries": 0,
+                },
+            )
+
+    assert len(raised.value.errors) == errors
+    with pytest.raises(ValueError, match="Unknown error_mode"):
+        infra_hex_py.get_hex_summary(*FIXTURE_BBOX, resolution=11, error_mode="first")
+
+
+def _contained_hexes(pipes_geojson, geometry, containment):
+    result = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson,
+        10,
+        clip_geojson=_clip_from_bng(geometry),
+        containment=containment,
+    )
+    return result, set(result.column("hex_id").to_pylist())
+
+
+def test_containment_decides_a_hex_straddling_the_boundary(pipes_geojson, pipe_hexes):
+    """Test that intersects, centroid and within treat a half-covered hex apart."""
+    target = pipe_hexes.index[0]
+    hexagon = pipe_hexes[target]
+    minx, miny, maxx, maxy = hexagon.bounds
+    centre = hexagon.centroid.x
+    # Boundaries a metre either side of the target hex's centre
+    past_centre = shapely.box(minx - 500, miny - 500, centre + 1, maxy + 500)
+    short_of_centre = shapely.box(minx - 500, miny - 500, centre - 1, maxy + 500)
+
+    kept = {
+        (name, containment): _contained_hexes(pipes_geojson, area, containment)[1]
+        for name, area in [("past", past_centre), ("short", short_of_centre)]
+        for containment in ("intersects", "centroid", "within")
+    }
+    assert target in kept["past", "intersects"]
+    assert target in kept["short", "intersects"]
+    assert target in kept["past", "centroid"]
+    assert target not in kept["short", "centroid"]
+    assert target not in kept["past", "within"]
+    assert target not in kept["short", "within"]
+    for name, area in [("past", past_centre), ("short", short_of_centre)]:
+        assert kept[name, "within"] <= kept[name, "centroid"]
+        assert kept[name, "centroid"] <= kept[name, "intersects"]
+        near = area.buffer(0.01)
+        assert all(near.covers(pipe_hexes[h]) for h in kept[name, "within"])
+
+    whole, kept_whole = _contained_hexes(
+        pipes_geojson, hexagon.buffer(2, join_style="mitre"), "within"
+    )
+    assert kept_whole == {target}
+    assert whole.schema.metadata[b"containment"] == b"within"
+    default, _ = _contained_hexes(pipes_geojson, past_centre, "intersects")
+    assert b"containment" not in default.schema.metadata
+    with pytest.raises(ValueError, match="containment"):
+        _contained_hexes(pipes_geojson, past_centre, "touches")
+
+
+def test_containment_with_multipolygon_parts_closer_than_a_cell(
+    pipes_geojson, pipe_hexes
+):
+    """Test that a hex spanning the narrow gap between two parts is judged on both."""
+    target = pipe_hexes.index[0]
+    hexagon = pipe_hexes[target]
+    minx, miny, maxx, maxy = hexagon.bounds
+    centre = hexagon.centroid.x
+    gap = (maxx - minx) / 10
+    area = shapely.MultiPolygon(
+        [
+            shapely.box(minx - 500, miny - 500, centre - gap / 2, maxy + 500),
+            shapely.box(centre + gap / 2, miny - 500, maxx + 500, maxy + 500),
+        ]
+    )
+
+    intersects, kept_intersects = _contained_hexes(pipes_geojson, area, "intersects")
+    _, kept_centroid = _contained_hexes(pipes_geojson, area, "centroid")
+    _, kept_within = _contained_hexes(pipes_geojson, area, "within")
+
+    assert target in kept_intersects
+    assert intersects.column("hex_id").to_pylist().count(target) == 1
+    assert target not in kept_centroid
+    assert target not in kept_within
+    assert kept_within <= kept_centroid <= kept_intersects
+    inside = {h for h in pipe_hexes.index if area.buffer(-0.01).covers(pipe_hexes[h])}
+    near = {h for h in pipe_hexes.index if area.buffer(0.01).covers(pipe_hexes[h])}
+    assert inside <= kept_within <= near
+
+
+def test_containment_applies_to_the_bbox_edges():
+    """Test that bbox summaries keep hexes by the same containment rules."""
+    min_lat, min_lon, max_lat, max_lon = FIXTURE_BBOX
+    bbox = gpd.GeoSeries(
+        [shapely.box(min_lon, min_lat, max_lon, max_lat)], crs=4326
+    ).to_crs(27700)[0]
+    with FixtureServer(_grid_features(16)) as server:
+        results = {
+            containment: infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX,
+                resolution=10,
+                http_options={"cadent_url": server.cadent_url, "api_key": "test"},
+                containment=containment,
+            )
+            for containment in ("intersects", "centroid", "within")
+        }
+
+    hexes = {
+        containment: gpd.GeoDataFrame.from_arrow(
+            pa.Table.from_batches([result])
+        ).set_index("hex_id")
+        for containment, result in results.items()
+    }
+    assert set(hexes["within"].index) < set(hexes["intersects"].index)
+    assert set(hexes["within"].index) <= set(hexes["centroid"].index)
+    assert set(hexes["centroid"].index) <= set(hexes["intersects"].index)
+    assert hexes["within"].geometry.within(bbox.buffer(0.01)).all()
+    assert hexes["centroid"].geometry.centroid.within(bbox).all()
+    assert not hexes["intersects"].geometry.within(bbox.buffer(0.01)).all()
+    assert results["centroid"].schema.metadata[b"containment"] == b"centroid"
+
+
+def test_data_bbox_records_the_extent_of_the_returned_hexes():
+    """Test that data_bbox covers the returned hexes, not the requested bbox."""
+    min_lat, min_lon, max_lat, max_lon = FIXTURE_BBOX
+    features = _grid_features(16)
+    south_west = [
+        f
+        for f in features
+        if f["geometry"]["coordinates"][0][1] < (min_lat + max_lat) / 2
+        and f["geometry"]["coordinates"][0][0] < (min_lon + max_lon) / 2
+    ]
+    with FixtureServer(features) as server:
+        full = _fixture_summary(server, 10)
+    with FixtureServer(south_west) as server:
+        partial = _fixture_summary(server, 10)
+    with FixtureServer([]) as server:
+        empty = _fixture_summary(server, 10)
+
+    for result in (full, partial):
+        data_bbox = json.loads(result.schema.metadata[b"data_bbox"])
+        gdf = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([result]))
+        hex_min_lon, hex_min_lat, hex_max_lon, hex_max_lat = (
+            gdf.to_crs(4326).total_bounds
+        )
+        assert data_bbox[0] <= hex_min_lat and data_bbox[1] <= hex_min_lon
+        assert data_bbox[2] >= hex_max_lat and data_bbox[3] >= hex_max_lon
+        assert data_bbox == pytest.approx(
+            [hex_min_lat, hex_min_lon, hex_max_lat, hex_max_lon], abs=0.001
+        )
+
+    full_bbox = json.loads(full.schema.metadata[b"data_bbox"])
+    assert full_bbox[0] < min_lat and full_bbox[1] < min_lon
+    assert full_bbox[2] > max_lat and full_bbox[3] > max_lon
+    partial_bbox = json.loads(partial.schema.metadata[b"data_bbox"])
+    assert partial_bbox[2] < max_lat and partial_bbox[3] < max_lon
+    assert empty.num_rows == 0
+    assert b"data_bbox" not in empty.schema.metadata
+
+
+@pytest.mark.parametrize("ignore_offset", [False, True])
+def test_telemetry_matches_what_the_server_served(ignore_offset):
+    """Test that telemetry counts every request, retry and byte, across quadrants."""
+    with FixtureServer(
+        _grid_features(16),
+        rate_limited=2,
+        fail_offsets=[100],
+        ignore_offset=ignore_offset,
+    ) as server:
+        result = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX,
+            resolution=11,
+            telemetry=True,
+            http_options={"cadent_url": server.cadent_url, "api_key": "test"},
+        )
+
+    telemetry = json.loads(result.schema.metadata[b"telemetry"])
+    assert telemetry["requests"] == len(server.requests)
+    # Two 429s and the failed page at offset 100
+    assert telemetry["retries"] == 3
+    assert telemetry["bytes_transferred"] == server.bytes_served
+    assert telemetry["bytes_decoded"] == server.bytes_served
+    assert result.schema.metadata[b"bytes_transferred"] == str(
+        server.bytes_served
+    ).encode()
+    latency = telemetry["latency_seconds"]
+    assert 0 < latency["min"] <= latency["p50"] <= latency["p90"]
+    assert latency["p90"] <= latency["p99"] <= latency["max"]
+    assert latency["min"] <= latency["mean"] <= latency["max"]
+
+
+def test_telemetry_is_off_by_default():
+    """Test that only telemetry=True adds the telemetry metadata entry."""
+    with FixtureServer(_grid_features(4)) as server:
+        result = _fixture_summary(server, 11)
+
+    assert b"telemetry" not in result.schema.metadata
+    assert int(result.schema.metadata[b"bytes_transferred"]) == server.bytes_served
+
+
+def _point_feature(lon, lat, **properties):
+    return {
+        "type": "Feature",
+        "geometry": {"type": "Point", "coordinates": [lon, lat]},
+        "properties": properties,
+    }
+
+
+def _point_features():
+    """Two governors at the same spot and a third a few hundred metres away."""
+    return [
+        _point_feature(-2.2400, 53.4800, asset_id="gov-1"),
+        _point_feature(-2.2400, 53.4800, asset_id="gov-2"),
+        _point_feature(-2.2350, 53.4820, asset_id="gov-3"),
+    ]
+
+
+def test_include_points_bins_each_point_into_its_hex():
+    """Test that point assets are counted once, in the hex containing them."""
+    features = _point_features()
+    collection = json.dumps({"type": "FeatureCollection", "features": features})
+
+    skipped = infra_hex_py.hex_summary_from_geojson(collection, 10)
+    result = infra_hex_py.hex_summary_from_geojson(collection, 10, include_points=True)
+    hexes = gpd.GeoDataFrame.from_arrow(pa.Table.from_batches([result]))
+    points = gpd.GeoDataFrame.from_features(features, crs=4326).to_crs(hexes.crs)
+
+    assert skipped.num_rows == 0
+    assert skipped.schema.metadata[b"skipped_features"] == b"3"
+    assert sorted(result.column("pipe_count").to_pylist()) == [1, 2]
+    for _, hexagon in hexes.iterrows():
+        inside = points[points.within(hexagon.geometry)]
+        assert len(inside) == hexagon.pipe_count
+
+
+def test_fetched_point_records_bin_like_lines():
+    """Test that point records from the API land in the hex containing them."""
+    with FixtureServer(_point_features()) as server:
+        result = _fixture_summary(server, 10)
+
+    assert sorted(result.column("pipe_count").to_pylist()) == [1, 2]
+
+
+def _crossing_collection():
+    """Pipes long enough to cross several hexes, one of them in two parts."""
+    features = [
+        _line_feature(
+            [[-2.250 + i * 0.001, 53.475], [-2.244 + i * 0.001, 53.480 + i * 0.0005]],
+            asset_id="LONG-{}".format(i),
+        )
+        for i in range(8)
+    ]
+    features.append(
+        {
+            "type": "Feature",
+            "geometry": {
+                "type": "MultiLineString",
+                "coordinates": [
+                    [[-2.240, 53.485], [-2.236, 53.485]],
+                    [[-2.230, 53.486], [-2.226, 53.487]],
+                ],
+            },
+            "properties": {"asset_id": "SPLIT"},
+        }
+    )
+    collection = {"type": "FeatureCollection", "features": features}
+    return json.dumps(collection), len(features)
+
+
+def test_midpoint_assignment_conserves_the_record_count():
+    """Test that midpoint assignment counts every record in exactly one hex."""
+    collection, records = _crossing_collection()
+
+    coverage = infra_hex_py.hex_summary_from_geojson(collection, 11)
+    midpoint = infra_hex_py.hex_summary_from_geojson(
+        collection, 11, assignment="midpoint", weighting="length"
+    )
+
+    assert sum(midpoint.column("pipe_count").to_pylist()) == records
+    assert sum(midpoint.column("weighted_count").to_pylist()) == pytest.approx(records)
+    assert sum(coverage.column("pipe_count").to_pylist()) > records
+    assert midpoint.schema.metadata[b"assignment"] == b"midpoint"
+    assert b"assignment" not in coverage.schema.metadata
+    with pytest.raises(ValueError, match="assignment"):
+        infra_hex_py.hex_summary_from_geojson(collection, 11, assignment="centre")
+
+
+def test_coverage_assignment_is_a_superset_of_midpoint():
+    """Test that coverage counts each pipe in its midpoint hex and possibly more."""
+    collection, _ = _crossing_collection()
+
+    coverage = _summary_counts(infra_hex_py.hex_summary_from_geojson(collection, 11))
+    midpoint = _summary_counts(
+        infra_hex_py.hex_summary_from_geojson(collection, 11, assignment="midpoint")
+    )
+
+    assert set(midpoint) <= set(coverage)
+    assert len(coverage) > len(midpoint)
+    for hex_id, count in midpoint.items():
+        assert coverage[hex_id] >= count
+
+
+def test_http_proxy_carries_proxy_auth_credentials():
+    """Test that http_proxy routes requests through it with Basic credentials."""
+    credentials = base64.b64encode(b"analyst:s3cret").decode()
+    with FixtureServer(_mock_pipe_features()) as server, RecordingProxy() as proxy:
+        direct = _fixture_summary(server, 11)
+        proxied = _fixture_summary(
+            server,
+            11,
+            http_proxy=proxy.url,
+            proxy_username="analyst",
+            proxy_password="s3cret",
+        )
+
+    assert _summary_counts(proxied) == _summary_counts(direct)
+    assert proxy.requests
+    for url, authorization in proxy.requests:
+        assert url.startswith(server.cadent_url)
+        assert authorization == "Basic " + credentials
+
+
+def test_proxy_environment_variables_are_honoured(monkeypatch):
+    """Test that http_proxy in the environment is used when no option is given."""
+    for name in ("HTTP_PROXY", "ALL_PROXY", "all_proxy", "NO_PROXY", "no_proxy"):
+        monkeypatch.delenv(name, raising=False)
+    with FixtureServer(_mock_pipe_features()) as server, RecordingProxy() as proxy:
+        monkeypatch.setenv("http_proxy", proxy.url)
+        infra_hex_py.reset_clients()
+        try:
+            result = _fixture_summary(server, 11)
+        finally:
+            monkeypatch.delenv("http_proxy")
+            infra_hex_py.reset_clients()
+
+    assert result.num_rows > 0
+    assert all(url.startswith(server.cadent_url) for url, _ in proxy.requests)
+    assert proxy.requests
+
+
+def test_proxy_credentials_must_come_in_pairs():
+    """Test that a proxy username without a password is rejected up-front."""
+    with pytest.raises(ValueError, match="proxy_password"):
+        infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX,
+            resolution=11,
+            http_options={"http_proxy": "http://127.0.0.1:9", "proxy_username": "a"},
+        )
+
+
+def test_area_straddling_the_coverage_edge_warns_and_is_clamped():
+    """Test that an area half outside Cadent's coverage warns and is clamped."""
+    # From Manchester east into West Yorkshire, which another network supplies
+    polygon = shapely.to_geojson(shapely.box(-2.25, 53.45, -1.45, 53.5))
+    min_lat, min_lon, max_lat, max_lon = FIXTURE_BBOX
+    with FixtureServer(_mock_pipe_features()) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        with pytest.warns(UserWarning, match=r"[45]\d\.\d% of Polygon lies outside"):
+            result = infra_hex_py.get_hex_summary_for_polygon(
+                polygon, 9, http_options=http_options
+            )
+        where = [r["where"] for r in server.requests if "where" in r]
+        with pytest.raises(ValueError, match="strict_coverage=False"):
+            infra_hex_py.get_hex_summary_for_polygon(
+                polygon, 9, strict_coverage=True, http_options=http_options
+            )
+        inside = infra_hex_py.get_hex_summary_for_polygon(
+            shapely.to_geojson(shapely.box(min_lon, min_lat, max_lon, max_lat)),
+            9,
+            strict_coverage=True,
+            http_options=http_options,
+        )
+
+    fraction = float(result.schema.metadata[b"coverage_fraction"])
+    assert fraction == pytest.approx(0.5, abs=0.05)
+    assert result.num_rows > 0
+    assert where and all(float(IN_BBOX.search(w).group(4)) < -1.8 for w in where)
+    assert inside.schema.metadata[b"coverage_fraction"] == b"1"
+
+
+def test_include_density_divides_counts_by_clipped_cell_area():
+    """Test that densities use each hex's ground area, clipped and sliver-safe."""
+    pipes = json.dumps({"type": "FeatureCollection", "features": _mock_pipe_features()})
+    clip = shapely.to_geojson(shapely.box(-2.25, 53.475, -2.235, 53.485))
+
+    def rows(**options):
+        result = infra_hex_py.hex_summary_from_geojson(
+            pipes, 11, hex_area=True, include_density=True, output="pyarrow", **options
+        )
+        assert result.schema.names[-5:-3] == ["cell_area_km2", "pipe_density_per_km2"]
+        return result.to_pylist()
+
+    for row in rows():
+        assert row["cell_area_km2"] == pytest.approx(row["hex_area_m2"] / 1e6)
+        assert row["pipe_density_per_km2"] == pytest.approx(
+            row["pipe_count"] / row["cell_area_km2"]
+        )
+
+    clipped = rows(clip_geojson=clip)
+    assert any(row["cell_area_km2"] < row["hex_area_m2"] / 1e6 for row in clipped)
+    for row in clipped:
+        whole_km2 = row["hex_area_m2"] / 1e6
+        assert 0 <= row["cell_area_km2"] <= whole_km2 * (1 + 1e-9)
+        if row["cell_area_km2"] < 0.01 * whole_km2:
+            assert row["pipe_density_per_km2"] is None
+        else:
+            assert row["pipe_density_per_km2"] == pytest.approx(
+                row["pipe_count"] / row["cell_area_km2"]
+            )
+
+
+def test_built_up_areas_are_looked_up_in_one_query():
+    """Test that several OBJECTIDs share one IN query, falling back to one each."""
+    features = [_built_up_area_feature(i, "Area {}".format(i)) for i in (3, 5, 8)]
+    with FixtureServer([], ons_features=features) as server:
+        http_options = {"ons_url": server.ons_url}
+        areas = infra_hex_py.get_built_up_areas([8, 3, 8], http_options=http_options)
+        with pytest.raises(infra_hex_py.AreaNotFoundError) as excinfo:
+            infra_hex_py.get_built_up_areas([3, 4], http_options=http_options)
+
+    assert [area["object_id"] for area in areas] == [8, 3, 8]
+    assert [area["name"] for area in areas] == ["Area 8", "Area 3", "Area 8"]
+    assert [r["where"] for r in server.requests] == [
+        "OBJECTID IN (3,8)",
+        "OBJECTID IN (3,4)",
+    ]
+    assert excinfo.value.object_id == 4
+
+    with FixtureServer([], ons_features=features[:1], reject_in_filters=True) as server:
+        http_options = {"ons_url": server.ons_url, "arcgis_output_format": "geojson"}
+        areas = infra_hex_py.get_built_up_areas([3], http_options=http_options)
+
+    assert [area["name"] for area in areas] == ["Area 3"]
+    assert [r["where"] for r in server.requests] == ["OBJECTID IN (3)", "OBJECTID=3"]
+
+
+def test_majority_containment_weighs_the_area_inside(pipes_geojson, pipe_hexes):
+    """Test that majority keeps a hex by the area inside, wherever its centre is."""
+    target = pipe_hexes.index[0]
+    hexagon = pipe_hexes[target]
+    minx, miny, maxx, maxy = hexagon.bounds
+
+    def left_part(fraction):
+        # Bisect for the edge leaving `fraction` of the target's area to its left
+        low, high = minx, maxx
+        for _ in range(40):
+            edge = (low + high) / 2
+            box = shapely.box(minx - 500, miny - 500, edge, maxy + 500)
+            if hexagon.intersection(box).area < fraction * hexagon.area:
+                low = edge
+            else:
+                high = edge
+        return shapely.box(minx - 500, miny - 500, low, maxy + 500)
+
+    centre = hexagon.centroid.buffer(5)
+    areas = {
+        "most_without_centre": left_part(0.7).difference(centre),
+        "little_with_centre": left_part(0.3).union(centre),
+    }
+    kept = {
+        (name, containment): _contained_hexes(pipes_geojson, area, containment)[1]
+        for name, area in areas.items()
+        for containment in ("centroid", "majority")
+    }
+    assert target in kept["most_without_centre", "majority"]
+    assert target not in kept["most_without_centre", "centroid"]
+    assert target not in kept["little_with_centre", "majority"]
+    assert target in kept["little_with_centre", "centroid"]
+
+    majority, _ = _contained_hexes(pipes_geojson, left_part(0.7), "majority")
+    assert majority.schema.metadata[b"containment"] == b"majority"
+
+
+@pytest.mark.parametrize("partial_on_deadline", [False, True])
+def test_deadline_cancels_slow_pages(partial_on_deadline):
+    """Test that deadline_seconds cancels a slow page, raising or summarising."""
+    features = _grid_features(16)
+    with FixtureServer(features, page_delay=5) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        complete = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX,
+            resolution=11,
+            deadline_seconds=60,
+            http_options=http_options,
+        )
+        server.slow_offsets = {100}
+        started = time.monotonic()
+        if partial_on_deadline:
+            with pytest.warns(UserWarning, match="deadline_seconds=0.5"):
+                partial, stats = infra_hex_py.get_hex_summary(
+                    *FIXTURE_BBOX,
+                    resolution=11,
+                    with_stats=True,
+                    deadline_seconds=0.5,
+                    partial_on_deadline=True,
+                    http_options=http_options,
+                )
+        else:
+            with pytest.raises(TimeoutError, match="Deadline of 0.5s"):
+                infra_hex_py.get_hex_summary(
+                    *FIXTURE_BBOX,
+                    resolution=11,
+                    deadline_seconds=0.5,
+                    http_options=http_options,
+                )
+        elapsed = time.monotonic() - started
+
+    assert complete.schema.metadata[b"complete"] == b"true"
+    assert elapsed < 5
+    if partial_on_deadline:
+        assert partial.schema.metadata[b"complete"] == b"false"
+        assert 0 < stats["total_records"] < len(features)
+    with pytest.raises(ValueError, match="deadline_seconds"):
+        infra_hex_py.get_hex_summary(MOCK_BBOX, 11, deadline_seconds=0)
+
+
+@pytest.mark.parametrize(
+    "options",
+    [
+        {},
+        {"weighting": "length", "boundary_pipe_count": True, "parent_zoom": 9},
+        {"hex_area": True, "include_density": True, "geometry_encoding": "wkb"},
+        {"include_metadata_columns": False, "geometry_encoding": "geoarrow"},
+    ],
+)
+def test_summaries_match_hex_summary_schema(pipes_geojson, options):
+    """Test that summaries have exactly the columns hex_summary_schema gives."""
+    expected = infra_hex_py.hex_summary_schema(**options)
+    result = infra_hex_py.hex_summary_from_geojson(
+        pipes_geojson, 11, output="pyarrow", **options
+    )
+
+    assert result.schema.equals(expected)
+    assert expected.field("hex_id").type == pa.string()
+    assert expected.field("pipe_count").type == pa.uint32()
+    assert expected.metadata[b"schema_version"] == str(
+        infra_hex_py.HEX_SUMMARY_SCHEMA_VERSION
+    ).encode()
+    if options.get("geometry_encoding") == "wkb":
+        assert pa.types.is_binary(expected.field("geometry").type)
+
+
+def test_osgb_grid_summary_is_keyed_by_grid_squares():
+    """Test that grid="osgb" bins pipes into 1 km National Grid squares."""
+    features = _grid_features(4)
+    with FixtureServer(features) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        result = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX,
+            grid="osgb",
+            assignment="midpoint",
+            geometry_encoding="wkb",
+            output="pyarrow",
+            http_options=http_options,
+        )
+        with pytest.raises(ValueError, match="k_ring"):
+            infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX, grid="osgb", k_ring=1, http_options=http_options
+            )
+        with pytest.raises(ValueError, match="grid_size_m"):
+            infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX, grid="osgb", grid_size_m=500, http_options=http_options
+            )
+
+    assert result.schema.equals(
+        infra_hex_py.hex_summary_schema(grid="osgb", geometry_encoding="wkb")
+    )
+    assert result.schema.metadata[b"grid_size_m"] == b"1000"
+    refs = result.column("grid_ref").to_pylist()
+    assert all(ref[:2] == "SJ" and len(ref) == 6 and ref[2:].isdigit() for ref in refs)
+    assert sum(result.column("pipe_count").to_pylist()) == len(features)
+    squares = shapely.from_wkb(result.column("geometry").to_pylist())
+    for ref, square in zip(refs, squares):
+        min_e, min_n = square.bounds[:2]
+        assert square.area == pytest.approx(1_000_000)
+        assert ref[2:] == "{:02d}{:02d}".format(
+            int(min_e) % 100_000 // 1000, int(min_n) % 100_000 // 1000
+        )
+
+
+def test_estimate_fetch_matches_the_requests_of_the_fetch():
+    """Test that estimate_fetch makes one request and predicts the fetch's cost."""
+    features = _grid_features(4)
+    with FixtureServer(features) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        estimate = infra_hex_py.estimate_fetch(
+            *FIXTURE_BBOX, resolution=11, http_options=http_options
+        )
+        assert len(server.requests) == 1
+        server.requests.clear()
+        infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution=11, http_options=http_options
+        )
+        fetch_requests = len(server.requests)
+
+    assert estimate["feature_count"] == len(features)
+    assert estimate["estimated_requests"] == fetch_requests
+    assert estimate["resolution"] == 11
+    assert estimate["estimated_hexes"] > 0
+
+
+def test_pages_that_do_not_fit_together_raise_pagination_error():
+    """Test that unordered or gapped pages fail, while a mere overlap self-heals."""
+    features = [
+        _line_feature(
+            [[-2.25 + i * 1e-4, 53.48], [-2.25 + i * 1e-4, 53.4801]],
+            asset_id="PIPE-{:03d}".format(i),
+        )
+        for i in range(250)
+    ]
+
+    def pipe_count(**misbehaviour):
+        with FixtureServer(features, **misbehaviour) as server:
+            http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+            summary = infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX,
+                resolution=9,
+                assignment="midpoint",
+                deduplicate=False,
+                http_options=http_options,
+            )
+            pages = [r for r in server.requests if "offset" in r]
+            assert all(page["order_by"] == "asset_id" for page in pages)
+        return sum(summary.column("pipe_count").to_pylist())
+
+    assert pipe_count() == 250
+    # The last page repeats one record but misses none
+    assert pipe_count(shifted_offsets=[200]) == 250
+    with pytest.raises(infra_hex_py.PaginationError, match="overlapped") as excinfo:
+        pipe_count(shifted_offsets=[100])
+    assert isinstance(excinfo.value, infra_hex_py.FetchError)
+    assert excinfo.value.errors[0]["kind"] == "pagination"
+    with pytest.raises(infra_hex_py.PaginationError, match="went back"):
+        pipe_count(unordered_offsets=[100])
+
+
+def test_extra_query_params_reach_the_wire_but_cannot_override_ours():
+    """Test that extra_query_params are sent, and reserved parameters are refused."""
+    with FixtureServer(_grid_features(3)) as server:
+        http_options = {
+            "cadent_url": server.cadent_url,
+            "api_key": "test",
+            "extra_query_params": {"timezone": "Europe/London"},
+        }
+        infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution=9, http_options=http_options
+        )
+        pages = [r for r in server.requests if "offset" in r]
+        assert pages
+        assert all(r["timezone"] == "Europe/London" for r in server.requests)
+
+        for name in ["resultOffset", "offset", "WHERE"]:
+            http_options["extra_query_params"] = {name: "5"}
+            with pytest.raises(ValueError, match="extra_query_params"):
+                infra_hex_py.get_hex_summary(
+                    *FIXTURE_BBOX, resolution=9, http_options=http_options
+                )
+
+
+def test_tls_and_pool_http_options(tmp_path):
+    """Test that TLS and pool settings are accepted and a bad CA file is refused."""
+    with FixtureServer(_grid_features(3)) as server:
+        http_options = {
+            "cadent_url": server.cadent_url,
+            "api_key": "test",
+            "danger_accept_invalid_certs": True,
+            "pool_idle_timeout_secs": 5.0,
+        }
+        summary = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution=9, http_options=http_options
+        )
+        assert sum(summary.column("pipe_count").to_pylist()) > 0
+
+    not_a_cert = tmp_path / "ca.pem"
+    not_a_cert.write_text("-----BEGIN CERTIFICATE-----\nnot base64\n")
+    for path in [not_a_cert, tmp_path / "missing.pem"]:
+        with pytest.raises(ValueError, match="ca_cert_path"):
+            infra_hex_py.check_endpoints(http_options={"ca_cert_path": path})
+    with pytest.raises(ValueError, match="pool_idle_timeout_secs"):
+        infra_hex_py.check_endpoints(http_options={"pool_idle_timeout_secs": 0})
+
+
+def test_normalize_adds_pipe_density_per_km2_or_per_km_of_edge():
+    """Test that normalize divides counts by cell area or by hex edge length."""
+    pipes = json.dumps({"type": "FeatureCollection", "features": _mock_pipe_features()})
+
+    def summary(normalize):
+        return infra_hex_py.hex_summary_from_geojson(
+            pipes, 11, include_density=True, normalize=normalize, output="pyarrow"
+        )
+
+    per_km2 = summary("per_km2")
+    assert per_km2.schema.equals(
+        infra_hex_py.hex_summary_schema(include_density=True, normalize="per_km2")
+    )
+    for row in per_km2.to_pylist():
+        assert row["pipe_density"] == pytest.approx(row["pipe_density_per_km2"])
+
+    for row in summary("per_km_edge").to_pylist():
+        # A regular hexagon of side s has area 3 * sqrt(3) / 2 * s**2
+        edge_km = (2 * row["cell_area_km2"] / (3 * 3**0.5)) ** 0.5
+        assert row["pipe_density"] * edge_km == pytest.approx(
+            row["pipe_count"], rel=1e-2
+        )
+
+    assert "pipe_density" not in summary("none").schema.names
+    with pytest.raises(ValueError, match="Unknown normalize"):
+        summary("per_hex")
+
+
+def test_max_batch_rows_splits_the_summary_without_changing_it():
+    """Test that max_batch_rows yields small batches that concatenate to the whole."""
+    pipes = json.dumps({"type": "FeatureCollection", "features": _mock_pipe_features()})
+    whole = infra_hex_py.hex_summary_from_geojson(pipes, 12, output="pyarrow")
+    assert whole.num_rows > 3
+
+    table = infra_hex_py.hex_summary_from_geojson(
+        pipes, 12, output="pyarrow", max_batch_rows=3
+    )
+    assert isinstance(table, pa.Table)
+    batches = table.to_batches()
+    assert len(batches) == -(-whole.num_rows // 3)
+    assert all(batch.num_rows <= 3 for batch in batches)
+    assert pa.Table.from_batches(batches).equals(pa.Table.from_batches([whole]))
+    assert table.schema.metadata == whole.schema.metadata
+
+    capsules = infra_hex_py.hex_summary_from_geojson(
+        pipes, 12, output="capsule", max_batch_rows=3
+    )
+    assert [len(batch) for batch in capsules] == [b.num_rows for b in batches]
+
+    for options in [
+        {"max_batch_rows": 0},
+        {"max_batch_rows": 3, "output_layout": "normalized"},
+    ]:
+        with pytest.raises(ValueError, match="max_batch_rows"):
+            infra_hex_py.hex_summary_from_geojson(pipes, 12, **options)
+
+
+def test_fixture_mode_replays_responses_without_network(tmp_path, monkeypatch):
+    """Test that fixture mode answers requests from bundled and recorded fixtures."""
+    monkeypatch.delenv("CADENT_API_KEY", raising=False)
+    infra_hex_py.enable_fixture_mode()
+    try:
+        bbox_summary = infra_hex_py.get_hex_summary(*FIXTURE_BBOX, resolution=11)
+        area_summary = infra_hex_py.get_hex_summary_polygon_area(1, 11)
+        with pytest.raises(infra_hex_py.AreaNotFoundError):
+            infra_hex_py.get_hex_summary_polygon_area(0, 11)
+        with pytest.raises(infra_hex_py.FetchError, match="No fixture for GET"):
+            infra_hex_py.get_hex_summary(53.4, -2.3, 53.41, -2.29, resolution=11)
+    finally:
+        assert infra_hex_py.disable_fixture_mode()
+    assert not infra_hex_py.disable_fixture_mode()
+    features = _mock_pipe_features()
+    assert _summary_counts(bbox_summary) == _expected_counts(features, 11)
+    assert area_summary.num_rows > 0
+
+    fixtures_dir = tmp_path / "recorded"
+    with FixtureServer(features) as server:
+        infra_hex_py.enable_fixture_mode(fixtures_dir, record=True)
+        try:
+            recorded = _fixture_summary(server, 11)
+        finally:
+            infra_hex_py.disable_fixture_mode()
+    assert len(list(fixtures_dir.glob("*.json"))) == len(server.requests)
+
+    infra_hex_py.enable_fixture_mode(fixtures_dir)
+    try:
+        replayed = _fixture_summary(server, 11)
+    finally:
+        infra_hex_py.disable_fixture_mode()
+    assert _summary_counts(replayed) == _summary_counts(recorded)
+    with pytest.raises(ValueError, match="not a directory"):
+        infra_hex_py.enable_fixture_mode(tmp_path / "missing")
+
+
+def test_redirect_policy_follows_records_or_refuses_redirects():
+    """Test that redirects are followed per redirect_policy and recorded."""
+    features = _mock_pipe_features()
+    with FixtureServer(features) as server:
+        origin = server.cadent_url.rsplit("/datasets", 1)[0]
+        with RedirectServer(origin) as mirror:
+            moved = {"cadent_url": mirror.url + "/datasets", "api_key": "test"}
+            result = infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX, resolution=11, http_options=moved
+            )
+            for policy, reason in [
+                ("error", 'redirect_policy is "error"'),
+                (0, "more than redirect_policy allows"),
+            ]:
+                with pytest.raises(infra_hex_py.FetchError, match=reason):
+                    infra_hex_py.get_hex_summary(
+                        *FIXTURE_BBOX,
+                        resolution=11,
+                        http_options={**moved, "redirect_policy": policy},
+                    )
+            with pytest.raises(infra_hex_py.FetchError, match="api_key_header"):
+                infra_hex_py.get_hex_summary(
+                    *FIXTURE_BBOX,
+                    resolution=11,
+                    http_options={**moved, "api_key_header": "X-Api-Key"},
+                )
+        direct = _fixture_summary(server, 11, redirect_policy="error")
+
+    assert _summary_counts(result) == _expected_counts(features, 11)
+    records = "/datasets/gas-pipe-infrastructure-gpi_open/records"
+    assert json.loads(result.schema.metadata[b"redirects"]) == {
+        mirror.url + records: origin + records
+    }
+    assert b"redirects" not in direct.schema.metadata
+    with pytest.raises(ValueError, match="redirect_policy"):
+        infra_hex_py.warmup(http_options={"redirect_policy": "sometimes"})
+
+
+def test_write_hex_summary_gpkg_writes_hexes_and_boundary(tmp_path):
+    """Test that the GeoPackage holds the area's summary and its boundary layer."""
+    features = _mock_pipe_features()
+    area = _built_up_area_feature(42, "Here")
+    with FixtureServer(features, ons_features=[area]) as server:
+        http_options = {
+            "cadent_url": server.cadent_url,
+            "ons_url": server.ons_url,
+            "api_key": "test",
+        }
+        expected = infra_hex_py.get_hex_summary_polygon_area(
+            42, 11, parent_zoom=8, http_options=http_options
+        )
+        path = tmp_path / "area.gpkg"
+        path.write_bytes(b"stale")
+        written = infra_hex_py.write_hex_summary_gpkg(
+            42, 11, path, parent_zoom=8, http_options=http_options
+        )
+        with pytest.raises(TypeError, match="output"):
+            infra_hex_py.write_hex_summary_gpkg(
+                42, 11, path, output="polars", http_options=http_options
+            )
+
+    assert set(gpd.list_layers(path)["name"]) == {"hex_summary", "area_boundary"}
+    hexes = gpd.read_file(path, layer="hex_summary")
+    assert written == len(hexes) == expected.num_rows
+    assert hexes.crs.to_epsg() == 27700
+    assert dict(zip(hexes["hex_id"], hexes["pipe_count"])) == _summary_counts(expected)
+    parents = expected.column("parent_hex_id").to_pylist()
+    assert set(hexes["parent_hex_id"]) == set(parents)
+
+    boundary = gpd.read_file(path, layer="area_boundary")
+    assert boundary.crs.to_epsg() == 27700
+    assert list(boundary["name"]) == ["Here"] and list(boundary["object_id"]) == [42]
+    expected_boundary = gpd.GeoSeries(
+        [shapely.box(-2.25, 53.47, -2.23, 53.49)], crs=4326
+    ).to_crs(27700)
+    assert boundary.geometry[0].symmetric_difference(expected_boundary[0]).area < 1
+
+
+def test_record_hex_assignments_fractions_sum_to_one():
+    """Test that each asset's fractions of length sum to 1.0, with stable ids."""
+    features = _mock_pipe_features()
+    for feature in features[:2]:
+        feature["properties"].pop("asset_id", None)
+    results = []
+    for ordered in (features, features[::-1]):
+        with FixtureServer(ordered) as server:
+            http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+            results.append(
+                infra_hex_py.get_record_hex_assignments(
+                    *FIXTURE_BBOX,
+                    resolution=11,
+                    deduplicate=False,
+                    http_options=http_options,
+                )
+            )
+            midpoints = infra_hex_py.get_record_hex_assignments(
+                *FIXTURE_BBOX,
+                resolution=11,
+                assignment="midpoint",
+                http_options=http_options,
+            )
+    mapping = results[0]
+
+    assert mapping.schema.names == ["asset_id", "hex_id", "fraction_of_length"]
+    totals = {}
+    for row in mapping.to_pylist():
+        totals[row["asset_id"]] = totals.get(row["asset_id"], 0.0)
+        totals[row["asset_id"]] += row["fraction_of_length"]
+    assert totals and all(total == pytest.approx(1.0) for total in totals.values())
+    # MOCK-M003's two records make one asset; the three without ids are separate
+    assert "MOCK-M003" in totals
+    unidentified = [a for a in totals if a.startswith("unidentified-")]
+    assert sorted(unidentified) == ["unidentified-{}".format(i) for i in range(3)]
+    # A main crossing several hexes is split between them
+    assert len(set(mapping.column("hex_id").to_pylist())) > len(totals)
+
+    # The synthetic ids do not depend on the order records were fetched in
+    assert results[1].to_pylist() == mapping.to_pylist()
+
+    assert midpoints.schema.names == ["asset_id", "hex_id"]
+    assert set(midpoints.column("asset_id").to_pylist()) == set(totals)
+
+
+def test_lonlat_and_swapped_coordinate_checks():
+    """Test lonlat=True, and that swapped coordinates are reported, not empty."""
+    min_lat, min_lon, max_lat, max_lon = FIXTURE_BBOX
+    offshore = (49.0, -9.5, 50.0, -8.5)
+    with FixtureServer(_mock_pipe_features()) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        expected = _fixture_summary(server, 11)
+        lonlat = infra_hex_py.get_hex_summary(
+            min_lon,
+            min_lat,
+            max_lon,
+            max_lat,
+            11,
+            lonlat=True,
+            http_options=http_options,
+        )
+        with pytest.raises(ValueError, match="may be swapped"):
+            infra_hex_py.get_hex_summary(
+                min_lon, min_lat, max_lon, max_lat, 11, http_options=http_options
+            )
+        with pytest.raises(TypeError, match="lonlat"):
+            infra_hex_py.get_hex_summary(
+                infra_hex_py.BBox(
+                    min_lat=min_lat, min_lon=min_lon, max_lat=max_lat, max_lon=max_lon
+                ),
+                11,
+                lonlat=True,
+            )
+        with pytest.warns(UserWarning, match="swapped") as warned:
+            empty = infra_hex_py.get_hex_summary(
+                *offshore, 6, http_options=http_options
+            )
+        with pytest.raises(ValueError, match="lat -9.5 to -8.5, lon 49 to 50"):
+            infra_hex_py.get_hex_summary(
+                *offshore, 6, strict_location=True, http_options=http_options
+            )
+
+    assert _summary_counts(lonlat) == _summary_counts(expected)
+    assert empty.num_rows == 0
+    assert "lat 49 to 50, lon -9.5 to -8.5" in str(warned[0].message)
+
+
+def test_square_grid_summary_uses_size_and_origin():
+    """Test that grid="square" bins pipes into squares of any size and origin."""
+    features = _grid_features(4)
+    with FixtureServer(features) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        result = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX,
+            grid="square",
+            grid_size_m=250,
+            grid_origin=(50, 50),
+            assignment="midpoint",
+            geometry_encoding="wkb",
+            output="pyarrow",
+            http_options=http_options,
+        )
+        with pytest.raises(ValueError, match="grid_origin"):
+            infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX,
+                grid="osgb",
+                grid_origin=(50, 50),
+                http_options=http_options,
+            )
+        with pytest.raises(ValueError, match="grid_size_m"):
+            infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX, grid="square", grid_size_m=0, http_options=http_options
+            )
+
+    assert result.schema.equals(
+        infra_hex_py.hex_summary_schema(grid="square", geometry_encoding="wkb")
+    )
+    assert result.schema.metadata[b"grid"] == b"square"
+    assert result.schema.metadata[b"grid_origin"] == b"[50, 50]"
+    assert sum(result.column("pipe_count").to_pylist()) == len(features)
+    squares = shapely.from_wkb(result.column("geometry").to_pylist())
+    for cell_id, square in zip(result.column("cell_id").to_pylist(), squares):
+        min_e, min_n = square.bounds[:2]
+        assert square.area == pytest.approx(250 * 250)
+        assert (min_e - 50) % 250 == 0 and (min_n - 50) % 250 == 0
+        assert cell_id == "250m_E{}_N{}".format(int(min_e), int(min_n))
+
+
+def test_batches_record_their_query(tmp_path):
+    """Test that query_bbox, query_object_id and zoom survive a Parquet round trip."""
+    import pyarrow.parquet as pq
+
+    area = _built_up_area_feature(42, "Here")
+    with FixtureServer(_mock_pipe_features(), ons_features=[area]) as server:
+        http_options = {
+            "cadent_url": server.cadent_url,
+            "ons_url": server.ons_url,
+            "api_key": "test",
+        }
+        summary = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution=10, http_options=http_options
+        )
+        clipped = infra_hex_py.get_hex_summary_polygon_area(
+            42, 9, http_options=http_options
+        )
+
+    metadata = summary.schema.metadata
+    assert json.loads(metadata[b"query_bbox"]) == list(FIXTURE_BBOX)
+    assert metadata[b"zoom"] == b"10"
+    assert b"query_object_id" not in metadata
+
+    path = tmp_path / "area.parquet"
+    pq.write_table(pa.table(clipped), path)
+    metadata = pq.read_schema(path).metadata
+    assert metadata[b"query_object_id"] == b"42" and metadata[b"zoom"] == b"9"
+    min_lat, min_lon, max_lat, max_lon = json.loads(metadata[b"query_bbox"])
+    assert (min_lon, max_lon) == pytest.approx((-2.25, -2.23))
+    assert (min_lat, max_lat) == pytest.approx((53.47, 53.49))
+
+
+def test_configure_sets_defaults_that_calls_override():
+    """Test that configure() supplies http_options defaults a call can override."""
+    features = _mock_pipe_features()
+    with FixtureServer(features, slow_offsets=[0], page_delay=1.0) as server:
+        try:
+            config = infra_hex_py.configure(
+                cadent_url=server.cadent_url,
+                api_key="test",
+                read_timeout_secs=0.2,
+                max_retries=0,
+            )
+            assert config["read_timeout_secs"] == 0.2
+            assert "api_key" in infra_hex_py.get_config()
+            assert "test" not in repr(config)
+            with pytest.raises(infra_hex_py.FetchError):
+                infra_hex_py.get_hex_summary(*FIXTURE_BBOX, resolution=11)
+
+            result = infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX,
+                resolution=11,
+                http_options={"read_timeout_secs": 5},
+            )
+            assert sum(result.column("pipe_count").to_pylist()) > 0
+
+            with pytest.raises(ValueError, match="Unknown http_options key"):
+                infra_hex_py.configure(read_timeout=1)
+            assert infra_hex_py.get_config()["read_timeout_secs"] == 0.2
+        finally:
+            infra_hex_py.configure(
+                cadent_url=None,
+                api_key=None,
+                read_timeout_secs=None,
+                max_retries=None,
+            )
+    assert len(infra_hex_py.get_config()) == 0
+
+
+def test_aggregations_add_per_hex_statistics():
+    """Test that aggregations adds per-hex statistics, skipping null values."""
+
+    def pipe(lon, lat, diameter, material):
+        return {
+            "type": "Feature",
+            "properties": {"diameter": diameter, "material": material},
+            "geometry": {
+                "type": "LineString",
+                "coordinates": [[lon, lat], [lon + 0.0001, lat]],
+            },
+        }
+
+    source = json.dumps(
+        {
+            "type": "FeatureCollection",
+            "features": [
+                pipe(-2.24, 53.48, 100.0, "PE"),
+                pipe(-2.24, 53.48, 200.0, "PE"),
+                pipe(-2.24, 53.48, None, "ST"),
+                pipe(-2.20, 53.60, None, None),
+            ],
+        }
+    )
+    aggregations = {
+        "diameter": ["min", "max", "mean", "sum"],
+        "material": ["count_distinct"],
+    }
+    result = infra_hex_py.hex_summary_from_geojson(
+        source, 10, aggregations=aggregations, output="pyarrow"
+    )
+    rows = sorted(result.to_pylist(), key=lambda row: -row["pipe_count"])
+    assert [row["pipe_count"] for row in rows] == [3, 1]
+    assert [
+        (row["diameter_min"], row["diameter_max"], row["diameter_mean"])
+        for row in rows
+    ] == [(100.0, 200.0, 150.0), (None, None, None)]
+    assert [row["diameter_sum"] for row in rows] == [300.0, None]
+    assert [row["material_count_distinct"] for row in rows] == [2, None]
+    assert result.schema.field("material_count_distinct").type == pa.uint32()
+
+    with pytest.raises(ValueError, match="not numeric"):
+        infra_hex_py.hex_summary_from_geojson(
+            source, 10, aggregations={"material": ["mean"]}
+        )
+    with pytest.raises(ValueError, match="Unknown aggregation"):
+        infra_hex_py.hex_summary_from_geojson(
+            source, 10, aggregations={"diameter": ["median"]}
+        )
+    with pytest.raises(ValueError, match="only applies to hex summaries"):
+        infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, grid="osgb", aggregations={"depth": ["max"]}
+        )
+
+
+def test_polygon_areas_output_dir_skips_existing_files(tmp_path):
+    """Test that a rerun with skip_existing fetches only areas without a file."""
+    features = _mock_pipe_features()
+    area = _built_up_area_feature(42, "Here")
+    (tmp_path / "7.parquet").write_bytes(b"from an earlier run")
+    with FixtureServer(features, ons_features=[area]) as server:
+        http_options = {
+            "cadent_url": server.cadent_url,
+            "ons_url": server.ons_url,
+            "api_key": "test",
+        }
+        by_id = infra_hex_py.get_hex_summary_polygon_areas(
+            [42, 42], 11, http_options=http_options
+        )
+        first = infra_hex_py.get_hex_summary_polygon_areas(
+            [42, 7],
+            11,
+            output_dir=tmp_path,
+            skip_existing=True,
+            http_options=http_options,
+        )
+        requests_after_first = len(server.requests)
+        rerun = infra_hex_py.get_hex_summary_polygon_areas(
+            [7, 42],
+            11,
+            output_dir=tmp_path,
+            skip_existing=True,
+            http_options=http_options,
+        )
+        assert len(server.requests) == requests_after_first
+        with pytest.raises(ValueError, match="output_dir"):
+            infra_hex_py.get_hex_summary_polygon_areas(
+                [42], 11, skip_existing=True, http_options=http_options
+            )
+
+    assert list(by_id) == [42]
+    assert first == {"fetched": [42], "skipped": [7]}
+    assert rerun == {"fetched": [], "skipped": [7, 42]}
+    table = pq.read_table(tmp_path / "42.parquet")
+    assert table.num_rows == by_id[42].num_rows
+    assert table.schema.metadata[b"query_object_id"] == b"42"
+    assert sorted(p.name for p in tmp_path.iterdir()) == ["42.parquet", "7.parquet"]
+
+
+def test_coarse_resolution_for_small_clip_warns(pipes_geojson, recwarn):
+    """Test that a clip covering under four hexes warns with a finer resolution."""
+    clip = shapely.to_geojson(shapely.box(-2.25, 53.47, -2.23, 53.49))
+    with pytest.warns(UserWarning, match="likely too coarse") as warned:
+        infra_hex_py.hex_summary_from_geojson(pipes_geojson, 7, clip_geojson=clip)
+    recwarn.clear()
+    infra_hex_py.hex_summary_from_geojson(pipes_geojson, 8, clip_geojson=clip)
+
+    message = str(warned[0].message)
+    assert "only 1 hex at resolution 7" in message
+    assert "use resolution 8 or finer" in message
+    assert not [w for w in recwarn if "too coarse" in str(w.message)]
+
+
+def test_query_id_is_stable_and_tracks_parameters():
+    """Test that query_id repeats for equal parameters and changes with any one."""
+    features = _mock_pipe_features()
+    area = _built_up_area_feature(42, "Here")
+    with FixtureServer(features, ons_features=[area]) as server:
+        http_options = {
+            "cadent_url": server.cadent_url,
+            "ons_url": server.ons_url,
+            "api_key": "test",
+        }
+
+        def query_id(*args, **kwargs):
+            summary = infra_hex_py.get_hex_summary(
+                *args, http_options=http_options, **kwargs
+            )
+            return summary.schema.metadata[b"query_id"].decode()
+
+        aggregations = {"depth": ["max", "min"], "material": ["count_distinct"]}
+        summary, stats = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX,
+            11,
+            aggregations=aggregations,
+            include_query_id=True,
+            with_stats=True,
+            http_options=http_options,
+        )
+        base = summary.schema.metadata[b"query_id"].decode()
+        with pytest.warns(DeprecationWarning):
+            same = [query_id(*FIXTURE_BBOX, zoom=11, aggregations=aggregations)]
+        same += [
+            query_id(*FIXTURE_BBOX, 11, aggregations=aggregations),
+            query_id(
+                infra_hex_py.BBox(
+                    min_lat=53.47, min_lon=-2.26, max_lat=53.49, max_lon=-2.22
+                ),
+                11,
+                aggregations={"material": ["count_distinct"], "depth": ["min", "max"]},
+                output="pyarrow",
+                telemetry=True,
+            ),
+        ]
+        changed = [
+            query_id(*FIXTURE_BBOX, 11),
+            query_id(*FIXTURE_BBOX, 10, aggregations=aggregations),
+            query_id(53.4701, -2.26, 53.49, -2.22, 11, aggregations=aggregations),
+            query_id(*FIXTURE_BBOX, 11, aggregations=aggregations, min_pipe_count=2),
+            query_id(*FIXTURE_BBOX, 11, aggregations=aggregations, deduplicate=False),
+            query_id(*FIXTURE_BBOX, 11, aggregations=aggregations, layer=["gas_pipes"]),
+            query_id(
+                *FIXTURE_BBOX, 11, aggregations=aggregations, date_from="2000-01-01"
+            ),
+        ]
+        # Whole and negative-zero floats are written like the integers they equal
+        assert query_id(53, -1, 54, 0, 8) == query_id(53.0, -1.0, 54.0, -0.0, 8)
+
+        def area_id(**kwargs):
+            summary = infra_hex_py.get_hex_summary_polygon_area(
+                42, 11, http_options=http_options, **kwargs
+            )
+            return summary.schema.metadata[b"query_id"].decode()
+
+        areas = [area_id(), area_id(include_query_id=True), area_id(buffer_m=50)]
+
+    assert len(base) == 16 and int(base, 16) >= 0
+    assert same == [base] * 3
+    assert len(set(changed)) == len(changed) and base not in changed
+    assert stats["query_id"] == base
+    assert set(summary.column("query_id").to_pylist()) == {base}
+    assert summary.schema.names[-2:] == ["query_id", "geometry"]
+    assert summary.schema.equals(
+        infra_hex_py.hex_summary_schema(
+            aggregations=aggregations, include_query_id=True
+        )
+    )
+    assert areas[0] == areas[1] != areas[2] and base not in areas
+
+
+def test_area_names_summary_stacks_areas_and_lists_every_problem():
+    """Test that named areas are looked up once each and summarised together."""
+    features = _mock_pipe_features()
+    ons_features = [
+        _built_up_area_feature(42, "Here"),
+        _built_up_area_feature(43, "There"),
+        _built_up_area_feature(7, "Twin"),
+        _built_up_area_feature(8, "Twin"),
+    ]
+    names = ["here", "Here ", "There", "Twin", "Nowhere"]
+    with FixtureServer(features, ons_features=ons_features) as server:
+        http_options = {
+            "cadent_url": server.cadent_url,
+            "ons_url": server.ons_url,
+            "api_key": "test",
+        }
+        single = infra_hex_py.get_hex_summary_polygon_area(
+            42, 11, http_options=http_options
+        )
+        with pytest.raises(infra_hex_py.AreaNotFoundError) as excinfo:
+            infra_hex_py.get_hex_summary_for_area_names(
+                names, 11, http_options=http_options
+            )
+        lookups_before = len(server.requests)
+        with pytest.warns(UserWarning, match="'Nowhere' matches no built-up area"):
+            batch = infra_hex_py.get_hex_summary_for_area_names(
+                names, 11, ignore_errors=True, http_options=http_options
+            )
+        name_lookups = [
+            query["where"]
+            for query in server.requests[lookups_before:]
+            if "BUA24NM" in query.get("where", "")
+        ]
+
+    message = str(excinfo.value)
+    assert "'Nowhere' matches no built-up area" in message
+    assert "Twin (OBJECTID 7), Twin (OBJECTID 8)" in message
+    assert excinfo.value.object_id is None
+    assert len(name_lookups) == 4
+    table = pa.table(batch)
+    assert table.num_rows == 2 * single.num_rows
+    assert table.column("area_name").to_pylist() == (
+        ["Here"] * single.num_rows + ["There"] * single.num_rows
+    )
+    assert set(table.column("object_id").to_pylist()) == {42, 43}
+    assert table.column_names.index("object_id") == table.num_columns - 2
+    unresolved = json.loads(table.schema.metadata[b"unresolved_area_names"])
+    assert unresolved == ["Twin", "Nowhere"]
+
+
+def test_fetch_raw_response_returns_the_page_body_unparsed():
+    """Test that the raw response is the first page's body exactly as served."""
+    features = _mock_pipe_features()
+    min_lat, min_lon, max_lat, max_lon = FIXTURE_BBOX
+    bbox = infra_hex_py.BBox(
+        min_lat=min_lat, min_lon=min_lon, max_lat=max_lat, max_lon=max_lon
+    )
+    with FixtureServer(features) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        body = infra_hex_py.fetch_raw_response(
+            bbox, limit=2, offset=1, http_options=http_options
+        )
+        query = server.requests[-1]
+        with pytest.raises(ValueError, match="limit"):
+            infra_hex_py.fetch_raw_response(bbox, limit=0, http_options=http_options)
+
+    assert isinstance(body, bytes)
+    page = json.loads(body)
+    inside = [
+        f
+        for f in features
+        if min_lat <= cadent_record(f)["geo_point_2d"]["lat"] <= max_lat
+        and min_lon <= cadent_record(f)["geo_point_2d"]["lon"] <= max_lon
+    ]
+    assert page["total_count"] == len(inside)
+    assert len(page["results"]) == 2
+    assert "geo_shape" in page["results"][0]
+    assert query["limit"] == "2"
+    assert query["offset"] == "1"
+    assert "in_bbox(geo_point_2d," in query["where"]
+
+
+def test_id_field_deduplicates_on_another_property():
+    first = [[-2.2484, 53.4805], [-2.2493, 53.4806]]
+    second = [[-2.2401, 53.4801], [-2.2412, 53.4809]]
+    unnamed = [[-2.2450, 53.4790], [-2.2460, 53.4795]]
+
+    def collection(*features):
+        return json.dumps({"type": "FeatureCollection", "features": list(features)})
+
+    repeated = collection(
+        _line_feature(first, PIPE_REF="P-1"),
+        _line_feature(second, PIPE_REF="P-1"),
+        _line_feature(unnamed),
+        _line_feature(unnamed),
+        _line_feature(second, PIPE_REF="P-2"),
+    )
+    expected = collection(
+        _line_feature(first, PIPE_REF="P-1"),
+        _line_feature(unnamed),
+        _line_feature(second, PIPE_REF="P-2"),
+    )
+
+    result = infra_hex_py.hex_summary_from_geojson(repeated, 11, id_field="PIPE_REF")
+    unique = infra_hex_py.hex_summary_from_geojson(expected, 11, deduplicate=False)
+    assert result.column("hex_id").to_pylist() == unique.column("hex_id").to_pylist()
+    assert (
+        result.column("pipe_count").to_pylist()
+        == unique.column("pipe_count").to_pylist()
+    )
+    assert result.schema.metadata[b"id_field"] == b"PIPE_REF"
+    assert result.schema.metadata[b"geometry_id_records"] == b"1"
+
+    # Without it every feature lacks an asset_id, so only the exact repeat goes
+    default = infra_hex_py.hex_summary_from_geojson(repeated, 11)
+    assert default.schema.metadata[b"id_field"] == b"asset_id"
+    assert default.schema.metadata[b"geometry_id_records"] == b"4"
+
+    with pytest.raises(ValueError, match="id_field"):
+        infra_hex_py.hex_summary_from_geojson(repeated, 11, id_field="")
+
+
+def _closed_port():
+    server = socket.socket()
+    server.bind(("127.0.0.1", 0))
+    port = server.getsockname()[1]
+    server.close()
+    return port
+
+
+@pytest.mark.parametrize(
+    ("host", "failure", "message"),
+    [
+        ("127.0.0.1:{port}", "connection_refused", "refused the connection"),
+        ("infra-hex.invalid", "dns", "Could not resolve infra-hex.invalid"),
+    ],
+)
+def test_unreachable_host_raises_network_error(host, failure, message):
+    """Test that a fetch reaching no server says why and names the host."""
+    host = host.format(port=_closed_port())
+    with pytest.raises(infra_hex_py.InfraNetworkError, match=message) as excinfo:
+        infra_hex_py.get_built_up_area(
+            1310,
+            http_options={
+                "ons_url": "http://{}/ons/FeatureServer/0".format(host),
+                "connect_timeout_secs": 5,
+                "max_retries": 0,
+            },
+        )
+
+    error = excinfo.value
+    assert isinstance(error, infra_hex_py.FetchError)
+    assert error.host == host.split(":")[0]
+    assert [detail["network_failure"] for detail in error.errors] == [failure]
+
+
+def test_region_cache_fetch_errors_lists_each_failure():
+    """Test that fetch_errors gives one row per failed page, with why it failed."""
+    http_options = {"api_key": "test", "max_retries": 0}
+    with FixtureServer(_grid_features(16), fail_offsets=[100]) as server:
+        options = {**http_options, "cadent_url": server.cadent_url}
+        with pytest.warns(UserWarning, match="1 part"):
+            partial = infra_hex_py.RegionCache.from_bbox(
+                *FIXTURE_BBOX, http_options=options, allow_partial=True
+            )
+
+        errors = partial.fetch_errors()
+        assert errors.schema.names == [
+            "bbox",
+            "offset",
+            "limit",
+            "error_kind",
+            "message",
+            "http_status",
+        ]
+        min_lat, min_lon, max_lat, max_lon = FIXTURE_BBOX
+        assert errors.column("bbox").to_pylist() == [
+            {
+                "min_lat": min_lat,
+                "min_lon": min_lon,
+                "max_lat": max_lat,
+                "max_lon": max_lon,
+            }
+        ]
+        assert errors.column("offset").to_pylist() == [100]
+        assert errors.column("error_kind").to_pylist() == ["http_status"]
+        assert errors.column("http_status").to_pylist() == [500]
+        assert "500" in errors.column("message")[0].as_py()
+
+        repaired = partial.refetch(http_options=options)
+    assert repaired.fetch_errors().num_rows == 0
+
+
+def test_summary_around_point_clips_to_a_circle():
+    """Test that the circle's hexes lie between a smaller and a larger ellipse's."""
+    lat, lon, radius_m = 53.48, -2.24, 500
+
+    def ellipse(radius_m):
+        dlat = radius_m / 111_320
+        dlon = radius_m / (111_320 * math.cos(math.radians(lat)))
+        return shapely.Polygon(
+            [
+                (lon + dlon * math.cos(t), lat + dlat * math.sin(t))
+                for t in (2 * math.pi * i / 256 for i in range(256))
+            ]
+        )
+
+    with FixtureServer(_mock_pipe_features()) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        circle = infra_hex_py.get_hex_summary_around_point(
+            lat, lon, radius_m, 11, http_options=http_options
+        )
+        inner, outer = (
+            infra_hex_py.get_hex_summary_for_polygon(
+                shapely.to_geojson(ellipse(radius_m * scale)),
+                11,
+                http_options=http_options,
+            )
+            for scale in (0.95, 1.05)
+        )
+
+    counts = _summary_counts(circle)
+    assert counts
+    assert _summary_counts(inner).items() <= counts.items()
+    assert counts.items() <= _summary_counts(outer).items()
+
+    with pytest.raises(ValueError, match="radius_m"):
+        infra_hex_py.get_hex_summary_around_point(lat, lon, 0, 11)
+
+
+def test_tile_size_deg_fetches_tiles_and_keeps_seam_records_once():
+    """Test that tile_size_deg splits the bbox into tiles without double counting."""
+    min_lat, min_lon, max_lat, max_lon = FIXTURE_BBOX
+    mid_lat = min_lat + (max_lat - min_lat) / 2
+    mid_lon = min_lon + (max_lon - min_lon) / 2
+    # Centred on the corner where four tiles meet
+    seam = _line_feature(
+        [[mid_lon - 0.0005, mid_lat], [mid_lon + 0.0005, mid_lat]], asset_id="SEAM"
+    )
+    features = _grid_features(8) + [seam]
+    with FixtureServer(features) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        whole = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution=11, http_options=http_options
+        )
+        untiled_requests = len(server.requests)
+        tiled, stats = infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX,
+            resolution=11,
+            with_stats=True,
+            http_options={
+                **http_options,
+                "tile_size_deg": 0.011,
+                "max_concurrent_tiles": 3,
+            },
+        )
+        tiled_wheres = {r["where"] for r in server.requests[untiled_requests:]}
+
+    assert _summary_counts(tiled) == _summary_counts(whole)
+    assert stats["total_records"] == len(features)
+    assert len(tiled_wheres) == 8
+    with pytest.raises(ValueError, match="tile_size_deg"):
+        infra_hex_py.get_hex_summary(
+            *FIXTURE_BBOX, resolution=11, http_options={"tile_size_deg": 0}
+        )
+
+
+def test_pipe_cell_traces_follow_each_pipe_in_order():
+    """Test that get_pipe_cell_traces lists each pipe's hexes in line order."""
+    coords = [[-2.2531, 53.4762], [-2.2417, 53.4809], [-2.2338, 53.4851]]
+    features = [
+        _line_feature(coords, asset_id="FORWARD"),
+        _line_feature(coords[::-1], asset_id="BACKWARD"),
+    ]
+    with FixtureServer(features) as server:
+        http_options = {"cadent_url": server.cadent_url, "api_key": "test"}
+        traces = infra_hex_py.get_pipe_cell_traces(
+            *FIXTURE_BBOX, resolution=11, http_options=http_options
+        )
+        rows = infra_hex_py.get_pipe_hex_assignments(
+            *FIXTURE_BBOX, resolution=11, http_options=http_options
+        )
+
+    assert traces.schema.field("cell_sequence").type == pa.list_(
+        pa.field("item", pa.string(), nullable=False)
+    )
+    sequences = dict(
+        zip(
+            traces.column("pipe_id").to_pylist(),
+            traces.column("cell_sequence").to_pylist(),
+        )
+    )
+    forward = sequences["FORWARD"]
+    assert len(forward) > 3
+    assert all(a != b for a, b in zip(forward, forward[1:]))
+    assert sequences["BACKWARD"] == forward[::-1]
+    assignments = {
+        hex_id
+        for pipe_id, hex_id in zip(
+            rows.column("pipe_id").to_pylist(), rows.column("hex_id").to_pylist()
+        )
+        if pipe_id == "FORWARD"
+    }
+    assert set(forward) == assignments
+
+
+def test_package_re_exports_every_extension_name():
+    """Test that every public name of the compiled module is in the package."""
+    extension = infra_hex_py.infra_hex_py
+    names = {name for name in dir(extension) if not name.startswith("_")}
+    assert names <= set(infra_hex_py.__all__)
+    for name in names:
+        assert getattr(infra_hex_py, name) is getattr(extension, name)
+
+
+def test_all_intersecting_assignment_names_coverage():
+    """Test that "all_intersecting" matches coverage assignment."""
+    collection, records = _crossing_collection()
+
+    default = infra_hex_py.hex_summary_from_geojson(collection, 11)
+    intersecting = infra_hex_py.hex_summary_from_geojson(
+        collection, 11, assignment="all_intersecting"
+    )
+    midpoint = infra_hex_py.hex_summary_from_geojson(
+        collection, 11, assignment="midpoint"
+    )
+
+    assert _summary_counts(intersecting) == _summary_counts(default)
+    assert b"assignment" not in intersecting.schema.metadata
+    assert sum(midpoint.column("pipe_count").to_pylist()) == records
+    assert sum(intersecting.column("pipe_count").to_pylist()) > records
+
+
+def test_tile_size_deg_rejects_grids_of_too_many_tiles():
+    """Test that a tiny tile_size_deg raises ValueError before any request."""
+    with FixtureServer(_mock_pipe_features()) as server:
+        http_options = {
+            "cadent_url": server.cadent_url,
+            "api_key": "test",
+            "tile_size_deg": 1e-7,
+        }
+        with pytest.raises(ValueError, match="tile_size_deg"):
+            infra_hex_py.get_hex_summary(
+                *FIXTURE_BBOX, resolution=11, http_options=http_options
+            )
+        with pytest.raises(ValueError, match="tile_size_deg"):
+            infra_hex_py.RegionCache.from_bbox(FIXTURE_BBOX, http_options=http_options)
+
+    assert server.requests == []
+
+
+if __name__ == "__main__":
+    pytest.main([__file__, "-vv", "-s"])